@@ -0,0 +1,216 @@
+//! Clients for the servers that generate the responses, either Ollama or anything with an
+//! OpenAI-compatible API, e.g. llama.cpp's server, LM Studio, vLLM or OpenAI itself
+
+use std::future::Future;
+use std::pin::Pin;
+
+use tokio_stream::Stream;
+
+use crate::conversation::{GenerationParams, ResponseStats};
+use crate::models::{CreateStream, ModelDetails, PullStream};
+use crate::settings::{Server, ServerKind};
+use crate::structured::OutputFormat;
+use crate::tools::{Tool, ToolCall, ToolExchange};
+use crate::{ChatMessage, Error, LocalModel, Result};
+
+mod ollama;
+mod openai;
+
+pub use ollama::OllamaProvider;
+pub use openai::OpenAiProvider;
+
+/// A piece of a response as it streams in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResponseChunk {
+    pub content: String,
+    /// Only on the last chunk, if the server reported them
+    pub stats: Option<ResponseStats>,
+}
+
+/// What a model replied with when it was offered tools, either calls to them or its answer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolReply {
+    pub content: String,
+    pub calls: Vec<ToolCall>,
+    pub stats: ResponseStats,
+}
+
+pub type ResponseStream = Pin<Box<dyn Stream<Item = Result<ResponseChunk>> + Send>>;
+
+/// What generating responses needs from a server, whichever API it speaks
+pub trait Provider {
+    fn list_models(&self) -> impl Future<Output = Result<Vec<LocalModel>>> + Send;
+
+    /// Sends the whole conversation to the model and waits for the whole response
+    fn chat(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> impl Future<Output = Result<String>> + Send;
+
+    /// Sends the whole conversation to the model and streams back the response
+    fn chat_stream(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> impl Future<Output = Result<ResponseStream>> + Send;
+
+    /// Embeds each of the texts with an embedding model, e.g. `nomic-embed-text`
+    fn embed(
+        &self,
+        model_name: String,
+        texts: Vec<String>,
+    ) -> impl Future<Output = Result<Vec<Vec<f32>>>> + Send;
+}
+
+/// Client for the server in use, of whichever kind it is
+#[derive(Debug, Clone)]
+pub enum Backend {
+    Ollama(OllamaProvider),
+    OpenAi(OpenAiProvider),
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::Ollama(OllamaProvider::default())
+    }
+}
+
+impl Backend {
+    pub fn new(server: &Server) -> Result<Self> {
+        match server.kind {
+            ServerKind::Ollama => OllamaProvider::new(server).map(Self::Ollama),
+            ServerKind::OpenAi => OpenAiProvider::new(server).map(Self::OpenAi),
+        }
+    }
+
+    /// Whether models can be pulled to, created on and deleted from the server, which only Ollama
+    /// allows
+    pub fn manages_models(&self) -> bool {
+        matches!(self, Self::Ollama(_))
+    }
+
+    /// Checks whether the server can be reached, without waiting on anything slow like loading a
+    /// model
+    pub async fn ping(&self) -> Result<()> {
+        match self {
+            Self::Ollama(ollama) => ollama.ping().await,
+            Self::OpenAi(openai) => openai.ping().await,
+        }
+    }
+
+    /// Lists the installed models with their sizes, parameter counts and quantization, which only
+    /// Ollama reports
+    pub async fn model_details(&self) -> Result<Vec<ModelDetails>> {
+        match self {
+            Self::Ollama(ollama) => ollama.model_details().await,
+            Self::OpenAi(_) => Ok(vec![]),
+        }
+    }
+
+    /// Downloads a model from the Ollama registry, streaming back how far it's got
+    pub async fn pull_model(&self, model_name: String) -> Result<PullStream> {
+        match self {
+            Self::Ollama(ollama) => ollama.pull_model(model_name).await,
+            Self::OpenAi(_) => Err(unmanaged_models()),
+        }
+    }
+
+    /// Creates a model on the Ollama server from a Modelfile, streaming back what it's doing
+    pub async fn create_model(
+        &self,
+        model_name: String,
+        modelfile: String,
+    ) -> Result<CreateStream> {
+        match self {
+            Self::Ollama(ollama) => ollama.create_model(model_name, modelfile).await,
+            Self::OpenAi(_) => Err(unmanaged_models()),
+        }
+    }
+
+    /// Whether models can be offered tools to call, which is only done with Ollama's API
+    pub fn supports_tools(&self) -> bool {
+        matches!(self, Self::Ollama(_))
+    }
+
+    /// Whether responses can be asked to be JSON, which is only done with Ollama's API
+    pub fn supports_structured_output(&self) -> bool {
+        matches!(self, Self::Ollama(_))
+    }
+
+    /// Sends the conversation along with the tools the model can call, the calls it's made so far
+    /// for this response and the format it's to reply in, waiting for the whole reply
+    pub async fn chat_with_tools(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        exchanges: Vec<ToolExchange>,
+        tools: Vec<Tool>,
+        format: Option<OutputFormat>,
+        params: GenerationParams,
+    ) -> Result<ToolReply> {
+        match self {
+            Self::Ollama(ollama) => {
+                ollama
+                    .chat_with_tools(model_name, conversation, exchanges, tools, format, params)
+                    .await
+            }
+            Self::OpenAi(_) => Err(Error::Backend(
+                "tools can only be used with Ollama servers".to_string(),
+            )),
+        }
+    }
+
+    pub async fn delete_model(&self, model_name: String) -> Result<()> {
+        match self {
+            Self::Ollama(ollama) => ollama.delete_model(model_name).await,
+            Self::OpenAi(_) => Err(unmanaged_models()),
+        }
+    }
+}
+
+fn unmanaged_models() -> Error {
+    Error::Backend("models can only be pulled, created and deleted on Ollama servers".to_string())
+}
+
+impl Provider for Backend {
+    async fn list_models(&self) -> Result<Vec<LocalModel>> {
+        match self {
+            Self::Ollama(ollama) => ollama.list_models().await,
+            Self::OpenAi(openai) => openai.list_models().await,
+        }
+    }
+
+    async fn chat(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> Result<String> {
+        match self {
+            Self::Ollama(ollama) => ollama.chat(model_name, conversation, params).await,
+            Self::OpenAi(openai) => openai.chat(model_name, conversation, params).await,
+        }
+    }
+
+    async fn chat_stream(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> Result<ResponseStream> {
+        match self {
+            Self::Ollama(ollama) => ollama.chat_stream(model_name, conversation, params).await,
+            Self::OpenAi(openai) => openai.chat_stream(model_name, conversation, params).await,
+        }
+    }
+
+    async fn embed(&self, model_name: String, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        match self {
+            Self::Ollama(ollama) => ollama.embed(model_name, texts).await,
+            Self::OpenAi(openai) => openai.embed(model_name, texts).await,
+        }
+    }
+}
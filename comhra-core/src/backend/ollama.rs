@@ -0,0 +1,406 @@
+//! Generating responses with an Ollama server, which is also the only kind of server models can
+//! be pulled to, created on and deleted from
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ollama_rs::generation::chat::request::ChatMessageRequest;
+use ollama_rs::generation::options::GenerationOptions;
+use ollama_rs::models::create::CreateModelRequest;
+use ollama_rs::Ollama;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+use tokio_stream::StreamExt;
+use url::Url;
+
+use super::{Provider, ResponseChunk, ResponseStream, ToolReply};
+use crate::conversation::{GenerationParams, ResponseStats};
+use crate::models::{CreateStream, ModelDetails, ModelList, PullStream};
+use crate::settings::Server;
+use crate::structured::OutputFormat;
+use crate::tools::{Tool, ToolCall, ToolExchange};
+use crate::{ChatMessage, Error, LocalModel, MessageRole, Result};
+
+#[derive(Serialize)]
+struct EmbedRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+/// A chat request offering the model tools or asking for JSON matching a schema, which the
+/// Ollama client doesn't have a way to send
+#[derive(Serialize)]
+struct ToolChatRequest {
+    model: String,
+    messages: Vec<ToolChatMessage>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
+    options: GenerationOptions,
+    stream: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ToolChatMessage {
+    Chat(ChatMessage),
+    /// The model calling a tool, as it replied earlier in the response
+    Call {
+        role: MessageRole,
+        content: String,
+        tool_calls: Vec<FunctionCall>,
+    },
+    Result {
+        role: &'static str,
+        content: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct FunctionCall {
+    function: ToolCall,
+}
+
+#[derive(Deserialize)]
+struct ToolChatResponse {
+    message: ToolResponseMessage,
+    #[serde(default)]
+    prompt_eval_count: u32,
+    #[serde(default)]
+    eval_count: u32,
+    #[serde(default)]
+    eval_duration: u64,
+    #[serde(default)]
+    total_duration: u64,
+}
+
+#[derive(Deserialize)]
+struct ToolResponseMessage {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<FunctionCall>,
+}
+
+/// The conversation with each tool call made for the response so far after it, followed by its
+/// result
+fn tool_chat_messages(
+    conversation: Vec<ChatMessage>,
+    exchanges: Vec<ToolExchange>,
+) -> Vec<ToolChatMessage> {
+    conversation
+        .into_iter()
+        // The response being written is sent as its tool calls instead
+        .filter(|chat_message| {
+            chat_message.role != MessageRole::Assistant || !chat_message.content.is_empty()
+        })
+        .map(ToolChatMessage::Chat)
+        .chain(exchanges.into_iter().flat_map(|exchange| {
+            [
+                ToolChatMessage::Call {
+                    role: MessageRole::Assistant,
+                    content: String::new(),
+                    tool_calls: vec![FunctionCall {
+                        function: exchange.call,
+                    }],
+                },
+                ToolChatMessage::Result {
+                    role: "tool",
+                    content: exchange.result,
+                },
+            ]
+        }))
+        .collect()
+}
+
+/// The port Ollama listens on unless it's told otherwise
+const DEFAULT_PORT: u16 = 11434;
+
+/// The server's API key as a bearer token, or else its login with basic auth, for a reverse proxy
+/// in front of it that asks for one
+fn authorization(server: &Server) -> Option<HeaderValue> {
+    let authorization = match (&server.api_key, &server.username) {
+        (Some(api_key), _) if !api_key.is_empty() => format!("Bearer {api_key}"),
+        (_, Some(username)) if !username.is_empty() => {
+            let login = format!(
+                "{username}:{}",
+                server.password.as_deref().unwrap_or_default()
+            );
+            format!("Basic {}", STANDARD.encode(login))
+        }
+        _ => return None,
+    };
+    let mut authorization = HeaderValue::from_str(&authorization).ok()?;
+    authorization.set_sensitive(true);
+    Some(authorization)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OllamaProvider {
+    ollama: Ollama,
+    /// For the parts of the API the Ollama client doesn't cover
+    http: reqwest::Client,
+}
+
+impl OllamaProvider {
+    pub fn new(server: &Server) -> Result<Self> {
+        let url = Url::parse(&server.url)
+            .map_err(|err| Error::Backend(format!("{} isn't a valid URL: {err}", server.url)))?;
+        let mut headers = HeaderMap::new();
+        if let Some(authorization) = authorization(server) {
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        // Shared with the Ollama client, so the login's sent with everything it asks for too
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        let port = url.port_or_known_default().unwrap_or(DEFAULT_PORT);
+        Ok(Self {
+            ollama: Ollama::new_with_client(url, port, http.clone()),
+            http,
+        })
+    }
+
+    /// Checks the server's answering by asking for its version, which is the quickest thing to ask
+    pub async fn ping(&self) -> Result<()> {
+        let url = format!("{}api/version", self.ollama.url_str());
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_response| ())
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+
+    /// Lists the installed models with their sizes, parameter counts and quantization
+    pub async fn model_details(&self) -> Result<Vec<ModelDetails>> {
+        let url = format!("{}api/tags", self.ollama.url_str());
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        response
+            .json::<ModelList>()
+            .await
+            .map(|model_list| model_list.models)
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+
+    /// Downloads a model from the Ollama registry, streaming back how far it's got
+    pub async fn pull_model(&self, model_name: String) -> Result<PullStream> {
+        let stream = self
+            .ollama
+            .pull_model_stream(model_name, false)
+            .await
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        Ok(Box::pin(stream.map(|status| {
+            status.map_err(|err| Error::Backend(err.to_string()))
+        })))
+    }
+
+    /// Creates a model from a Modelfile, streaming back what the server's doing
+    pub async fn create_model(
+        &self,
+        model_name: String,
+        modelfile: String,
+    ) -> Result<CreateStream> {
+        let stream = self
+            .ollama
+            .create_model_stream(CreateModelRequest::modelfile(model_name, modelfile))
+            .await
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        Ok(Box::pin(stream.map(|status| {
+            status.map_err(|err| Error::Backend(err.to_string()))
+        })))
+    }
+
+    /// Sends the conversation offering the model tools, and the calls it's already made for this
+    /// response with their results, waiting for the whole reply, which is JSON if a format's given
+    pub async fn chat_with_tools(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        exchanges: Vec<ToolExchange>,
+        tools: Vec<Tool>,
+        format: Option<OutputFormat>,
+        params: GenerationParams,
+    ) -> Result<ToolReply> {
+        let url = format!("{}api/chat", self.ollama.url_str());
+        let response = self
+            .http
+            .post(url)
+            .json(&ToolChatRequest {
+                model: model_name,
+                messages: tool_chat_messages(conversation, exchanges),
+                tools: tools.into_iter().map(Tool::definition).collect(),
+                format: format.as_ref().map(OutputFormat::request_value),
+                options: params.to_options(),
+                stream: false,
+            })
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        let response = response
+            .json::<ToolChatResponse>()
+            .await
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        Ok(ToolReply {
+            content: response.message.content,
+            calls: response
+                .message
+                .tool_calls
+                .into_iter()
+                .map(|function_call| function_call.function)
+                .collect(),
+            stats: ResponseStats {
+                prompt_tokens: response.prompt_eval_count,
+                generated_tokens: response.eval_count,
+                generation_nanos: response.eval_duration,
+                total_nanos: response.total_duration,
+            },
+        })
+    }
+
+    pub async fn delete_model(&self, model_name: String) -> Result<()> {
+        self.ollama
+            .delete_model(model_name)
+            .await
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+}
+
+impl Provider for OllamaProvider {
+    async fn list_models(&self) -> Result<Vec<LocalModel>> {
+        self.ollama
+            .list_local_models()
+            .await
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+
+    async fn chat(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> Result<String> {
+        self.ollama
+            .send_chat_messages(
+                ChatMessageRequest::new(model_name, conversation).options(params.to_options()),
+            )
+            .await
+            .map(|response| {
+                response
+                    .message
+                    .map(|chat_message| chat_message.content)
+                    .unwrap_or_default()
+            })
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+
+    async fn chat_stream(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> Result<ResponseStream> {
+        let stream = self
+            .ollama
+            .send_chat_messages_stream(
+                ChatMessageRequest::new(model_name, conversation).options(params.to_options()),
+            )
+            .await
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        Ok(Box::pin(stream.map(
+            |stream_response| -> Result<ResponseChunk> {
+                let response = stream_response.map_err(|()| Error::Stream)?;
+                Ok(ResponseChunk {
+                    content: response
+                        .message
+                        .map(|chat_message| chat_message.content)
+                        .unwrap_or_default(),
+                    stats: response.final_data.as_ref().map(ResponseStats::from),
+                })
+            },
+        )))
+    }
+
+    async fn embed(&self, model_name: String, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let url = format!("{}api/embed", self.ollama.url_str());
+        let response = self
+            .http
+            .post(url)
+            .json(&EmbedRequest {
+                model: model_name,
+                input: texts,
+            })
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        response
+            .json::<EmbedResponse>()
+            .await
+            .map(|embed_response| embed_response.embeddings)
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_calls_follow_the_conversation_with_their_results() {
+        let call = ToolCall {
+            name: "calculator".to_string(),
+            arguments: serde_json::json!({"expression": "6 * 7"}),
+        };
+        let messages = tool_chat_messages(
+            vec![
+                ChatMessage::user("What's six times seven?".to_string()),
+                ChatMessage::assistant(String::new()),
+            ],
+            vec![ToolExchange {
+                call,
+                result: "42".to_string(),
+            }],
+        );
+        assert_eq!(messages.len(), 3);
+        assert!(
+            matches!(&messages[0], ToolChatMessage::Chat(prompt) if prompt.role == MessageRole::User)
+        );
+        assert_eq!(
+            serde_json::to_value(&messages[1..]).unwrap(),
+            serde_json::json!([
+                {"role": "assistant", "content": "", "tool_calls": [
+                    {"function": {"name": "calculator", "arguments": {"expression": "6 * 7"}}},
+                ]},
+                {"role": "tool", "content": "42"},
+            ])
+        );
+    }
+
+    #[test]
+    fn api_keys_are_sent_before_logins() {
+        let mut server = Server {
+            username: Some("me".to_string()),
+            password: Some("secret".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(authorization(&server).unwrap(), "Basic bWU6c2VjcmV0");
+        server.api_key = Some("token".to_string());
+        assert_eq!(authorization(&server).unwrap(), "Bearer token");
+        assert_eq!(authorization(&Server::default()), None);
+    }
+}
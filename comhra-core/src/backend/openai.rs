@@ -0,0 +1,471 @@
+//! Generating responses with a server that has an OpenAI-compatible API, e.g. llama.cpp's
+//! server, LM Studio, vLLM or OpenAI itself
+//!
+//! Only the generation params the API has in common with Ollama's, temperature, top p and the
+//! seed, are sent. The others are left to the server's defaults.
+
+use std::pin::Pin;
+use std::time::Instant;
+
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use tokio_stream::{Stream, StreamExt};
+use url::Url;
+
+use super::{Provider, ResponseChunk, ResponseStream};
+use crate::conversation::{GenerationParams, ResponseStats};
+use crate::images;
+use crate::settings::Server;
+use crate::{ChatMessage, Error, LocalModel, MessageRole, Result};
+
+#[derive(Serialize)]
+struct ChatRequest {
+    model: String,
+    messages: Vec<RequestMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct StreamOptions {
+    /// Has the server send the token counts in a last chunk before it finishes
+    include_usage: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct RequestMessage {
+    role: MessageRole,
+    content: RequestContent,
+}
+
+/// Plain text, or a list of parts when there are images to send along with the text
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+enum RequestContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct ImageUrl {
+    url: String,
+}
+
+impl From<ChatMessage> for RequestMessage {
+    fn from(chat_message: ChatMessage) -> Self {
+        let content = match chat_message.images {
+            Some(images) if !images.is_empty() => RequestContent::Parts(
+                std::iter::once(ContentPart::Text {
+                    text: chat_message.content,
+                })
+                .chain(images.iter().map(|image| ContentPart::ImageUrl {
+                    image_url: ImageUrl {
+                        url: images::data_url(image),
+                    },
+                }))
+                .collect(),
+            ),
+            _ => RequestContent::Text(chat_message.content),
+        };
+        Self {
+            role: chat_message.role,
+            content,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// One of the server-sent events a streamed response comes in
+#[derive(Deserialize)]
+struct StreamEvent {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Deserialize)]
+struct StreamChoice {
+    delta: ResponseMessage,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Usage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelObject>,
+}
+
+#[derive(Deserialize)]
+struct ModelObject {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct EmbeddingsRequest {
+    model: String,
+    input: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<Embedding>,
+}
+
+#[derive(Deserialize)]
+struct Embedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenAiProvider {
+    /// Up to and including the API version, e.g. `http://localhost:8080/v1/`
+    base_url: Url,
+    api_key: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    http: reqwest::Client,
+}
+
+impl OpenAiProvider {
+    pub fn new(server: &Server) -> Result<Self> {
+        let mut base_url = Url::parse(&server.url)
+            .map_err(|err| Error::Backend(format!("{} isn't a valid URL: {err}", server.url)))?;
+        // Without the trailing slash, joining the endpoints on would replace the API version
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        Ok(Self {
+            base_url,
+            api_key: server.api_key.clone().filter(|api_key| !api_key.is_empty()),
+            username: server
+                .username
+                .clone()
+                .filter(|username| !username.is_empty()),
+            password: server.password.clone(),
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// A request to an endpoint, with the API key, or the login if there's no key
+    fn request(&self, method: reqwest::Method, endpoint: &str) -> Result<reqwest::RequestBuilder> {
+        let url = self
+            .base_url
+            .join(endpoint)
+            .map_err(|err| Error::Backend(err.to_string()))?;
+        let request = self.http.request(method, url);
+        Ok(match (&self.api_key, &self.username) {
+            (Some(api_key), _) => request.bearer_auth(api_key),
+            (None, Some(username)) => request.basic_auth(username, self.password.as_ref()),
+            (None, None) => request,
+        })
+    }
+
+    /// Checks the server's answering by listing its models, as there's nothing quicker to ask
+    pub async fn ping(&self) -> Result<()> {
+        self.send(self.request(reqwest::Method::GET, "models")?)
+            .await
+            .map(|_response| ())
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+
+    fn chat_request(
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+        stream: bool,
+    ) -> ChatRequest {
+        ChatRequest {
+            model: model_name,
+            messages: conversation.into_iter().map(RequestMessage::from).collect(),
+            stream,
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+            temperature: params.temperature,
+            top_p: params.top_p,
+            seed: params.seed,
+        }
+    }
+}
+
+impl Provider for OpenAiProvider {
+    /// Lists the models the server has, which it only gives the names of
+    async fn list_models(&self) -> Result<Vec<LocalModel>> {
+        let response = self
+            .send(self.request(reqwest::Method::GET, "models")?)
+            .await?;
+        response
+            .json::<ModelsResponse>()
+            .await
+            .map(|models_response| {
+                models_response
+                    .data
+                    .into_iter()
+                    .map(|model| LocalModel {
+                        name: model.id,
+                        modified_at: String::new(),
+                        size: 0,
+                    })
+                    .collect()
+            })
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+
+    async fn chat(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> Result<String> {
+        let request = self
+            .request(reqwest::Method::POST, "chat/completions")?
+            .json(&Self::chat_request(model_name, conversation, params, false));
+        let response = self.send(request).await?;
+        response
+            .json::<ChatResponse>()
+            .await
+            .map(|chat_response| {
+                chat_response
+                    .choices
+                    .into_iter()
+                    .next()
+                    .and_then(|choice| choice.message.content)
+                    .unwrap_or_default()
+            })
+            .map_err(|err| Error::Backend(err.to_string()))
+    }
+
+    async fn chat_stream(
+        &self,
+        model_name: String,
+        conversation: Vec<ChatMessage>,
+        params: GenerationParams,
+    ) -> Result<ResponseStream> {
+        let started = Instant::now();
+        let request = self
+            .request(reqwest::Method::POST, "chat/completions")?
+            .json(&Self::chat_request(model_name, conversation, params, true));
+        let response = self.send(request).await?;
+        let events = EventStream {
+            bytes: Box::pin(
+                response
+                    .bytes_stream()
+                    .map(|bytes| bytes.map(|bytes| bytes.to_vec())),
+            ),
+            buffer: vec![],
+            is_finished: false,
+            started,
+            first_token: None,
+        };
+        Ok(Box::pin(stream::unfold(events, |mut events| async move {
+            let chunk = events.next_chunk().await?;
+            Some((chunk, events))
+        })))
+    }
+
+    async fn embed(&self, model_name: String, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+        let request = self
+            .request(reqwest::Method::POST, "embeddings")?
+            .json(&EmbeddingsRequest {
+                model: model_name,
+                input: texts,
+            });
+        let response = self.send(request).await?;
+        let mut embeddings = response
+            .json::<EmbeddingsResponse>()
+            .await
+            .map_err(|err| Error::Backend(err.to_string()))?
+            .data;
+        embeddings.sort_by_key(|embedding| embedding.index);
+        Ok(embeddings
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+}
+
+/// Splits a streamed response's body into its events, as `data: {...}` lines
+struct EventStream {
+    bytes: Pin<Box<dyn Stream<Item = reqwest::Result<Vec<u8>>> + Send>>,
+    /// Bytes received after the last complete line
+    buffer: Vec<u8>,
+    is_finished: bool,
+    started: Instant,
+    /// When the first of the response's text arrived, which the generation time is counted from
+    first_token: Option<Instant>,
+}
+
+impl EventStream {
+    /// The next piece of the response, or `None` once the server says it's done
+    async fn next_chunk(&mut self) -> Option<Result<ResponseChunk>> {
+        loop {
+            if let Some(line_end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=line_end).collect();
+                match parse_line(&String::from_utf8_lossy(&line)) {
+                    Some(Ok(Some(event))) => return Some(Ok(self.response_chunk(event))),
+                    Some(Ok(None)) => return None,
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => continue,
+                }
+            }
+            if self.is_finished {
+                return None;
+            }
+            match self.bytes.next().await {
+                Some(Ok(bytes)) => self.buffer.extend(bytes),
+                Some(Err(err)) => {
+                    self.is_finished = true;
+                    return Some(Err(Error::Backend(err.to_string())));
+                }
+                None => {
+                    // The last line might not end in a newline
+                    self.is_finished = true;
+                    self.buffer.push(b'\n');
+                }
+            }
+        }
+    }
+
+    fn response_chunk(&mut self, event: StreamEvent) -> ResponseChunk {
+        let content: String = event
+            .choices
+            .into_iter()
+            .filter_map(|choice| choice.delta.content)
+            .collect();
+        if !content.is_empty() {
+            self.first_token.get_or_insert_with(Instant::now);
+        }
+        let stats = event.usage.map(|usage| ResponseStats {
+            prompt_tokens: usage.prompt_tokens,
+            generated_tokens: usage.completion_tokens,
+            generation_nanos: self
+                .first_token
+                .map(|first_token| first_token.elapsed().as_nanos() as u64)
+                .unwrap_or_default(),
+            total_nanos: self.started.elapsed().as_nanos() as u64,
+        });
+        ResponseChunk { content, stats }
+    }
+}
+
+/// Parses a line of a streamed response, `None` if it isn't an event and `Some(Ok(None))` if it's
+/// the one marking the end of the response
+fn parse_line(line: &str) -> Option<Result<Option<StreamEvent>>> {
+    let data = line.trim().strip_prefix("data:")?.trim();
+    if data == "[DONE]" {
+        return Some(Ok(None));
+    }
+    Some(
+        serde_json::from_str(data)
+            .map(Some)
+            .map_err(|err| Error::Backend(format!("couldn't read the response: {err}"))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Image;
+
+    #[test]
+    fn streamed_events_are_parsed() {
+        let event = parse_line(r#"data: {"choices":[{"delta":{"content":"Hel"}}]}"#)
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.choices[0].delta.content.as_deref(), Some("Hel"));
+        let last = parse_line(
+            r#"data: {"choices":[],"usage":{"prompt_tokens":12,"completion_tokens":3}}"#,
+        )
+        .unwrap()
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            last.usage,
+            Some(Usage {
+                prompt_tokens: 12,
+                completion_tokens: 3
+            })
+        );
+        assert!(matches!(parse_line("data: [DONE]"), Some(Ok(None))));
+        assert!(parse_line(": keep-alive").is_none());
+        assert!(parse_line("").is_none());
+        assert!(matches!(
+            parse_line("data: {"),
+            Some(Err(Error::Backend(_)))
+        ));
+    }
+
+    #[test]
+    fn images_are_sent_as_data_urls() {
+        let mut chat_message = ChatMessage::user("What's this?".to_string());
+        assert_eq!(
+            serde_json::to_value(RequestMessage::from(chat_message.clone())).unwrap(),
+            serde_json::json!({"role": "user", "content": "What's this?"})
+        );
+        chat_message.images = Some(vec![Image::from_base64("iVBORw0KGgo=")]);
+        assert_eq!(
+            serde_json::to_value(RequestMessage::from(chat_message)).unwrap(),
+            serde_json::json!({"role": "user", "content": [
+                {"type": "text", "text": "What's this?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,iVBORw0KGgo="}},
+            ]})
+        );
+    }
+
+    #[test]
+    fn the_api_version_is_kept_when_joining_endpoints() {
+        let provider = OpenAiProvider::new(&Server {
+            url: "http://localhost:8080/v1".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            provider.base_url.join("chat/completions").unwrap().as_str(),
+            "http://localhost:8080/v1/chat/completions"
+        );
+    }
+}
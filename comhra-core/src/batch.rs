@@ -0,0 +1,210 @@
+//! Runs a list of prompts one after another against a model, each as a conversation of its own,
+//! to compare the outputs or export them all for evaluating a prompt or model
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+use tokio_stream::StreamExt;
+
+use crate::backend::{Backend, Provider};
+use crate::benchmark::csv_field;
+use crate::conversation::GenerationParams;
+use crate::{ChatMessage, Error, Result};
+
+/// What the model replied to one prompt, or why it couldn't
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchResult {
+    pub prompt: String,
+    pub output: String,
+    pub error: Option<String>,
+    pub total_time: Duration,
+    /// Generation speed as measured by the server, if it reported it
+    pub tokens_per_second: Option<f64>,
+}
+
+/// Sends the prompt to the model after the system prompt, if there is one
+///
+/// A prompt that fails is given an error instead of stopping the batch.
+pub async fn run(
+    backend: Backend,
+    model: String,
+    system_prompt: Option<String>,
+    prompt: String,
+    params: GenerationParams,
+) -> BatchResult {
+    let started = Instant::now();
+    let mut messages: Vec<ChatMessage> =
+        system_prompt.into_iter().map(ChatMessage::system).collect();
+    messages.push(ChatMessage::user(prompt.clone()));
+    let mut output = String::new();
+    let mut tokens_per_second = None;
+    let generated: Result<()> = async {
+        let mut stream = backend.chat_stream(model, messages, params).await?;
+        while let Some(response_chunk) = stream.next().await {
+            let response_chunk = response_chunk?;
+            output.push_str(&response_chunk.content);
+            if let Some(stats) = response_chunk.stats {
+                tokens_per_second = stats.tokens_per_second();
+            }
+        }
+        Ok(())
+    }
+    .await;
+    BatchResult {
+        prompt,
+        output,
+        error: generated.err().map(|err| err.to_string()),
+        total_time: started.elapsed(),
+        tokens_per_second,
+    }
+}
+
+/// The prompts in a file: a column of a CSV file, by its name in the header row or the first
+/// column if it isn't named, or otherwise one per line
+pub async fn load_prompts(path: PathBuf, column: String) -> Result<Vec<String>> {
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|err| Error::Read {
+            path: path.clone(),
+            message: err.to_string(),
+        })?;
+    let is_csv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    if !is_csv {
+        return Ok(prompts_from_lines(&contents));
+    }
+    prompts_from_csv(&contents, column.trim()).map_err(|message| Error::Corrupt { path, message })
+}
+
+/// Each non-empty line as a prompt
+pub fn prompts_from_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn prompts_from_csv(csv: &str, column: &str) -> std::result::Result<Vec<String>, String> {
+    let mut rows = parse_csv(csv).into_iter();
+    let header = rows.next().ok_or("the file is empty")?;
+    let index = if column.is_empty() {
+        0
+    } else {
+        header
+            .iter()
+            .position(|name| name.trim().eq_ignore_ascii_case(column))
+            .ok_or_else(|| format!("there's no column named {column}"))?
+    };
+    Ok(rows
+        .filter_map(|mut row| (index < row.len()).then(|| row.swap_remove(index)))
+        .filter(|prompt| !prompt.trim().is_empty())
+        .collect())
+}
+
+/// Splits CSV into rows of fields, with quoted fields taking in commas, line breaks and doubled
+/// quotes
+fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut is_quoted = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if is_quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if is_quoted => is_quoted = false,
+            '"' if field.is_empty() => is_quoted = true,
+            ',' if !is_quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !is_quoted => {}
+            '\n' if !is_quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Formats the results as CSV with a header row, times in seconds
+pub fn to_csv(results: &[BatchResult]) -> String {
+    let mut csv = String::from("prompt,output,error,total_time_s,tokens_per_second\n");
+    for result in results {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{:.3},{}",
+            csv_field(&result.prompt),
+            csv_field(&result.output),
+            csv_field(result.error.as_deref().unwrap_or_default()),
+            result.total_time.as_secs_f64(),
+            result
+                .tokens_per_second
+                .map(|tokens_per_second| format!("{tokens_per_second:.2}"))
+                .unwrap_or_default(),
+        );
+    }
+    csv
+}
+
+/// Formats the results as a JSON array of objects with the same fields as the CSV
+pub fn to_json(results: &[BatchResult]) -> String {
+    let results: Vec<serde_json::Value> = results
+        .iter()
+        .map(|result| {
+            json!({
+                "prompt": result.prompt,
+                "output": result.output,
+                "error": result.error,
+                "total_time_s": result.total_time.as_secs_f64(),
+                "tokens_per_second": result.tokens_per_second,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&results).expect("JSON values can always be serialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompts_are_read_from_a_csv_column() {
+        let csv = "id,Prompt\r\n1,\"Say \"\"hi\"\", briefly\"\n2,\"Two\nlines\"\n3,\n";
+        assert_eq!(
+            prompts_from_csv(csv, "prompt").unwrap(),
+            ["Say \"hi\", briefly", "Two\nlines"]
+        );
+        assert_eq!(prompts_from_csv(csv, "").unwrap(), ["1", "2", "3"]);
+        assert!(prompts_from_csv(csv, "question").is_err());
+        assert_eq!(prompts_from_lines(" One\n\nTwo \n"), ["One", "Two"]);
+    }
+
+    #[test]
+    fn results_are_exported() {
+        let results = vec![BatchResult {
+            prompt: "Hi, there".to_string(),
+            output: "Hello".to_string(),
+            error: None,
+            total_time: Duration::from_millis(1500),
+            tokens_per_second: Some(42.0),
+        }];
+        assert_eq!(
+            to_csv(&results),
+            "prompt,output,error,total_time_s,tokens_per_second\n\"Hi, there\",Hello,,1.500,42.00\n"
+        );
+        let json: serde_json::Value = serde_json::from_str(&to_json(&results)).unwrap();
+        assert_eq!(json[0]["output"], "Hello");
+        assert_eq!(json[0]["error"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,111 @@
+//! Runs the same prompt against several models to compare their output and speed
+
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+use tokio_stream::StreamExt;
+
+use crate::backend::{Backend, Provider};
+use crate::conversation::GenerationParams;
+use crate::{ChatMessage, Result};
+
+/// How one model did on one prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkResult {
+    pub model: String,
+    pub prompt: String,
+    pub output: String,
+    /// From sending the prompt to the first chunk of the response arriving, which includes loading
+    /// the model if it wasn't already
+    pub time_to_first_token: Duration,
+    pub total_time: Duration,
+    /// Generation speed as measured by the server, if it reported it
+    pub tokens_per_second: Option<f64>,
+}
+
+/// Sends the prompt to the model as a new conversation and times the response
+pub async fn run(backend: Backend, model: String, prompt: String) -> Result<BenchmarkResult> {
+    let started = Instant::now();
+    let mut stream = backend
+        .chat_stream(
+            model.clone(),
+            vec![ChatMessage::user(prompt.clone())],
+            GenerationParams::default(),
+        )
+        .await?;
+    let mut output = String::new();
+    let mut time_to_first_token = None;
+    let mut tokens_per_second = None;
+    while let Some(response_chunk) = stream.next().await {
+        let response_chunk = response_chunk?;
+        if !response_chunk.content.is_empty() {
+            time_to_first_token.get_or_insert_with(|| started.elapsed());
+            output.push_str(&response_chunk.content);
+        }
+        if let Some(stats) = response_chunk.stats {
+            tokens_per_second = stats.tokens_per_second();
+        }
+    }
+    let total_time = started.elapsed();
+    Ok(BenchmarkResult {
+        model,
+        prompt,
+        output,
+        time_to_first_token: time_to_first_token.unwrap_or(total_time),
+        total_time,
+        tokens_per_second,
+    })
+}
+
+/// Formats the results as CSV with a header row, times in seconds
+pub fn to_csv(results: &[BenchmarkResult]) -> String {
+    let mut csv =
+        String::from("model,prompt,time_to_first_token_s,total_time_s,tokens_per_second,output\n");
+    for result in results {
+        let _ = writeln!(
+            csv,
+            "{},{},{:.3},{:.3},{},{}",
+            csv_field(&result.model),
+            csv_field(&result.prompt),
+            result.time_to_first_token.as_secs_f64(),
+            result.total_time.as_secs_f64(),
+            result
+                .tokens_per_second
+                .map(|tokens_per_second| format!("{tokens_per_second:.2}"))
+                .unwrap_or_default(),
+            csv_field(&result.output)
+        );
+    }
+    csv
+}
+
+/// Quotes the field if it has anything that would break the row up, doubling any quotes in it
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        let results = vec![BenchmarkResult {
+            model: "llama3.2".to_string(),
+            prompt: "Say \"hi\", briefly".to_string(),
+            output: "Hi\nthere".to_string(),
+            time_to_first_token: Duration::from_millis(250),
+            total_time: Duration::from_millis(1500),
+            tokens_per_second: Some(42.0),
+        }];
+        assert_eq!(
+            to_csv(&results),
+            "model,prompt,time_to_first_token_s,total_time_s,tokens_per_second,output\n\
+             llama3.2,\"Say \"\"hi\"\", briefly\",0.250,1.500,42.00,\"Hi\nthere\"\n"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! The prompt being written: undoing and redoing changes to it, and going back through the
+//! prompts sent before it the way a shell goes back through its history
+
+use std::path::PathBuf;
+
+use crate::storage::{self, write_atomically};
+use crate::{crypto, Error, Result};
+
+/// Number of changes to the prompt that can be undone
+const MAX_UNDO_STEPS: usize = 200;
+
+/// Number of sent prompts kept to go back through
+pub const MAX_SENT_PROMPTS: usize = 500;
+
+/// Consecutive changes of the same kind, undone all at once like a word typed or deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditRun {
+    Typing,
+    Deleting,
+}
+
+/// What the prompt was before each change to it, and after each change undone
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    undo: Vec<String>,
+    redo: Vec<String>,
+    run: Option<EditRun>,
+}
+
+impl UndoHistory {
+    /// Records the prompt as it was before a change, unless the change carries on the last one's
+    /// run
+    pub fn edited(&mut self, before: &str, run: Option<EditRun>) {
+        self.redo.clear();
+        if run.is_none() || run != self.run {
+            self.undo.push(before.to_string());
+            if self.undo.len() > MAX_UNDO_STEPS {
+                self.undo.remove(0);
+            }
+        }
+        self.run = run;
+    }
+
+    /// The prompt before the last change, if there's one to undo
+    pub fn undo(&mut self, current: &str) -> Option<String> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current.to_string());
+        self.run = None;
+        Some(previous)
+    }
+
+    /// The prompt after the last change undone, if there's one to redo
+    pub fn redo(&mut self, current: &str) -> Option<String> {
+        let next = self.redo.pop()?;
+        self.undo.push(current.to_string());
+        self.run = None;
+        Some(next)
+    }
+
+    pub fn clear(&mut self) {
+        *self = UndoHistory::default();
+    }
+}
+
+/// Prompts sent before, oldest first, and which of them is being looked at
+#[derive(Debug, Default)]
+pub struct PromptHistory {
+    sent: Vec<String>,
+    /// The sent prompt in the composer, and the prompt that was being written before going back
+    browsing: Option<(usize, String)>,
+}
+
+impl PromptHistory {
+    pub fn new(sent: Vec<String>) -> Self {
+        PromptHistory {
+            sent,
+            browsing: None,
+        }
+    }
+
+    pub fn sent_prompts(&self) -> &[String] {
+        &self.sent
+    }
+
+    pub fn is_browsing(&self) -> bool {
+        self.browsing.is_some()
+    }
+
+    /// Adds a prompt that's been sent, moving it to the end if it was sent before
+    pub fn push(&mut self, prompt: &str) {
+        self.browsing = None;
+        if prompt.trim().is_empty() {
+            return;
+        }
+        self.sent.retain(|sent| sent != prompt);
+        self.sent.push(prompt.to_string());
+        if self.sent.len() > MAX_SENT_PROMPTS {
+            self.sent.remove(0);
+        }
+    }
+
+    /// The prompt sent before the one being looked at, or the last one sent if going back from
+    /// the prompt being written, which is kept to come back to
+    pub fn previous(&mut self, current: &str) -> Option<&str> {
+        let index = match &self.browsing {
+            Some((index, _draft)) => index.checked_sub(1)?,
+            None => self.sent.len().checked_sub(1)?,
+        };
+        let draft = match self.browsing.take() {
+            Some((_index, draft)) => draft,
+            None => current.to_string(),
+        };
+        self.browsing = Some((index, draft));
+        Some(&self.sent[index])
+    }
+
+    /// The prompt sent after the one being looked at, or the prompt that was being written after
+    /// the last one
+    pub fn next(&mut self) -> Option<String> {
+        let (index, draft) = self.browsing.take()?;
+        match self.sent.get(index + 1) {
+            Some(prompt) => {
+                let prompt = prompt.clone();
+                self.browsing = Some((index + 1, draft));
+                Some(prompt)
+            }
+            None => Some(draft),
+        }
+    }
+
+    /// Stops looking through the history, keeping whatever's in the composer
+    pub fn stop_browsing(&mut self) {
+        self.browsing = None;
+    }
+}
+
+fn history_file() -> Result<PathBuf> {
+    Ok(storage::data_dir()?.join("prompt-history.json"))
+}
+
+/// Loads the prompts sent in earlier sessions, none if there's no history yet
+///
+/// Needs to be called after unlocking if conversations are encrypted.
+pub async fn load_history() -> Result<Vec<String>> {
+    let path = history_file()?;
+    let history_json = match tokio::fs::read(&path).await {
+        Ok(history_json) => history_json,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => {
+            return Err(Error::Read {
+                path,
+                message: err.to_string(),
+            })
+        }
+    };
+    let history_json = crypto::open(&path, history_json)?;
+    serde_json::from_slice(&history_json).map_err(|err| Error::Corrupt {
+        path,
+        message: err.to_string(),
+    })
+}
+
+pub async fn save_history(sent: Vec<String>) -> Result<()> {
+    let path = history_file()?;
+    let write_error = |message: String| Error::Write {
+        path: path.clone(),
+        message,
+    };
+    tokio::fs::create_dir_all(storage::data_dir()?)
+        .await
+        .map_err(|err| write_error(err.to_string()))?;
+    let history_json = serde_json::to_vec(&sent).map_err(|err| write_error(err.to_string()))?;
+    write_atomically(&path, crypto::seal(history_json)?)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_are_undone_at_once() {
+        let mut undo_history = UndoHistory::default();
+        undo_history.edited("", Some(EditRun::Typing));
+        undo_history.edited("H", Some(EditRun::Typing));
+        undo_history.edited("Hi", None);
+        undo_history.edited("Hi ", Some(EditRun::Typing));
+        assert_eq!(undo_history.undo("Hi there").as_deref(), Some("Hi "));
+        assert_eq!(undo_history.undo("Hi ").as_deref(), Some("Hi"));
+        assert_eq!(undo_history.undo("Hi").as_deref(), Some(""));
+        assert_eq!(undo_history.undo(""), None);
+        assert_eq!(undo_history.redo("").as_deref(), Some("Hi"));
+        undo_history.edited("Hi", Some(EditRun::Deleting));
+        assert_eq!(undo_history.redo("H"), None);
+    }
+
+    #[test]
+    fn sent_prompts_are_gone_back_through() {
+        let mut history = PromptHistory::new(vec!["first".to_string()]);
+        history.push("second");
+        history.push("first");
+        assert_eq!(history.sent_prompts(), ["second", "first"]);
+        assert_eq!(history.previous("half written"), Some("first"));
+        assert_eq!(history.previous("first"), Some("second"));
+        assert_eq!(history.previous("second"), None);
+        assert_eq!(history.next().as_deref(), Some("first"));
+        assert_eq!(history.next().as_deref(), Some("half written"));
+        assert!(!history.is_browsing());
+        assert_eq!(history.next(), None);
+    }
+}
@@ -0,0 +1,248 @@
+//! Keeping conversations within the model's context window, by estimating how many tokens they
+//! take up and condensing the earliest messages into a summary once they don't fit
+//!
+//! Token counts are estimates, as each model has a tokenizer of its own that isn't exposed.
+
+use std::borrow::Borrow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{Backend, Provider};
+use crate::conversation::GenerationParams;
+use crate::reasoning;
+use crate::{ChatMessage, Error, MessageRole, Result};
+
+/// Ollama's context length for a model when `num_ctx` isn't set
+pub const DEFAULT_CONTEXT_LENGTH: u32 = 2048;
+
+/// Share of the context window a conversation can fill before its earliest messages are
+/// summarised, leaving the rest for the response
+pub const SUMMARY_THRESHOLD: f32 = 0.75;
+
+/// Latest messages that are always sent in full, so the model has the exchange it's answering
+const KEPT_MESSAGES: usize = 4;
+
+/// Tokens each message takes for its role and the chat template around it
+const MESSAGE_OVERHEAD: u32 = 4;
+
+/// Tokens left for the summary in the context window, as it's asked to be 200 words at most
+const SUMMARY_TOKENS: u32 = 300;
+
+/// Asked after a transcript of the messages being summarised
+const SUMMARY_INSTRUCTION: &str = "Summarise the conversation above in at most 200 words, \
+    keeping any names, numbers, decisions and code details that could be needed later. \
+    Reply with only the summary.";
+
+/// Starts the system message the summary is sent in
+const SUMMARY_HEADING: &str = "Summary of the earlier part of this conversation:";
+
+/// A summary of a conversation's earliest messages, sent in their place
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextSummary {
+    /// How many messages from the start it covers
+    pub covers: usize,
+    /// Identifies the messages it covers, so it stops being used once any are edited or deleted
+    pub fingerprint: u64,
+    pub text: String,
+}
+
+impl ContextSummary {
+    /// Whether it still sums up the start of the conversation
+    pub fn applies_to<M: Borrow<ChatMessage>>(&self, messages: &[M]) -> bool {
+        self.covers <= messages.len() && fingerprint(&messages[..self.covers]) == self.fingerprint
+    }
+}
+
+/// Roughly four characters to a token, which is about right for English
+pub fn estimate_tokens(text: &str) -> u32 {
+    (text.chars().count() as u32).div_ceil(4)
+}
+
+pub fn estimate_message(chat_message: &ChatMessage) -> u32 {
+    estimate_tokens(&chat_message.content) + MESSAGE_OVERHEAD
+}
+
+pub fn estimate_messages<M: Borrow<ChatMessage>>(messages: &[M]) -> u32 {
+    messages
+        .iter()
+        .map(|chat_message| estimate_message(chat_message.borrow()))
+        .sum()
+}
+
+/// Estimates the tokens the messages take up as they're sent, with the summary in place of those
+/// it covers
+pub fn estimate_sent<M: Borrow<ChatMessage>>(
+    messages: &[M],
+    summary: Option<&ContextSummary>,
+) -> u32 {
+    let Some(summary) = summary.filter(|summary| summary.applies_to(messages)) else {
+        return estimate_messages(messages);
+    };
+    let system_messages: u32 = messages[..summary.covers]
+        .iter()
+        .map(Borrow::borrow)
+        .filter(|chat_message| chat_message.role == MessageRole::System)
+        .map(estimate_message)
+        .sum();
+    system_messages
+        + estimate_tokens(SUMMARY_HEADING)
+        + estimate_tokens(&summary.text)
+        + MESSAGE_OVERHEAD
+        + estimate_messages(&messages[summary.covers..])
+}
+
+/// FNV-1a over the roles and contents, which unlike std's hashers stays the same between builds
+fn fingerprint<M: Borrow<ChatMessage>>(messages: &[M]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for chat_message in messages.iter().map(Borrow::borrow) {
+        let role = [u8::from(chat_message.role == MessageRole::Assistant)];
+        for byte in role.iter().chain(chat_message.content.as_bytes()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// The messages as they're sent to the model, with the summary in place of those it covers and
+/// the system messages among them kept
+pub fn with_summary(
+    messages: &[ChatMessage],
+    summary: Option<&ContextSummary>,
+) -> Vec<ChatMessage> {
+    let Some(summary) = summary.filter(|summary| summary.applies_to(messages)) else {
+        return messages.to_vec();
+    };
+    messages[..summary.covers]
+        .iter()
+        .filter(|chat_message| chat_message.role == MessageRole::System)
+        .cloned()
+        .chain(std::iter::once(ChatMessage::system(format!(
+            "{SUMMARY_HEADING}\n\n{}",
+            summary.text
+        ))))
+        .chain(messages[summary.covers..].iter().cloned())
+        .collect()
+}
+
+/// How many of the earliest messages need summarising for the rest to fit comfortably in the
+/// context window, if it's getting full, always ending on a response so whole exchanges are
+/// summarised
+pub fn messages_to_summarize(messages: &[ChatMessage], context_length: u32) -> Option<usize> {
+    let budget = (context_length as f32 * SUMMARY_THRESHOLD) as u32;
+    let mut remaining = estimate_messages(messages);
+    if remaining <= budget {
+        return None;
+    }
+    let budget = budget.saturating_sub(SUMMARY_TOKENS);
+    let mut covers = None;
+    for index in 0..messages.len().saturating_sub(KEPT_MESSAGES) {
+        // System messages are sent either way
+        if messages[index].role != MessageRole::System {
+            remaining -= estimate_message(&messages[index]);
+        }
+        if messages[index + 1].role == MessageRole::User {
+            covers = Some(index + 1);
+            if remaining <= budget {
+                break;
+            }
+        }
+    }
+    covers
+}
+
+/// Asks the model to summarise the first `covers` messages, building on the summary there already
+/// is of the start of them
+pub async fn summarize(
+    backend: Backend,
+    model: String,
+    messages: Vec<ChatMessage>,
+    covers: usize,
+    previous: Option<ContextSummary>,
+) -> Result<ContextSummary> {
+    let previous = previous
+        .filter(|previous| previous.covers <= covers && previous.applies_to(&messages[..covers]));
+    let mut transcript = String::new();
+    if let Some(previous) = previous.as_ref() {
+        transcript.push_str(&format!("{SUMMARY_HEADING}\n\n{}\n\n", previous.text));
+    }
+    let summarised = previous.as_ref().map_or(0, |previous| previous.covers);
+    for chat_message in &messages[summarised..covers] {
+        let speaker = match chat_message.role {
+            MessageRole::User => "User",
+            MessageRole::Assistant => "Assistant",
+            _ => continue,
+        };
+        transcript.push_str(&format!("{speaker}: {}\n\n", chat_message.content));
+    }
+    transcript.push_str(SUMMARY_INSTRUCTION);
+    let response = backend
+        .chat(
+            model,
+            vec![ChatMessage::user(transcript)],
+            GenerationParams::default(),
+        )
+        .await?;
+    // Reasoning models think out loud before answering
+    let text = reasoning::answer(&response).trim();
+    if text.is_empty() {
+        return Err(Error::Backend(
+            "the model didn't reply with a summary".to_string(),
+        ));
+    }
+    Ok(ContextSummary {
+        covers,
+        fingerprint: fingerprint(&messages[..covers]),
+        text: text.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(count: usize, length: usize) -> Vec<ChatMessage> {
+        let mut messages = vec![ChatMessage::system("Be brief.".to_string())];
+        for _ in 0..count {
+            messages.push(ChatMessage::user("q".repeat(length)));
+            messages.push(ChatMessage::assistant("a".repeat(length)));
+        }
+        messages
+    }
+
+    #[test]
+    fn whole_exchanges_are_summarised_once_the_window_fills() {
+        assert_eq!(estimate_tokens("Dia duit"), 2);
+        assert_eq!(messages_to_summarize(&exchange(3, 400), 2048), None);
+        // Each message is about 100 tokens, so 1536 tokens fit before summarising and 1236 after
+        let messages = exchange(10, 400);
+        let covers = messages_to_summarize(&messages, 2048).unwrap();
+        assert_eq!(messages[covers].role, MessageRole::User);
+        assert!(estimate_messages(&messages[covers..]) <= 1236);
+        assert!(covers <= messages.len() - KEPT_MESSAGES);
+    }
+
+    #[test]
+    fn summaries_replace_what_they_cover_until_it_changes() {
+        let mut messages = exchange(3, 10);
+        let summary = ContextSummary {
+            covers: 3,
+            fingerprint: fingerprint(&messages[..3]),
+            text: "They said hello.".to_string(),
+        };
+        let sent = with_summary(&messages, Some(&summary));
+        assert_eq!(sent.len(), messages.len() - 3 + 2);
+        assert_eq!(sent[0].content, "Be brief.");
+        assert!(sent[1].content.ends_with("They said hello."));
+        assert_eq!(
+            estimate_sent(&messages, Some(&summary)),
+            estimate_messages(&sent)
+        );
+        assert_eq!(sent[2].content, messages[3].content);
+        messages[1].content = "Edited".to_string();
+        assert_eq!(
+            with_summary(&messages, Some(&summary)).len(),
+            messages.len()
+        );
+    }
+}
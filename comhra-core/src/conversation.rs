@@ -0,0 +1,498 @@
+//! A conversation as it's saved, with the options its responses are generated with
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use ollama_rs::generation::chat::ChatMessageFinalResponseData;
+use ollama_rs::generation::options::GenerationOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::context::ContextSummary;
+use crate::knowledge::Citation;
+use crate::structured::OutputFormat;
+use crate::tools::ToolExchange;
+use crate::web_search::WebSource;
+use crate::ChatMessage;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conversation {
+    #[serde(default)]
+    pub params: GenerationParams,
+    pub messages: Vec<ChatMessage>,
+    /// Statistics of the responses generated since they started being recorded, by the index of
+    /// the response in `messages`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stats: BTreeMap<usize, ResponseStats>,
+    /// Folder the conversation is filed under in the sidebar
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Whether it's pinned to the top of the sidebar or archived out of the way
+    #[serde(default, skip_serializing_if = "Listing::is_default")]
+    pub listing: Listing,
+    /// Name of the model its responses were last generated with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Other versions of the conversation from a message on, by the index of that message
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub branches: BTreeMap<usize, Branches>,
+    /// Folder of documents whose relevant passages are sent along with each prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub knowledge_dir: Option<PathBuf>,
+    /// Passages from the knowledge folder each response was given, by the index of the response
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub citations: BTreeMap<usize, Vec<Citation>>,
+    /// Pages from the web each response was given, by the index of the response
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub web_sources: BTreeMap<usize, Vec<WebSource>>,
+    /// URL of the server its responses were last generated with, switched to when it's opened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// When each message was sent, or for responses when they finished arriving, by the index of
+    /// the message, for the messages it's known for
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sent_at: BTreeMap<usize, SystemTime>,
+    /// Summary of the earliest messages, sent in their place once they didn't fit in the context
+    /// window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<ContextSummary>,
+    /// Tools the model called while writing each response, by the index of the response
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tool_calls: BTreeMap<usize, Vec<ToolExchange>>,
+    /// What each response asked to be written as JSON was asked to be, by the index of the
+    /// response
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub formats: BTreeMap<usize, OutputFormat>,
+    /// Language responses are asked to be written in, by its English name, or whatever the model
+    /// answers in if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_language: Option<String>,
+}
+
+/// Where a conversation is listed in the sidebar
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Listing {
+    /// With the others, most recently used first
+    #[default]
+    Listed,
+    /// Above the others
+    Pinned,
+    /// In a section of its own that's kept collapsed
+    Archived,
+}
+
+impl Listing {
+    fn is_default(&self) -> bool {
+        *self == Listing::default()
+    }
+}
+
+/// A conversation from some message on, along with the versions that branch off it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Branch {
+    pub messages: Vec<ChatMessage>,
+    /// Statistics of its responses, by index in `messages`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stats: BTreeMap<usize, ResponseStats>,
+    /// The rest of what's kept about its messages, like [`Conversation`]'s, by index in `messages`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub citations: BTreeMap<usize, Vec<Citation>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub web_sources: BTreeMap<usize, Vec<WebSource>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sent_at: BTreeMap<usize, SystemTime>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tool_calls: BTreeMap<usize, Vec<ToolExchange>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub formats: BTreeMap<usize, OutputFormat>,
+    /// Versions of it from a later message on, by index in `messages`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub branches: BTreeMap<usize, Branches>,
+}
+
+/// The versions of a conversation that part ways at a message, kept when a response is
+/// regenerated or a message is edited
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Branches {
+    /// Where the version being shown comes among the others
+    pub position: usize,
+    /// Every version other than the one being shown, in order
+    pub others: Vec<Branch>,
+}
+
+/// Sampling options passed to the model, each left to the model's own default when not set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f32>,
+    /// Makes responses reproducible for the same prompt
+    pub seed: Option<i32>,
+    /// Context length in tokens
+    pub num_ctx: Option<u32>,
+}
+
+/// What the server reported about generating a response, once it finished
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResponseStats {
+    /// Tokens of the conversation the server had to read, which leaves out any it had cached
+    pub prompt_tokens: u32,
+    pub generated_tokens: u32,
+    /// Time spent generating the response's tokens, in nanoseconds
+    pub generation_nanos: u64,
+    /// Time spent on the whole request including loading the model, in nanoseconds
+    pub total_nanos: u64,
+}
+
+impl Conversation {
+    pub fn new(messages: Vec<ChatMessage>) -> Self {
+        Self {
+            params: GenerationParams::default(),
+            messages,
+            stats: BTreeMap::new(),
+            folder: None,
+            tags: Vec::new(),
+            listing: Listing::default(),
+            model: None,
+            branches: BTreeMap::new(),
+            knowledge_dir: None,
+            citations: BTreeMap::new(),
+            web_sources: BTreeMap::new(),
+            server: None,
+            sent_at: BTreeMap::new(),
+            summary: None,
+            tool_calls: BTreeMap::new(),
+            formats: BTreeMap::new(),
+            response_language: None,
+        }
+    }
+
+    /// Reads a conversation file's JSON, which was only the list of messages before generation
+    /// params were saved with it
+    pub(crate) fn from_json(conversation_json: &[u8]) -> serde_json::Result<Self> {
+        let is_message_list = conversation_json
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            == Some(&b'[');
+        if is_message_list {
+            serde_json::from_slice(conversation_json).map(Self::new)
+        } else {
+            serde_json::from_slice(conversation_json)
+        }
+    }
+}
+
+/// Reads tags typed as a comma separated list, leaving out blanks and repeats
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim) {
+        if !tag.is_empty() && !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Reads a folder name, where a blank one leaves the conversation unfiled
+pub fn parse_folder(text: &str) -> Option<String> {
+    let folder = text.trim();
+    (!folder.is_empty()).then(|| folder.to_string())
+}
+
+impl Branches {
+    /// Number of versions, including the one being shown
+    pub fn count(&self) -> usize {
+        self.others.len() + 1
+    }
+}
+
+impl Branch {
+    /// Takes the messages from `index` on off the end, with everything kept about them and their
+    /// branches
+    ///
+    /// Versions parting at `index` itself are left behind, as they're alternatives to what was
+    /// taken rather than part of it.
+    pub fn split_off(&mut self, index: usize) -> Branch {
+        let messages = self.messages.split_off(index.min(self.messages.len()));
+        let branches = self
+            .branches
+            .split_off(&(index + 1))
+            .into_iter()
+            .map(|(branch_index, branches)| (branch_index - index, branches))
+            .collect();
+        Branch {
+            messages,
+            stats: split_by_index(&mut self.stats, index),
+            citations: split_by_index(&mut self.citations, index),
+            web_sources: split_by_index(&mut self.web_sources, index),
+            sent_at: split_by_index(&mut self.sent_at, index),
+            tool_calls: split_by_index(&mut self.tool_calls, index),
+            formats: split_by_index(&mut self.formats, index),
+            branches,
+        }
+    }
+
+    /// Adds a branch's messages, everything kept about them and its branches to the end
+    pub fn append(&mut self, branch: Branch) {
+        let offset = self.messages.len();
+        self.messages.extend(branch.messages);
+        append_by_index(&mut self.stats, branch.stats, offset);
+        append_by_index(&mut self.citations, branch.citations, offset);
+        append_by_index(&mut self.web_sources, branch.web_sources, offset);
+        append_by_index(&mut self.sent_at, branch.sent_at, offset);
+        append_by_index(&mut self.tool_calls, branch.tool_calls, offset);
+        append_by_index(&mut self.formats, branch.formats, offset);
+        append_by_index(&mut self.branches, branch.branches, offset);
+    }
+
+    /// Removes the messages from `index` on, keeping them as another version to go back to,
+    /// with whatever replaces them coming last
+    pub fn keep_version(&mut self, index: usize) {
+        let replaced = self.split_off(index);
+        let branches = self.branches.entry(index).or_default();
+        branches
+            .others
+            .insert(branches.position.min(branches.others.len()), replaced);
+        branches.position = branches.others.len();
+    }
+
+    /// Shows another version of the conversation from `index` on, where `version` is its
+    /// position among all of them
+    pub fn switch_version(&mut self, index: usize, version: usize) {
+        let is_valid = self
+            .branches
+            .get(&index)
+            .is_some_and(|branches| version < branches.count() && version != branches.position);
+        if !is_valid {
+            return;
+        }
+        let shown = self.split_off(index);
+        let branches = self
+            .branches
+            .get_mut(&index)
+            .expect("checked there are branches at the index");
+        branches.others.insert(branches.position, shown);
+        let chosen = branches.others.remove(version);
+        branches.position = version;
+        self.append(chosen);
+    }
+
+    /// Removes a single message, along with any other versions that part from it
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.messages.len() {
+            return;
+        }
+        self.branches.remove(&index);
+        let mut rest = self.split_off(index);
+        rest.messages.remove(0);
+        // Everything kept about the messages after it moves up one
+        let rest = Branch {
+            messages: rest.messages,
+            stats: split_by_index(&mut rest.stats, 1),
+            citations: split_by_index(&mut rest.citations, 1),
+            web_sources: split_by_index(&mut rest.web_sources, 1),
+            sent_at: split_by_index(&mut rest.sent_at, 1),
+            tool_calls: split_by_index(&mut rest.tool_calls, 1),
+            formats: split_by_index(&mut rest.formats, 1),
+            branches: split_by_index(&mut rest.branches, 1),
+        };
+        self.append(rest);
+    }
+}
+
+/// Takes the values from `index` on, rebased to count from it
+fn split_by_index<V>(values: &mut BTreeMap<usize, V>, index: usize) -> BTreeMap<usize, V> {
+    values
+        .split_off(&index)
+        .into_iter()
+        .map(|(value_index, value)| (value_index - index, value))
+        .collect()
+}
+
+fn append_by_index<V>(
+    values: &mut BTreeMap<usize, V>,
+    appended: BTreeMap<usize, V>,
+    offset: usize,
+) {
+    values.extend(
+        appended
+            .into_iter()
+            .map(|(index, value)| (index + offset, value)),
+    );
+}
+
+impl ResponseStats {
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        (self.generation_nanos > 0).then(|| {
+            f64::from(self.generated_tokens)
+                / Duration::from_nanos(self.generation_nanos).as_secs_f64()
+        })
+    }
+
+    pub fn total_time(&self) -> Duration {
+        Duration::from_nanos(self.total_nanos)
+    }
+
+    /// Every token the server read or wrote for the response
+    pub fn total_tokens(&self) -> u32 {
+        self.prompt_tokens + self.generated_tokens
+    }
+}
+
+impl From<&ChatMessageFinalResponseData> for ResponseStats {
+    fn from(final_data: &ChatMessageFinalResponseData) -> Self {
+        Self {
+            prompt_tokens: final_data.prompt_eval_count.into(),
+            generated_tokens: final_data.eval_count.into(),
+            generation_nanos: final_data.eval_duration,
+            total_nanos: final_data.total_duration,
+        }
+    }
+}
+
+impl GenerationParams {
+    pub(crate) fn to_options(self) -> GenerationOptions {
+        let mut options = GenerationOptions::default();
+        if let Some(temperature) = self.temperature {
+            options = options.temperature(temperature);
+        }
+        if let Some(top_p) = self.top_p {
+            options = options.top_p(top_p);
+        }
+        if let Some(top_k) = self.top_k {
+            options = options.top_k(top_k);
+        }
+        if let Some(repeat_penalty) = self.repeat_penalty {
+            options = options.repeat_penalty(repeat_penalty);
+        }
+        if let Some(seed) = self.seed {
+            options = options.seed(seed);
+        }
+        if let Some(num_ctx) = self.num_ctx {
+            options = options.num_ctx(num_ctx);
+        }
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversations_saved_as_message_lists_still_load() {
+        let conversation =
+            Conversation::from_json(br#" [{"role":"user","content":"Hi","images":null}]"#).unwrap();
+        assert_eq!(conversation.messages[0].content, "Hi");
+        assert_eq!(conversation.params, GenerationParams::default());
+
+        let conversation = Conversation::from_json(
+            br#"{"params":{"temperature":0.2},"messages":[{"role":"user","content":"Hi","images":null}]}"#,
+        )
+        .unwrap();
+        assert_eq!(conversation.params.temperature, Some(0.2));
+        assert_eq!(conversation.messages.len(), 1);
+        assert!(conversation.stats.is_empty());
+    }
+
+    #[test]
+    fn stats_measure_generation_speed() {
+        let stats = ResponseStats {
+            prompt_tokens: 30,
+            generated_tokens: 50,
+            generation_nanos: 2_000_000_000,
+            total_nanos: 2_500_000_000,
+        };
+        assert_eq!(stats.tokens_per_second(), Some(25.0));
+        assert_eq!(stats.total_time(), Duration::from_millis(2500));
+        assert_eq!(stats.total_tokens(), 80);
+        assert_eq!(ResponseStats::default().tokens_per_second(), None);
+    }
+
+    #[test]
+    fn tags_and_folders_are_tidied() {
+        assert_eq!(
+            parse_tags(" work, rust,, work ,ideas "),
+            ["work", "rust", "ideas"]
+        );
+        assert!(parse_tags(" , ").is_empty());
+        assert_eq!(parse_folder("  Projects "), Some("Projects".to_string()));
+        assert_eq!(parse_folder("   "), None);
+    }
+
+    fn contents(branch: &Branch) -> Vec<&str> {
+        branch
+            .messages
+            .iter()
+            .map(|chat_message| chat_message.content.as_str())
+            .collect()
+    }
+
+    fn branch(contents: &[&str]) -> Branch {
+        Branch {
+            messages: contents
+                .iter()
+                .map(|content| ChatMessage::user(content.to_string()))
+                .collect(),
+            ..Branch::default()
+        }
+    }
+
+    #[test]
+    fn versions_can_be_kept_and_switched_between() {
+        let mut conversation = branch(&["Question", "First answer"]);
+        conversation.stats.insert(1, ResponseStats::default());
+        conversation.keep_version(1);
+        conversation.append(branch(&["Second answer"]));
+        assert_eq!(conversation.branches[&1].position, 1);
+        assert_eq!(conversation.branches[&1].count(), 2);
+        assert!(conversation.stats.is_empty());
+
+        conversation.switch_version(1, 0);
+        assert_eq!(contents(&conversation), ["Question", "First answer"]);
+        assert!(conversation.stats.contains_key(&1));
+        conversation.switch_version(1, 1);
+        assert_eq!(contents(&conversation), ["Question", "Second answer"]);
+        assert!(conversation.stats.is_empty());
+
+        // Editing the question keeps both answers with the old question
+        conversation.keep_version(0);
+        conversation.append(branch(&["Edited question", "Third answer"]));
+        assert_eq!(conversation.branches.len(), 1);
+        conversation.switch_version(0, 0);
+        assert_eq!(contents(&conversation), ["Question", "Second answer"]);
+        assert_eq!(conversation.branches[&1].count(), 2);
+        assert_eq!(conversation.branches[&0].count(), 2);
+
+        conversation.remove(0);
+        assert_eq!(contents(&conversation), ["Second answer"]);
+        assert_eq!(conversation.branches[&0].count(), 2);
+    }
+
+    #[test]
+    fn what_is_kept_about_messages_goes_with_their_version() {
+        let sent_at = SystemTime::UNIX_EPOCH + Duration::from_secs(60);
+        let mut conversation = branch(&["Question", "First answer"]);
+        conversation.sent_at.insert(0, sent_at);
+        conversation.sent_at.insert(1, sent_at);
+        conversation.formats.insert(1, OutputFormat::Json);
+        conversation.keep_version(1);
+        conversation.append(branch(&["Second answer"]));
+        assert!(conversation.formats.is_empty());
+        assert_eq!(conversation.sent_at.len(), 1);
+
+        conversation.switch_version(1, 0);
+        assert_eq!(conversation.formats.get(&1), Some(&OutputFormat::Json));
+        assert_eq!(conversation.sent_at.get(&1), Some(&sent_at));
+
+        conversation.remove(0);
+        assert_eq!(conversation.formats.get(&0), Some(&OutputFormat::Json));
+        assert_eq!(conversation.sent_at.get(&0), Some(&sent_at));
+        assert_eq!(conversation.sent_at.len(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+//! A message's text as it's copied, either the Markdown it was written in, that rendered down to
+//! plain text, or only its code
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    Markdown,
+    /// Without the formatting, for pasting somewhere that doesn't render Markdown
+    PlainText,
+    /// The code blocks one after another, for pasting into an editor
+    Code,
+}
+
+impl CopyFormat {
+    pub const ALL: [Self; 3] = [Self::Markdown, Self::PlainText, Self::Code];
+}
+
+pub fn text(markdown: &str, format: CopyFormat) -> String {
+    match format {
+        CopyFormat::Markdown => markdown.to_string(),
+        CopyFormat::PlainText => plain_text(markdown),
+        CopyFormat::Code => code(markdown),
+    }
+}
+
+/// The text of the Markdown laid out as it's rendered, with lists still bulleted or numbered and
+/// code blocks kept as they are
+fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    // The next number of each list being written, or `None` for bulleted ones
+    let mut lists: Vec<Option<u64>> = vec![];
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::List(first_number)) => lists.push(first_number),
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    end_block(&mut text);
+                }
+            }
+            Event::Start(Tag::Item) => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(number)) => {
+                        text.push_str(&format!("{number}. "));
+                        *number += 1;
+                    }
+                    _ => text.push_str("- "),
+                }
+            }
+            Event::Text(content) | Event::Code(content) => text.push_str(&content),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock | TagEnd::BlockQuote,
+            ) if lists.is_empty() => end_block(&mut text),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
+
+/// Leaves a blank line after the block just written
+fn end_block(text: &mut String) {
+    text.truncate(text.trim_end_matches('\n').len());
+    if !text.is_empty() {
+        text.push_str("\n\n");
+    }
+}
+
+/// Every code block, including ones in lists, with a blank line between each
+fn code(markdown: &str) -> String {
+    let mut code_blocks: Vec<String> = vec![];
+    let mut in_code_block = false;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code_block = true;
+                code_blocks.push(String::new());
+            }
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(content) if in_code_block => {
+                if let Some(code_block) = code_blocks.last_mut() {
+                    code_block.push_str(&content);
+                }
+            }
+            _ => {}
+        }
+    }
+    code_blocks
+        .iter()
+        .map(|code_block| code_block.trim_end_matches('\n'))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = "# Sorting\n\nUse **`sort`** on the\nvector:\n\n\
+        ```rust\nv.sort();\n```\n\n1. It's *stable*\n2. It's [fast](https://example.ie)\n\
+        \x20  - Mostly\n\nOr:\n\n    v.sort_unstable();\n";
+
+    #[test]
+    fn markdown_is_copied_as_plain_text() {
+        assert_eq!(
+            text(RESPONSE, CopyFormat::PlainText),
+            "Sorting\n\nUse sort on the vector:\n\nv.sort();\n\n1. It's stable\n2. It's fast\n  \
+            - Mostly\n\nOr:\n\nv.sort_unstable();"
+        );
+        assert_eq!(text(RESPONSE, CopyFormat::Markdown), RESPONSE);
+    }
+
+    #[test]
+    fn only_code_is_copied() {
+        assert_eq!(
+            text(RESPONSE, CopyFormat::Code),
+            "v.sort();\n\nv.sort_unstable();"
+        );
+        assert_eq!(text("No code here", CopyFormat::Code), "");
+    }
+}
@@ -0,0 +1,237 @@
+//! Optional passphrase-based encryption of conversations and the recovery file at rest
+//!
+//! The key is derived from the passphrase with Argon2 and only ever kept in memory. Encrypted
+//! files start with [`MAGIC`], followed by a random XChaCha20-Poly1305 nonce and the ciphertext.
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, write_atomically};
+use crate::{history, Error, Result};
+
+const MAGIC: &[u8] = b"comhra-encrypted-v1\n";
+const NONCE_LENGTH: usize = 24;
+const SALT_LENGTH: usize = 16;
+/// Encrypted into the key file so a wrong passphrase is caught before any conversation is read
+const CHECK_PLAINTEXT: &[u8] = b"comhra";
+
+/// The unlocked key, shared by everything that reads or writes conversations
+static KEY: RwLock<Option<Key>> = RwLock::new(None);
+
+#[derive(Clone)]
+struct Key([u8; 32]);
+
+/// Everything needed to check a passphrase, stored next to the conversations' data
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    salt: Vec<u8>,
+    check: Vec<u8>,
+}
+
+#[cfg(not(test))]
+fn key_file() -> Result<PathBuf> {
+    Ok(storage::data_dir()?.join("encryption.json"))
+}
+
+#[cfg(test)]
+fn key_file() -> Result<PathBuf> {
+    Ok(crate::test_support::key_file())
+}
+
+/// Whether conversations have been encrypted and need a passphrase to read
+pub fn is_enabled() -> bool {
+    key_file().is_ok_and(|path| path.exists())
+}
+
+pub fn is_unlocked() -> bool {
+    KEY.read().is_ok_and(|key| key.is_some())
+}
+
+fn current_key() -> Option<Key> {
+    KEY.read().ok().and_then(|key| key.clone())
+}
+
+fn set_key(key: Option<Key>) {
+    if let Ok(mut current_key) = KEY.write() {
+        *current_key = key;
+    }
+}
+
+impl Key {
+    fn derive(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        let mut key = [0; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|_| Error::WrongPassphrase)?;
+        Ok(Self(key))
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&self.0.into())
+            .encrypt(&nonce, plaintext)
+            .expect("encrypting into a Vec can't run out of space");
+        [MAGIC, nonce.as_slice(), &ciphertext].concat()
+    }
+
+    /// Returns `None` if the data was tampered with or encrypted with a different key
+    fn decrypt(&self, encrypted: &[u8]) -> Option<Vec<u8>> {
+        let encrypted = encrypted.strip_prefix(MAGIC)?;
+        if encrypted.len() < NONCE_LENGTH {
+            return None;
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+        XChaCha20Poly1305::new(&self.0.into())
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .ok()
+    }
+}
+
+/// Encrypts the contents of a file about to be written if encryption is on, failing if it hasn't
+/// been unlocked yet rather than writing them unencrypted
+pub(crate) fn seal(contents: Vec<u8>) -> Result<Vec<u8>> {
+    match current_key() {
+        Some(key) => Ok(key.encrypt(&contents)),
+        None if is_enabled() => Err(Error::Locked),
+        None => Ok(contents),
+    }
+}
+
+/// Whether contents were sealed the way [`seal`] would seal them now, i.e. encrypted only if
+/// encryption is unlocked
+pub(crate) fn is_sealed_as_current(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC) == is_unlocked()
+}
+
+/// Decrypts the contents of a file that was read, passing unencrypted files through unchanged
+pub(crate) fn open(path: &Path, contents: Vec<u8>) -> Result<Vec<u8>> {
+    if !contents.starts_with(MAGIC) {
+        return Ok(contents);
+    }
+    current_key()
+        .ok_or(Error::Locked)?
+        .decrypt(&contents)
+        .ok_or_else(|| Error::Corrupt {
+            path: path.to_path_buf(),
+            message: "it couldn't be decrypted".to_string(),
+        })
+}
+
+async fn read_key_file() -> Result<KeyFile> {
+    let path = key_file()?;
+    let key_json = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|err| Error::Read {
+            path: path.clone(),
+            message: err.to_string(),
+        })?;
+    serde_json::from_str(&key_json).map_err(|err| Error::Corrupt {
+        path,
+        message: err.to_string(),
+    })
+}
+
+/// Derives the key from the passphrase and keeps it in memory if it's the right one
+pub async fn unlock(passphrase: String) -> Result<()> {
+    let key_file = read_key_file().await?;
+    let key = Key::derive(&passphrase, &key_file.salt)?;
+    if key.decrypt(&key_file.check).as_deref() != Some(CHECK_PLAINTEXT) {
+        return Err(Error::WrongPassphrase);
+    }
+    set_key(Some(key));
+    Ok(())
+}
+
+/// Encrypts every conversation in `conversations_dir`, and everything saved from now on, with
+/// a key derived from the passphrase
+///
+/// Their history is deleted, as the versions in it were saved unencrypted.
+pub async fn enable(passphrase: String, conversations_dir: PathBuf) -> Result<()> {
+    let mut salt = [0; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let key = Key::derive(&passphrase, &salt)?;
+    let key_file_json = serde_json::to_string(&KeyFile {
+        salt: salt.to_vec(),
+        check: key.encrypt(CHECK_PLAINTEXT),
+    })
+    .expect("the key file can always be serialized");
+    let path = key_file()?;
+    let write_error = |err: std::io::Error| Error::Write {
+        path: path.clone(),
+        message: err.to_string(),
+    };
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await.map_err(write_error)?;
+    }
+    write_atomically(&path, key_file_json)
+        .await
+        .map_err(write_error)?;
+    set_key(Some(key));
+    rewrite_conversations(&conversations_dir, || {}).await?;
+    history::forget(&conversations_dir).await
+}
+
+/// Decrypts every conversation in `conversations_dir` and forgets the key, which has to be
+/// unlocked first
+pub async fn disable(conversations_dir: PathBuf) -> Result<()> {
+    let Some(key) = current_key() else {
+        return Err(Error::Locked);
+    };
+    let path = key_file()?;
+    // Set aside so conversations are saved unencrypted, and only removed once they all are, as
+    // it's needed to unlock any that weren't
+    let set_aside = path.with_extension("json.disabling");
+    let write_error = |path: &Path, err: std::io::Error| Error::Write {
+        path: path.to_path_buf(),
+        message: err.to_string(),
+    };
+    tokio::fs::rename(&path, &set_aside)
+        .await
+        .map_err(|err| write_error(&path, err))?;
+    if let Err(err) = rewrite_conversations(&conversations_dir, || set_key(None)).await {
+        let _ = tokio::fs::rename(&set_aside, &path).await;
+        set_key(Some(key));
+        return Err(err);
+    }
+    tokio::fs::remove_file(&set_aside)
+        .await
+        .map_err(|err| write_error(&set_aside, err))
+}
+
+/// Reads every conversation, calls `before_saving` and saves them all back
+async fn rewrite_conversations(
+    conversations_dir: &Path,
+    before_saving: impl FnOnce(),
+) -> Result<()> {
+    let mut conversations = vec![];
+    for path in storage::list_conversations(conversations_dir).await? {
+        let conversation = storage::load_conversation(path.clone()).await?;
+        conversations.push((path, conversation));
+    }
+    before_saving();
+    for (path, conversation) in conversations {
+        storage::save_conversation(path, conversation).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypted_contents_only_open_with_the_same_key() {
+        let key = Key::derive("correct horse", b"0123456789abcdef").unwrap();
+        let encrypted = key.encrypt(b"secret");
+        assert!(encrypted.starts_with(MAGIC));
+        assert_eq!(key.decrypt(&encrypted).as_deref(), Some(&b"secret"[..]));
+        let other_key = Key::derive("battery staple", b"0123456789abcdef").unwrap();
+        assert_eq!(other_key.decrypt(&encrypted), None);
+    }
+}
@@ -0,0 +1,787 @@
+//! Conversations kept in a SQLite database in the conversations dir, with a row for each message
+//! so saving only writes the messages that changed
+//!
+//! Message contents, attached images and everything about a conversation other than its title
+//! are sealed with [`crypto::seal`], so encryption covers the database the same way it covered
+//! conversation files.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::context::ContextSummary;
+use crate::conversation::{Branches, Conversation, GenerationParams, Listing, ResponseStats};
+use crate::knowledge::Citation;
+use crate::structured::OutputFormat;
+use crate::tools::ToolExchange;
+use crate::usage::MessageRecord;
+use crate::web_search::WebSource;
+use crate::{crypto, storage, ChatMessage, Error, Image, MessageRole, Result};
+
+pub(crate) const DATABASE_FILE_NAME: &str = "conversations.sqlite3";
+
+/// Stored in `user_version` once conversation files have been imported, which is left at 0 until
+/// then so the import is tried again if it couldn't be done
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS conversations (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL UNIQUE,
+        model TEXT,
+        details BLOB NOT NULL,
+        created_at INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        conversation_id INTEGER NOT NULL REFERENCES conversations (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        content BLOB NOT NULL,
+        images BLOB,
+        stats TEXT,
+        model TEXT,
+        created_at INTEGER NOT NULL,
+        PRIMARY KEY (conversation_id, position)
+    );
+";
+
+/// Everything about a conversation other than its messages, sealed together
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Details {
+    params: GenerationParams,
+    folder: Option<String>,
+    tags: Vec<String>,
+    listing: Listing,
+    branches: BTreeMap<usize, Branches>,
+    knowledge_dir: Option<PathBuf>,
+    citations: BTreeMap<usize, Vec<Citation>>,
+    web_sources: BTreeMap<usize, Vec<WebSource>>,
+    server: Option<String>,
+    summary: Option<ContextSummary>,
+    tool_calls: BTreeMap<usize, Vec<ToolExchange>>,
+    formats: BTreeMap<usize, OutputFormat>,
+    response_language: Option<String>,
+}
+
+/// A message as it's already stored, opened to compare with the one about to be saved
+struct StoredMessage {
+    role: String,
+    content: Vec<u8>,
+    images: Option<Vec<u8>>,
+}
+
+pub(crate) struct Database {
+    connection: Connection,
+    path: PathBuf,
+    /// Whether the conversation files saved in its dir before it existed have been imported, which
+    /// waits for unlocking if they're encrypted
+    has_imported_files: bool,
+}
+
+impl Database {
+    /// Opens the database in `dir`, creating it and importing the conversation files saved there
+    /// before conversations moved into it if it's new
+    pub(crate) fn open(dir: &Path) -> Result<Self> {
+        let path = dir.join(DATABASE_FILE_NAME);
+        let open_error = |message: String| Error::Read {
+            path: path.clone(),
+            message,
+        };
+        fs::create_dir_all(dir).map_err(|err| open_error(err.to_string()))?;
+        let connection = Connection::open(&path).map_err(|err| open_error(err.to_string()))?;
+        let mut database = Self {
+            connection,
+            path,
+            has_imported_files: false,
+        };
+        database.set_up()?;
+        database.import_files_if_new()?;
+        Ok(database)
+    }
+
+    fn read_error(&self, err: impl ToString) -> Error {
+        Error::Read {
+            path: self.path.clone(),
+            message: err.to_string(),
+        }
+    }
+
+    fn write_error(&self, err: impl ToString) -> Error {
+        Error::Write {
+            path: self.path.clone(),
+            message: err.to_string(),
+        }
+    }
+
+    fn corrupt_error(&self, err: impl ToString) -> Error {
+        Error::Corrupt {
+            path: self.path.clone(),
+            message: err.to_string(),
+        }
+    }
+
+    fn set_up(&mut self) -> Result<()> {
+        // The app, its background worker and the CLI can all have it open at once
+        self.connection
+            .busy_timeout(Duration::from_secs(5))
+            .map_err(|err| self.read_error(err))?;
+        // Conversations dirs are often synced, and a write-ahead log is kept in files of its own a
+        // sync tool could copy at a different time from the database, so writes go straight into
+        // the database instead. Switching a database left in WAL mode by an earlier version needs
+        // it to be the only connection, so it's tried again next time if it isn't.
+        let _ = self
+            .connection
+            .execute_batch("PRAGMA journal_mode = DELETE;");
+        self.connection
+            .execute_batch("PRAGMA foreign_keys = ON;")
+            .map_err(|err| self.read_error(err))?;
+        self.connection
+            .execute_batch(SCHEMA)
+            .map_err(|err| self.write_error(err))
+    }
+
+    /// Imports the conversation files saved in its dir before conversations moved into the
+    /// database, if they haven't been yet
+    pub(crate) fn import_files_if_new(&mut self) -> Result<()> {
+        if self.has_imported_files {
+            return Ok(());
+        }
+        let schema_version: i32 = self
+            .connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|err| self.read_error(err))?;
+        // Encrypted files can't be read until they're unlocked, so they're imported after that
+        let can_import = crypto::is_unlocked() || !crypto::is_enabled();
+        if schema_version == 0 && can_import {
+            let dir = self.path.parent().unwrap_or(Path::new(".")).to_path_buf();
+            self.import_files(&conversation_files(&dir))?;
+            self.connection
+                .pragma_update(None, "user_version", SCHEMA_VERSION)
+                .map_err(|err| self.write_error(err))?;
+        }
+        self.has_imported_files = schema_version != 0 || can_import;
+        Ok(())
+    }
+
+    /// Copies conversation files into the database, returning the ones that were imported
+    ///
+    /// Files that can't be read, or whose title is already taken, are left out.
+    pub(crate) fn import_files(&mut self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut imported = vec![];
+        for path in paths {
+            let title = title(path);
+            if self.contains(&title)? {
+                continue;
+            }
+            let Ok(conversation) = storage::read_conversation_file(path) else {
+                continue;
+            };
+            let modified = storage::file_modified_time(path).unwrap_or_else(SystemTime::now);
+            self.save_at(&title, &conversation, modified)?;
+            imported.push(path.clone());
+        }
+        Ok(imported)
+    }
+
+    /// Titles of the conversations with when they were last saved, the ones with the most recent
+    /// messages first
+    pub(crate) fn list(&self) -> Result<Vec<(String, SystemTime)>> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT title, updated_at FROM conversations
+                 ORDER BY COALESCE(
+                     (SELECT MAX(created_at) FROM messages WHERE conversation_id = conversations.id),
+                     updated_at
+                 ) DESC, id DESC",
+            )
+            .map_err(|err| self.read_error(err))?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, from_millis(row.get(1)?))))
+            .map_err(|err| self.read_error(err))?;
+        rows.collect::<rusqlite::Result<_>>()
+            .map_err(|err| self.read_error(err))
+    }
+
+    /// Every message's role, model, stats and when it was written, across all conversations,
+    /// without opening their contents
+    pub(crate) fn message_records(&self) -> Result<Vec<MessageRecord>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT role, model, stats, created_at FROM messages")
+            .map_err(|err| self.read_error(err))?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })
+            .map_err(|err| self.read_error(err))?;
+        let mut records = vec![];
+        for row in rows {
+            let (role, model, stats, created_at) = row.map_err(|err| self.read_error(err))?;
+            let role: MessageRole = serde_json::from_value(serde_json::Value::String(role))
+                .map_err(|err| self.corrupt_error(err))?;
+            let stats: Option<ResponseStats> = stats
+                .map(|stats| serde_json::from_str(&stats))
+                .transpose()
+                .map_err(|err| self.corrupt_error(err))?;
+            records.push(MessageRecord {
+                role,
+                model,
+                stats,
+                sent_at: from_millis(created_at),
+            });
+        }
+        Ok(records)
+    }
+
+    pub(crate) fn contains(&self, title: &str) -> Result<bool> {
+        Ok(self.modified_time(title)?.is_some())
+    }
+
+    /// When the conversation was last saved, or `None` if there isn't one with that title
+    pub(crate) fn modified_time(&self, title: &str) -> Result<Option<SystemTime>> {
+        self.connection
+            .query_row(
+                "SELECT updated_at FROM conversations WHERE title = ?1",
+                [title],
+                |row| row.get(0),
+            )
+            .optional()
+            .map(|updated_at| updated_at.map(from_millis))
+            .map_err(|err| self.read_error(err))
+    }
+
+    fn missing_error(&self, title: &str) -> Error {
+        self.read_error(format!("there's no conversation called \"{title}\""))
+    }
+
+    pub(crate) fn load(&self, title: &str) -> Result<Conversation> {
+        let (id, model, details): (i64, Option<String>, Vec<u8>) = self
+            .connection
+            .query_row(
+                "SELECT id, model, details FROM conversations WHERE title = ?1",
+                [title],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(|err| self.read_error(err))?
+            .ok_or_else(|| self.missing_error(title))?;
+        let details: Details = serde_json::from_slice(&crypto::open(&self.path, details)?)
+            .map_err(|err| self.corrupt_error(err))?;
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT role, content, images, stats, created_at FROM messages
+                 WHERE conversation_id = ?1 ORDER BY position",
+            )
+            .map_err(|err| self.read_error(err))?;
+        let rows = statement
+            .query_map([id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, Option<Vec<u8>>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            })
+            .map_err(|err| self.read_error(err))?;
+        let mut messages = vec![];
+        let mut stats = BTreeMap::new();
+        let mut sent_at = BTreeMap::new();
+        for row in rows {
+            let (role, content, images, message_stats, created_at) =
+                row.map_err(|err| self.read_error(err))?;
+            let role: MessageRole = serde_json::from_value(serde_json::Value::String(role))
+                .map_err(|err| self.corrupt_error(err))?;
+            let content = String::from_utf8(crypto::open(&self.path, content)?)
+                .map_err(|err| self.corrupt_error(err))?;
+            let images: Option<Vec<Image>> = match images {
+                Some(images) => Some(
+                    serde_json::from_slice(&crypto::open(&self.path, images)?)
+                        .map_err(|err| self.corrupt_error(err))?,
+                ),
+                None => None,
+            };
+            if let Some(message_stats) = message_stats {
+                let message_stats: ResponseStats =
+                    serde_json::from_str(&message_stats).map_err(|err| self.corrupt_error(err))?;
+                stats.insert(messages.len(), message_stats);
+            }
+            sent_at.insert(messages.len(), from_millis(created_at));
+            messages.push(ChatMessage {
+                role,
+                content,
+                images,
+            });
+        }
+        Ok(Conversation {
+            params: details.params,
+            messages,
+            stats,
+            folder: details.folder,
+            tags: details.tags,
+            listing: details.listing,
+            model,
+            branches: details.branches,
+            knowledge_dir: details.knowledge_dir,
+            citations: details.citations,
+            web_sources: details.web_sources,
+            server: details.server,
+            sent_at,
+            summary: details.summary,
+            tool_calls: details.tool_calls,
+            formats: details.formats,
+            response_language: details.response_language,
+        })
+    }
+
+    /// Saves the conversation, returning the time it was saved at
+    pub(crate) fn save(&mut self, title: &str, conversation: &Conversation) -> Result<SystemTime> {
+        self.save_at(title, conversation, SystemTime::now())
+    }
+
+    pub(crate) fn save_at(
+        &mut self,
+        title: &str,
+        conversation: &Conversation,
+        now: SystemTime,
+    ) -> Result<SystemTime> {
+        let details = serde_json::to_vec(&Details {
+            params: conversation.params,
+            folder: conversation.folder.clone(),
+            tags: conversation.tags.clone(),
+            listing: conversation.listing,
+            branches: conversation.branches.clone(),
+            knowledge_dir: conversation.knowledge_dir.clone(),
+            citations: conversation.citations.clone(),
+            web_sources: conversation.web_sources.clone(),
+            server: conversation.server.clone(),
+            summary: conversation.summary.clone(),
+            tool_calls: conversation.tool_calls.clone(),
+            formats: conversation.formats.clone(),
+            response_language: conversation.response_language.clone(),
+        })
+        .map_err(|err| self.write_error(err))?;
+        let details = crypto::seal(details)?;
+        let stored_messages = self.stored_messages(title)?;
+        let path = self.path.clone();
+        let write_error = |err: rusqlite::Error| Error::Write {
+            path: path.clone(),
+            message: err.to_string(),
+        };
+        let transaction = self.connection.transaction().map_err(write_error)?;
+        let existing: Option<(i64, i64)> = transaction
+            .query_row(
+                "SELECT id, updated_at FROM conversations WHERE title = ?1",
+                [title],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(write_error)?;
+        // Always moves forward, so a save can be told apart from the one before it
+        let updated_at = match existing {
+            Some((_id, previous)) => to_millis(now).max(previous + 1),
+            None => to_millis(now),
+        };
+        let id = match existing {
+            Some((id, _previous)) => {
+                transaction
+                    .execute(
+                        "UPDATE conversations SET model = ?1, details = ?2, updated_at = ?3
+                         WHERE id = ?4",
+                        params![conversation.model, details, updated_at, id],
+                    )
+                    .map_err(write_error)?;
+                id
+            }
+            None => {
+                transaction
+                    .execute(
+                        "INSERT INTO conversations (title, model, details, created_at, updated_at)
+                         VALUES (?1, ?2, ?3, ?4, ?4)",
+                        params![title, conversation.model, details, updated_at],
+                    )
+                    .map_err(write_error)?;
+                transaction.last_insert_rowid()
+            }
+        };
+        for (position, chat_message) in conversation.messages.iter().enumerate() {
+            let role = role_name(&chat_message.role);
+            let images = chat_message
+                .images
+                .as_ref()
+                .map(serde_json::to_vec)
+                .transpose()
+                .map_err(|err| Error::Write {
+                    path: path.clone(),
+                    message: err.to_string(),
+                })?;
+            let stats = conversation
+                .stats
+                .get(&position)
+                .map(serde_json::to_string)
+                .transpose()
+                .map_err(|err| Error::Write {
+                    path: path.clone(),
+                    message: err.to_string(),
+                })?;
+            let is_unchanged = stored_messages
+                .get(position)
+                .and_then(Option::as_ref)
+                .is_some_and(|stored| {
+                    stored.role == role
+                        && stored.content == chat_message.content.as_bytes()
+                        && stored.images == images
+                });
+            let created_at = conversation
+                .sent_at
+                .get(&position)
+                .map_or(updated_at, |sent_at| to_millis(*sent_at));
+            // Unchanged messages keep when they were written and the model that wrote them
+            if is_unchanged {
+                transaction
+                    .execute(
+                        "UPDATE messages SET stats = ?1 WHERE conversation_id = ?2 AND position = ?3",
+                        params![stats, id, position as i64],
+                    )
+                    .map_err(write_error)?;
+            } else {
+                transaction
+                    .execute(
+                        "INSERT OR REPLACE INTO messages
+                         (conversation_id, position, role, content, images, stats, model, created_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        params![
+                            id,
+                            position as i64,
+                            role,
+                            crypto::seal(chat_message.content.clone().into_bytes())?,
+                            images.map(crypto::seal).transpose()?,
+                            stats,
+                            conversation.model,
+                            created_at
+                        ],
+                    )
+                    .map_err(write_error)?;
+            }
+        }
+        transaction
+            .execute(
+                "DELETE FROM messages WHERE conversation_id = ?1 AND position >= ?2",
+                params![id, conversation.messages.len() as i64],
+            )
+            .map_err(write_error)?;
+        transaction.commit().map_err(write_error)?;
+        Ok(from_millis(updated_at))
+    }
+
+    /// The messages already saved for a conversation, decrypted, in order
+    fn stored_messages(&self, title: &str) -> Result<Vec<Option<StoredMessage>>> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT messages.role, messages.content, messages.images FROM messages
+                 JOIN conversations ON conversations.id = messages.conversation_id
+                 WHERE conversations.title = ?1 ORDER BY messages.position",
+            )
+            .map_err(|err| self.read_error(err))?;
+        let rows = statement
+            .query_map([title], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, Option<Vec<u8>>>(2)?,
+                ))
+            })
+            .map_err(|err| self.read_error(err))?;
+        let mut stored_messages = vec![];
+        for row in rows {
+            let (role, content, images) = row.map_err(|err| self.read_error(err))?;
+            // Messages sealed differently from how they'd be sealed now are written again anyway,
+            // which is how turning encryption on or off reaches them, so there's no need to open
+            // them, and once the key is forgotten they can't be
+            if !crypto::is_sealed_as_current(&content) {
+                stored_messages.push(None);
+                continue;
+            }
+            stored_messages.push(Some(StoredMessage {
+                role,
+                content: crypto::open(&self.path, content)?,
+                images: images
+                    .map(|images| crypto::open(&self.path, images))
+                    .transpose()?,
+            }));
+        }
+        Ok(stored_messages)
+    }
+
+    /// Gives a conversation a new title, failing if there's already one with that title
+    pub(crate) fn rename(&self, title: &str, new_title: &str) -> Result<()> {
+        if self.contains(new_title)? {
+            return Err(self.write_error("a conversation with that title already exists"));
+        }
+        let renamed = self
+            .connection
+            .execute(
+                "UPDATE conversations SET title = ?1 WHERE title = ?2",
+                [new_title, title],
+            )
+            .map_err(|err| self.write_error(err))?;
+        if renamed == 0 {
+            return Err(self.missing_error(title));
+        }
+        Ok(())
+    }
+
+    /// Copies a conversation under a new title, keeping when each message was written
+    pub(crate) fn duplicate(&mut self, title: &str, new_title: &str) -> Result<()> {
+        self.copy_conversation("main", title, new_title)
+    }
+
+    /// Copies a conversation from the attached `schema` under `new_title`, keeping its timestamps
+    /// and the models that wrote each message
+    fn copy_conversation(&mut self, schema: &str, title: &str, new_title: &str) -> Result<()> {
+        let missing_error = self.missing_error(title);
+        let path = self.path.clone();
+        let write_error = |err: rusqlite::Error| Error::Write {
+            path: path.clone(),
+            message: err.to_string(),
+        };
+        let transaction = self.connection.transaction().map_err(write_error)?;
+        let copied = transaction
+            .execute(
+                &format!(
+                    "INSERT INTO main.conversations (title, model, details, created_at, updated_at)
+                     SELECT ?1, model, details, created_at, updated_at
+                     FROM {schema}.conversations WHERE title = ?2"
+                ),
+                [new_title, title],
+            )
+            .map_err(write_error)?;
+        if copied == 0 {
+            return Err(missing_error);
+        }
+        let id = transaction.last_insert_rowid();
+        transaction
+            .execute(
+                &format!(
+                    "INSERT INTO main.messages
+                     (conversation_id, position, role, content, images, stats, model, created_at)
+                     SELECT ?1, messages.position, messages.role, messages.content,
+                            messages.images, messages.stats, messages.model, messages.created_at
+                     FROM {schema}.messages AS messages
+                     JOIN {schema}.conversations AS conversations
+                         ON conversations.id = messages.conversation_id
+                     WHERE conversations.title = ?2"
+                ),
+                params![id, title],
+            )
+            .map_err(write_error)?;
+        transaction.commit().map_err(write_error)
+    }
+
+    pub(crate) fn delete(&self, title: &str) -> Result<()> {
+        let deleted = self
+            .connection
+            .execute("DELETE FROM conversations WHERE title = ?1", [title])
+            .map_err(|err| self.write_error(err))?;
+        if deleted == 0 {
+            return Err(self.missing_error(title));
+        }
+        Ok(())
+    }
+
+    /// Moves every conversation from the database in `from` into this one, returning how many
+    /// were moved
+    ///
+    /// Conversations whose title is already taken are left where they are.
+    pub(crate) fn take_from(&mut self, from: &Path) -> Result<usize> {
+        let source = Database::open(from)?;
+        let titles = source.list()?;
+        self.connection
+            .execute(
+                "ATTACH DATABASE ?1 AS source",
+                [source.path.to_string_lossy()],
+            )
+            .map_err(|err| self.read_error(err))?;
+        let mut moved_count = 0;
+        let result = titles.iter().try_for_each(|(title, _modified)| {
+            if self.contains(title)? {
+                return Ok(());
+            }
+            self.copy_conversation("source", title, title)?;
+            source.delete(title)?;
+            moved_count += 1;
+            Ok(())
+        });
+        self.connection
+            .execute("DETACH DATABASE source", [])
+            .map_err(|err| self.read_error(err))?;
+        result.map(|()| moved_count)
+    }
+}
+
+/// The conversation files in `dir` that were saved before conversations moved into the database
+pub(crate) fn conversation_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|read_dir| read_dir.ok())
+        .map(|dir_entry| dir_entry.path())
+        .filter(|path| storage::is_conversation_file(path))
+        .collect()
+}
+
+/// A conversation's title, which is the name its file had before it moved into the database
+pub(crate) fn title(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn role_name(role: &MessageRole) -> String {
+    match serde_json::to_value(role) {
+        Ok(serde_json::Value::String(role_name)) => role_name,
+        _ => unreachable!("roles are serialized as strings"),
+    }
+}
+
+fn to_millis(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{chat_message, test_dir};
+
+    fn message_times(database: &Database, title: &str) -> Vec<i64> {
+        let mut statement = database
+            .connection
+            .prepare(
+                "SELECT messages.created_at FROM messages
+                 JOIN conversations ON conversations.id = messages.conversation_id
+                 WHERE conversations.title = ?1 ORDER BY messages.position",
+            )
+            .unwrap();
+        statement
+            .query_map([title], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn saving_keeps_unchanged_messages_and_their_times() {
+        let mut database = Database::open(&test_dir("save")).unwrap();
+        let mut conversation = Conversation::new(vec![
+            chat_message(MessageRole::User, "Hi"),
+            chat_message(MessageRole::Assistant, "Hello!"),
+        ]);
+        conversation.model = Some("llama3.1:latest".to_string());
+        conversation.tags = vec!["greetings".to_string()];
+        let first_save = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        database
+            .save_at("Greeting", &conversation, first_save)
+            .unwrap();
+
+        conversation.messages[1].content = "Hello there!".to_string();
+        conversation
+            .messages
+            .push(chat_message(MessageRole::User, "How are you?"));
+        conversation.stats.insert(
+            1,
+            ResponseStats {
+                generated_tokens: 3,
+                ..ResponseStats::default()
+            },
+        );
+        let second_save = database.save("Greeting", &conversation).unwrap();
+        let times = message_times(&database, "Greeting");
+        assert_eq!(times[0], to_millis(first_save));
+        assert_eq!(times[1], to_millis(second_save));
+        assert_eq!(times[2], to_millis(second_save));
+
+        let loaded = database.load("Greeting").unwrap();
+        assert_eq!(loaded.messages.len(), 3);
+        assert_eq!(loaded.messages[1].content, "Hello there!");
+        assert_eq!(loaded.stats[&1].generated_tokens, 3);
+        assert_eq!(loaded.model.as_deref(), Some("llama3.1:latest"));
+        assert_eq!(loaded.tags, ["greetings"]);
+
+        conversation.messages.truncate(1);
+        database.save("Greeting", &conversation).unwrap();
+        assert_eq!(database.load("Greeting").unwrap().messages.len(), 1);
+        assert_eq!(database.modified_time("Missing").unwrap(), None);
+    }
+
+    #[test]
+    fn conversations_with_the_latest_messages_list_first() {
+        let mut database = Database::open(&test_dir("sent-at")).unwrap();
+        let at = |seconds: u64| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+        let mut old = Conversation::new(vec![chat_message(MessageRole::User, "Hi")]);
+        old.sent_at.insert(0, at(1_000));
+        database.save_at("Old", &old, at(1_000)).unwrap();
+        let mut recent = Conversation::new(vec![chat_message(MessageRole::User, "Hey")]);
+        recent.sent_at.insert(0, at(2_000));
+        database.save_at("Recent", &recent, at(2_000)).unwrap();
+        // Saving without a new message, e.g. after tagging it, doesn't move it up
+        old.tags = vec!["greetings".to_string()];
+        database.save_at("Old", &old, at(3_000)).unwrap();
+
+        let titles: Vec<String> = database
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|(title, _modified)| title)
+            .collect();
+        assert_eq!(titles, ["Recent", "Old"]);
+        assert_eq!(database.load("Old").unwrap().sent_at[&0], at(1_000));
+    }
+
+    #[test]
+    fn conversation_files_are_imported_once() {
+        let dir = test_dir("import");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("Old.json"),
+            r#"[{"role":"user","content":"Hi","images":null}]"#,
+        )
+        .unwrap();
+        fs::write(dir.join("Broken.json"), "{ not json").unwrap();
+
+        let database = Database::open(&dir).unwrap();
+        let titles: Vec<String> = database
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|(title, _modified)| title)
+            .collect();
+        assert_eq!(titles, ["Old"]);
+        assert_eq!(database.load("Old").unwrap().messages[0].content, "Hi");
+        assert!(dir.join("Old.json").exists());
+
+        database.delete("Old").unwrap();
+        drop(database);
+        assert!(Database::open(&dir).unwrap().list().unwrap().is_empty());
+    }
+}
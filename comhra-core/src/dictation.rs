@@ -0,0 +1,168 @@
+//! Dictating prompts, recorded by a command-line recorder and transcribed on this machine by
+//! whisper.cpp
+//!
+//! The recorder writes raw samples to its stdout rather than a WAV file, as it's killed to stop it
+//! and wouldn't get to finish the file's header.
+
+use std::future::Future;
+use std::pin::pin;
+use std::process::Stdio;
+
+use futures_util::future::{self, Either};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+use crate::settings::Dictation;
+use crate::{Error, Result};
+
+/// Samples per second the recorder has to record at, the only rate whisper.cpp takes
+pub const SAMPLE_RATE: u32 = 16_000;
+
+/// Records 16-bit mono audio from the microphone until `stop` finishes, returning the samples
+pub async fn record(dictation: &Dictation, stop: impl Future<Output = ()>) -> Result<Vec<u8>> {
+    let mut recorder = dictation.recorder.split_whitespace();
+    let program = recorder
+        .next()
+        .ok_or_else(|| Error::Dictation("there's no command to record with".to_string()))?;
+    let mut child = Command::new(program)
+        .args(recorder)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| Error::Dictation(format!("couldn't run {program}: {err}")))?;
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| Error::Dictation(format!("couldn't read from {program}")))?;
+    let mut samples = vec![];
+    // Whatever was read before stopping is kept in `samples`
+    let finished = match future::select(pin!(stdout.read_to_end(&mut samples)), pin!(stop)).await {
+        Either::Left((result, _stop)) => Some(result),
+        Either::Right(((), _reading)) => None,
+    };
+    match finished {
+        // The recorder stopped by itself, e.g. as it couldn't open the microphone
+        Some(result) => {
+            result.map_err(|err| Error::Dictation(format!("{program} stopped: {err}")))?;
+            let output = child
+                .wait_with_output()
+                .await
+                .map_err(|err| Error::Dictation(format!("{program} stopped: {err}")))?;
+            if !output.status.success() {
+                return Err(Error::Dictation(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ));
+            }
+        }
+        None => {
+            let _ = child.kill().await;
+        }
+    }
+    // A sample cut in half by stopping would shift every sample after it
+    samples.truncate(samples.len() / 2 * 2);
+    Ok(samples)
+}
+
+/// Transcribes recorded samples with whisper.cpp, in whichever language was spoken
+pub async fn transcribe(dictation: Dictation, samples: Vec<u8>) -> Result<String> {
+    if samples.is_empty() {
+        return Ok(String::new());
+    }
+    let model = dictation
+        .whisper_model
+        .ok_or_else(|| Error::Dictation("whisper.cpp needs a model to be chosen".to_string()))?;
+    let wav_path =
+        std::env::temp_dir().join(format!("comhra-dictation-{}.wav", std::process::id()));
+    tokio::fs::write(&wav_path, wav(&samples))
+        .await
+        .map_err(|err| Error::Write {
+            path: wav_path.clone(),
+            message: err.to_string(),
+        })?;
+    let output = Command::new(&dictation.whisper)
+        .arg("--model")
+        .arg(model)
+        .arg("--file")
+        .arg(&wav_path)
+        .args(["--language", "auto", "--no-timestamps", "--no-prints"])
+        .kill_on_drop(true)
+        .output()
+        .await;
+    let _ = tokio::fs::remove_file(&wav_path).await;
+    let output = output
+        .map_err(|err| Error::Dictation(format!("couldn't run {}: {err}", dictation.whisper)))?;
+    if !output.status.success() {
+        return Err(Error::Dictation(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(transcript(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// The samples as a WAV file, for whisper.cpp to read
+fn wav(samples: &[u8]) -> Vec<u8> {
+    const CHANNELS: u16 = 1;
+    const BITS_PER_SAMPLE: u16 = 16;
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_size = samples.len() as u32;
+    let mut wav = Vec::with_capacity(44 + samples.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // Uncompressed PCM
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&CHANNELS.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * u32::from(block_align)).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&BITS_PER_SAMPLE.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    wav.extend_from_slice(samples);
+    wav
+}
+
+/// What whisper.cpp heard, without the markers it writes for silence and noises, e.g.
+/// `[BLANK_AUDIO]` or `(wind blowing)`
+fn transcript(output: &str) -> String {
+    let mut spoken = String::new();
+    let mut depth = 0_usize;
+    for c in output.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => spoken.push(c),
+            _ => {}
+        }
+    }
+    // Each segment is on its own line, starting with a space
+    spoken.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_get_a_wav_header() {
+        let wav = wav(&[0, 1, 2, 3]);
+        assert_eq!(wav.len(), 48);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[4..8], &40u32.to_le_bytes());
+        assert_eq!(&wav[24..28], &SAMPLE_RATE.to_le_bytes());
+        assert_eq!(&wav[40..44], &4u32.to_le_bytes());
+        assert_eq!(&wav[44..], &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn transcripts_leave_out_noises() {
+        assert_eq!(
+            transcript(" [BLANK_AUDIO]\n What's the capital\n of Ireland? (wind blowing)\n"),
+            "What's the capital of Ireland?"
+        );
+        assert_eq!(transcript("[BLANK_AUDIO]\n"), "");
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+/// Everything that can go wrong talking to the backend or reading and writing conversations
+///
+/// Errors are stored as messages so they can be cloned into UI messages.
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum Error {
+    #[error("Couldn't reach Ollama: {0}")]
+    Backend(String),
+    #[error("The response stream from Ollama broke off")]
+    Stream,
+    #[error("Couldn't find a directory to store the app's files in")]
+    NoAppDir,
+    #[error("Couldn't open {}: {message}", path.display())]
+    Read { path: PathBuf, message: String },
+    #[error("Couldn't save {}: {message}", path.display())]
+    Write { path: PathBuf, message: String },
+    #[error("{} isn't a valid conversation file: {message}", path.display())]
+    Corrupt { path: PathBuf, message: String },
+    #[error("Conversations are encrypted and need to be unlocked first")]
+    Locked,
+    #[error("That passphrase doesn't unlock the conversations")]
+    WrongPassphrase,
+    #[error("Conversation history isn't available: {0}")]
+    History(String),
+    #[error("\"{0}\" can't be used as a profile name")]
+    InvalidProfile(String),
+    #[error("Couldn't read the response aloud: {0}")]
+    Speech(String),
+    #[error("Couldn't take dictation: {0}")]
+    Dictation(String),
+    #[error("Couldn't render an equation: {0}")]
+    Math(String),
+    #[error("Couldn't share the conversation: {0}")]
+    Share(String),
+    #[error("Couldn't search the web: {0}")]
+    WebSearch(String),
+    #[error("Couldn't take a screenshot: {0}")]
+    Screenshot(String),
+    #[error("No model was given, pass one with --model or set a default model")]
+    NoModel,
+}
+
+impl Error {
+    /// The file the error is about, if there is one
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            Error::Read { path, .. } | Error::Write { path, .. } | Error::Corrupt { path, .. } => {
+                Some(path)
+            }
+            Error::Backend(_)
+            | Error::Stream
+            | Error::NoAppDir
+            | Error::Locked
+            | Error::WrongPassphrase
+            | Error::History(_)
+            | Error::InvalidProfile(_)
+            | Error::Speech(_)
+            | Error::Dictation(_)
+            | Error::Math(_)
+            | Error::Share(_)
+            | Error::WebSearch(_)
+            | Error::Screenshot(_)
+            | Error::NoModel => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
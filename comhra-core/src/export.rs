@@ -0,0 +1,332 @@
+//! Renders conversations to files that can be read without the app: Markdown, a single HTML page
+//! that opens in any browser, or a PDF
+
+mod pdf;
+
+use std::fmt::Write as _;
+use std::sync::LazyLock;
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use syntect::highlighting::ThemeSet;
+use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::reasoning;
+use crate::{ChatMessage, MessageRole};
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// Code is highlighted with CSS classes so the page can follow the reader's light or dark mode
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+const STYLE: &str = r#"
+:root { color-scheme: light dark; --background: #ffffff; --text: #1f2328; --muted: #59636e; --user: #eef3fb; --assistant: #f6f8fa; --border: #d1d9e0; }
+@media (prefers-color-scheme: dark) {
+  :root { --background: #1a1b26; --text: #c0caf5; --muted: #7982a9; --user: #24283b; --assistant: #1f2335; --border: #3b4261; }
+}
+body { background: var(--background); color: var(--text); font-family: system-ui, sans-serif; line-height: 1.5; margin: 0; }
+main { max-width: 50rem; margin: 0 auto; padding: 1rem; }
+h1 { font-size: 1.5rem; }
+.message { border: 1px solid var(--border); border-radius: 0.5rem; margin: 1rem 0; padding: 0 1rem; overflow-wrap: anywhere; }
+.message.user { background: var(--user); }
+.message.assistant, .message.system { background: var(--assistant); }
+.role { color: var(--muted); font-size: 0.875rem; font-weight: bold; margin-top: 0.75rem; }
+.code { position: relative; }
+.code pre { border-radius: 0.375rem; overflow-x: auto; padding: 0.75rem; }
+.code button { position: absolute; top: 0.375rem; right: 0.375rem; }
+code { font-family: ui-monospace, monospace; }
+img { max-width: 100%; border-radius: 0.375rem; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid var(--border); padding: 0.25rem 0.5rem; }
+footer { color: var(--muted); font-size: 0.75rem; text-align: center; }
+"#;
+
+const SCRIPT: &str = r#"
+for (const button of document.querySelectorAll(".code button")) {
+  button.hidden = !navigator.clipboard;
+  button.addEventListener("click", () => {
+    navigator.clipboard.writeText(button.parentElement.querySelector("pre").innerText);
+  });
+}
+"#;
+
+/// The kinds of file a conversation can be exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Html,
+    Pdf,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Markdown, Format::Html, Format::Pdf];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Html => "html",
+            Format::Pdf => "pdf",
+        }
+    }
+}
+
+/// Renders the conversation in the format, ready to be written to a file, leaving out what
+/// reasoning models wrote before answering unless it's to be included
+pub fn export(
+    format: Format,
+    title: &str,
+    conversation: &[ChatMessage],
+    role_name: impl Fn(&MessageRole) -> String,
+    copy_label: &str,
+    include_reasoning: bool,
+) -> Vec<u8> {
+    let answers: Vec<ChatMessage>;
+    let conversation = if include_reasoning {
+        conversation
+    } else {
+        answers = conversation
+            .iter()
+            .map(|chat_message| ChatMessage {
+                content: reasoning::answer(&chat_message.content).to_string(),
+                ..chat_message.clone()
+            })
+            .collect();
+        &answers
+    };
+    match format {
+        Format::Markdown => conversation_to_markdown(title, conversation, role_name).into_bytes(),
+        Format::Html => {
+            conversation_to_html(title, conversation, role_name, copy_label).into_bytes()
+        }
+        Format::Pdf => conversation_to_pdf(title, conversation, role_name),
+    }
+}
+
+/// Renders the conversation as Markdown, with a heading for each message's role and the
+/// messages as they were written
+pub fn conversation_to_markdown(
+    title: &str,
+    conversation: &[ChatMessage],
+    role_name: impl Fn(&MessageRole) -> String,
+) -> String {
+    let mut markdown = format!("# {title}\n");
+    for chat_message in conversation {
+        let _ = write!(
+            markdown,
+            "\n## {}\n\n{}\n",
+            role_name(&chat_message.role),
+            chat_message.content.trim_end()
+        );
+        for image in chat_message.images.iter().flatten() {
+            if let Some(base64) = image_base64(image) {
+                let _ = write!(
+                    markdown,
+                    "\n![](data:{};base64,{base64})\n",
+                    image_mime_type(&base64)
+                );
+            }
+        }
+    }
+    markdown
+}
+
+/// Renders the conversation as a PDF, with code blocks kept in a monospace font
+///
+/// Images aren't included, and characters outside Western European scripts show as `?`, since
+/// only the fonts built into every PDF reader are used.
+pub fn conversation_to_pdf(
+    title: &str,
+    conversation: &[ChatMessage],
+    role_name: impl Fn(&MessageRole) -> String,
+) -> Vec<u8> {
+    let mut document = pdf::Document::default();
+    document.title(title);
+    for chat_message in conversation {
+        document.role(&role_name(&chat_message.role));
+        document.markdown(&chat_message.content);
+    }
+    document.render()
+}
+
+/// Renders the conversation as a self-contained HTML page, with its styling, highlighting and
+/// images embedded
+///
+/// Raw HTML in messages is shown as text, so a shared page can't run anything it was sent.
+pub fn conversation_to_html(
+    title: &str,
+    conversation: &[ChatMessage],
+    role_name: impl Fn(&MessageRole) -> String,
+    copy_label: &str,
+) -> String {
+    let mut page = String::new();
+    let _ = write!(
+        page,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>{STYLE}{highlighting}</style>\n</head>\n<body>\n<main>\n\
+         <h1>{title}</h1>\n",
+        title = escape(title),
+        highlighting = highlighting_css(),
+    );
+    for chat_message in conversation {
+        let role_class = match chat_message.role {
+            MessageRole::User => "user",
+            MessageRole::Assistant => "assistant",
+            MessageRole::System => "system",
+        };
+        let _ = write!(
+            page,
+            "<section class=\"message {role_class}\">\n<div class=\"role\">{}</div>\n",
+            escape(&role_name(&chat_message.role))
+        );
+        page.push_str(&markdown_to_html(&chat_message.content, copy_label));
+        for image in chat_message.images.iter().flatten() {
+            if let Some(base64) = image_base64(image) {
+                let _ = writeln!(
+                    page,
+                    "<p><img src=\"data:{};base64,{base64}\" alt=\"\"></p>",
+                    image_mime_type(&base64)
+                );
+            }
+        }
+        page.push_str("</section>\n");
+    }
+    let _ = write!(
+        page,
+        "<footer>Comhrá</footer>\n</main>\n<script>{SCRIPT}</script>\n</body>\n</html>\n"
+    );
+    page
+}
+
+fn markdown_to_html(markdown: &str, copy_label: &str) -> String {
+    let mut events = vec![];
+    let mut code_block: Option<(String, String)> = None;
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    for event in Parser::new_ext(markdown, options) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(language) => language.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((language, String::new()));
+            }
+            Event::Text(text) if code_block.is_some() => {
+                if let Some((_language, code)) = code_block.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((language, code)) = code_block.take() {
+                    events.push(Event::Html(CowStr::from(format!(
+                        "<div class=\"code\"><button type=\"button\">{}</button>\
+                         <pre class=\"hl-code\"><code>{}</code></pre></div>\n",
+                        escape(copy_label),
+                        highlight(&code, &language)
+                    ))));
+                }
+            }
+            Event::Html(html) | Event::InlineHtml(html) => events.push(Event::Text(html)),
+            event => events.push(event),
+        }
+    }
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events.into_iter());
+    html
+}
+
+/// Highlights the code with CSS classes, or just escapes it if the language isn't known
+fn highlight(code: &str, language: &str) -> String {
+    // Fences can carry more than the language, e.g. "rust,ignore"
+    let token = language.split([',', ' ']).next().unwrap_or_default();
+    let Some(syntax) = SYNTAXES.find_syntax_by_token(token) else {
+        return escape(code);
+    };
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        if generator
+            .parse_html_for_line_which_includes_newline(line)
+            .is_err()
+        {
+            return escape(code);
+        }
+    }
+    generator.finalize()
+}
+
+/// Stylesheet for the highlighting classes, with a light and a dark theme
+fn highlighting_css() -> String {
+    let themes = ThemeSet::load_defaults();
+    let css_for = |name: &str| {
+        themes
+            .themes
+            .get(name)
+            .and_then(|theme| {
+                syntect::html::css_for_theme_with_class_style(theme, CLASS_STYLE).ok()
+            })
+            .unwrap_or_default()
+    };
+    format!(
+        "@media (prefers-color-scheme: light) {{\n{}}}\n@media (prefers-color-scheme: dark) {{\n{}}}\n",
+        css_for("InspiredGitHub"),
+        css_for("base16-ocean.dark")
+    )
+}
+
+/// The image's base64 is only reachable through its serialized form
+fn image_base64(image: &crate::Image) -> Option<String> {
+    match serde_json::to_value(image) {
+        Ok(serde_json::Value::String(base64)) => Some(base64),
+        _ => None,
+    }
+}
+
+/// Guesses the image type from the first bytes of its base64, which is all Ollama keeps
+fn image_mime_type(base64: &str) -> &'static str {
+    match base64.get(..4) {
+        Some("/9j/") => "image/jpeg",
+        Some("R0lG") => "image/gif",
+        Some("UklG") => "image/webp",
+        _ => "image/png",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_escape_html_and_highlight_code() {
+        let conversation = vec![
+            ChatMessage::user("<script>alert(1)</script>".to_string()),
+            ChatMessage::assistant("```rust\nfn main() {}\n```".to_string()),
+        ];
+        let page = conversation_to_html("A & B", &conversation, |role| format!("{role:?}"), "Copy");
+        assert!(page.contains("<title>A &amp; B</title>"));
+        assert!(page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(!page.contains("<script>alert(1)"));
+        assert!(page.contains("class=\"hl-"));
+        assert!(page.contains("<button type=\"button\">Copy</button>"));
+    }
+
+    #[test]
+    fn markdown_keeps_roles_and_code_blocks() {
+        let conversation = vec![
+            ChatMessage::user("Write hello world".to_string()),
+            ChatMessage::assistant("```rust\nfn main() {}\n```\n".to_string()),
+        ];
+        let markdown = conversation_to_markdown("Hello", &conversation, |role| format!("{role:?}"));
+        assert_eq!(
+            markdown,
+            "# Hello\n\n## User\n\nWrite hello world\n\n## Assistant\n\n```rust\nfn main() {}\n```\n"
+        );
+    }
+}
@@ -0,0 +1,395 @@
+//! A small PDF writer for exported conversations, using the fonts every PDF reader has built in
+//! so nothing needs embedding
+
+use std::fmt::Write as _;
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+/// A4, in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+
+const TITLE_SIZE: f32 = 18.0;
+const ROLE_SIZE: f32 = 12.0;
+const BODY_SIZE: f32 = 11.0;
+const CODE_SIZE: f32 = 9.0;
+const LINE_SPACING: f32 = 1.35;
+
+/// Indent of code blocks and of each level of list
+const INDENT: f32 = 14.0;
+
+/// Widths of the printable ASCII characters in Helvetica, in thousandths of the font size
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Font {
+    Regular,
+    Bold,
+    Mono,
+}
+
+impl Font {
+    /// Name of the font in each page's resources
+    fn resource_name(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3",
+        }
+    }
+
+    fn char_width(self, c: char, size: f32) -> f32 {
+        let width = match self {
+            Font::Mono => 600.0,
+            Font::Regular | Font::Bold => {
+                let width = (c as usize)
+                    .checked_sub(32)
+                    .and_then(|index| HELVETICA_WIDTHS.get(index))
+                    .map_or(556.0, |width| *width as f32);
+                // Bold is a little wider than regular, which is close enough for wrapping
+                if self == Font::Bold {
+                    width * 1.07
+                } else {
+                    width
+                }
+            }
+        };
+        width / 1000.0 * size
+    }
+
+    fn text_width(self, text: &str, size: f32) -> f32 {
+        text.chars().map(|c| self.char_width(c, size)).sum()
+    }
+}
+
+struct Line {
+    font: Font,
+    size: f32,
+    indent: f32,
+    text: String,
+}
+
+/// Lines of text laid out down the page, split across as many pages as they need
+#[derive(Default)]
+pub(super) struct Document {
+    /// Each line, or `None` for a gap between blocks
+    lines: Vec<Option<Line>>,
+}
+
+impl Document {
+    pub(super) fn title(&mut self, title: &str) {
+        self.wrap(title, Font::Bold, TITLE_SIZE, 0.0);
+        self.gap();
+    }
+
+    pub(super) fn role(&mut self, role: &str) {
+        self.gap();
+        self.wrap(role, Font::Bold, ROLE_SIZE, 0.0);
+    }
+
+    /// Lays out a message's markdown, keeping code blocks in a monospace font with their lines
+    /// as written
+    pub(super) fn markdown(&mut self, markdown: &str) {
+        let mut text = String::new();
+        let mut font = Font::Regular;
+        let mut code_block: Option<String> = None;
+        // The next number of each ordered list being written, or `None` for bulleted lists
+        let mut lists: Vec<Option<u64>> = vec![];
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+        for event in Parser::new_ext(markdown, options) {
+            let indent = lists.len() as f32 * INDENT;
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => code_block = Some(String::new()),
+                Event::End(TagEnd::CodeBlock) => {
+                    if let Some(code) = code_block.take() {
+                        for line in code.trim_end_matches('\n').lines() {
+                            self.wrap_chars(line, indent + INDENT);
+                        }
+                        self.gap();
+                    }
+                }
+                Event::Text(code) if code_block.is_some() => {
+                    if let Some(code_block) = code_block.as_mut() {
+                        code_block.push_str(&code);
+                    }
+                }
+                Event::Start(Tag::Heading { .. }) => font = Font::Bold,
+                Event::Start(Tag::List(start)) => {
+                    self.flush(&mut text, font, indent);
+                    lists.push(start);
+                }
+                Event::End(TagEnd::List(_)) => {
+                    lists.pop();
+                    if lists.is_empty() {
+                        self.gap();
+                    }
+                }
+                Event::Start(Tag::Item) => {
+                    self.flush(&mut text, font, indent);
+                    match lists.last_mut() {
+                        Some(Some(number)) => {
+                            let _ = write!(text, "{number}. ");
+                            *number += 1;
+                        }
+                        _ => text.push_str("• "),
+                    }
+                }
+                Event::End(TagEnd::Item) => self.flush(&mut text, font, indent - INDENT),
+                Event::End(TagEnd::Paragraph) | Event::End(TagEnd::Heading(_)) => {
+                    let indent = if lists.is_empty() {
+                        indent
+                    } else {
+                        indent - INDENT
+                    };
+                    self.flush(&mut text, font, indent);
+                    font = Font::Regular;
+                    if lists.is_empty() {
+                        self.gap();
+                    }
+                }
+                Event::End(TagEnd::TableCell) => text.push_str("  |  "),
+                Event::End(TagEnd::TableHead) | Event::End(TagEnd::TableRow) => {
+                    let row = text.trim_end_matches("  |  ").to_string();
+                    text.clear();
+                    self.wrap(&row, Font::Regular, BODY_SIZE, indent);
+                }
+                Event::End(TagEnd::Table) => self.gap(),
+                Event::Text(content)
+                | Event::Code(content)
+                | Event::Html(content)
+                | Event::InlineHtml(content) => text.push_str(&content),
+                Event::SoftBreak => text.push(' '),
+                Event::HardBreak => text.push('\n'),
+                Event::Rule => self.gap(),
+                _ => {}
+            }
+        }
+        self.flush(&mut text, font, 0.0);
+    }
+
+    /// Lays out what's been collected of a block of text, leaving it empty for the next one
+    fn flush(&mut self, text: &mut String, font: Font, indent: f32) {
+        for line in text.split('\n') {
+            if !line.trim().is_empty() {
+                self.wrap(line, font, BODY_SIZE, indent.max(0.0));
+            }
+        }
+        text.clear();
+    }
+
+    fn gap(&mut self) {
+        if matches!(self.lines.last(), Some(Some(_))) {
+            self.lines.push(None);
+        }
+    }
+
+    /// Adds text as lines broken between words to fit the page
+    fn wrap(&mut self, text: &str, font: Font, size: f32, indent: f32) {
+        let max_width = PAGE_WIDTH - 2.0 * MARGIN - indent;
+        let mut line = String::new();
+        for word in text.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if font.text_width(&candidate, size) <= max_width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                self.push_line(font, size, indent, std::mem::take(&mut line));
+            }
+            // Words too long for a line of their own, like URLs, are broken wherever they reach
+            // the edge
+            for c in word.chars() {
+                if font.text_width(&line, size) + font.char_width(c, size) > max_width {
+                    self.push_line(font, size, indent, std::mem::take(&mut line));
+                }
+                line.push(c);
+            }
+        }
+        if !line.is_empty() {
+            self.push_line(font, size, indent, line);
+        }
+    }
+
+    /// Adds a line of code, only breaking it if it runs off the page so its spacing is kept
+    fn wrap_chars(&mut self, text: &str, indent: f32) {
+        let max_chars =
+            ((PAGE_WIDTH - 2.0 * MARGIN - indent) / Font::Mono.char_width(' ', CODE_SIZE)) as usize;
+        let chars: Vec<char> = text.trim_end().chars().collect();
+        if chars.is_empty() {
+            self.push_line(Font::Mono, CODE_SIZE, indent, String::new());
+        }
+        for chunk in chars.chunks(max_chars.max(1)) {
+            self.push_line(Font::Mono, CODE_SIZE, indent, chunk.iter().collect());
+        }
+    }
+
+    fn push_line(&mut self, font: Font, size: f32, indent: f32, text: String) {
+        self.lines.push(Some(Line {
+            font,
+            size,
+            indent,
+            text,
+        }));
+    }
+
+    /// Writes out the PDF file
+    pub(super) fn render(self) -> Vec<u8> {
+        let mut pages: Vec<Vec<u8>> = vec![];
+        let mut content = vec![];
+        let mut y = PAGE_HEIGHT - MARGIN;
+        for line in self.lines {
+            let Some(line) = line else {
+                y -= BODY_SIZE * 0.6;
+                continue;
+            };
+            let height = line.size * LINE_SPACING;
+            if y - height < MARGIN {
+                pages.push(std::mem::take(&mut content));
+                y = PAGE_HEIGHT - MARGIN;
+            }
+            y -= height;
+            content.extend_from_slice(
+                format!(
+                    "BT /{} {} Tf {} {y:.1} Td (",
+                    line.font.resource_name(),
+                    line.size,
+                    MARGIN + line.indent
+                )
+                .as_bytes(),
+            );
+            content.extend(encode(&line.text));
+            content.extend_from_slice(b") Tj ET\n");
+        }
+        pages.push(content);
+
+        // Objects 1 and 2 are the catalog and page tree, 3 to 5 the fonts, then each page is
+        // followed by its contents
+        let mut objects: Vec<Vec<u8>> = vec![];
+        let page_ids: Vec<usize> = (0..pages.len()).map(|index| 6 + index * 2).collect();
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        objects.push(
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                page_ids
+                    .iter()
+                    .map(|id| format!("{id} 0 R"))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                pages.len()
+            )
+            .into_bytes(),
+        );
+        for base_font in ["Helvetica", "Helvetica-Bold", "Courier"] {
+            objects.push(
+                format!(
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /{base_font} \
+                     /Encoding /WinAnsiEncoding >>"
+                )
+                .into_bytes(),
+            );
+        }
+        for (page_id, content) in page_ids.iter().zip(pages) {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                     /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R >> >> \
+                     /Contents {} 0 R >>",
+                    page_id + 1
+                )
+                .into_bytes(),
+            );
+            let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+            stream.extend(content);
+            stream.extend_from_slice(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = vec![];
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", index + 1).as_bytes());
+            pdf.extend_from_slice(object);
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+        let xref_offset = pdf.len();
+        let mut xref = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(xref, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            xref,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            objects.len() + 1
+        );
+        pdf.extend_from_slice(xref.as_bytes());
+        pdf
+    }
+}
+
+/// Encodes text for a PDF string in the built in fonts' encoding, escaping what PDF strings need
+/// escaped and replacing what the encoding doesn't have
+fn encode(text: &str) -> Vec<u8> {
+    let mut bytes = vec![];
+    for c in text.chars() {
+        let byte = match c {
+            '(' | ')' | '\\' => {
+                bytes.push(b'\\');
+                c as u8
+            }
+            '\t' => b' ',
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        };
+        bytes.push(byte);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_documents_split_into_pages() {
+        let mut document = Document::default();
+        document.title("Notes (draft)");
+        document.markdown(&"A paragraph long enough to wrap. ".repeat(400));
+        document.markdown("```\nfn main() {}\n```");
+        let pdf = document.render();
+        let pdf_text = String::from_utf8_lossy(&pdf);
+        assert!(pdf_text.starts_with("%PDF-1.4"));
+        assert!(pdf_text.ends_with("%%EOF\n"));
+        assert!(pdf_text.contains("(Notes \\(draft\\)) Tj"));
+        assert!(pdf_text.contains("/F3 9 Tf"));
+        assert!(pdf_text.matches("/Type /Page ").count() > 1);
+    }
+
+    #[test]
+    fn text_outside_the_encoding_is_replaced() {
+        assert_eq!(
+            encode("Dia dhuit – fáilte 👋"),
+            b"Dia dhuit \x96 f\xe1ilte ?"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Text files attached to prompts, e.g. code or CSV, whose contents are sent inline in the prompt
+
+use std::path::{Path, PathBuf};
+
+use tokio::io::AsyncReadExt;
+
+use crate::{Error, Result};
+
+/// Number of bytes of a file inlined into a prompt, 100KB, past which the rest is left out
+pub const MAX_FILE_SIZE: usize = 100 * 1024;
+
+/// Number of bytes at the start of a file checked for NUL bytes, which text files don't have
+const BINARY_CHECK_LENGTH: usize = 8 * 1024;
+
+/// Extensions of the images that are attached as images rather than text
+const IMAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileAttachment {
+    pub name: String,
+    pub contents: String,
+    /// Whether the file was over [`MAX_FILE_SIZE`] and only its start is in `contents`
+    pub is_truncated: bool,
+}
+
+/// Whether the file should be attached as an image for vision models
+pub fn is_image(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        IMAGE_EXTENSIONS
+            .iter()
+            .any(|image_ext| ext.eq_ignore_ascii_case(image_ext))
+    })
+}
+
+/// Reads a text file to attach, up to [`MAX_FILE_SIZE`] of it
+pub async fn load(path: PathBuf) -> Result<FileAttachment> {
+    let read_error = |message: String| Error::Read {
+        path: path.clone(),
+        message,
+    };
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|err| read_error(err.to_string()))?;
+    // One byte more than the limit is read to tell whether there's more
+    let mut bytes = Vec::with_capacity(MAX_FILE_SIZE + 1);
+    file.take(MAX_FILE_SIZE as u64 + 1)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|err| read_error(err.to_string()))?;
+    if bytes[..bytes.len().min(BINARY_CHECK_LENGTH)].contains(&0) {
+        return Err(read_error("it isn't a text file".to_string()));
+    }
+    let is_truncated = bytes.len() > MAX_FILE_SIZE;
+    bytes.truncate(MAX_FILE_SIZE);
+    Ok(FileAttachment {
+        name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        // A character cut in half by the limit comes out as a replacement character
+        contents: String::from_utf8_lossy(&bytes).into_owned(),
+        is_truncated,
+    })
+}
+
+/// The prompt with each file's contents after it in a code block, labelled with its name
+pub fn inline(prompt: &str, files: &[FileAttachment]) -> String {
+    let mut inlined = prompt.to_string();
+    for file in files {
+        let language = Path::new(&file.name)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        // The fence has to be longer than any run of backticks in the file to contain it
+        let longest_backticks = file
+            .contents
+            .split(|c| c != '`')
+            .map(str::len)
+            .max()
+            .unwrap_or_default();
+        let fence = "`".repeat(longest_backticks.max(2) + 1);
+        inlined.push_str(&format!(
+            "\n\n{}:\n{fence}{language}\n{}\n{fence}",
+            file.name,
+            file.contents.trim_end()
+        ));
+        if file.is_truncated {
+            inlined.push_str(&format!(
+                "\n(Only the first {}KB of {} is included.)",
+                MAX_FILE_SIZE / 1024,
+                file.name
+            ));
+        }
+    }
+    inlined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn large_files_are_truncated_and_binary_ones_refused() {
+        let dir =
+            std::env::temp_dir().join(format!("comhra-core-test-{}-files", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv_path = dir.join("data.csv");
+        std::fs::write(&csv_path, "a,b\n1,2\n").unwrap();
+        let large_path = dir.join("large.txt");
+        std::fs::write(&large_path, "x".repeat(MAX_FILE_SIZE + 10)).unwrap();
+        let binary_path = dir.join("binary.dat");
+        std::fs::write(&binary_path, b"\x00\x01\x02").unwrap();
+
+        let csv = load(csv_path).await.unwrap();
+        assert_eq!(csv.name, "data.csv");
+        assert!(!csv.is_truncated);
+        let large = load(large_path).await.unwrap();
+        assert!(large.is_truncated);
+        assert_eq!(large.contents.len(), MAX_FILE_SIZE);
+        assert!(matches!(load(binary_path).await, Err(Error::Read { .. })));
+    }
+
+    #[test]
+    fn files_are_inlined_in_code_blocks() {
+        let file = FileAttachment {
+            name: "main.rs".to_string(),
+            contents: "fn main() {}\n".to_string(),
+            is_truncated: true,
+        };
+        assert_eq!(
+            inline("What does this do?", &[file]),
+            "What does this do?\n\nmain.rs:\n```rs\nfn main() {}\n```\n(Only the first 100KB of main.rs is included.)"
+        );
+        let readme = FileAttachment {
+            name: "README.md".to_string(),
+            contents: "```sh\ncargo run\n```".to_string(),
+            is_truncated: false,
+        };
+        assert!(inline("", &[readme]).contains("````md\n```sh"));
+        assert!(is_image(Path::new("photo.JPG")));
+        assert!(!is_image(Path::new("notes.txt")));
+    }
+}
@@ -0,0 +1,231 @@
+//! Optional history of every saved version of each conversation, kept by running `git` in a
+//! dir of its own inside the conversations dir
+
+use std::path::{Path, PathBuf};
+
+use tokio::process::Command;
+
+use crate::conversation::Conversation;
+use crate::storage::{self, write_atomically};
+use crate::{crypto, Error, Result};
+
+/// A saved version of a conversation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Version {
+    pub commit: String,
+    /// When it was saved, as git formats dates, e.g. `2024-10-02 18:40:12 +0100`
+    pub date: String,
+    pub message: String,
+}
+
+/// Runs git in `dir`, returning what it printed
+async fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        // The history belongs to the app, so it doesn't need the user's identity to be set up
+        .args([
+            "-c",
+            "user.name=Comhrá",
+            "-c",
+            "user.email=comhra@localhost",
+        ])
+        .args(args)
+        .output()
+        .await
+        .map_err(|err| Error::History(format!("couldn't run git: {err}")))?;
+    if !output.status.success() {
+        return Err(Error::History(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Where the history is kept, apart from the conversations so the snapshots git tracks aren't
+/// mistaken for conversation files saved before the database
+fn history_dir(conversations_dir: &Path) -> PathBuf {
+    conversations_dir.join(".history")
+}
+
+fn file_name(path: &Path) -> Result<&str> {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or_else(|| Error::History(format!("{} isn't a file", path.display())))
+}
+
+/// Records the current version of a conversation, creating the repository if there isn't one
+pub async fn commit(conversations_dir: PathBuf, path: PathBuf, message: String) -> Result<()> {
+    let history_dir = history_dir(&conversations_dir);
+    if !history_dir.join(".git").exists() {
+        tokio::fs::create_dir_all(&history_dir)
+            .await
+            .map_err(|err| Error::Write {
+                path: history_dir.clone(),
+                message: err.to_string(),
+            })?;
+        git(&history_dir, &["init", "--quiet"]).await?;
+    }
+    let file_name = file_name(&path)?;
+    write_snapshot(&path, &history_dir.join(file_name)).await?;
+    git(&history_dir, &["add", "--", file_name]).await?;
+    // Saving a conversation that hasn't changed leaves nothing to commit
+    let staged = git(
+        &history_dir,
+        &["diff", "--cached", "--name-only", "--", file_name],
+    )
+    .await?;
+    if staged.is_empty() {
+        return Ok(());
+    }
+    git(
+        &history_dir,
+        &["commit", "--quiet", "--message", &message, "--", file_name],
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Writes the conversation at `path` out of the database into `snapshot_path`, the file git
+/// tracks for it
+///
+/// The file is left alone if it already holds this version, since encrypting it again would look
+/// like a change to git.
+async fn write_snapshot(path: &Path, snapshot_path: &Path) -> Result<()> {
+    let conversation = storage::load_conversation(path.to_path_buf()).await?;
+    let write_error = |err: String| Error::Write {
+        path: snapshot_path.to_path_buf(),
+        message: err,
+    };
+    let conversation_json =
+        serde_json::to_vec(&conversation).map_err(|err| write_error(err.to_string()))?;
+    let snapshot = tokio::fs::read(snapshot_path)
+        .await
+        .ok()
+        .and_then(|snapshot| crypto::open(snapshot_path, snapshot).ok());
+    if snapshot.as_ref() == Some(&conversation_json) {
+        return Ok(());
+    }
+    write_atomically(snapshot_path, crypto::seal(conversation_json)?)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+/// Deletes the history of every conversation, which is needed once they're encrypted as the
+/// versions saved before that would otherwise stay readable
+pub async fn forget(conversations_dir: &Path) -> Result<()> {
+    let history_dir = history_dir(conversations_dir);
+    match tokio::fs::remove_dir_all(&history_dir).await {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(Error::Write {
+            path: history_dir,
+            message: err.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Lists the saved versions of a conversation, newest first
+pub async fn versions(conversations_dir: PathBuf, path: PathBuf) -> Result<Vec<Version>> {
+    let history_dir = history_dir(&conversations_dir);
+    if !history_dir.join(".git").exists() {
+        return Ok(vec![]);
+    }
+    let log = git(
+        &history_dir,
+        &["log", "--format=%H%x1f%ci%x1f%s", "--", file_name(&path)?],
+    )
+    .await?;
+    Ok(String::from_utf8_lossy(&log)
+        .lines()
+        .filter_map(parse_version)
+        .collect())
+}
+
+fn parse_version(log_line: &str) -> Option<Version> {
+    let mut fields = log_line.split('\x1f');
+    Some(Version {
+        commit: fields.next()?.to_string(),
+        date: fields.next()?.to_string(),
+        message: fields.next()?.to_string(),
+    })
+}
+
+/// Reads a conversation as it was in an earlier version
+pub async fn load_version(
+    conversations_dir: PathBuf,
+    path: PathBuf,
+    commit: String,
+) -> Result<Conversation> {
+    let conversation_json = git(
+        &history_dir(&conversations_dir),
+        &["show", &format!("{commit}:{}", file_name(&path)?)],
+    )
+    .await?;
+    let conversation_json = crypto::open(&path, conversation_json)?;
+    Conversation::from_json(&conversation_json).map_err(|err| Error::Corrupt {
+        path,
+        message: err.to_string(),
+    })
+}
+
+/// Puts an earlier version of a conversation back, recording that as a new version
+pub async fn restore_version(
+    conversations_dir: PathBuf,
+    path: PathBuf,
+    version: Version,
+    message: String,
+) -> Result<()> {
+    let conversation =
+        load_version(conversations_dir.clone(), path.clone(), version.commit).await?;
+    storage::save_conversation(path.clone(), conversation).await?;
+    commit(conversations_dir, path, message).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{chat_message, test_dir};
+    use crate::MessageRole;
+
+    #[test]
+    fn log_lines_parse_into_versions() {
+        assert_eq!(
+            parse_version("abc123\x1f2024-10-02 18:40:12 +0100\x1fSave \"Hi\" with 2 messages"),
+            Some(Version {
+                commit: "abc123".to_string(),
+                date: "2024-10-02 18:40:12 +0100".to_string(),
+                message: "Save \"Hi\" with 2 messages".to_string(),
+            })
+        );
+        assert_eq!(parse_version("abc123"), None);
+    }
+
+    #[tokio::test]
+    async fn earlier_versions_can_be_loaded_back() {
+        let dir = test_dir("history");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Hi.json");
+        for (content, commit_message) in [("First", "One"), ("Second", "Two")] {
+            storage::save_conversation(
+                path.clone(),
+                Conversation::new(vec![chat_message(MessageRole::User, content)]),
+            )
+            .await
+            .unwrap();
+            commit(dir.clone(), path.clone(), commit_message.to_string())
+                .await
+                .unwrap();
+        }
+        // Nothing changed, so there's nothing new to record
+        commit(dir.clone(), path.clone(), "Three".to_string())
+            .await
+            .unwrap();
+        let versions = versions(dir.clone(), path.clone()).await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].message, "Two");
+        let first = load_version(dir, path, versions[1].commit.clone())
+            .await
+            .unwrap();
+        assert_eq!(first.messages[0].content, "First");
+    }
+}
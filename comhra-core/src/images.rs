@@ -0,0 +1,84 @@
+//! Images attached to prompts for vision models, e.g. llava, which Ollama takes base64 encoded
+
+use std::path::PathBuf;
+
+use crate::{Error, Image, Result};
+use base64::Engine;
+
+const PNG_MAGIC: &[u8] = b"\x89PNG";
+const JPEG_MAGIC: &[u8] = b"\xFF\xD8\xFF";
+
+/// The types of image that can be attached, to offer them in file dialogs
+pub const MIME_TYPES: &[&str] = &["image/png", "image/jpeg"];
+
+/// Reads an image file to attach, which has to be a PNG or JPEG as that's what models take
+pub async fn load(path: PathBuf) -> Result<Image> {
+    let bytes = tokio::fs::read(&path).await.map_err(|err| Error::Read {
+        path: path.clone(),
+        message: err.to_string(),
+    })?;
+    if !bytes.starts_with(PNG_MAGIC) && !bytes.starts_with(JPEG_MAGIC) {
+        return Err(Error::Read {
+            path,
+            message: "it isn't a PNG or JPEG image".to_string(),
+        });
+    }
+    Ok(from_bytes(&bytes))
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Image {
+    Image::from_base64(&base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// The image's base64 data, which `Image` only hands over by serializing it
+fn base64_data(image: &Image) -> String {
+    match serde_json::to_value(image) {
+        Ok(serde_json::Value::String(data)) => data,
+        _ => String::new(),
+    }
+}
+
+/// The image as a `data:` URL, which is how OpenAI-compatible servers take images
+pub(crate) fn data_url(image: &Image) -> String {
+    let data = base64_data(image);
+    // Base64 of the PNG magic bytes starts with "iVBOR", anything else attached is a JPEG
+    let mime_type = if data.starts_with("iVBOR") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    };
+    format!("data:{mime_type};base64,{data}")
+}
+
+/// An SVG showing the image scaled to fit a square, so it can be drawn as a thumbnail by an SVG
+/// renderer without decoding it separately
+pub fn thumbnail_svg(image: &Image, size: u32) -> Vec<u8> {
+    let data_url = data_url(image);
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{size}" height="{size}"><image href="{data_url}" width="{size}" height="{size}" preserveAspectRatio="xMidYMid meet"/></svg>"#
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_png_and_jpeg_images_load() {
+        let dir =
+            std::env::temp_dir().join(format!("comhra-core-test-{}-images", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let png_path = dir.join("image.png");
+        std::fs::write(&png_path, b"\x89PNG\r\n\x1a\n").unwrap();
+        let text_path = dir.join("notes.png");
+        std::fs::write(&text_path, "Not an image").unwrap();
+
+        let image = load(png_path).await.unwrap();
+        assert_eq!(base64_data(&image), "iVBORw0KGgo=");
+        assert!(String::from_utf8(thumbnail_svg(&image, 64))
+            .unwrap()
+            .contains("data:image/png;base64,iVBORw0KGgo="));
+        assert!(matches!(load(text_path).await, Err(Error::Read { .. })));
+    }
+}
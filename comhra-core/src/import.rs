@@ -0,0 +1,411 @@
+//! Bringing in conversations from other chat apps, so moving to this one doesn't mean losing them
+//!
+//! Reads ChatGPT's `conversations.json` export, Open WebUI's chat export, any JSON list of
+//! `{"role", "content"}` messages and the Ollama CLI's `~/.ollama/history` of prompts.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::conversation::Conversation;
+use crate::database::{self, Database};
+use crate::storage::{self, copy_title};
+use crate::{ChatMessage, Error, MessageRole, Result};
+
+/// Title of the conversation the Ollama CLI's prompts are imported into
+const OLLAMA_HISTORY_TITLE: &str = "Ollama CLI history";
+
+/// The file the Ollama CLI keeps the prompts typed into it in, if there is one
+pub fn ollama_history_file() -> Option<PathBuf> {
+    let path = dirs::home_dir()?.join(".ollama").join("history");
+    path.is_file().then_some(path)
+}
+
+/// Adds the conversations in another app's export to the conversations dir, returning how many
+/// were added
+///
+/// Each keeps the title it had, with copies numbered if it's taken by a different conversation.
+/// Ones that were already imported are left out, so importing the same export twice is harmless.
+pub async fn import_file(path: PathBuf, conversations_dir: PathBuf) -> Result<usize> {
+    let contents = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|err| Error::Read {
+            path: path.clone(),
+            message: err.to_string(),
+        })?;
+    let conversations =
+        parse(&contents, &database::title(&path)).map_err(|message| Error::Corrupt {
+            path: path.clone(),
+            message,
+        })?;
+    storage::in_database(conversations_dir, |database| {
+        import_conversations(database, conversations)
+    })
+    .await
+}
+
+/// Saves the conversations that haven't been imported already, returning how many were saved
+fn import_conversations(
+    database: &mut Database,
+    conversations: Vec<(String, Conversation)>,
+) -> Result<usize> {
+    let mut imported = 0;
+    for (title, conversation) in conversations {
+        // Titles are cut short and kept from having slashes like those of new conversations
+        let mut title = database::title(Path::new(&storage::conversation_file_name(&title)));
+        if database.contains(&title)? {
+            if same_messages(&database.load(&title)?, &conversation) {
+                continue;
+            }
+            title = copy_title(database, &title)?;
+        }
+        let last_sent = conversation.sent_at.values().max().copied();
+        database.save_at(
+            &title,
+            &conversation,
+            last_sent.unwrap_or_else(SystemTime::now),
+        )?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+fn same_messages(saved: &Conversation, imported: &Conversation) -> bool {
+    saved.messages.len() == imported.messages.len()
+        && saved
+            .messages
+            .iter()
+            .zip(&imported.messages)
+            .all(|(saved, imported)| {
+                saved.role == imported.role && saved.content == imported.content
+            })
+}
+
+/// The conversations in an export with their titles, working out which app it's from by its
+/// shape, or a description of why it couldn't be read
+fn parse(
+    contents: &str,
+    file_title: &str,
+) -> std::result::Result<Vec<(String, Conversation)>, String> {
+    let Ok(json) = serde_json::from_str::<Value>(contents) else {
+        return ollama_history(contents).map(|conversation| vec![conversation]);
+    };
+    let first = match &json {
+        Value::Array(items) => items.first(),
+        json => Some(json),
+    };
+    let conversations = if first.is_some_and(|first| first.get("mapping").is_some()) {
+        one_or_many::<ChatGptConversation>(json)?
+            .into_iter()
+            .map(ChatGptConversation::into_conversation)
+            .collect()
+    } else if first.is_some_and(|first| first.get("chat").is_some()) {
+        one_or_many::<OpenWebUiChat>(json)?
+            .into_iter()
+            .map(|chat| (chat.title, conversation(chat.chat.messages)))
+            .collect()
+    } else {
+        let messages = match json {
+            Value::Object(mut object) if object.contains_key("messages") => {
+                object.remove("messages").unwrap_or_default()
+            }
+            json => json,
+        };
+        let messages: Vec<ExportedMessage> =
+            serde_json::from_value(messages).map_err(|_| unknown_format())?;
+        vec![(file_title.to_string(), conversation(messages))]
+    };
+    let conversations: Vec<(String, Conversation)> = conversations
+        .into_iter()
+        .filter(|(_title, conversation)| !conversation.messages.is_empty())
+        .collect();
+    if conversations.is_empty() {
+        return Err("there are no conversations in it".to_string());
+    }
+    Ok(conversations)
+}
+
+fn unknown_format() -> String {
+    "it isn't an export this app knows how to read".to_string()
+}
+
+/// Exports are a list of conversations, or a single one when it's been cut out of a list
+fn one_or_many<T: for<'de> Deserialize<'de>>(json: Value) -> std::result::Result<Vec<T>, String> {
+    match json {
+        Value::Array(_) => serde_json::from_value(json),
+        json => serde_json::from_value(json).map(|item| vec![item]),
+    }
+    .map_err(|err| format!("{}: {err}", unknown_format()))
+}
+
+/// Each prompt typed into `ollama run`, one per line, as one conversation of prompts
+fn ollama_history(contents: &str) -> std::result::Result<(String, Conversation), String> {
+    let messages: Vec<ChatMessage> = contents
+        .lines()
+        .map(str::trim)
+        // Commands like `/bye` aren't prompts
+        .filter(|line| !line.is_empty() && !line.starts_with('/'))
+        .map(|line| ChatMessage::user(line.to_string()))
+        .collect();
+    if messages.is_empty() && !contents.trim().is_empty() {
+        return Err(unknown_format());
+    }
+    Ok((
+        OLLAMA_HISTORY_TITLE.to_string(),
+        Conversation::new(messages),
+    ))
+}
+
+/// A conversation of the messages, with when they were sent where that's known and messages in a
+/// row from the same side joined together, as they're shown as one
+fn conversation(exported: Vec<ExportedMessage>) -> Conversation {
+    let mut messages: Vec<ChatMessage> = vec![];
+    let mut sent_at = vec![];
+    for exported in exported {
+        let role = match exported.role.as_str() {
+            "user" => MessageRole::User,
+            "assistant" => MessageRole::Assistant,
+            "system" => MessageRole::System,
+            _ => continue,
+        };
+        let content = exported.content.text();
+        if content.trim().is_empty() {
+            continue;
+        }
+        match messages.last_mut() {
+            Some(last) if last.role == role && role != MessageRole::System => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&content);
+            }
+            _ => {
+                messages.push(ChatMessage::new(role, content));
+                sent_at.push(None);
+            }
+        }
+        if let Some(time) = exported.timestamp.and_then(time_from_seconds) {
+            *sent_at.last_mut().expect("a message was just added") = Some(time);
+        }
+    }
+    let mut conversation = Conversation::new(messages);
+    conversation.sent_at = sent_at
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, time)| Some((index, time?)))
+        .collect();
+    conversation
+}
+
+/// Exports give times in seconds since 1970, or milliseconds in some versions of Open WebUI
+fn time_from_seconds(seconds: f64) -> Option<SystemTime> {
+    let seconds = if seconds > 1e11 {
+        seconds / 1000.0
+    } else {
+        seconds
+    };
+    (seconds.is_finite() && seconds > 0.0)
+        .then(|| SystemTime::UNIX_EPOCH + Duration::from_secs_f64(seconds))
+}
+
+/// A message as most apps export it, with the content as text or OpenAI's list of parts
+#[derive(Deserialize)]
+struct ExportedMessage {
+    role: String,
+    content: ExportedContent,
+    #[serde(default, alias = "create_time")]
+    timestamp: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExportedContent {
+    Text(String),
+    Parts(Vec<Value>),
+}
+
+impl ExportedContent {
+    /// The text parts joined, leaving out images and other attachments
+    fn text(self) -> String {
+        match self {
+            ExportedContent::Text(text) => text,
+            ExportedContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| part.as_str().or_else(|| part.get("text")?.as_str()))
+                .collect::<Vec<&str>>()
+                .join("\n"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenWebUiChat {
+    #[serde(default)]
+    title: String,
+    chat: OpenWebUiMessages,
+}
+
+#[derive(Deserialize)]
+struct OpenWebUiMessages {
+    #[serde(default)]
+    messages: Vec<ExportedMessage>,
+}
+
+/// A conversation in ChatGPT's export, kept as a tree of messages so every version of an edited
+/// message or regenerated response is there
+#[derive(Deserialize)]
+struct ChatGptConversation {
+    #[serde(default)]
+    title: Option<String>,
+    mapping: HashMap<String, ChatGptNode>,
+    /// The last message of the version that was being shown
+    #[serde(default)]
+    current_node: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptNode {
+    #[serde(default)]
+    message: Option<ChatGptMessage>,
+    #[serde(default)]
+    parent: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatGptMessage {
+    author: ChatGptAuthor,
+    content: ChatGptContent,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    metadata: Value,
+}
+
+#[derive(Deserialize)]
+struct ChatGptAuthor {
+    role: String,
+}
+
+#[derive(Deserialize)]
+struct ChatGptContent {
+    #[serde(default)]
+    content_type: String,
+    #[serde(default)]
+    parts: Vec<Value>,
+}
+
+impl ChatGptConversation {
+    /// The version of the conversation that was being shown, following the messages back from
+    /// its last one
+    fn into_conversation(mut self) -> (String, Conversation) {
+        let mut node_id = self.current_node.take().or_else(|| self.latest_node());
+        let mut messages = vec![];
+        // A node can only be visited once, so a broken export can't loop forever
+        while let Some(node) = node_id.and_then(|node_id| self.mapping.remove(&node_id)) {
+            node_id = node.parent;
+            let Some(message) = node.message else {
+                continue;
+            };
+            // Code the model ran, what it browsed and its hidden instructions aren't shown
+            let is_hidden = message.metadata["is_visually_hidden_from_conversation"] == true;
+            if is_hidden
+                || !["text", "multimodal_text"].contains(&message.content.content_type.as_str())
+            {
+                continue;
+            }
+            messages.push(ExportedMessage {
+                role: message.author.role,
+                content: ExportedContent::Parts(message.content.parts),
+                timestamp: message.create_time,
+            });
+        }
+        messages.reverse();
+        (self.title.unwrap_or_default(), conversation(messages))
+    }
+
+    /// The most recently written message, for exports that don't say which was being shown
+    fn latest_node(&self) -> Option<String> {
+        self.mapping
+            .iter()
+            .filter_map(|(id, node)| Some((node.message.as_ref()?.create_time?, id)))
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_time, id)| id.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn chatgpt_exports_follow_the_version_being_shown() {
+        let node = |role: &str, text: &str, parent: Option<&str>| {
+            json!({
+                "message": {
+                    "author": {"role": role},
+                    "content": {"content_type": "text", "parts": [text]},
+                    "create_time": 1_700_000_000.0,
+                    "metadata": {},
+                },
+                "parent": parent,
+            })
+        };
+        let export = json!([{
+            "title": "Capitals",
+            "current_node": "answer",
+            "mapping": {
+                "root": {"message": null, "parent": null},
+                "hidden": {
+                    "message": {
+                        "author": {"role": "system"},
+                        "content": {"content_type": "text", "parts": [""]},
+                        "metadata": {"is_visually_hidden_from_conversation": true},
+                    },
+                    "parent": "root",
+                },
+                "question": node("user", "What's the capital of Ireland?", Some("hidden")),
+                "first": node("assistant", "Cork", Some("question")),
+                "answer": node("assistant", "Dublin", Some("question")),
+            },
+        }]);
+        let conversations = parse(&export.to_string(), "conversations").unwrap();
+        assert_eq!(conversations.len(), 1);
+        let (title, conversation) = &conversations[0];
+        assert_eq!(title, "Capitals");
+        let messages: Vec<(&MessageRole, &str)> = conversation
+            .messages
+            .iter()
+            .map(|chat_message| (&chat_message.role, chat_message.content.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (&MessageRole::User, "What's the capital of Ireland?"),
+                (&MessageRole::Assistant, "Dublin"),
+            ]
+        );
+        assert_eq!(conversation.sent_at.len(), 2);
+    }
+
+    #[test]
+    fn message_lists_and_prompt_histories_are_read() {
+        let export = json!({"messages": [
+            {"role": "user", "content": [{"type": "text", "text": "Dia duit"}]},
+            {"role": "assistant", "content": "Dia is Muire duit"},
+            {"role": "tool", "content": "{}"},
+        ]});
+        let conversations = parse(&export.to_string(), "greeting").unwrap();
+        assert_eq!(conversations[0].0, "greeting");
+        assert_eq!(conversations[0].1.messages.len(), 2);
+        assert_eq!(conversations[0].1.messages[0].content, "Dia duit");
+
+        let history = parse("why is the sky blue\n/bye\ntell me a joke\n", "history").unwrap();
+        let (title, conversation) = &history[0];
+        assert_eq!(title, OLLAMA_HISTORY_TITLE);
+        assert_eq!(conversation.messages.len(), 2);
+        assert!(parse("{\"unrelated\": true}", "settings").is_err());
+    }
+}
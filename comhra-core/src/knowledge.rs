@@ -0,0 +1,356 @@
+//! Folders of documents attached to conversations, split into passages and embedded so the ones
+//! relevant to a prompt can be sent to the model along with it
+//!
+//! Each folder's embeddings are kept in their own file in the data dir, and only files changed
+//! since they were last embedded are embedded again.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{Backend, Provider};
+use crate::storage::{self, write_atomically};
+use crate::{crypto, ChatMessage, Error, Result};
+
+/// Number of characters in each passage
+const CHUNK_LENGTH: usize = 1000;
+
+/// Number of characters each passage shares with the one before it, so a sentence split between
+/// two is still whole in one of them
+const CHUNK_OVERLAP: usize = 200;
+
+/// Number of passages embedded with each request to the server
+const EMBED_BATCH_SIZE: usize = 16;
+
+/// Number of passages sent with each prompt
+pub const TOP_K: usize = 4;
+
+/// Extensions of the files that are read from a knowledge folder
+const DOCUMENT_EXTENSIONS: [&str; 5] = ["txt", "md", "markdown", "rst", "pdf"];
+
+/// Put before the passages so the model knows where they came from and to say which it used
+const CONTEXT_INSTRUCTION: &str = "Use the following excerpts from the user's documents to answer \
+    their next message if they're relevant, and mention the file an answer came from. If they \
+    don't help, answer as you would without them.";
+
+/// A passage from a document that was sent to the model along with a prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    pub path: PathBuf,
+    pub excerpt: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Chunk {
+    path: PathBuf,
+    text: String,
+    embedding: Vec<f32>,
+}
+
+/// The embedded passages of a folder's documents
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KnowledgeIndex {
+    /// Embedding model the passages were embedded with, which prompts have to be embedded with too
+    pub model: String,
+    /// When each file was last modified as of embedding it
+    files: BTreeMap<PathBuf, SystemTime>,
+    chunks: Vec<Chunk>,
+}
+
+impl KnowledgeIndex {
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// The `count` passages closest in meaning to the embedded prompt, closest first
+    pub fn search(&self, embedding: &[f32], count: usize) -> Vec<Citation> {
+        let mut scored: Vec<(f32, &Chunk)> = self
+            .chunks
+            .iter()
+            .map(|chunk| (cosine_similarity(&chunk.embedding, embedding), chunk))
+            .collect();
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        scored
+            .into_iter()
+            .take(count)
+            .map(|(_similarity, chunk)| Citation {
+                path: chunk.path.clone(),
+                excerpt: chunk.text.clone(),
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let magnitude = |vector: &[f32]| vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    let magnitudes = magnitude(a) * magnitude(b);
+    if magnitudes == 0.0 {
+        0.0
+    } else {
+        dot / magnitudes
+    }
+}
+
+/// A hash of a path to name the files kept for it in the data dir after
+pub(crate) fn path_hash(path: &Path) -> u64 {
+    stable_hash(path.as_os_str().as_encoded_bytes())
+}
+
+/// A hash for keeping things on disk by
+///
+/// The hash is FNV-1a rather than std's hasher, which isn't guaranteed to stay the same between
+/// Rust versions.
+pub(crate) fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Where a folder's embeddings are kept, named after a hash of its path
+fn index_file(dir: &Path) -> Result<PathBuf> {
+    Ok(storage::data_dir()?
+        .join("knowledge/")
+        .join(format!("{:016x}.json", path_hash(dir))))
+}
+
+/// Loads a folder's embeddings, `None` if it hasn't been indexed yet
+///
+/// Its passages are kept with their embeddings, so they're encrypted if conversations are and
+/// this needs to be called after unlocking.
+pub async fn load(dir: &Path) -> Result<Option<KnowledgeIndex>> {
+    let path = index_file(dir)?;
+    match tokio::fs::read(&path).await {
+        Ok(index_json) => {
+            let index_json = crypto::open(&path, index_json)?;
+            serde_json::from_slice(&index_json)
+                .map(Some)
+                .map_err(|err| Error::Corrupt {
+                    path,
+                    message: err.to_string(),
+                })
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::Read {
+            path,
+            message: err.to_string(),
+        }),
+    }
+}
+
+async fn save(dir: &Path, index: &KnowledgeIndex) -> Result<()> {
+    let path = index_file(dir)?;
+    let write_error = |message: String| Error::Write {
+        path: path.clone(),
+        message,
+    };
+    let index_json = serde_json::to_vec(index).map_err(|err| write_error(err.to_string()))?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|err| write_error(err.to_string()))?;
+    }
+    write_atomically(&path, crypto::seal(index_json)?)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+/// Lists the documents in `dir` and the folders inside it, in order
+pub fn document_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let read_dir = fs::read_dir(&dir).map_err(|err| Error::Read {
+            path: dir.clone(),
+            message: err.to_string(),
+        })?;
+        for dir_entry in read_dir.filter_map(|read_dir| read_dir.ok()) {
+            let path = dir_entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| {
+                DOCUMENT_EXTENSIONS
+                    .iter()
+                    .any(|document_ext| ext.eq_ignore_ascii_case(document_ext))
+            }) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Reads the text out of a document
+pub fn read_document(path: &Path) -> Result<String> {
+    let read_error = |message: String| Error::Read {
+        path: path.to_path_buf(),
+        message,
+    };
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+    {
+        pdf_extract::extract_text(path).map_err(|err| read_error(err.to_string()))
+    } else {
+        let bytes = fs::read(path).map_err(|err| read_error(err.to_string()))?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+/// Splits text into overlapping passages, each starting on a word where there's one to start on
+pub fn chunk(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = vec![];
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start + CHUNK_LENGTH).min(chars.len());
+        let chunk: String = chars[start..end].iter().collect();
+        let chunk = chunk.trim();
+        if !chunk.is_empty() {
+            chunks.push(chunk.to_string());
+        }
+        if end == chars.len() {
+            break;
+        }
+        let mut next_start = end - CHUNK_OVERLAP;
+        while next_start < end && !chars[next_start - 1].is_whitespace() {
+            next_start += 1;
+        }
+        start = if next_start == end {
+            end - CHUNK_OVERLAP
+        } else {
+            next_start
+        };
+    }
+    chunks
+}
+
+/// Embeds the documents in `dir` that changed since it was last indexed, calling `progress` with
+/// how far through them it is
+///
+/// Everything is embedded again if the embedding model changed, as embeddings from different
+/// models can't be compared.
+pub async fn index(
+    backend: &Backend,
+    dir: &Path,
+    model: &str,
+    mut progress: impl FnMut(f32),
+) -> Result<KnowledgeIndex> {
+    let previous = load(dir)
+        .await?
+        .filter(|previous| previous.model == model)
+        .unwrap_or_default();
+    let files = document_files(dir)?;
+    let mut index = KnowledgeIndex {
+        model: model.to_string(),
+        ..Default::default()
+    };
+    for (count, path) in files.iter().enumerate() {
+        let modified = storage::file_modified_time(path).unwrap_or(SystemTime::UNIX_EPOCH);
+        if previous.files.get(path) == Some(&modified) {
+            index.chunks.extend(
+                previous
+                    .chunks
+                    .iter()
+                    .filter(|chunk| chunk.path == *path)
+                    .cloned(),
+            );
+        } else {
+            let passages = chunk(&read_document(path)?);
+            for batch in passages.chunks(EMBED_BATCH_SIZE) {
+                let embeddings = backend.embed(model.to_string(), batch.to_vec()).await?;
+                index
+                    .chunks
+                    .extend(batch.iter().zip(embeddings).map(|(text, embedding)| Chunk {
+                        path: path.clone(),
+                        text: text.clone(),
+                        embedding,
+                    }));
+            }
+        }
+        index.files.insert(path.clone(), modified);
+        progress((count + 1) as f32 / files.len() as f32);
+    }
+    save(dir, &index).await?;
+    Ok(index)
+}
+
+/// The passages from the documents in `dir` most relevant to the prompt
+pub async fn retrieve(backend: &Backend, dir: &Path, prompt: &str) -> Result<Vec<Citation>> {
+    let index = load(dir).await?.ok_or_else(|| Error::Read {
+        path: dir.to_path_buf(),
+        message: "the folder hasn't been indexed yet".to_string(),
+    })?;
+    if index.chunks.is_empty() {
+        return Ok(vec![]);
+    }
+    let embedding = backend
+        .embed(index.model.clone(), vec![prompt.to_string()])
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::Backend("the server didn't embed the prompt".to_string()))?;
+    Ok(index.search(&embedding, TOP_K))
+}
+
+/// A system message with the passages, to go before the prompt they were found for
+pub fn context_message(citations: &[Citation]) -> ChatMessage {
+    let mut context = CONTEXT_INSTRUCTION.to_string();
+    for citation in citations {
+        context.push_str(&format!(
+            "\n\nFrom {}:\n{}",
+            citation
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            citation.excerpt
+        ));
+    }
+    ChatMessage::system(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passages_overlap_and_start_on_words() {
+        let text = "word ".repeat(500);
+        let chunks = chunk(&text);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.starts_with("word")));
+        assert!(chunks[0].chars().count() <= CHUNK_LENGTH);
+        assert!(chunk("   ").is_empty());
+        assert_eq!(chunk("Short note"), ["Short note"]);
+    }
+
+    #[test]
+    fn search_finds_the_closest_passages() {
+        let chunk = |text: &str, embedding: Vec<f32>| Chunk {
+            path: PathBuf::from(format!("{text}.md")),
+            text: text.to_string(),
+            embedding,
+        };
+        let index = KnowledgeIndex {
+            model: "nomic-embed-text".to_string(),
+            files: BTreeMap::new(),
+            chunks: vec![
+                chunk("bread", vec![1.0, 0.0]),
+                chunk("cheese", vec![0.0, 1.0]),
+                chunk("sandwich", vec![0.7, 0.7]),
+            ],
+        };
+        let excerpts: Vec<String> = index
+            .search(&[0.9, 0.1], 2)
+            .into_iter()
+            .map(|citation| citation.excerpt)
+            .collect();
+        assert_eq!(excerpts, ["bread", "sandwich"]);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+}
@@ -0,0 +1,52 @@
+//! Conversation storage, settings and backend clients shared by the Comhrá frontends.
+
+pub mod backend;
+pub mod batch;
+pub mod benchmark;
+pub mod composer;
+pub mod context;
+pub mod conversation;
+pub mod copy;
+pub mod crypto;
+mod database;
+pub mod dictation;
+mod error;
+pub mod export;
+pub mod files;
+pub mod history;
+pub mod images;
+pub mod import;
+pub mod knowledge;
+pub mod markdown_cache;
+pub mod math;
+pub mod modelfile;
+pub mod models;
+pub mod personas;
+pub mod profile;
+pub mod reasoning;
+pub mod recovery;
+pub mod related;
+pub mod screenshot;
+pub mod search;
+pub mod session;
+pub mod settings;
+pub mod share;
+pub mod speech;
+pub mod storage;
+pub mod streamed;
+pub mod structured;
+pub mod templates;
+#[cfg(test)]
+mod test_support;
+pub mod time;
+pub mod title;
+pub mod tools;
+pub mod translation;
+pub mod usage;
+pub mod web_search;
+
+pub use error::{Error, Result};
+
+pub use ollama_rs::generation::chat::{ChatMessage, MessageRole};
+pub use ollama_rs::generation::images::Image;
+pub use ollama_rs::models::LocalModel;
@@ -0,0 +1,201 @@
+//! Messages' markdown as it's prepared to be parsed for display, kept between sessions by a hash
+//! of each message so reopening conversations doesn't prepare them all over again
+//!
+//! iced's parsed markdown can't be written out, so what's kept on disk is the markdown with its
+//! maths prepared by [`math::prepare`]. The caches in memory and on disk are both held to a budget
+//! in bytes, dropping what was used longest ago first.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::knowledge::stable_hash;
+use crate::storage::{self, write_atomically};
+use crate::{crypto, math, Error, Result};
+
+/// A hash of a message's content to cache what's made from it by
+pub fn content_hash(content: &str) -> u64 {
+    stable_hash(content.as_bytes())
+}
+
+/// Values by key, held to a budget in bytes by dropping the least recently used
+#[derive(Debug, Clone)]
+pub struct Lru<V> {
+    entries: HashMap<u64, Entry<V>>,
+    bytes: usize,
+    budget: usize,
+    /// Counts up with each use, to tell which entry was used longest ago
+    clock: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    value: V,
+    bytes: usize,
+    last_used: u64,
+}
+
+impl<V> Lru<V> {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            bytes: 0,
+            budget,
+            clock: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<&V> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(&key)?;
+        entry.last_used = self.clock;
+        Some(&entry.value)
+    }
+
+    /// Adds a value taking up `bytes` of the budget, dropping whatever was used longest ago until
+    /// everything fits in it again
+    pub fn insert(&mut self, key: u64, value: V, bytes: usize) {
+        self.clock += 1;
+        let entry = Entry {
+            value,
+            bytes,
+            last_used: self.clock,
+        };
+        if let Some(replaced) = self.entries.insert(key, entry) {
+            self.bytes -= replaced.bytes;
+        }
+        self.bytes += bytes;
+        self.evict();
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    /// The keys from the one used longest ago to the one used last
+    fn keys_by_recency(&self) -> Vec<u64> {
+        let mut keys: Vec<(u64, u64)> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (entry.last_used, *key))
+            .collect();
+        keys.sort_unstable();
+        keys.into_iter().map(|(_last_used, key)| key).collect()
+    }
+
+    fn evict(&mut self) {
+        if self.bytes <= self.budget {
+            return;
+        }
+        for key in self.keys_by_recency() {
+            if let Some(entry) = self.entries.remove(&key) {
+                self.bytes -= entry.bytes;
+            }
+            if self.bytes <= self.budget {
+                break;
+            }
+        }
+    }
+}
+
+/// The message's markdown with its maths prepared, from the cache if it's been prepared before
+///
+/// Messages without maths are the same once prepared, so they're left out of the cache.
+pub fn prepare(cache: &mut Lru<String>, content: &str) -> String {
+    let key = content_hash(content);
+    if let Some(prepared) = cache.get(key) {
+        return prepared.clone();
+    }
+    match math::prepare(content) {
+        Cow::Borrowed(content) => content.to_string(),
+        Cow::Owned(prepared) => {
+            cache.insert(key, prepared.clone(), prepared.len());
+            prepared
+        }
+    }
+}
+
+fn cache_file() -> Result<PathBuf> {
+    Ok(storage::data_dir()?.join("markdown-cache.json"))
+}
+
+/// Loads the markdown prepared in earlier sessions, with no budget until one's set
+///
+/// Needs to be called after unlocking if conversations are encrypted.
+pub async fn load() -> Result<Lru<String>> {
+    let mut cache = Lru::new(usize::MAX);
+    let path = cache_file()?;
+    let cache_json = match tokio::fs::read(&path).await {
+        Ok(cache_json) => crypto::open(&path, cache_json)?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(cache),
+        Err(err) => {
+            return Err(Error::Read {
+                path,
+                message: err.to_string(),
+            })
+        }
+    };
+    let entries: Vec<(u64, String)> =
+        serde_json::from_slice(&cache_json).map_err(|err| Error::Corrupt {
+            path,
+            message: err.to_string(),
+        })?;
+    // Saved from the one used longest ago, so they're used in the same order again
+    for (key, prepared) in entries {
+        let bytes = prepared.len();
+        cache.insert(key, prepared, bytes);
+    }
+    Ok(cache)
+}
+
+pub async fn save(mut cache: Lru<String>) -> Result<()> {
+    let path = cache_file()?;
+    let write_error = |message: String| Error::Write {
+        path: path.clone(),
+        message,
+    };
+    let entries: Vec<(u64, String)> = cache
+        .keys_by_recency()
+        .into_iter()
+        .filter_map(|key| Some((key, cache.entries.remove(&key)?.value)))
+        .collect();
+    let cache_json = serde_json::to_vec(&entries).map_err(|err| write_error(err.to_string()))?;
+    tokio::fs::create_dir_all(storage::data_dir()?)
+        .await
+        .map_err(|err| write_error(err.to_string()))?;
+    write_atomically(&path, crypto::seal(cache_json)?)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_least_recently_used_are_dropped_to_stay_in_budget() {
+        let mut cache = Lru::new(10);
+        cache.insert(1, "one", 4);
+        cache.insert(2, "two", 4);
+        assert_eq!(cache.get(1), Some(&"one"));
+        cache.insert(3, "three", 4);
+        assert_eq!(cache.get(2), None);
+        assert_eq!(cache.get(1), Some(&"one"));
+        assert_eq!(cache.get(3), Some(&"three"));
+        cache.insert(3, "three again", 7);
+        assert_eq!(cache.get(1), None);
+        cache.set_budget(5);
+        assert_eq!(cache.get(3), None);
+        assert_eq!(cache.bytes, 0);
+    }
+
+    #[test]
+    fn only_markdown_with_maths_is_cached() {
+        let mut cache = Lru::new(usize::MAX);
+        assert_eq!(prepare(&mut cache, "No maths"), "No maths");
+        assert!(cache.entries.is_empty());
+        assert_eq!(prepare(&mut cache, "Some $x^2$"), "Some x²");
+        assert_eq!(cache.get(content_hash("Some $x^2$")).unwrap(), "Some x²");
+    }
+}
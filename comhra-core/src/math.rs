@@ -0,0 +1,490 @@
+//! Maths in responses, written in LaTeX between `$` signs, which would otherwise be shown just as
+//! it's written
+//!
+//! Inline maths is turned into Unicode text, e.g. `$\alpha^2 \leq x_i$` into `α² ≤ xᵢ`, as it sits
+//! in a line of text that can't hold images. Display maths between `$$` gets a block of its own,
+//! rendered to SVG by MathJax's `tex2svg` where it's installed and shown as Unicode text where
+//! it isn't.
+
+use std::borrow::Cow;
+use std::iter::Peekable;
+use std::process::Stdio;
+use std::str::Chars;
+
+use pulldown_cmark::{Event, Options, Parser};
+use tokio::process::Command;
+
+use crate::{Error, Result};
+
+/// Language of the fenced blocks display maths is moved into
+pub const MATH_LANGUAGE: &str = "math";
+
+/// The markdown with its inline maths as Unicode text and each displayed equation in a fenced
+/// [`MATH_LANGUAGE`] block, leaving code alone
+pub fn prepare(markdown: &str) -> Cow<'_, str> {
+    if !markdown.contains('$') {
+        return Cow::Borrowed(markdown);
+    }
+    let mut prepared = String::with_capacity(markdown.len());
+    let mut copied_up_to = 0;
+    for (event, range) in Parser::new_ext(markdown, Options::ENABLE_MATH).into_offset_iter() {
+        let replacement = match event {
+            Event::InlineMath(tex) => escape_markdown(&to_unicode(&tex)),
+            Event::DisplayMath(tex) => format!("\n\n```{MATH_LANGUAGE}\n{}\n```\n\n", tex.trim()),
+            _ => continue,
+        };
+        prepared.push_str(&markdown[copied_up_to..range.start]);
+        prepared.push_str(&replacement);
+        copied_up_to = range.end;
+    }
+    prepared.push_str(&markdown[copied_up_to..]);
+    Cow::Owned(prepared)
+}
+
+/// The displayed equations in the markdown, to be rendered
+pub fn display_math(markdown: &str) -> Vec<String> {
+    if !markdown.contains("$$") {
+        return vec![];
+    }
+    Parser::new_ext(markdown, Options::ENABLE_MATH)
+        .filter_map(|event| match event {
+            Event::DisplayMath(tex) => Some(tex.trim().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Renders an equation to SVG with a command that takes the TeX as its last argument, after `--`,
+/// and prints the SVG, e.g. `tex2svg` from `mathjax-node-cli`
+pub async fn render(command: String, tex: String) -> Result<Vec<u8>> {
+    let mut command = command.split_whitespace();
+    let program = command
+        .next()
+        .ok_or_else(|| Error::Math("there's no command to render it with".to_string()))?;
+    let output = Command::new(program)
+        .args(command)
+        // So an equation starting with `-` isn't taken for an option
+        .arg("--")
+        .arg(&tex)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|err| Error::Math(format!("couldn't run {program}: {err}")))?;
+    if !output.status.success() {
+        return Err(Error::Math(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    if !output.stdout.windows(4).any(|bytes| bytes == b"<svg") {
+        return Err(Error::Math(format!("{program} didn't print an SVG")));
+    }
+    Ok(output.stdout)
+}
+
+/// Backslash-escapes what markdown would read as formatting
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~' | '$'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// LaTeX maths written out in Unicode, as closely as plain text can get, e.g. `\frac{1}{2}` as
+/// `1/2` and `x^{n+1}` as `xⁿ⁺¹`
+pub fn to_unicode(tex: &str) -> String {
+    let mut converter = Converter {
+        chars: tex.chars().peekable(),
+    };
+    let mut text = String::new();
+    while converter.chars.peek().is_some() {
+        text.push_str(&converter.item());
+    }
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+struct Converter<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Converter<'_> {
+    /// A command, script, group or character
+    fn item(&mut self) -> String {
+        match self.chars.next() {
+            Some('{') => self.group(),
+            Some('\\') => self.command(),
+            Some('^') => {
+                let script = self.argument();
+                // `^\circ` is how degrees are written
+                if script == "∘" {
+                    return "°".to_string();
+                }
+                scripted(&script, '^', superscript)
+            }
+            Some('_') => scripted(&self.argument(), '_', subscript),
+            // Stray closing braces and alignment markers
+            Some('}' | '&') => String::new(),
+            Some('~') => " ".to_string(),
+            Some(c) => c.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// What's left of a `{` group, up to its closing brace
+    fn group(&mut self) -> String {
+        let mut group = String::new();
+        while let Some(c) = self.chars.peek() {
+            if *c == '}' {
+                self.chars.next();
+                break;
+            }
+            group.push_str(&self.item());
+        }
+        group
+    }
+
+    /// A command's or script's argument, which is a group or a single item
+    fn argument(&mut self) -> String {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.item()
+    }
+
+    fn command(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(c) = self.chars.next_if(char::is_ascii_alphabetic) {
+            name.push(c);
+        }
+        if name.is_empty() {
+            // A command that's a single symbol, e.g. `\{` or `\,`
+            return match self.chars.next() {
+                Some(',' | ':' | ';' | ' ' | '\\') => " ".to_string(),
+                Some('!') => String::new(),
+                Some('|') => "‖".to_string(),
+                Some(c) => c.to_string(),
+                None => String::new(),
+            };
+        }
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = self.argument();
+                let denominator = self.argument();
+                format!("{}/{}", bracketed(&numerator), bracketed(&denominator))
+            }
+            "sqrt" => {
+                // The root's degree, e.g. `\sqrt[3]{x}`
+                let degree = if self.chars.next_if_eq(&'[').is_some() {
+                    let mut degree = String::new();
+                    while let Some(c) = self.chars.next_if(|c| *c != ']') {
+                        degree.push(c);
+                    }
+                    self.chars.next();
+                    match degree.trim() {
+                        "3" => "∛",
+                        "4" => "∜",
+                        _ => "√",
+                    }
+                } else {
+                    "√"
+                };
+                format!("{degree}{}", bracketed(&self.argument()))
+            }
+            "mathbb" => self.argument().chars().map(double_struck).collect(),
+            "text" | "textrm" | "textbf" | "textit" | "mathrm" | "mathbf" | "mathit" | "mathsf"
+            | "mathtt" | "mathcal" | "boldsymbol" | "operatorname" | "overline" | "hat" | "vec"
+            | "bar" | "tilde" => self.argument(),
+            "left" | "right" | "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl"
+            | "Bigr" | "displaystyle" | "limits" | "nolimits" => String::new(),
+            "begin" | "end" => {
+                // The environment's name
+                self.argument();
+                String::new()
+            }
+            "quad" | "qquad" => " ".to_string(),
+            _ => symbol(&name).map_or_else(|| format!("\\{name}"), str::to_string),
+        }
+    }
+}
+
+/// Brackets what's more than a single number or name, so `\frac{a+b}{2}` reads `(a+b)/2`
+fn bracketed(text: &str) -> String {
+    if text.chars().all(|c| c.is_ascii_alphanumeric()) || text.chars().count() == 1 {
+        text.to_string()
+    } else {
+        format!("({text})")
+    }
+}
+
+/// Written with superscript or subscript characters if they all have one, otherwise after the
+/// marker and in brackets
+fn scripted(script: &str, marker: char, to_script: fn(char) -> Option<char>) -> String {
+    match script.chars().map(to_script).collect::<Option<String>>() {
+        Some(scripted) => scripted,
+        None => format!("{marker}{}", bracketed(script)),
+    }
+}
+
+fn superscript(c: char) -> Option<char> {
+    const DIGITS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+    Some(match c {
+        '0'..='9' => DIGITS[c as usize - '0' as usize],
+        '+' => '⁺',
+        '-' | '−' => '⁻',
+        '=' => '⁼',
+        '(' => '⁽',
+        ')' => '⁾',
+        'a' => 'ᵃ',
+        'b' => 'ᵇ',
+        'c' => 'ᶜ',
+        'd' => 'ᵈ',
+        'e' => 'ᵉ',
+        'f' => 'ᶠ',
+        'g' => 'ᵍ',
+        'h' => 'ʰ',
+        'i' => 'ⁱ',
+        'j' => 'ʲ',
+        'k' => 'ᵏ',
+        'l' => 'ˡ',
+        'm' => 'ᵐ',
+        'n' => 'ⁿ',
+        'o' => 'ᵒ',
+        'p' => 'ᵖ',
+        'r' => 'ʳ',
+        's' => 'ˢ',
+        't' => 'ᵗ',
+        'u' => 'ᵘ',
+        'v' => 'ᵛ',
+        'w' => 'ʷ',
+        'x' => 'ˣ',
+        'y' => 'ʸ',
+        'z' => 'ᶻ',
+        'T' => 'ᵀ',
+        '′' | '\'' => '′',
+        _ => return None,
+    })
+}
+
+fn subscript(c: char) -> Option<char> {
+    const DIGITS: [char; 10] = ['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+    Some(match c {
+        '0'..='9' => DIGITS[c as usize - '0' as usize],
+        '+' => '₊',
+        '-' | '−' => '₋',
+        '=' => '₌',
+        '(' => '₍',
+        ')' => '₎',
+        'a' => 'ₐ',
+        'e' => 'ₑ',
+        'h' => 'ₕ',
+        'i' => 'ᵢ',
+        'j' => 'ⱼ',
+        'k' => 'ₖ',
+        'l' => 'ₗ',
+        'm' => 'ₘ',
+        'n' => 'ₙ',
+        'o' => 'ₒ',
+        'p' => 'ₚ',
+        'r' => 'ᵣ',
+        's' => 'ₛ',
+        't' => 'ₜ',
+        'u' => 'ᵤ',
+        'v' => 'ᵥ',
+        'x' => 'ₓ',
+        _ => return None,
+    })
+}
+
+fn double_struck(c: char) -> char {
+    match c {
+        'C' => 'ℂ',
+        'H' => 'ℍ',
+        'N' => 'ℕ',
+        'P' => 'ℙ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'Z' => 'ℤ',
+        _ => c,
+    }
+}
+
+/// The character for a command that stands for one, or the name of a function like `\sin`
+fn symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "rho" => "ρ",
+        "sigma" => "σ",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "times" => "×",
+        "cdot" | "cdotp" => "⋅",
+        "div" => "÷",
+        "pm" => "±",
+        "mp" => "∓",
+        "leq" | "le" => "≤",
+        "geq" | "ge" => "≥",
+        "neq" | "ne" => "≠",
+        "ll" => "≪",
+        "gg" => "≫",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "simeq" => "≃",
+        "cong" => "≅",
+        "propto" => "∝",
+        "infty" => "∞",
+        "sum" => "∑",
+        "prod" => "∏",
+        "int" => "∫",
+        "iint" => "∬",
+        "oint" => "∮",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "implies" => "⟹",
+        "mapsto" => "↦",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "subset" => "⊂",
+        "subseteq" => "⊆",
+        "supset" => "⊃",
+        "supseteq" => "⊇",
+        "cup" => "∪",
+        "cap" => "∩",
+        "setminus" => "∖",
+        "emptyset" | "varnothing" => "∅",
+        "forall" => "∀",
+        "exists" => "∃",
+        "neg" | "lnot" => "¬",
+        "land" | "wedge" => "∧",
+        "lor" | "vee" => "∨",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        "circ" => "∘",
+        "bullet" => "∙",
+        "star" => "⋆",
+        "ast" => "∗",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "prime" => "′",
+        "degree" => "°",
+        "angle" => "∠",
+        "perp" => "⊥",
+        "parallel" => "∥",
+        "mid" | "vert" | "lvert" | "rvert" => "|",
+        "Vert" | "lVert" | "rVert" => "‖",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        "aleph" => "ℵ",
+        "sin" => "sin",
+        "cos" => "cos",
+        "tan" => "tan",
+        "sec" => "sec",
+        "csc" => "csc",
+        "cot" => "cot",
+        "arcsin" => "arcsin",
+        "arccos" => "arccos",
+        "arctan" => "arctan",
+        "sinh" => "sinh",
+        "cosh" => "cosh",
+        "tanh" => "tanh",
+        "log" => "log",
+        "ln" => "ln",
+        "exp" => "exp",
+        "lim" => "lim",
+        "sup" => "sup",
+        "inf" => "inf",
+        "max" => "max",
+        "min" => "min",
+        "det" => "det",
+        "dim" => "dim",
+        "ker" => "ker",
+        "gcd" => "gcd",
+        "deg" => "deg",
+        "arg" => "arg",
+        "mod" | "bmod" => "mod",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latex_is_written_out_in_unicode() {
+        assert_eq!(to_unicode(r"\alpha^2 \leq x_i"), "α² ≤ xᵢ");
+        assert_eq!(to_unicode(r"\frac{1}{2} + \sqrt{x+1}"), "1/2 + √(x+1)");
+        assert_eq!(to_unicode(r"x^{n+1} = e^{i\pi}"), "xⁿ⁺¹ = e^(iπ)");
+        assert_eq!(to_unicode(r"\mathbb{R}^n, 90^\circ"), "ℝⁿ, 90°");
+        assert_eq!(to_unicode(r"\text{if } x > 0"), "if x > 0");
+        assert_eq!(
+            to_unicode(r"\sqrt[3]{8} \cdot \frac{a+b}{2}"),
+            "∛8 ⋅ (a+b)/2"
+        );
+    }
+
+    #[test]
+    fn display_maths_is_moved_into_blocks_and_code_left_alone() {
+        let response = "So $a^2 + b^2 = c^2$, and:\n\n$$\\sum_{i=1}^n i$$\n\n`echo $HOME`";
+        assert_eq!(
+            prepare(response),
+            "So a² + b² = c², and:\n\n\n\n```math\n\\sum_{i=1}^n i\n```\n\n\n\n`echo $HOME`"
+        );
+        assert_eq!(display_math(response), vec![r"\sum_{i=1}^n i"]);
+        assert!(matches!(prepare("No maths here"), Cow::Borrowed(_)));
+    }
+}
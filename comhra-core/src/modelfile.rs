@@ -0,0 +1,62 @@
+//! Modelfiles for creating a model from one that's installed, with its own system prompt and
+//! default parameters, the way `ollama create` does
+
+use std::fmt::Write as _;
+
+use crate::conversation::GenerationParams;
+
+/// A Modelfile deriving a model from `base`, leaving out the system prompt if it's blank and
+/// any params that aren't set
+pub fn build(base: &str, system_prompt: &str, params: &GenerationParams) -> String {
+    let mut modelfile = format!("FROM {}\n", base.trim());
+    let system_prompt = system_prompt.trim();
+    if !system_prompt.is_empty() {
+        // Modelfiles have no way to escape the quotes that end a multiline string
+        let system_prompt = system_prompt.replace("\"\"\"", "\"\"");
+        let _ = writeln!(modelfile, "SYSTEM \"\"\"{system_prompt}\"\"\"");
+    }
+    let parameters = [
+        (
+            "temperature",
+            params.temperature.map(|value| value.to_string()),
+        ),
+        ("top_p", params.top_p.map(|value| value.to_string())),
+        ("top_k", params.top_k.map(|value| value.to_string())),
+        (
+            "repeat_penalty",
+            params.repeat_penalty.map(|value| value.to_string()),
+        ),
+        ("seed", params.seed.map(|value| value.to_string())),
+        ("num_ctx", params.num_ctx.map(|value| value.to_string())),
+    ];
+    for (name, value) in parameters
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+    {
+        let _ = writeln!(modelfile, "PARAMETER {name} {value}");
+    }
+    modelfile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modelfiles_have_only_whats_set() {
+        let params = GenerationParams {
+            temperature: Some(0.2),
+            num_ctx: Some(8192),
+            ..GenerationParams::default()
+        };
+        assert_eq!(
+            build("llama3.2", " Say \"\"\"hi\"\"\". ", &params),
+            "FROM llama3.2\nSYSTEM \"\"\"Say \"\"hi\"\".\"\"\"\nPARAMETER temperature 0.2\n\
+            PARAMETER num_ctx 8192\n"
+        );
+        assert_eq!(
+            build("mistral", "", &GenerationParams::default()),
+            "FROM mistral\n"
+        );
+    }
+}
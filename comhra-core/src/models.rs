@@ -0,0 +1,107 @@
+//! Details of the models installed on the Ollama server, and progress of pulling or creating new
+//! ones
+
+use std::pin::Pin;
+
+use serde::Deserialize;
+use tokio_stream::Stream;
+
+use crate::Result;
+
+pub use ollama_rs::models::create::CreateModelStatus;
+pub use ollama_rs::models::pull::PullModelStatus;
+
+/// Statuses of a model being pulled, as the server reports them
+pub type PullStream = Pin<Box<dyn Stream<Item = Result<PullModelStatus>> + Send>>;
+
+/// Statuses of a model being created from a Modelfile, as the server reports them
+pub type CreateStream = Pin<Box<dyn Stream<Item = Result<CreateModelStatus>> + Send>>;
+
+/// What the server knows about an installed model
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ModelDetails {
+    pub name: String,
+    /// Size on disk, in bytes
+    pub size: u64,
+    /// When it was pulled or last changed, e.g. `2024-10-02T18:40:12.123+01:00`
+    pub modified_at: String,
+    #[serde(default)]
+    pub details: ModelFamily,
+}
+
+/// The parts of a model's details that are worth showing, which older servers may leave out
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ModelFamily {
+    /// Number of parameters, e.g. `8.0B`
+    pub parameter_size: String,
+    /// How the weights are quantized, e.g. `Q4_0`
+    pub quantization_level: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ModelList {
+    pub(crate) models: Vec<ModelDetails>,
+}
+
+/// The day from a model's modification time, without the time
+pub fn modified_date(modified_at: &str) -> &str {
+    modified_at
+        .split_once('T')
+        .map_or(modified_at, |(date, _time)| date)
+}
+
+/// Formats a size in bytes the way the Ollama CLI does, e.g. `4.7 GB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next_unit in UNITS {
+        if size < 1000.0 {
+            break;
+        }
+        size /= 1000.0;
+        unit = next_unit;
+    }
+    if unit == "B" {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {unit}")
+    }
+}
+
+/// How far through downloading the current layer a pull is, if it's downloading anything
+pub fn pull_progress(status: &PullModelStatus) -> Option<f32> {
+    match (status.completed, status.total) {
+        (Some(completed), Some(total)) if total > 0 => Some(completed as f32 / total as f32),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_lists_parse_with_or_without_details() {
+        let model_list: ModelList = serde_json::from_str(
+            r#"{"models": [
+                {"name": "llama3.1:latest", "modified_at": "2024-10-02T18:40:12.123+01:00",
+                 "size": 4661224676, "digest": "abc",
+                 "details": {"family": "llama", "parameter_size": "8.0B",
+                             "quantization_level": "Q4_0"}},
+                {"name": "old:latest", "modified_at": "2024-01-01T00:00:00Z", "size": 512}
+            ]}"#,
+        )
+        .unwrap();
+        let [llama, old] = model_list.models.as_slice() else {
+            panic!("expected two models");
+        };
+        assert_eq!(llama.details.parameter_size, "8.0B");
+        assert_eq!(llama.details.quantization_level, "Q4_0");
+        assert_eq!(modified_date(&llama.modified_at), "2024-10-02");
+        assert_eq!(format_size(llama.size), "4.7 GB");
+        assert_eq!(old.details, ModelFamily::default());
+        assert_eq!(format_size(old.size), "512 B");
+    }
+}
@@ -0,0 +1,90 @@
+//! Named system prompts to start conversations with, e.g. a code reviewer or a translator
+
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::write_atomically;
+use crate::{settings, Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Persona {
+    pub name: String,
+    /// Put at the start of conversations as their system message
+    pub system_prompt: String,
+}
+
+impl fmt::Display for Persona {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+/// Kept in a TOML file alongside the settings so they can be edited by hand
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PersonasFile {
+    #[serde(default)]
+    personas: Vec<Persona>,
+}
+
+pub fn personas_file() -> Result<PathBuf> {
+    Ok(settings::config_dir()?.join("personas.toml"))
+}
+
+/// Loads the saved personas, none if there's no personas file yet
+pub async fn load() -> Result<Vec<Persona>> {
+    let path = personas_file()?;
+    match tokio::fs::read_to_string(&path).await {
+        Ok(personas_toml) => toml::from_str::<PersonasFile>(&personas_toml)
+            .map(|personas_file| personas_file.personas)
+            .map_err(|err| Error::Corrupt {
+                path,
+                message: err.to_string(),
+            }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(Error::Read {
+            path,
+            message: err.to_string(),
+        }),
+    }
+}
+
+pub async fn save(personas: Vec<Persona>) -> Result<()> {
+    let path = personas_file()?;
+    let write_error = |message: String| Error::Write {
+        path: path.clone(),
+        message,
+    };
+    let personas_toml = toml::to_string_pretty(&PersonasFile { personas })
+        .map_err(|err| write_error(err.to_string()))?;
+    tokio::fs::create_dir_all(settings::config_dir()?)
+        .await
+        .map_err(|err| write_error(err.to_string()))?;
+    write_atomically(&path, personas_toml)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn personas_survive_a_roundtrip() {
+        let personas = vec![Persona {
+            name: "Translator".to_string(),
+            system_prompt: "Translate everything into Irish.\nKeep the tone.".to_string(),
+        }];
+        let personas_toml = toml::to_string_pretty(&PersonasFile {
+            personas: personas.clone(),
+        })
+        .unwrap();
+        assert_eq!(
+            toml::from_str::<PersonasFile>(&personas_toml)
+                .unwrap()
+                .personas,
+            personas
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Profiles keep separate conversations, settings and data, e.g. for work and personal use
+//!
+//! Without a profile selected everything is stored directly in the app's directories, as it was
+//! before profiles existed. Each profile gets a `profiles/<name>/` directory inside them instead.
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::{storage, Error, Result};
+
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// The selected profile, or `None` for the default one
+pub fn current() -> Option<String> {
+    PROFILE.read().ok()?.clone()
+}
+
+/// Switches to the profile, creating it if it doesn't exist yet
+///
+/// Call this before anything is loaded, as every path the app uses depends on it.
+pub fn select(name: Option<String>) -> Result<()> {
+    if let Some(name) = name.as_ref() {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidProfile(name.clone()));
+        }
+    }
+    if let Ok(mut profile) = PROFILE.write() {
+        *profile = name;
+    }
+    let data_dir = storage::data_dir()?;
+    std::fs::create_dir_all(&data_dir).map_err(|err| Error::Write {
+        path: data_dir,
+        message: err.to_string(),
+    })
+}
+
+/// Names of the profiles that have been created, in alphabetical order
+pub fn list() -> Result<Vec<String>> {
+    let profiles_dir = storage::app_data_dir()?.join("profiles/");
+    let read_dir = match std::fs::read_dir(&profiles_dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => {
+            return Err(Error::Read {
+                path: profiles_dir,
+                message: err.to_string(),
+            })
+        }
+    };
+    let mut profiles: Vec<String> = read_dir
+        .filter_map(|dir_entry| dir_entry.ok())
+        .filter(|dir_entry| dir_entry.path().is_dir())
+        .filter_map(|dir_entry| dir_entry.file_name().into_string().ok())
+        .filter(|name| is_valid_name(name))
+        .collect();
+    profiles.sort();
+    Ok(profiles)
+}
+
+/// Whether the name can be used as a directory name on every platform
+pub fn is_valid_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\', ':', '*', '?', '"', '<', '>', '|'])
+        && !name.chars().any(char::is_control)
+}
+
+/// The app directory `dir` moved inside the selected profile's directory
+pub(crate) fn scoped(dir: &Path) -> PathBuf {
+    match current() {
+        Some(name) => dir.join("profiles/").join(format!("{name}/")),
+        None => dir.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_names_cant_leave_their_directory() {
+        assert!(is_valid_name("Work"));
+        assert!(is_valid_name("Síle's stuff"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name(".."));
+        assert!(!is_valid_name("../escape"));
+        assert!(!is_valid_name("a\\b"));
+    }
+}
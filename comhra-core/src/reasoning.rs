@@ -0,0 +1,60 @@
+//! The reasoning models write out between `<think>` and `</think>` before answering, kept apart
+//! from the answer so it can be shown folded away and left out of what's copied or exported
+
+const OPENING_TAG: &str = "<think>";
+const CLOSING_TAG: &str = "</think>";
+
+/// A response's reasoning, if it has any, and its answer
+///
+/// Reasoning that hasn't been closed yet is still being written, so the answer is empty until it
+/// is. Some chat templates start the section themselves, leaving only its end in the response.
+pub fn split(content: &str) -> (Option<&str>, &str) {
+    let trimmed = content.trim_start();
+    if let Some(thinking) = trimmed.strip_prefix(OPENING_TAG) {
+        return match thinking.split_once(CLOSING_TAG) {
+            Some((reasoning, answer)) => (Some(reasoning.trim()), answer.trim_start()),
+            None => (Some(thinking.trim()), ""),
+        };
+    }
+    // The opening tag only partly streamed in so far
+    if !trimmed.is_empty() && OPENING_TAG.starts_with(trimmed) {
+        return (Some(""), "");
+    }
+    match content.split_once(CLOSING_TAG) {
+        Some((reasoning, answer)) => (Some(reasoning.trim()), answer.trim_start()),
+        None => (None, content),
+    }
+}
+
+/// A response without its reasoning
+pub fn answer(content: &str) -> &str {
+    split(content).1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reasoning_is_split_from_the_answer() {
+        assert_eq!(
+            split("<think>\nThey want a haiku.\n</think>\n\nAn old silent pond"),
+            (Some("They want a haiku."), "An old silent pond")
+        );
+        assert_eq!(
+            split("They want a haiku.\n</think>\nAn old silent pond"),
+            (Some("They want a haiku."), "An old silent pond")
+        );
+        assert_eq!(split("Just an answer"), (None, "Just an answer"));
+    }
+
+    #[test]
+    fn reasoning_being_streamed_has_no_answer_yet() {
+        assert_eq!(split("<thi"), (Some(""), ""));
+        assert_eq!(
+            split("<think>\nThey want a hai"),
+            (Some("They want a hai"), "")
+        );
+        assert_eq!(answer("<think>\nDone.\n</think>\nAn"), "An");
+    }
+}
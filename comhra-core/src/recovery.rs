@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, write_atomically};
+use crate::{crypto, Error, Result};
+
+/// Work in progress that would be lost if the app was killed, checkpointed so it can be restored
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecoveryState {
+    pub conversation: Option<PathBuf>,
+    /// The prompt that was being written but hadn't been sent
+    pub draft: String,
+    /// The response that was being generated, if there was one
+    pub partial_response: Option<String>,
+}
+
+impl RecoveryState {
+    /// Whether there's anything worth restoring
+    pub fn is_empty(&self) -> bool {
+        self.draft.is_empty() && self.partial_response.is_none()
+    }
+}
+
+fn recovery_file() -> Result<PathBuf> {
+    Ok(storage::data_dir()?.join("recovery.json"))
+}
+
+/// Needs to be called after unlocking if conversations are encrypted
+pub async fn load() -> Option<RecoveryState> {
+    let path = recovery_file().ok()?;
+    let recovery_json = tokio::fs::read(&path).await.ok()?;
+    let recovery_json = crypto::open(&path, recovery_json).ok()?;
+    serde_json::from_slice(&recovery_json)
+        .ok()
+        .filter(|recovery_state: &RecoveryState| !recovery_state.is_empty())
+}
+
+/// Checkpoints the state, or removes the checkpoint if there's nothing to recover
+pub async fn save(recovery_state: RecoveryState) -> Result<()> {
+    let path = recovery_file()?;
+    let write_error = |err: std::io::Error| Error::Write {
+        path: path.clone(),
+        message: err.to_string(),
+    };
+    if recovery_state.is_empty() {
+        return match tokio::fs::remove_file(&path).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(write_error(err)),
+            _ => Ok(()),
+        };
+    }
+    tokio::fs::create_dir_all(storage::data_dir()?)
+        .await
+        .map_err(write_error)?;
+    let recovery_json = serde_json::to_vec(&recovery_state).map_err(|err| Error::Write {
+        path: path.clone(),
+        message: err.to_string(),
+    })?;
+    write_atomically(&path, crypto::seal(recovery_json)?)
+        .await
+        .map_err(write_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_drafts_and_responses_are_worth_recovering() {
+        let mut recovery_state = RecoveryState {
+            conversation: Some(PathBuf::from("conversation.json")),
+            ..Default::default()
+        };
+        assert!(recovery_state.is_empty());
+        recovery_state.draft = "Half written".to_string();
+        assert!(!recovery_state.is_empty());
+        recovery_state.draft.clear();
+        recovery_state.partial_response = Some(String::new());
+        assert!(!recovery_state.is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+//! Conversations about the same things as one another: a summary of each conversation is embedded
+//! with the embedding model and compared with the others, as prompts are with the passages in
+//! knowledge folders
+//!
+//! The embeddings are kept in a file in the data dir for each conversations dir, encrypted along
+//! with the conversations as they'd give away what those are about, and only conversations saved
+//! since they were last embedded are embedded again.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{Backend, Provider};
+use crate::conversation::Conversation;
+use crate::knowledge::{cosine_similarity, path_hash};
+use crate::storage::{self, write_atomically};
+use crate::{crypto, reasoning, Error, MessageRole, Result};
+
+/// Number of related conversations shown for each one
+pub const RELATED_COUNT: usize = 5;
+
+/// Similarity below which conversations don't count as related
+const MIN_SIMILARITY: f32 = 0.5;
+
+/// Characters of each conversation that are embedded, as embedding models only take in so much
+const SUMMARY_LENGTH: usize = 2000;
+
+/// Number of conversations embedded with each request to the server
+const EMBED_BATCH_SIZE: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EmbeddedConversation {
+    /// When the conversation was last saved as of embedding it
+    modified: SystemTime,
+    embedding: Vec<f32>,
+}
+
+/// The embedded summaries of the conversations in a conversations dir
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConversationEmbeddings {
+    /// Embedding model the summaries were embedded with
+    pub model: String,
+    conversations: BTreeMap<PathBuf, EmbeddedConversation>,
+}
+
+impl ConversationEmbeddings {
+    /// When the conversation was last saved as of embedding it, `None` if it hasn't been
+    pub fn embedded_at(&self, conversation: &Path) -> Option<SystemTime> {
+        self.conversations
+            .get(conversation)
+            .map(|embedded| embedded.modified)
+    }
+
+    /// The `count` other conversations closest in meaning to `conversation`, closest first, with
+    /// how alike they are from 0 to 1
+    pub fn related(&self, conversation: &Path, count: usize) -> Vec<(PathBuf, f32)> {
+        let Some(embedded) = self.conversations.get(conversation) else {
+            return vec![];
+        };
+        let mut related: Vec<(PathBuf, f32)> = self
+            .conversations
+            .iter()
+            .filter(|(path, _embedded)| *path != conversation)
+            .map(|(path, other)| {
+                (
+                    path.clone(),
+                    cosine_similarity(&embedded.embedding, &other.embedding),
+                )
+            })
+            .filter(|(_path, similarity)| *similarity >= MIN_SIMILARITY)
+            .collect();
+        related.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        related.truncate(count);
+        related
+    }
+}
+
+/// What's embedded of a conversation: its title, then its messages until there's enough to go on
+pub fn summary(title: &str, conversation: &Conversation) -> String {
+    let mut summary = title.to_string();
+    for chat_message in &conversation.messages {
+        let content = match chat_message.role {
+            MessageRole::System => continue,
+            MessageRole::Assistant => reasoning::answer(&chat_message.content),
+            _ => chat_message.content.as_str(),
+        };
+        summary.push_str("\n\n");
+        summary.push_str(content.trim());
+        if summary.chars().count() >= SUMMARY_LENGTH {
+            break;
+        }
+    }
+    summary.chars().take(SUMMARY_LENGTH).collect()
+}
+
+/// Where the embeddings of a conversations dir are kept, named after a hash of its path
+fn embeddings_file(conversations_dir: &Path) -> Result<PathBuf> {
+    Ok(storage::data_dir()?
+        .join("related/")
+        .join(format!("{:016x}.json", path_hash(conversations_dir))))
+}
+
+/// Loads a conversations dir's embeddings, `None` if they haven't been embedded yet
+///
+/// Needs to be called after unlocking if conversations are encrypted.
+pub async fn load(conversations_dir: &Path) -> Result<Option<ConversationEmbeddings>> {
+    let path = embeddings_file(conversations_dir)?;
+    match tokio::fs::read(&path).await {
+        Ok(embeddings_json) => {
+            let embeddings_json = crypto::open(&path, embeddings_json)?;
+            serde_json::from_slice(&embeddings_json)
+                .map(Some)
+                .map_err(|err| Error::Corrupt {
+                    path,
+                    message: err.to_string(),
+                })
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::Read {
+            path,
+            message: err.to_string(),
+        }),
+    }
+}
+
+async fn save(conversations_dir: &Path, embeddings: &ConversationEmbeddings) -> Result<()> {
+    let path = embeddings_file(conversations_dir)?;
+    let write_error = |message: String| Error::Write {
+        path: path.clone(),
+        message,
+    };
+    let embeddings_json =
+        serde_json::to_vec(embeddings).map_err(|err| write_error(err.to_string()))?;
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|err| write_error(err.to_string()))?;
+    }
+    write_atomically(&path, crypto::seal(embeddings_json)?)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+/// Embeds the conversations, by their paths and when they were last saved, that were saved since
+/// they were last embedded, calling `progress` with how far through them it is
+///
+/// Conversations that aren't listed any more are left out, and everything is embedded again if
+/// the embedding model changed.
+pub async fn index(
+    backend: &Backend,
+    conversations_dir: &Path,
+    conversations: Vec<(PathBuf, SystemTime)>,
+    model: &str,
+    mut progress: impl FnMut(f32),
+) -> Result<ConversationEmbeddings> {
+    let previous = load(conversations_dir)
+        .await?
+        .filter(|previous| previous.model == model)
+        .unwrap_or_default();
+    let mut embeddings = ConversationEmbeddings {
+        model: model.to_string(),
+        ..Default::default()
+    };
+    let mut changed = vec![];
+    for (path, modified) in conversations {
+        match previous.conversations.get(&path) {
+            Some(embedded) if embedded.modified == modified => {
+                embeddings.conversations.insert(path, embedded.clone());
+            }
+            _ => changed.push((path, modified)),
+        }
+    }
+    for (count, batch) in changed.chunks(EMBED_BATCH_SIZE).enumerate() {
+        let mut loaded = Vec::with_capacity(batch.len());
+        let mut summaries = Vec::with_capacity(batch.len());
+        for (path, modified) in batch {
+            // Left for next time if it was deleted or can't be read now
+            let Ok(conversation) = storage::load_conversation(path.clone()).await else {
+                continue;
+            };
+            let title = path.file_stem().unwrap_or_default().to_string_lossy();
+            summaries.push(summary(&title, &conversation));
+            loaded.push((path.clone(), *modified));
+        }
+        let embedded = if summaries.is_empty() {
+            vec![]
+        } else {
+            backend.embed(model.to_string(), summaries).await?
+        };
+        embeddings
+            .conversations
+            .extend(
+                loaded
+                    .into_iter()
+                    .zip(embedded)
+                    .map(|((path, modified), embedding)| {
+                        (
+                            path,
+                            EmbeddedConversation {
+                                modified,
+                                embedding,
+                            },
+                        )
+                    }),
+            );
+        let done = ((count + 1) * EMBED_BATCH_SIZE).min(changed.len());
+        progress(done as f32 / changed.len() as f32);
+    }
+    save(conversations_dir, &embeddings).await?;
+    Ok(embeddings)
+}
+
+/// How alike the embedding model finds each of the texts to each of the others, from -1 to 1, by
+/// their indexes
+pub async fn compare(backend: Backend, model: String, texts: Vec<String>) -> Result<Vec<Vec<f32>>> {
+    let embeddings = backend.embed(model, texts).await?;
+    Ok(similarities(&embeddings))
+}
+
+fn similarities(embeddings: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    embeddings
+        .iter()
+        .map(|a| embeddings.iter().map(|b| cosine_similarity(a, b)).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ChatMessage;
+
+    use super::*;
+
+    #[test]
+    fn summaries_start_with_the_title_and_skip_reasoning() {
+        let conversation = Conversation {
+            messages: vec![
+                ChatMessage::system("You're a baker.".to_string()),
+                ChatMessage::user("How do I make soda bread?".to_string()),
+                ChatMessage::assistant("<think>Easy one.</think>With buttermilk.".to_string()),
+            ],
+            ..Conversation::default()
+        };
+        assert_eq!(
+            summary("Soda bread", &conversation),
+            "Soda bread\n\nHow do I make soda bread?\n\nWith buttermilk."
+        );
+        let long = Conversation {
+            messages: vec![ChatMessage::user("word ".repeat(1000))],
+            ..Conversation::default()
+        };
+        assert_eq!(summary("Long", &long).chars().count(), SUMMARY_LENGTH);
+    }
+
+    #[test]
+    fn related_conversations_are_the_closest_others() {
+        let embedded = |embedding: Vec<f32>| EmbeddedConversation {
+            modified: SystemTime::UNIX_EPOCH,
+            embedding,
+        };
+        let embeddings = ConversationEmbeddings {
+            model: "nomic-embed-text".to_string(),
+            conversations: BTreeMap::from([
+                (PathBuf::from("bread"), embedded(vec![1.0, 0.0])),
+                (PathBuf::from("scones"), embedded(vec![0.9, 0.3])),
+                (PathBuf::from("cake"), embedded(vec![0.7, 0.7])),
+                (PathBuf::from("rust"), embedded(vec![0.0, 1.0])),
+            ]),
+        };
+        let related: Vec<PathBuf> = embeddings
+            .related(Path::new("bread"), 5)
+            .into_iter()
+            .map(|(path, _similarity)| path)
+            .collect();
+        assert_eq!(related, [PathBuf::from("scones"), PathBuf::from("cake")]);
+        assert_eq!(embeddings.related(Path::new("bread"), 1).len(), 1);
+        assert!(embeddings.related(Path::new("unsaved"), 5).is_empty());
+        let similarities = similarities(&[vec![1.0, 0.0], vec![0.0, 2.0]]);
+        assert_eq!(similarities, [[1.0, 0.0], [0.0, 1.0]]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Screenshots of part of the screen to attach to prompts, taken with whichever of the usual
+//! screenshot tools is installed, as there's no portable way to capture the screen
+//!
+//! Each tool lets the region be dragged out and writes it to a PNG file, which is read back as an
+//! attachment.
+
+use std::io::ErrorKind;
+use std::path::Path;
+
+use tokio::process::Command;
+
+use crate::{images, Error, Image, Result};
+
+/// Tools that capture a dragged out region, in the order they're tried, with their arguments
+/// before the file to write
+const TOOLS: [(&str, &[&str]); 5] = [
+    (
+        "spectacle",
+        &["--background", "--nonotify", "--region", "--output"],
+    ),
+    ("gnome-screenshot", &["--area", "--file"]),
+    ("maim", &["--select"]),
+    ("scrot", &["--select"]),
+    // macOS
+    ("screencapture", &["-i"]),
+];
+
+/// Lets a region of the screen be picked and captures it, or `None` if picking it was cancelled
+pub async fn capture_region() -> Result<Option<Image>> {
+    let path = std::env::temp_dir().join(format!("comhra-screenshot-{}.png", std::process::id()));
+    let _ = tokio::fs::remove_file(&path).await;
+    let captured = capture_to(&path).await;
+    let image = match captured {
+        Ok(true) if path.exists() => images::load(path.clone()).await.map(Some),
+        Ok(_) => Ok(None),
+        Err(err) => Err(err),
+    };
+    let _ = tokio::fs::remove_file(&path).await;
+    image
+}
+
+/// Runs the first tool that's installed, returning whether it finished without being cancelled
+async fn capture_to(path: &Path) -> Result<bool> {
+    // grim needs the region picked with slurp first, and is what works on wlroots compositors
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        match Command::new("slurp").output().await {
+            Ok(output) if !output.status.success() => return Ok(false),
+            Ok(output) => {
+                let region = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                return run("grim", &["-g", &region], path).await?.ok_or_else(|| {
+                    Error::Screenshot("slurp is installed but grim isn't".to_string())
+                });
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(Error::Screenshot(format!("couldn't run slurp: {err}"))),
+        }
+    }
+    for (program, args) in TOOLS {
+        if let Some(is_captured) = run(program, args, path).await? {
+            return Ok(is_captured);
+        }
+    }
+    Err(Error::Screenshot(
+        "no screenshot tool was found, e.g. spectacle, gnome-screenshot, maim or grim".to_string(),
+    ))
+}
+
+/// Runs a tool with the file to write, `None` if it isn't installed
+async fn run(program: &str, args: &[&str], path: &Path) -> Result<Option<bool>> {
+    match Command::new(program).args(args).arg(path).status().await {
+        Ok(status) => Ok(Some(status.success())),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::Screenshot(format!("couldn't run {program}: {err}"))),
+    }
+}
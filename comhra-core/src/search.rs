@@ -0,0 +1,122 @@
+//! Finding conversations by their titles and what was said in them
+
+use crate::ChatMessage;
+
+/// Number of characters of a message shown either side of a match
+const SNIPPET_CONTEXT: usize = 40;
+
+/// A conversation's messages, kept in memory so every conversation can be searched while typing
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchIndex {
+    /// Each message's content as written, and lowercased for matching regardless of case
+    messages: Vec<(String, String)>,
+}
+
+/// Where a search matched in a conversation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    /// Index of the first matching message in the conversation, or `None` if only the title matched
+    pub message_index: Option<usize>,
+    /// The text around the match, empty if only the title matched
+    pub snippet: String,
+}
+
+impl SearchIndex {
+    pub fn new(conversation: &[ChatMessage]) -> Self {
+        Self {
+            messages: conversation
+                .iter()
+                .map(|chat_message| {
+                    (
+                        chat_message.content.clone(),
+                        chat_message.content.to_lowercase(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Looks for `query` in the conversation's messages, then its title, ignoring case
+    pub fn search(&self, title: &str, query: &str) -> Option<SearchMatch> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return None;
+        }
+        let message_match =
+            self.messages
+                .iter()
+                .enumerate()
+                .find_map(|(message_index, (content, lowercase))| {
+                    let match_start = lowercase.find(&query)?;
+                    Some(SearchMatch {
+                        message_index: Some(message_index),
+                        snippet: snippet(content, lowercase, match_start, &query),
+                    })
+                });
+        message_match.or_else(|| {
+            title.to_lowercase().contains(&query).then(|| SearchMatch {
+                message_index: None,
+                snippet: String::new(),
+            })
+        })
+    }
+}
+
+/// The match with some of the message around it, on one line
+fn snippet(content: &str, lowercase: &str, match_start: usize, query: &str) -> String {
+    // Lowercasing can change the length of some characters, so the match is found by its
+    // position in characters rather than bytes
+    let match_start = lowercase[..match_start].chars().count();
+    let match_end = match_start + query.chars().count();
+    let snippet_start = match_start.saturating_sub(SNIPPET_CONTEXT);
+    let snippet_end = match_end + SNIPPET_CONTEXT;
+    let mut snippet: String = content
+        .chars()
+        .skip(snippet_start)
+        .take(snippet_end - snippet_start)
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    if snippet_start > 0 {
+        snippet.insert(0, '…');
+    }
+    if content.chars().count() > snippet_end {
+        snippet.push('…');
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MessageRole;
+
+    fn chat_message(content: &str) -> ChatMessage {
+        ChatMessage {
+            role: MessageRole::User,
+            content: content.to_string(),
+            images: None,
+        }
+    }
+
+    #[test]
+    fn messages_match_before_titles_regardless_of_case() {
+        let index = SearchIndex::new(&[
+            chat_message("How do I bake bread?"),
+            chat_message(&format!(
+                "{}Knead the DOUGH\nfor ten minutes",
+                "a".repeat(50)
+            )),
+        ]);
+        let search_match = index.search("Bread", "dough").unwrap();
+        assert_eq!(search_match.message_index, Some(1));
+        assert_eq!(
+            search_match.snippet,
+            format!("…{}Knead the DOUGH for ten minutes", "a".repeat(30))
+        );
+
+        let search_match = index.search("Baking", "baking").unwrap();
+        assert_eq!(search_match.message_index, None);
+        assert_eq!(index.search("Bread", "cake"), None);
+        assert_eq!(index.search("Bread", "  "), None);
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, write_atomically};
+use crate::{crypto, Error, Result};
+
+/// What was open when the app was last closed, so the next launch can pick up where it left off
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub conversation: Option<PathBuf>,
+    /// Name of the model that was selected
+    pub model: Option<String>,
+    /// The prompt that was being written but hadn't been sent
+    pub draft: String,
+    /// How many of the conversation's latest messages were loaded, so the scroll position lines up
+    pub loaded_messages: usize,
+    /// Scroll position in the chat, from 0 at the top to 1 at the bottom
+    pub scroll_offset: f32,
+    /// Whether the sidebar was open, or left to the setting if it's not known
+    pub show_sidebar: Option<bool>,
+}
+
+fn session_file() -> Result<PathBuf> {
+    Ok(storage::data_dir()?.join("session.json"))
+}
+
+/// Loads the last session and removes it, so a launch after a crash doesn't restore a stale one
+///
+/// Needs to be called after unlocking if conversations are encrypted.
+pub async fn take() -> Option<Session> {
+    let path = session_file().ok()?;
+    let session_json = tokio::fs::read(&path).await.ok()?;
+    let _ = tokio::fs::remove_file(&path).await;
+    let session_json = crypto::open(&path, session_json).ok()?;
+    serde_json::from_slice(&session_json).ok()
+}
+
+pub async fn save(session: Session) -> Result<()> {
+    let path = session_file()?;
+    let write_error = |err: std::io::Error| Error::Write {
+        path: path.clone(),
+        message: err.to_string(),
+    };
+    tokio::fs::create_dir_all(storage::data_dir()?)
+        .await
+        .map_err(write_error)?;
+    let session_json = serde_json::to_vec(&session).map_err(|err| Error::Write {
+        path: path.clone(),
+        message: err.to_string(),
+    })?;
+    write_atomically(&path, crypto::seal(session_json)?)
+        .await
+        .map_err(write_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_missing_fields_still_load() {
+        let session: Session = serde_json::from_str(r#"{"draft":"Half written"}"#).unwrap();
+        assert_eq!(session.draft, "Half written");
+        assert_eq!(session.conversation, None);
+        assert_eq!(session.scroll_offset, 0.0);
+        assert_eq!(session.show_sidebar, None);
+    }
+}
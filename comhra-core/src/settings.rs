@@ -0,0 +1,551 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::profile;
+use crate::storage::{self, write_atomically};
+use crate::tools::Tool;
+use crate::{Error, Result};
+
+/// Everything configurable about the app, persisted as a human-editable TOML file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// URL of the server new conversations start on, including the port, as ones saved already
+    /// carry on with the server they were using
+    pub server_url: String,
+    /// Servers to switch between, e.g. Ollama running on another machine on the LAN or an
+    /// OpenAI-compatible one
+    pub servers: Vec<Server>,
+    /// Name of the theme, as iced names its built in themes, or [`SYSTEM_THEME`] to follow the
+    /// system's light or dark preference, or [`CUSTOM_THEME`] for `custom_palette`
+    pub theme: String,
+    pub custom_palette: CustomPalette,
+    /// Scale of the whole interface, text included, on top of the display's own scale
+    pub zoom: f32,
+    /// Model selected on launch, instead of starting from the model picker, and for new
+    /// conversations
+    pub default_model: Option<String>,
+    pub show_sidebar: bool,
+    /// Where conversations are stored, if not in the data dir, e.g. a synced folder
+    pub conversations_dir: Option<PathBuf>,
+    /// Encrypt conversations with a passphrase, asked for when this is turned on and on launch
+    pub encrypt_conversations: bool,
+    /// Keep every saved version of each conversation in a git repository in the conversations dir
+    pub git_history: bool,
+    /// Language code for the interface, e.g. `ga` for Irish, or the system's language if not set
+    pub language: Option<String>,
+    /// Offer to start a chat about text copied in other apps
+    pub watch_clipboard: bool,
+    /// Keep running in the system tray when the main window is closed, rather than quitting
+    pub minimize_to_tray: bool,
+    /// Have a model title each conversation after its first response, instead of naming it
+    /// after the start of the first prompt
+    pub auto_title: bool,
+    /// Model that writes the titles, e.g. a small fast one, or the conversation's own model if
+    /// not set
+    pub title_model: Option<String>,
+    /// Model that embeds documents in knowledge folders and the prompts searched for in them
+    pub embedding_model: String,
+    /// Summarise a conversation's earliest messages before sending it once it's filling up the
+    /// model's context window, rather than leaving the server to cut them off
+    pub summarize_context: bool,
+    /// Keep the reasoning models write out before answering in what's copied and exported
+    pub include_reasoning: bool,
+    /// Where shared conversations are uploaded, as the URL their pages are sent to followed by a
+    /// file name, e.g. a transfer.sh server, or left empty to save them as files instead
+    pub share_url: String,
+    pub web_search: WebSearch,
+    /// Tools models are offered to call while writing responses, on Ollama servers
+    pub tools: Vec<Tool>,
+    /// Renders displayed equations to SVG, given the TeX as its last argument, or left empty to
+    /// show them as text
+    pub math_renderer: String,
+    /// Megabytes of message content whose parsed markdown is kept in memory, for long
+    /// conversations, past which it's dropped for the messages furthest out of view
+    pub markdown_memory_budget: u32,
+    pub shortcuts: Shortcuts,
+    pub speech: Speech,
+    pub dictation: Dictation,
+}
+
+/// Range the interface can be zoomed through, and how far each zoom shortcut goes
+pub const MIN_ZOOM: f32 = 0.5;
+pub const MAX_ZOOM: f32 = 3.0;
+pub const ZOOM_STEP: f32 = 0.1;
+
+/// Range the markdown memory budget can be set to, in megabytes
+pub const MIN_MARKDOWN_MEMORY_BUDGET: u32 = 1;
+pub const MAX_MARKDOWN_MEMORY_BUDGET: u32 = 64;
+
+/// The theme that's light or dark along with the system
+pub const SYSTEM_THEME: &str = "System";
+
+/// The theme made from the custom palette
+pub const CUSTOM_THEME: &str = "Custom";
+
+/// Colours of the custom theme, written as hex like `#7aa2f7`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CustomPalette {
+    pub background: String,
+    pub text: String,
+    /// Buttons, selections and links
+    pub primary: String,
+    pub success: String,
+    pub danger: String,
+}
+
+/// Starts out as Tokyo Night Storm's colours, the default theme, to tweak from there
+impl Default for CustomPalette {
+    fn default() -> Self {
+        Self {
+            background: "#24283b".to_string(),
+            text: "#c0caf5".to_string(),
+            primary: "#7aa2f7".to_string(),
+            success: "#9ece6a".to_string(),
+            danger: "#f7768e".to_string(),
+        }
+    }
+}
+
+/// Reads a colour written as hex, like `#7aa2f7` or `#fff`, into its red, green and blue
+pub fn parse_hex_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim().strip_prefix('#').unwrap_or(hex.trim());
+    if !hex.is_ascii() {
+        return None;
+    }
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match hex.len() {
+        3 => {
+            let mut rgb = [0; 3];
+            for (index, digit) in hex.chars().enumerate() {
+                rgb[index] = channel(&digit.to_string())? * 0x11;
+            }
+            Some(rgb)
+        }
+        6 => Some([
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        ]),
+        _ => None,
+    }
+}
+
+/// Key combinations for actions, written like `Ctrl+Shift+Tab`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shortcuts {
+    pub new_chat: String,
+    pub toggle_sidebar: String,
+    pub search: String,
+    /// Sends the prompt from anywhere, not only while typing it
+    pub submit: String,
+    pub stop_generation: String,
+    pub next_conversation: String,
+    pub previous_conversation: String,
+    /// Opens the quick chat window from anywhere on the desktop, registered with the desktop's
+    /// global shortcuts, or left empty for none
+    pub quick_chat: String,
+    pub zoom_in: String,
+    pub zoom_out: String,
+    pub reset_zoom: String,
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            new_chat: "Ctrl+N".to_string(),
+            toggle_sidebar: "Ctrl+B".to_string(),
+            search: "Ctrl+F".to_string(),
+            submit: "Ctrl+Enter".to_string(),
+            stop_generation: "Escape".to_string(),
+            next_conversation: "Ctrl+Tab".to_string(),
+            previous_conversation: "Ctrl+Shift+Tab".to_string(),
+            quick_chat: "Ctrl+Alt+Space".to_string(),
+            zoom_in: "Ctrl+=".to_string(),
+            zoom_out: "Ctrl+-".to_string(),
+            reset_zoom: "Ctrl+0".to_string(),
+        }
+    }
+}
+
+/// How responses are read aloud
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Speech {
+    pub engine: TtsEngine,
+    /// espeak-ng voice, by its language, e.g. `en-gb` or `ga`, or espeak-ng's default if not set
+    pub voice: Option<String>,
+    /// Piper voice model, e.g. `en_GB-alan-medium.onnx`, which Piper needs to speak at all
+    pub piper_model: Option<PathBuf>,
+    /// Command that plays the WAV files Piper writes, with the file added on the end
+    pub player: String,
+}
+
+/// Program that turns text into speech, run as a separate process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TtsEngine {
+    #[default]
+    #[serde(rename = "espeak-ng")]
+    EspeakNg,
+    /// Sounds more natural than espeak-ng, but needs a voice model downloaded for it
+    #[serde(rename = "piper")]
+    Piper,
+}
+
+impl Default for Speech {
+    fn default() -> Self {
+        Self {
+            engine: TtsEngine::default(),
+            voice: None,
+            piper_model: None,
+            player: if cfg!(target_os = "macos") {
+                "afplay".to_string()
+            } else {
+                "aplay -q".to_string()
+            },
+        }
+    }
+}
+
+/// How prompts are dictated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Dictation {
+    /// Command that records raw 16kHz mono 16-bit samples from the microphone to its stdout until
+    /// it's killed
+    pub recorder: String,
+    /// whisper.cpp's command-line program
+    pub whisper: String,
+    /// whisper.cpp model, e.g. `ggml-base.bin`, which it needs to transcribe at all
+    pub whisper_model: Option<PathBuf>,
+}
+
+impl Default for Dictation {
+    fn default() -> Self {
+        Self {
+            recorder: if cfg!(target_os = "linux") {
+                "arecord -q -t raw -f S16_LE -r 16000 -c 1".to_string()
+            } else {
+                "sox -q -d -t raw -r 16000 -c 1 -b 16 -e signed-integer -".to_string()
+            },
+            whisper: "whisper-cli".to_string(),
+            whisper_model: None,
+        }
+    }
+}
+
+/// Where prompts are searched for on the web when that's turned on for them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebSearch {
+    pub engine: SearchEngine,
+    /// URL of the SearxNG instance, which needs its JSON format turned on
+    pub url: String,
+    /// Key for Brave's search API
+    pub api_key: String,
+}
+
+/// Search engine with an API the web is searched through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchEngine {
+    /// Self-hosted, or one of the public instances that allow it
+    #[default]
+    #[serde(rename = "searxng")]
+    Searxng,
+    #[serde(rename = "brave")]
+    Brave,
+}
+
+impl WebSearch {
+    /// Whether there's enough set to search with
+    pub fn is_set_up(&self) -> bool {
+        match self.engine {
+            SearchEngine::Searxng => !self.url.trim().is_empty(),
+            SearchEngine::Brave => !self.api_key.trim().is_empty(),
+        }
+    }
+}
+
+/// A server to generate responses with, with the login for it if it's behind a reverse proxy that
+/// asks for one
+///
+/// Passwords and API keys are saved in the settings file as they are, so they're only as safe as
+/// that file is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Server {
+    pub name: String,
+    /// Including the port, e.g. `http://192.168.1.20:11434`, and for OpenAI-compatible servers the
+    /// API version too, e.g. `http://localhost:8080/v1`
+    pub url: String,
+    pub kind: ServerKind,
+    /// Sent with HTTP basic auth, along with the password
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sent as a bearer token instead of the login, e.g. for OpenAI itself or a proxy in front of
+    /// Ollama
+    pub api_key: Option<String>,
+}
+
+/// Which API a server speaks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServerKind {
+    #[default]
+    #[serde(rename = "ollama")]
+    Ollama,
+    /// The chat completions API, which llama.cpp's server, LM Studio and vLLM have as well as
+    /// OpenAI
+    #[serde(rename = "openai")]
+    OpenAi,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            url: Settings::default().server_url,
+            kind: ServerKind::default(),
+            username: None,
+            password: None,
+            api_key: None,
+        }
+    }
+}
+
+impl fmt::Display for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.name.is_empty() {
+            f.write_str(&self.url)
+        } else {
+            f.write_str(&self.name)
+        }
+    }
+}
+
+impl Settings {
+    /// The server new conversations start on, with its login if it's one of the saved servers
+    pub fn server(&self) -> Server {
+        self.servers
+            .iter()
+            .find(|server| server.url == self.server_url)
+            .cloned()
+            .unwrap_or_else(|| Server {
+                name: self.server_url.clone(),
+                url: self.server_url.clone(),
+                ..Default::default()
+            })
+    }
+
+    /// The server to carry on a conversation with that was saved using the server at `url`
+    ///
+    /// Only saved servers are used, as they're the ones with their logins, so conversations from
+    /// any other server carry on with the default one.
+    pub fn server_for(&self, url: Option<&str>) -> Server {
+        url.and_then(|url| self.servers.iter().find(|server| server.url == url))
+            .cloned()
+            .unwrap_or_else(|| self.server())
+    }
+
+    /// The zoom a number of steps in or out from the current one, kept in range and rounded to a
+    /// whole step so stepping back and forth doesn't drift
+    pub fn zoomed(&self, steps: i32) -> f32 {
+        let zoom = ((self.zoom / ZOOM_STEP).round() + steps as f32) * ZOOM_STEP;
+        ((zoom * 100.0).round() / 100.0).clamp(MIN_ZOOM, MAX_ZOOM)
+    }
+
+    /// The markdown memory budget in bytes, kept in range in case the settings file was edited by
+    /// hand
+    pub fn markdown_memory_budget_bytes(&self) -> usize {
+        let megabytes = self
+            .markdown_memory_budget
+            .clamp(MIN_MARKDOWN_MEMORY_BUDGET, MAX_MARKDOWN_MEMORY_BUDGET);
+        megabytes as usize * 1024 * 1024
+    }
+
+    pub fn conversations_dir(&self) -> Result<PathBuf> {
+        match self.conversations_dir.as_ref() {
+            Some(conversations_dir) => Ok(conversations_dir.clone()),
+            None => storage::default_conversations_dir(),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            server_url: "http://127.0.0.1:11434".to_string(),
+            servers: vec![],
+            theme: "Tokyo Night Storm".to_string(),
+            custom_palette: CustomPalette::default(),
+            zoom: 1.0,
+            default_model: None,
+            show_sidebar: true,
+            conversations_dir: None,
+            encrypt_conversations: false,
+            git_history: false,
+            language: None,
+            watch_clipboard: false,
+            minimize_to_tray: false,
+            auto_title: false,
+            title_model: None,
+            embedding_model: "nomic-embed-text".to_string(),
+            summarize_context: false,
+            include_reasoning: false,
+            share_url: String::new(),
+            web_search: WebSearch::default(),
+            tools: vec![],
+            math_renderer: "tex2svg".to_string(),
+            markdown_memory_budget: 1,
+            shortcuts: Shortcuts::default(),
+            speech: Speech::default(),
+            dictation: Dictation::default(),
+        }
+    }
+}
+
+/// Directory for the selected profile's settings
+pub fn config_dir() -> Result<PathBuf> {
+    let mut config_dir = dirs::config_dir().ok_or(Error::NoAppDir)?;
+    config_dir.push("github.com.leo030303.comhra/");
+    Ok(profile::scoped(&config_dir))
+}
+
+pub fn settings_file() -> Result<PathBuf> {
+    Ok(config_dir()?.join("settings.toml"))
+}
+
+/// Loads the settings, writing out the defaults first if there's no settings file yet
+pub async fn load() -> Result<Settings> {
+    let path = settings_file()?;
+    match tokio::fs::read_to_string(&path).await {
+        Ok(settings_toml) => toml::from_str(&settings_toml).map_err(|err| Error::Corrupt {
+            path,
+            message: err.to_string(),
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let settings = Settings::default();
+            save(settings.clone()).await?;
+            Ok(settings)
+        }
+        Err(err) => Err(Error::Read {
+            path,
+            message: err.to_string(),
+        }),
+    }
+}
+
+pub async fn save(settings: Settings) -> Result<()> {
+    let path = settings_file()?;
+    let write_error = |message: String| Error::Write {
+        path: path.clone(),
+        message,
+    };
+    let settings_toml =
+        toml::to_string_pretty(&settings).map_err(|err| write_error(err.to_string()))?;
+    tokio::fs::create_dir_all(config_dir()?)
+        .await
+        .map_err(|err| write_error(err.to_string()))?;
+    write_atomically(&path, settings_toml)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_settings_use_defaults() {
+        let settings: Settings =
+            toml::from_str("theme = \"Dracula\"\n[shortcuts]\nnew_chat = \"Alt+N\"").unwrap();
+        assert_eq!(settings.theme, "Dracula");
+        assert_eq!(settings.server_url, Settings::default().server_url);
+        assert_eq!(settings.shortcuts.new_chat, "Alt+N");
+        assert_eq!(settings.shortcuts.search, Shortcuts::default().search);
+    }
+
+    #[test]
+    fn the_server_in_use_comes_with_its_login() {
+        let remote = Server {
+            name: "Desktop".to_string(),
+            url: "http://192.168.1.20:11434".to_string(),
+            username: Some("me".to_string()),
+            password: Some("secret".to_string()),
+            ..Default::default()
+        };
+        let mut settings = Settings {
+            servers: vec![remote.clone()],
+            ..Default::default()
+        };
+        assert_eq!(settings.server().url, Settings::default().server_url);
+        assert_eq!(settings.server().username, None);
+        assert_eq!(settings.server_for(Some(&remote.url)), remote);
+        assert_eq!(
+            settings.server_for(Some("http://elsewhere:11434")),
+            settings.server()
+        );
+        assert_eq!(settings.server_for(None), settings.server());
+        settings.server_url = remote.url.clone();
+        assert_eq!(settings.server(), remote);
+    }
+
+    #[test]
+    fn zooming_steps_through_the_range() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.zoomed(1), 1.1);
+        assert_eq!(settings.zoomed(-3), 0.7);
+        settings.zoom = 1.1000001;
+        assert_eq!(settings.zoomed(-1), 1.0);
+        assert_eq!(settings.zoomed(100), MAX_ZOOM);
+        assert_eq!(settings.zoomed(-100), MIN_ZOOM);
+    }
+
+    #[test]
+    fn hex_colors_are_parsed() {
+        assert_eq!(parse_hex_color("#7aa2f7"), Some([0x7a, 0xa2, 0xf7]));
+        assert_eq!(parse_hex_color(" 24283B "), Some([0x24, 0x28, 0x3b]));
+        assert_eq!(parse_hex_color("#fff"), Some([0xff, 0xff, 0xff]));
+        assert_eq!(parse_hex_color("#12345"), None);
+        assert_eq!(parse_hex_color("#ggg"), None);
+        assert_eq!(parse_hex_color("#éé"), None);
+        let palette = CustomPalette::default();
+        assert!([
+            &palette.background,
+            &palette.text,
+            &palette.primary,
+            &palette.success,
+            &palette.danger
+        ]
+        .iter()
+        .all(|color| parse_hex_color(color).is_some()));
+    }
+
+    #[test]
+    fn settings_survive_a_roundtrip() {
+        let settings = Settings {
+            default_model: Some("llama3.2".to_string()),
+            show_sidebar: false,
+            servers: vec![Server {
+                url: "http://localhost:8080/v1".to_string(),
+                kind: ServerKind::OpenAi,
+                api_key: Some("sk-test".to_string()),
+                ..Default::default()
+            }],
+            speech: Speech {
+                engine: TtsEngine::Piper,
+                piper_model: Some(PathBuf::from("en_GB-alan-medium.onnx")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let settings_toml = toml::to_string_pretty(&settings).unwrap();
+        assert_eq!(
+            toml::from_str::<Settings>(&settings_toml).unwrap(),
+            settings
+        );
+    }
+}
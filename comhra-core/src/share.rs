@@ -0,0 +1,61 @@
+//! Sharing conversations as links, by uploading their HTML export to a paste or static host
+//!
+//! The page is sent with a `PUT` to the host's URL followed by a file name, which is how
+//! transfer.sh-style paste hosts and WebDAV folders served over HTTP both take files.
+
+use reqwest::header::CONTENT_TYPE;
+
+use crate::{Error, Result};
+
+/// Uploads the page to the host, named after the conversation, giving back the link to it
+pub async fn upload(share_url: &str, title: &str, page: Vec<u8>) -> Result<String> {
+    let url = format!("{}/{}", share_url.trim_end_matches('/'), file_name(title));
+    let body = reqwest::Client::new()
+        .put(&url)
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(page)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|err| Error::Share(err.to_string()))?
+        .text()
+        .await
+        .map_err(|err| Error::Share(err.to_string()))?;
+    // Paste hosts reply with the link, which can differ from where the page was sent, and static
+    // hosts with nothing
+    Ok(body
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("https://") || line.starts_with("http://"))
+        .map_or(url, str::to_string))
+}
+
+/// The conversation's title as a file name, without anything that means something in a URL
+fn file_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let name = name
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() {
+        "conversation.html".to_string()
+    } else {
+        format!("{name}.html")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_become_file_names() {
+        assert_eq!(file_name("Baking Bread"), "Baking-Bread.html");
+        assert_eq!(file_name("Cad é? / 100% Gaeilge"), "Cad-é-100-Gaeilge.html");
+        assert_eq!(file_name("?!"), "conversation.html");
+    }
+}
@@ -0,0 +1,196 @@
+//! Reading responses aloud by running a local text-to-speech engine, espeak-ng or Piper
+//!
+//! The engines run as child processes that are killed when the future speaking is dropped, so
+//! aborting it stops the speech.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::process::Stdio;
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::settings::{Speech, TtsEngine};
+use crate::{Error, Result};
+
+/// A voice espeak-ng can speak with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Voice {
+    /// The language it speaks, which espeak-ng picks it by, e.g. `en-gb`
+    pub language: String,
+    pub name: String,
+}
+
+impl fmt::Display for Voice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.language)
+    }
+}
+
+/// Runs a program with `input` written to its stdin, returning what it printed
+async fn run(program: &str, args: Vec<OsString>, input: Option<&str>) -> Result<Vec<u8>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| Error::Speech(format!("couldn't run {program}: {err}")))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin
+            .write_all(input.as_bytes())
+            .await
+            .map_err(|err| Error::Speech(format!("couldn't send the text to {program}: {err}")))?;
+        // Closing stdin tells the engine the text is over
+        drop(stdin);
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|err| Error::Speech(format!("{program} stopped: {err}")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(Error::Speech(if stderr.is_empty() {
+            format!("{program} failed with {}", output.status)
+        } else {
+            stderr
+        }));
+    }
+    Ok(output.stdout)
+}
+
+/// Speaks the text, finishing when it's been read out
+pub async fn speak(speech: Speech, text: String) -> Result<()> {
+    match speech.engine {
+        TtsEngine::EspeakNg => {
+            let mut args = vec![];
+            if let Some(voice) = speech.voice.filter(|voice| !voice.is_empty()) {
+                args.extend([OsString::from("-v"), OsString::from(voice)]);
+            }
+            run("espeak-ng", args, Some(&text)).await.map(drop)
+        }
+        TtsEngine::Piper => {
+            let model = speech.piper_model.ok_or_else(|| {
+                Error::Speech("Piper needs a voice model to be chosen".to_string())
+            })?;
+            // Left behind if speaking is stopped during playback, to be overwritten next time
+            let wav_path =
+                std::env::temp_dir().join(format!("comhra-speech-{}.wav", std::process::id()));
+            run(
+                "piper",
+                vec![
+                    "--model".into(),
+                    model.into(),
+                    "--output_file".into(),
+                    wav_path.clone().into(),
+                ],
+                Some(&text),
+            )
+            .await?;
+            let mut player = speech.player.split_whitespace();
+            let program = player.next().ok_or_else(|| {
+                Error::Speech("there's no command to play audio with".to_string())
+            })?;
+            let mut args: Vec<OsString> = player.map(OsString::from).collect();
+            args.push(wav_path.clone().into());
+            let played = run(program, args, None).await;
+            let _ = tokio::fs::remove_file(&wav_path).await;
+            played.map(drop)
+        }
+    }
+}
+
+/// Lists the voices espeak-ng has installed
+pub async fn voices() -> Result<Vec<Voice>> {
+    let output = run("espeak-ng", vec!["--voices".into()], None).await?;
+    Ok(parse_voices(&String::from_utf8_lossy(&output)))
+}
+
+/// Reads `espeak-ng --voices`, a table with a header row like
+/// `Pty Language Age/Gender VoiceName File Other Languages`
+fn parse_voices(table: &str) -> Vec<Voice> {
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let language = columns.nth(1)?;
+            let name = columns.nth(1)?;
+            Some(Voice {
+                language: language.to_string(),
+                name: name.replace('_', " "),
+            })
+        })
+        .collect()
+}
+
+/// The text of a Markdown response as it should be read out, without the formatting or code blocks
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    let mut in_code_block = false;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(content) | Event::Code(content) if !in_code_block => {
+                text.push_str(&content);
+            }
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak
+            | Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item) => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn espeak_voices_are_read_from_its_table() {
+        let table =
+            "Pty Language       Age/Gender VoiceName          File                 Other Languages
+ 5  af              --/M      Afrikaans          gmw/af
+ 2  en-gb           --/M      English_(Great_Britain) gmw/en            (en 2)
+ 5  ga              --/M      Irish_Gaelic       cel/ga
+";
+        assert_eq!(
+            parse_voices(table),
+            vec![
+                Voice {
+                    language: "af".to_string(),
+                    name: "Afrikaans".to_string(),
+                },
+                Voice {
+                    language: "en-gb".to_string(),
+                    name: "English (Great Britain)".to_string(),
+                },
+                Voice {
+                    language: "ga".to_string(),
+                    name: "Irish Gaelic".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn responses_are_read_without_formatting_or_code() {
+        let response = "# Sorting\n\nUse **`sort`** on the\nvector:\n\n```rust\nv.sort();\n```\n\n- It's *stable*\n- It's fast";
+        assert_eq!(
+            plain_text(response),
+            "Sorting\nUse sort on the vector:\nIt's stable\nIt's fast"
+        );
+    }
+}
@@ -0,0 +1,827 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use crate::conversation::{Conversation, Listing};
+use crate::database::{self, Database, DATABASE_FILE_NAME};
+use crate::search::SearchIndex;
+use crate::{crypto, profile, settings, ChatMessage, Error, Result};
+
+/// Number of characters of the first prompt used to name a new conversation
+const TITLE_LENGTH: usize = 40;
+
+/// Number of characters of the last message shown in a conversation's summary
+const PREVIEW_LENGTH: usize = 100;
+
+/// Directory for the selected profile's own data, such as logs
+pub fn data_dir() -> Result<PathBuf> {
+    Ok(profile::scoped(&app_data_dir()?))
+}
+
+/// Directory for the app's data, shared by every profile
+pub(crate) fn app_data_dir() -> Result<PathBuf> {
+    let mut data_dir = dirs::data_dir().ok_or(Error::NoAppDir)?;
+    data_dir.push("github.com.leo030303.comhra/");
+    Ok(data_dir)
+}
+
+/// Where conversations are stored unless the settings move them somewhere else
+pub fn default_conversations_dir() -> Result<PathBuf> {
+    Ok(data_dir()?.join("conversations/"))
+}
+
+/// Where conversations were stored before they moved to the data dir
+fn legacy_conversations_dir() -> Result<PathBuf> {
+    Ok(settings::config_dir()?.join("conversations/"))
+}
+
+/// Markers sync tools put in the names of the copies they make when two devices edit the same
+/// file: Syncthing's, and the one used by Nextcloud, ownCloud and Dropbox
+const CONFLICT_MARKERS: [&str; 2] = [".sync-conflict-", " (conflicted copy"];
+
+/// Marks the copies this app makes itself when a conversation changed on disk while it was open
+const OWN_CONFLICT_MARKER: &str = ".sync-conflict-comhra";
+
+/// Whether the file is a conversation saved before conversations moved into the database, or a
+/// copy of one kept for its history
+pub fn is_conversation_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json") && conflict_original(path).is_none()
+}
+
+/// Whether the file is the conversations database or one of the files SQLite keeps alongside it
+pub fn is_database_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .is_some_and(|file_name| file_name.starts_with(DATABASE_FILE_NAME))
+}
+
+/// Whether the file is a copy of the conversations database a sync tool made because it changed on
+/// two devices at once
+pub fn is_database_conflict(path: &Path) -> bool {
+    let database_stem = DATABASE_FILE_NAME.trim_end_matches(".sqlite3");
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .is_some_and(|file_name| {
+            file_name.starts_with(database_stem)
+                && file_name.ends_with(".sqlite3")
+                && CONFLICT_MARKERS
+                    .iter()
+                    .any(|marker| file_name.contains(marker))
+        })
+}
+
+/// The connection to each conversations dir's database, kept open rather than opened for every
+/// query
+static DATABASES: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<Database>>>>> =
+    LazyLock::new(Mutex::default);
+
+/// Runs a query on the database in `dir`, on a thread where blocking's allowed, as SQLite waits
+/// on the disk and on other processes writing to it
+pub(crate) async fn in_database<T: Send + 'static>(
+    dir: PathBuf,
+    query: impl FnOnce(&mut Database) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let path = dir.join(DATABASE_FILE_NAME);
+    tokio::task::spawn_blocking(move || {
+        let database = database(&dir)?;
+        let mut database = database.lock().unwrap_or_else(PoisonError::into_inner);
+        database.import_files_if_new()?;
+        query(&mut database)
+    })
+    .await
+    .map_err(|err| Error::Read {
+        path,
+        message: err.to_string(),
+    })?
+}
+
+fn database(dir: &Path) -> Result<Arc<Mutex<Database>>> {
+    let mut databases = DATABASES.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(database) = databases.get(dir) {
+        return Ok(database.clone());
+    }
+    let database = Arc::new(Mutex::new(Database::open(dir)?));
+    databases.insert(dir.to_path_buf(), database.clone());
+    Ok(database)
+}
+
+/// Runs a query on the database holding a conversation, given its title
+///
+/// Conversations are still addressed by the path their file would have in the conversations
+/// dir, so their title is the file's name.
+async fn in_database_for<T: Send + 'static>(
+    path: &Path,
+    query: impl FnOnce(&mut Database, &str) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let title = database::title(path);
+    in_database(dir, move |database| query(database, &title)).await
+}
+
+/// The conversation a sync tool's conflicting copy was made from, or `None` if `path` isn't one
+pub fn conflict_original(path: &Path) -> Option<PathBuf> {
+    if path.extension().is_none_or(|ext| ext != "json") {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let original_stem = CONFLICT_MARKERS
+        .iter()
+        .find_map(|marker| stem.find(marker).map(|position| &stem[..position]))?;
+    Some(path.with_file_name(format!("{original_stem}.json")))
+}
+
+/// Where a conversation is saved instead if it changed on disk since it was loaded
+fn own_conflict_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}{OWN_CONFLICT_MARKER}.json"))
+}
+
+/// Names a new conversation after the start of its first prompt
+pub fn conversation_file_name(first_prompt: &str) -> String {
+    let mut filename: String = first_prompt
+        .chars()
+        .take(TITLE_LENGTH)
+        .map(|c| if c == '/' { '-' } else { c })
+        .collect();
+    filename.push_str(".json");
+    filename
+}
+
+/// Where a new conversation is saved, named after the start of its first prompt, or as a copy of
+/// that if another conversation already has the name
+pub async fn new_conversation_path(
+    conversations_dir: PathBuf,
+    first_prompt: String,
+) -> Result<PathBuf> {
+    let title = database::title(Path::new(&conversation_file_name(&first_prompt)));
+    let title = in_database(conversations_dir.clone(), move |database| {
+        if database.contains(&title)? {
+            copy_title(database, &title)
+        } else {
+            Ok(title)
+        }
+    })
+    .await?;
+    Ok(conversations_dir.join(format!("{title}.json")))
+}
+
+/// Moves every conversation from `from` into `to`, returning how many were moved
+///
+/// Conversations that would overwrite one with the same title are left where they are.
+pub async fn move_conversations(from: PathBuf, to: PathBuf) -> Result<usize> {
+    if from == to || !from.exists() {
+        return Ok(0);
+    }
+    in_database(to, move |database| database.take_from(&from)).await
+}
+
+/// Moves conversations out of the config dir, where they were stored by older versions
+pub async fn migrate_legacy_conversations(to: PathBuf) -> Result<usize> {
+    let legacy_dir = legacy_conversations_dir()?;
+    if !legacy_dir.exists() {
+        return Ok(0);
+    }
+    let files = database::conversation_files(&legacy_dir);
+    let imported = in_database(to, move |database| database.import_files(&files)).await?;
+    for path in &imported {
+        let _ = fs::remove_file(path);
+    }
+    let _ = fs::remove_dir(&legacy_dir);
+    Ok(imported.len())
+}
+
+/// Lists the conversations in `dir`, most recently modified first, creating it if it doesn't exist
+pub async fn list_conversations(dir: &Path) -> Result<Vec<PathBuf>> {
+    Ok(conversation_times(dir)
+        .await?
+        .into_iter()
+        .map(|(path, _modified)| path)
+        .collect())
+}
+
+/// Lists the conversations in `dir` with when each was last saved, most recent first
+pub async fn conversation_times(dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>> {
+    Ok(in_database(dir.to_path_buf(), |database| database.list())
+        .await?
+        .into_iter()
+        .map(|(title, modified)| (dir.join(format!("{title}.json")), modified))
+        .collect())
+}
+
+/// When the conversation was last saved, to check whether something else has saved it since
+pub async fn modified_time(path: &Path) -> Option<SystemTime> {
+    if conflict_original(path).is_some() {
+        return file_modified_time(path);
+    }
+    in_database_for(path, |database, title| database.modified_time(title))
+        .await
+        .ok()
+        .flatten()
+}
+
+pub async fn conversation_exists(path: &Path) -> bool {
+    modified_time(path).await.is_some()
+}
+
+pub(crate) fn file_modified_time(path: &Path) -> Option<SystemTime> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Two versions of a conversation that were edited separately, e.g. on two synced devices
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub original: PathBuf,
+    pub copy: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    KeepOriginal,
+    KeepCopy,
+    /// Keeps the messages the two have in common followed by what each added
+    Merge,
+}
+
+/// Lists the conflicting copies in `dir` along with the conversations they were made from
+pub fn list_conflicts(dir: &Path) -> Result<Vec<Conflict>> {
+    let read_error = |err: std::io::Error| Error::Read {
+        path: dir.to_path_buf(),
+        message: err.to_string(),
+    };
+    let mut conflicts: Vec<Conflict> = fs::read_dir(dir)
+        .map_err(read_error)?
+        .filter_map(|read_dir| read_dir.ok())
+        .filter_map(|dir_entry| {
+            let copy = dir_entry.path();
+            conflict_original(&copy).map(|original| Conflict { original, copy })
+        })
+        .collect();
+    conflicts.sort_by(|a, b| a.copy.cmp(&b.copy));
+    Ok(conflicts)
+}
+
+/// Lists the copies of the database in `dir` that sync tools made when it changed on two devices
+///
+/// What was saved in a copy isn't in the database, and copies can't be merged back in, so they're
+/// only listed to warn about.
+pub fn list_database_conflicts(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut copies: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|err| Error::Read {
+            path: dir.to_path_buf(),
+            message: err.to_string(),
+        })?
+        .filter_map(|read_dir| read_dir.ok())
+        .map(|dir_entry| dir_entry.path())
+        .filter(|path| is_database_conflict(path))
+        .collect();
+    copies.sort();
+    Ok(copies)
+}
+
+/// Combines two versions of a conversation, keeping their shared start once
+pub fn merge_conversations(original: &[ChatMessage], copy: &[ChatMessage]) -> Vec<ChatMessage> {
+    let shared_length = original
+        .iter()
+        .zip(copy)
+        .take_while(|(a, b)| a.role == b.role && a.content == b.content)
+        .count();
+    original
+        .iter()
+        .chain(&copy[shared_length..])
+        .cloned()
+        .collect()
+}
+
+pub async fn resolve_conflict(conflict: Conflict, resolution: Resolution) -> Result<()> {
+    let remove_error = |err: std::io::Error| Error::Write {
+        path: conflict.copy.clone(),
+        message: err.to_string(),
+    };
+    match resolution {
+        Resolution::KeepOriginal => tokio::fs::remove_file(&conflict.copy)
+            .await
+            .map_err(remove_error),
+        Resolution::KeepCopy => {
+            let copy = load_conversation(conflict.copy.clone()).await?;
+            save_conversation(conflict.original.clone(), copy).await?;
+            tokio::fs::remove_file(&conflict.copy)
+                .await
+                .map_err(remove_error)
+        }
+        Resolution::Merge => {
+            let original = if conversation_exists(&conflict.original).await {
+                load_conversation(conflict.original.clone()).await?
+            } else {
+                Conversation::default()
+            };
+            let copy = load_conversation(conflict.copy.clone()).await?;
+            // The original's generation params win, as there's no sensible way to merge them
+            let messages = merge_conversations(&original.messages, &copy.messages);
+            // The copy's own messages follow on from the original's
+            let shared_length = copy.messages.len() - (messages.len() - original.messages.len());
+            let mut stats = original.stats.clone();
+            stats.extend(
+                copy.stats
+                    .into_iter()
+                    .filter(|(index, _stats)| *index >= shared_length)
+                    .map(|(index, stats)| (index - shared_length + original.messages.len(), stats)),
+            );
+            save_conversation(
+                conflict.original.clone(),
+                Conversation {
+                    messages,
+                    stats,
+                    ..original
+                },
+            )
+            .await?;
+            tokio::fs::remove_file(&conflict.copy)
+                .await
+                .map_err(remove_error)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveOutcome {
+    /// Saved where it was asked to be, with the file's new modified time
+    Saved(Option<SystemTime>),
+    /// The conversation had been saved by something else, so this was saved as a conflicting copy
+    /// at the given path
+    Conflicted(PathBuf),
+}
+
+/// Saves the conversation unless something else has saved it since `last_modified`, such as
+/// another window or the CLI, in which case it's saved next to it as a conflicting copy instead of
+/// overwriting those changes
+pub async fn save_conversation_unless_changed(
+    path: PathBuf,
+    conversation: Conversation,
+    last_modified: Option<SystemTime>,
+) -> Result<SaveOutcome> {
+    let current_modified = modified_time(&path).await;
+    if current_modified.is_some() && current_modified != last_modified {
+        let conflict_path = own_conflict_path(&path);
+        save_conversation(conflict_path.clone(), conversation).await?;
+        return Ok(SaveOutcome::Conflicted(conflict_path));
+    }
+    save_conversation(path.clone(), conversation).await?;
+    Ok(SaveOutcome::Saved(modified_time(&path).await))
+}
+
+pub async fn load_conversation(path: PathBuf) -> Result<Conversation> {
+    if conflict_original(&path).is_some() {
+        return read_conversation_file(&path);
+    }
+    in_database_for(&path, |database, title| database.load(title)).await
+}
+
+/// Loads a conversation along with when it was last saved, to tell whether something else saves
+/// it while it's open
+pub async fn load_conversation_with_modified_time(
+    path: PathBuf,
+) -> Result<(Conversation, Option<SystemTime>)> {
+    let modified = modified_time(&path).await;
+    Ok((load_conversation(path).await?, modified))
+}
+
+pub async fn save_conversation(path: PathBuf, conversation: Conversation) -> Result<()> {
+    if conflict_original(&path).is_some() {
+        return write_conversation_file(&path, &conversation).await;
+    }
+    in_database_for(&path, move |database, title| {
+        database.save(title, &conversation).map(|_modified| ())
+    })
+    .await
+}
+
+/// Reads a conversation saved as a file, like the conflicting copies sync tools make
+pub(crate) fn read_conversation_file(path: &Path) -> Result<Conversation> {
+    let conversation_json = fs::read(path).map_err(|err| Error::Read {
+        path: path.to_path_buf(),
+        message: err.to_string(),
+    })?;
+    let conversation_json = crypto::open(path, conversation_json)?;
+    Conversation::from_json(&conversation_json).map_err(|err| Error::Corrupt {
+        path: path.to_path_buf(),
+        message: err.to_string(),
+    })
+}
+
+async fn write_conversation_file(path: &Path, conversation: &Conversation) -> Result<()> {
+    let write_error = |message: String| Error::Write {
+        path: path.to_path_buf(),
+        message,
+    };
+    let conversation_json =
+        serde_json::to_vec(conversation).map_err(|err| write_error(err.to_string()))?;
+    write_atomically(path, crypto::seal(conversation_json)?)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+/// Renames a conversation to a new title, returning where it was moved to
+///
+/// Fails rather than overwriting a conversation that already has that title.
+pub async fn rename_conversation(path: PathBuf, title: String) -> Result<PathBuf> {
+    let new_path = path.with_file_name(conversation_file_name(title.trim()));
+    if new_path == path {
+        return Ok(path);
+    }
+    let new_title = database::title(&new_path);
+    in_database_for(&path, move |database, title| {
+        database.rename(title, &new_title)
+    })
+    .await?;
+    Ok(new_path)
+}
+
+/// Files a conversation under a folder and replaces its tags, leaving everything else as it is
+pub async fn organize_conversation(
+    path: PathBuf,
+    folder: Option<String>,
+    tags: Vec<String>,
+) -> Result<()> {
+    let mut conversation = load_conversation(path.clone()).await?;
+    conversation.folder = folder;
+    conversation.tags = tags;
+    save_conversation(path, conversation).await
+}
+
+/// Pins a conversation to the top of the sidebar, archives it or lists it with the others again
+pub async fn set_listing(path: PathBuf, listing: Listing) -> Result<()> {
+    let mut conversation = load_conversation(path.clone()).await?;
+    conversation.listing = listing;
+    save_conversation(path, conversation).await
+}
+
+/// Copies a conversation alongside itself, returning the path of the copy
+pub async fn duplicate_conversation(path: PathBuf) -> Result<PathBuf> {
+    let copy_title = in_database_for(&path, |database, title| {
+        let copy_title = copy_title(database, title)?;
+        database.duplicate(title, &copy_title)?;
+        Ok(copy_title)
+    })
+    .await?;
+    Ok(path.with_file_name(format!("{copy_title}.json")))
+}
+
+/// Copies a conversation file from elsewhere into `conversations_dir`, returning its path there
+pub async fn import_conversation_file(
+    path: PathBuf,
+    conversations_dir: PathBuf,
+) -> Result<PathBuf> {
+    let conversation = read_conversation_file(&path)?;
+    let title = database::title(&path);
+    let title = in_database(conversations_dir.clone(), move |database| {
+        let title = if database.contains(&title)? {
+            copy_title(database, &title)?
+        } else {
+            title
+        };
+        database.save(&title, &conversation)?;
+        Ok(title)
+    })
+    .await?;
+    Ok(conversations_dir.join(format!("{title}.json")))
+}
+
+/// The first title for a copy of a conversation that isn't taken
+pub(crate) fn copy_title(database: &Database, title: &str) -> Result<String> {
+    for number in 1.. {
+        let copy_title = match number {
+            1 => format!("{title} (copy)"),
+            number => format!("{title} (copy {number})"),
+        };
+        if !database.contains(&copy_title)? {
+            return Ok(copy_title);
+        }
+    }
+    unreachable!("there are always more numbers to try")
+}
+
+pub async fn delete_conversation(path: PathBuf) -> Result<()> {
+    in_database_for(&path, |database, title| database.delete(title)).await
+}
+
+/// Writes to a temporary file and renames it over the target, so a crash mid-write can't corrupt it
+pub(crate) async fn write_atomically(
+    path: &Path,
+    contents: impl AsRef<[u8]>,
+) -> std::io::Result<()> {
+    let temp_file = path.with_extension("tmp");
+    tokio::fs::write(&temp_file, contents).await?;
+    tokio::fs::rename(&temp_file, path).await
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConversationSummary {
+    pub message_count: usize,
+    pub preview: String,
+    pub search_index: SearchIndex,
+    pub folder: Option<String>,
+    pub tags: Vec<String>,
+    pub listing: Listing,
+    /// When the latest message was sent, if it's known
+    pub last_message_at: Option<SystemTime>,
+}
+
+impl ConversationSummary {
+    pub fn new(conversation: &Conversation) -> Self {
+        let messages = &conversation.messages;
+        Self {
+            message_count: messages.len(),
+            preview: messages
+                .last()
+                .map(|chat_message| chat_message.content.chars().take(PREVIEW_LENGTH).collect())
+                .unwrap_or_default(),
+            search_index: SearchIndex::new(messages),
+            folder: conversation.folder.clone(),
+            tags: conversation.tags.clone(),
+            listing: conversation.listing,
+            last_message_at: conversation.sent_at.values().max().copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{chat_message, test_dir};
+    use crate::MessageRole;
+
+    #[test]
+    fn conversation_file_name_is_truncated_prompt() {
+        assert_eq!(conversation_file_name("Hello there"), "Hello there.json");
+        assert_eq!(
+            conversation_file_name(&"a".repeat(100)),
+            format!("{}.json", "a".repeat(40))
+        );
+        assert_eq!(
+            conversation_file_name(&"á".repeat(100)),
+            format!("{}.json", "á".repeat(40))
+        );
+        assert_eq!(conversation_file_name("either/or"), "either-or.json");
+    }
+
+    #[tokio::test]
+    async fn saved_conversation_loads_back() {
+        let dir = test_dir("roundtrip");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("conversation.json");
+        let mut conversation = Conversation::new(vec![
+            chat_message(MessageRole::User, "Hi"),
+            chat_message(MessageRole::Assistant, "Hello!"),
+        ]);
+        conversation.params.seed = Some(42);
+        save_conversation(path.clone(), conversation.clone())
+            .await
+            .unwrap();
+        let loaded = load_conversation(path).await.unwrap();
+        assert_eq!(loaded.messages.len(), 2);
+        assert_eq!(loaded.messages[1].content, "Hello!");
+        assert_eq!(loaded.params, conversation.params);
+        assert!(dir.join(DATABASE_FILE_NAME).exists());
+    }
+
+    #[tokio::test]
+    async fn conversations_can_be_renamed_duplicated_and_deleted() {
+        let dir = test_dir("manage");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Hi.json");
+        save_conversation(path.clone(), Conversation::default())
+            .await
+            .unwrap();
+        save_conversation(dir.join("Taken.json"), Conversation::default())
+            .await
+            .unwrap();
+
+        assert!(rename_conversation(path.clone(), "Taken".to_string())
+            .await
+            .is_err());
+        let renamed = rename_conversation(path.clone(), " Greeting ".to_string())
+            .await
+            .unwrap();
+        assert_eq!(renamed, dir.join("Greeting.json"));
+        assert!(!conversation_exists(&path).await);
+
+        let copy = duplicate_conversation(renamed.clone()).await.unwrap();
+        assert_eq!(copy, dir.join("Greeting (copy).json"));
+        let second_copy = duplicate_conversation(renamed.clone()).await.unwrap();
+        assert_eq!(second_copy, dir.join("Greeting (copy 2).json"));
+
+        delete_conversation(copy.clone()).await.unwrap();
+        assert!(!conversation_exists(&copy).await);
+        assert!(conversation_exists(&second_copy).await);
+    }
+
+    #[tokio::test]
+    async fn organizing_keeps_the_messages() {
+        let dir = test_dir("organize");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("conversation.json");
+        save_conversation(
+            path.clone(),
+            Conversation::new(vec![chat_message(MessageRole::User, "Hi")]),
+        )
+        .await
+        .unwrap();
+
+        organize_conversation(
+            path.clone(),
+            Some("Work".to_string()),
+            vec!["rust".to_string()],
+        )
+        .await
+        .unwrap();
+        let organized = load_conversation(path.clone()).await.unwrap();
+        assert_eq!(organized.messages[0].content, "Hi");
+        assert_eq!(organized.folder.as_deref(), Some("Work"));
+        assert_eq!(organized.tags, ["rust"]);
+        let summary = ConversationSummary::new(&organized);
+        assert_eq!(summary.folder.as_deref(), Some("Work"));
+
+        organize_conversation(path.clone(), None, Vec::new())
+            .await
+            .unwrap();
+        let unfiled = load_conversation(path.clone()).await.unwrap();
+        assert_eq!(unfiled.folder, None);
+        assert!(unfiled.tags.is_empty());
+
+        set_listing(path.clone(), Listing::Archived).await.unwrap();
+        let archived = load_conversation(path).await.unwrap();
+        assert_eq!(archived.messages[0].content, "Hi");
+        assert_eq!(
+            ConversationSummary::new(&archived).listing,
+            Listing::Archived
+        );
+    }
+
+    #[tokio::test]
+    async fn move_conversations_keeps_existing_ones() {
+        let from = test_dir("move-from");
+        let to = test_dir("move-to");
+        let hi = |content: &str| Conversation::new(vec![chat_message(MessageRole::User, content)]);
+        save_conversation(from.join("moved.json"), hi("Moved"))
+            .await
+            .unwrap();
+        save_conversation(from.join("clashing.json"), hi("Old"))
+            .await
+            .unwrap();
+        save_conversation(to.join("clashing.json"), hi("New"))
+            .await
+            .unwrap();
+        assert_eq!(
+            move_conversations(from.clone(), to.clone()).await.unwrap(),
+            1
+        );
+        assert!(conversation_exists(&to.join("moved.json")).await);
+        assert!(!conversation_exists(&from.join("moved.json")).await);
+        assert!(conversation_exists(&from.join("clashing.json")).await);
+        let clashing = load_conversation(to.join("clashing.json")).await.unwrap();
+        assert_eq!(clashing.messages[0].content, "New");
+    }
+
+    #[tokio::test]
+    async fn corrupt_conflicting_copy_is_an_error() {
+        let dir = test_dir("corrupt");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("corrupt.sync-conflict-20240101-120000-ABCDEF.json");
+        fs::write(&path, "{ not json").unwrap();
+        assert!(matches!(
+            load_conversation(path).await,
+            Err(Error::Corrupt { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn list_conversations_is_most_recent_first() {
+        let dir = test_dir("list");
+        save_conversation(dir.join("first.json"), Conversation::default())
+            .await
+            .unwrap();
+        save_conversation(dir.join("second.json"), Conversation::default())
+            .await
+            .unwrap();
+        fs::write(dir.join("second.tmp"), "[]").unwrap();
+        fs::write(own_conflict_path(&dir.join("first.json")), "[]").unwrap();
+        assert_eq!(
+            list_conversations(&dir).await.unwrap(),
+            [dir.join("second.json"), dir.join("first.json")]
+        );
+    }
+
+    #[test]
+    fn conflicting_copies_point_at_their_original() {
+        let dir = Path::new("/conversations");
+        assert_eq!(
+            conflict_original(&dir.join("Hi.sync-conflict-20240101-120000-ABCDEF.json")),
+            Some(dir.join("Hi.json"))
+        );
+        assert_eq!(
+            conflict_original(&dir.join("Hi (conflicted copy 2024-01-01).json")),
+            Some(dir.join("Hi.json"))
+        );
+        assert_eq!(
+            conflict_original(&own_conflict_path(&dir.join("Hi.json"))),
+            Some(dir.join("Hi.json"))
+        );
+        assert_eq!(conflict_original(&dir.join("Hi.json")), None);
+        assert!(!is_conversation_file(
+            &dir.join("Hi.sync-conflict-20240101-120000-ABCDEF.json")
+        ));
+    }
+
+    #[test]
+    fn conflicting_copies_of_the_database_are_told_apart_from_its_files() {
+        let dir = Path::new("/conversations");
+        assert!(is_database_conflict(&dir.join(
+            "conversations.sync-conflict-20240101-120000-ABCDEF.sqlite3"
+        )));
+        assert!(is_database_conflict(
+            &dir.join("conversations (conflicted copy 2024-01-01).sqlite3")
+        ));
+        assert!(!is_database_conflict(&dir.join(DATABASE_FILE_NAME)));
+        assert!(!is_database_conflict(
+            &dir.join("conversations.sqlite3-journal")
+        ));
+        assert!(!is_database_conflict(
+            &dir.join("Hi.sync-conflict-20240101-120000-ABCDEF.json")
+        ));
+    }
+
+    #[test]
+    fn merging_keeps_shared_messages_once() {
+        let shared = chat_message(MessageRole::User, "Question");
+        let merged = merge_conversations(
+            &[
+                shared.clone(),
+                chat_message(MessageRole::Assistant, "Answer on laptop"),
+            ],
+            &[
+                shared,
+                chat_message(MessageRole::Assistant, "Answer on desktop"),
+            ],
+        );
+        let contents: Vec<&str> = merged
+            .iter()
+            .map(|chat_message| chat_message.content.as_str())
+            .collect();
+        assert_eq!(
+            contents,
+            ["Question", "Answer on laptop", "Answer on desktop"]
+        );
+    }
+
+    #[tokio::test]
+    async fn changed_conversation_is_saved_as_a_conflict() {
+        let dir = test_dir("changed");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("changed.json");
+        let conversation = Conversation::new(vec![chat_message(MessageRole::User, "Hi")]);
+        let SaveOutcome::Saved(last_modified) =
+            save_conversation_unless_changed(path.clone(), conversation.clone(), None)
+                .await
+                .unwrap()
+        else {
+            panic!("a new conversation can't conflict");
+        };
+        // Saved by something else in the meantime
+        save_conversation(path.clone(), Conversation::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            save_conversation_unless_changed(path.clone(), conversation, last_modified)
+                .await
+                .unwrap(),
+            SaveOutcome::Conflicted(own_conflict_path(&path))
+        );
+        assert!(load_conversation(path.clone())
+            .await
+            .unwrap()
+            .messages
+            .is_empty());
+        let conflicts = list_conflicts(&dir).unwrap();
+        assert_eq!(conflicts.len(), 1);
+
+        resolve_conflict(conflicts[0].clone(), Resolution::KeepCopy)
+            .await
+            .unwrap();
+        assert_eq!(load_conversation(path).await.unwrap().messages.len(), 1);
+        assert!(list_conflicts(&dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn summary_previews_last_message() {
+        let summary = ConversationSummary::new(&Conversation::new(vec![
+            chat_message(MessageRole::User, "Question"),
+            chat_message(MessageRole::Assistant, &"b".repeat(200)),
+        ]));
+        assert_eq!(summary.message_count, 2);
+        assert_eq!(summary.preview, "b".repeat(100));
+    }
+}
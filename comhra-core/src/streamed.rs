@@ -0,0 +1,77 @@
+//! Responses being streamed, which are parsed a block at a time as they arrive so only the block
+//! being written is parsed again each time more of it comes in
+
+/// How far into the markdown its finished blocks go, up to the start of the last unindented line
+/// after a blank one that isn't in a code block or displayed equation
+pub fn finished_blocks_len(content: &str) -> usize {
+    let mut finished_len = 0;
+    let mut offset = 0;
+    let mut fence: Option<&str> = None;
+    let mut is_in_math = false;
+    let mut follows_blank_line = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if !line.ends_with('\n') {
+            // The line still being written could turn out to be anything
+            break;
+        }
+        if follows_blank_line
+            && fence.is_none()
+            && !is_in_math
+            && !trimmed.is_empty()
+            && !line.starts_with([' ', '\t'])
+        {
+            finished_len = offset;
+        }
+        match fence {
+            // Closed by a fence of the same kind at least as long, with nothing after it
+            Some(open)
+                if trimmed.starts_with(open)
+                    && trimmed.bytes().all(|byte| byte == open.as_bytes()[0]) =>
+            {
+                fence = None;
+            }
+            Some(_) => {}
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                let marker = trimmed.chars().next().unwrap_or('`');
+                let length = trimmed.chars().take_while(|c| *c == marker).count();
+                fence = Some(&trimmed[..length]);
+            }
+            None => is_in_math ^= trimmed.matches("$$").count() % 2 == 1,
+        }
+        follows_blank_line = trimmed.is_empty();
+        offset += line.len();
+    }
+    finished_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_finish_at_the_next_unindented_line_after_a_blank_one() {
+        assert_eq!(finished_blocks_len("First paragraph"), 0);
+        assert_eq!(finished_blocks_len("First paragraph\n\nSecond"), 0);
+        assert_eq!(finished_blocks_len("First paragraph\n\nSecond\n"), 17);
+        // A list item's continuation is part of the list
+        assert_eq!(finished_blocks_len("- One\n\n  more of one\n"), 0);
+        assert_eq!(finished_blocks_len("- One\n\n\tmore of one\n- Two\n"), 0);
+    }
+
+    #[test]
+    fn code_blocks_and_equations_are_finished_once_closed() {
+        let code = "Code:\n\n```rust\nfn main() {\n\n    run();\n}\n";
+        assert_eq!(finished_blocks_len(code), 7);
+        let closed = format!("{code}```\n\nAfter\n");
+        assert_eq!(finished_blocks_len(&closed), closed.len() - "After\n".len());
+        // Only a fence of the same kind at least as long closes one
+        let nested = "````\n```\n\nStill code\n```\n\nStill code\n";
+        assert_eq!(finished_blocks_len(nested), 0);
+        let tildes = "~~~\n```\n\nStill code\n~~~\n\nAfter\n";
+        assert_eq!(finished_blocks_len(tildes), tildes.len() - "After\n".len());
+        let math = "$$\nx = 1\n\ny = 2\n$$\n\nAfter\n";
+        assert_eq!(finished_blocks_len(math), math.len() - "After\n".len());
+        assert_eq!(finished_blocks_len("$$\nx = 1\n\ny = 2\n"), 0);
+    }
+}
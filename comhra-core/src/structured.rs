@@ -0,0 +1,250 @@
+//! Structured output, with Ollama's `format` option: asking the model to reply with JSON, or with
+//! JSON matching a schema, and checking what it replied with against the schema
+//!
+//! Only the parts of JSON Schema that describe the shape of a reply are checked: types, enums,
+//! properties and required properties, array items, and the length and range limits.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// What a response was asked to be written as
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Any JSON
+    Json,
+    /// JSON matching a JSON schema
+    Schema(Value),
+}
+
+impl OutputFormat {
+    /// The `format` sent with the request
+    pub(crate) fn request_value(&self) -> Value {
+        match self {
+            OutputFormat::Json => Value::String("json".to_string()),
+            OutputFormat::Schema(schema) => schema.clone(),
+        }
+    }
+
+    pub fn schema(&self) -> Option<&Value> {
+        match self {
+            OutputFormat::Json => None,
+            OutputFormat::Schema(schema) => Some(schema),
+        }
+    }
+}
+
+/// Reads a schema typed in, which has to be a JSON object
+pub fn parse_schema(text: &str) -> Result<Value, String> {
+    match serde_json::from_str(text) {
+        Ok(schema @ Value::Object(_)) => Ok(schema),
+        Ok(_) => Err("a schema has to be a JSON object".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Reads a response as JSON, which models sometimes put in a code block anyway
+pub fn parse_reply(content: &str) -> Option<Value> {
+    let content = content.trim();
+    let content = content
+        .strip_prefix("```json")
+        .or_else(|| content.strip_prefix("```"))
+        .and_then(|fenced| fenced.strip_suffix("```"))
+        .unwrap_or(content);
+    serde_json::from_str(content).ok()
+}
+
+/// Everything about the value that doesn't match the schema, each starting with where in the
+/// value it is, like `$.people[2].age`
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut problems = vec![];
+    check(value, schema, "$", &mut problems);
+    problems
+}
+
+fn check(value: &Value, schema: &Value, path: &str, problems: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `false` allows nothing and `true` anything
+        if schema == &Value::Bool(false) {
+            problems.push(format!("{path}: isn't allowed"));
+        }
+        return;
+    };
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(expected) => vec![expected.as_str()],
+            Value::Array(expected) => expected.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if !types.is_empty() && !types.iter().any(|expected| is_type(value, expected)) {
+            problems.push(format!(
+                "{path}: should be {}, not {}",
+                types.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            problems.push(format!("{path}: {value} isn't one of the allowed values"));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            problems.push(format!("{path}: should be {expected}"));
+        }
+    }
+    if let Some(options) = schema.get("anyOf").and_then(Value::as_array) {
+        if !options
+            .iter()
+            .any(|option| validate(value, option).is_empty())
+        {
+            problems.push(format!("{path}: doesn't match any of the allowed shapes"));
+        }
+    }
+    let limit = |name: &str| schema.get(name).and_then(Value::as_f64);
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for required in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !object.contains_key(required) {
+                    problems.push(format!("{path}: is missing {required}"));
+                }
+            }
+            for (name, property) in object {
+                let property_path = format!("{path}.{name}");
+                match (
+                    properties.and_then(|properties| properties.get(name)),
+                    schema.get("additionalProperties"),
+                ) {
+                    (Some(property_schema), _) => {
+                        check(property, property_schema, &property_path, problems);
+                    }
+                    (None, Some(Value::Bool(false))) => {
+                        problems.push(format!("{property_path}: isn't one of the properties"));
+                    }
+                    (None, Some(additional)) => {
+                        check(property, additional, &property_path, problems)
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            let count = items.len() as f64;
+            if limit("minItems").is_some_and(|min| count < min) {
+                problems.push(format!("{path}: has too few items"));
+            }
+            if limit("maxItems").is_some_and(|max| count > max) {
+                problems.push(format!("{path}: has too many items"));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item, item_schema, &format!("{path}[{index}]"), problems);
+                }
+            }
+        }
+        Value::String(string) => {
+            let length = string.chars().count() as f64;
+            if limit("minLength").is_some_and(|min| length < min) {
+                problems.push(format!("{path}: is too short"));
+            }
+            if limit("maxLength").is_some_and(|max| length > max) {
+                problems.push(format!("{path}: is too long"));
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            let too_small = limit("minimum").is_some_and(|min| number < min)
+                || limit("exclusiveMinimum").is_some_and(|min| number <= min);
+            let too_large = limit("maximum").is_some_and(|max| number > max)
+                || limit("exclusiveMaximum").is_some_and(|max| number >= max);
+            if too_small {
+                problems.push(format!("{path}: {number} is too small"));
+            }
+            if too_large {
+                problems.push(format!("{path}: {number} is too large"));
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+}
+
+fn is_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        // Whole numbers are numbers too
+        "number" => value.is_number(),
+        expected => type_name(value) == expected,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn replies_are_checked_against_the_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}},
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false,
+        });
+        let reply = json!({"name": "Áine", "age": 30, "tags": ["a"]});
+        assert!(validate(&reply, &schema).is_empty());
+        let reply = json!({"name": "", "age": 2.5, "tags": ["c"], "extra": true});
+        assert_eq!(
+            validate(&reply, &schema),
+            [
+                "$.age: should be integer, not number",
+                "$.extra: isn't one of the properties",
+                "$.name: is too short",
+                "$.tags[0]: \"c\" isn't one of the allowed values",
+            ]
+        );
+        assert_eq!(
+            validate(&json!([]), &schema),
+            ["$: should be object, not array"]
+        );
+        assert_eq!(validate(&json!({}), &schema).len(), 2);
+    }
+
+    #[test]
+    fn replies_and_schemas_are_read_as_json() {
+        assert_eq!(parse_reply(" {\"a\": 1}\n"), Some(json!({"a": 1})));
+        assert_eq!(parse_reply("```json\n[1, 2]\n```"), Some(json!([1, 2])));
+        assert_eq!(parse_reply("Not JSON"), None);
+        assert!(parse_schema("{\"type\": \"object\"}").is_ok());
+        assert!(parse_schema("[]").is_err());
+        assert!(parse_schema("{").is_err());
+        assert_eq!(
+            OutputFormat::Json.request_value(),
+            Value::String("json".to_string())
+        );
+    }
+}
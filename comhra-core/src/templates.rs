@@ -0,0 +1,159 @@
+//! Reusable prompts with `{{variable}}` placeholders that are filled in each time they're used,
+//! e.g. `Summarise this {{kind}} in {{count}} bullet points`
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::write_atomically;
+use crate::{settings, Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub prompt: String,
+}
+
+impl Template {
+    /// Names of the template's variables in the order they first appear, each only once
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables: Vec<String> = vec![];
+        for (_start, _end, name) in placeholders(&self.prompt) {
+            if !variables.iter().any(|variable| variable == name) {
+                variables.push(name.to_string());
+            }
+        }
+        variables
+    }
+
+    /// The prompt with each placeholder replaced by its variable's value, or left as it is if
+    /// there's no value for it
+    pub fn fill(&self, values: &[(String, String)]) -> String {
+        let mut filled = String::with_capacity(self.prompt.len());
+        let mut copied_up_to = 0;
+        for (start, end, name) in placeholders(&self.prompt) {
+            if let Some((_name, value)) = values.iter().find(|(variable, _)| variable == name) {
+                filled.push_str(&self.prompt[copied_up_to..start]);
+                filled.push_str(value);
+                copied_up_to = end;
+            }
+        }
+        filled.push_str(&self.prompt[copied_up_to..]);
+        filled
+    }
+}
+
+/// Where each `{{name}}` starts and ends in the prompt, with the name trimmed of spaces
+fn placeholders(prompt: &str) -> Vec<(usize, usize, &str)> {
+    let mut placeholders = vec![];
+    let mut search_from = 0;
+    while let Some(open) = prompt[search_from..]
+        .find("{{")
+        .map(|open| search_from + open)
+    {
+        let Some(close) = prompt[open + 2..].find("}}").map(|close| open + 2 + close) else {
+            break;
+        };
+        let name = prompt[open + 2..close].trim();
+        // Braces that aren't around a name are left alone, e.g. `{{}}` in a code sample
+        if !name.is_empty() && !name.contains(['{', '}', '\n']) {
+            placeholders.push((open, close + 2, name));
+            search_from = close + 2;
+        } else {
+            search_from = open + 2;
+        }
+    }
+    placeholders
+}
+
+/// Kept in a TOML file alongside the settings so they can be edited by hand
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TemplatesFile {
+    #[serde(default)]
+    templates: Vec<Template>,
+}
+
+pub fn templates_file() -> Result<PathBuf> {
+    Ok(settings::config_dir()?.join("templates.toml"))
+}
+
+/// Loads the saved templates, none if there's no templates file yet
+pub async fn load() -> Result<Vec<Template>> {
+    let path = templates_file()?;
+    match tokio::fs::read_to_string(&path).await {
+        Ok(templates_toml) => toml::from_str::<TemplatesFile>(&templates_toml)
+            .map(|templates_file| templates_file.templates)
+            .map_err(|err| Error::Corrupt {
+                path,
+                message: err.to_string(),
+            }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(Error::Read {
+            path,
+            message: err.to_string(),
+        }),
+    }
+}
+
+pub async fn save(templates: Vec<Template>) -> Result<()> {
+    let path = templates_file()?;
+    let write_error = |message: String| Error::Write {
+        path: path.clone(),
+        message,
+    };
+    let templates_toml = toml::to_string_pretty(&TemplatesFile { templates })
+        .map_err(|err| write_error(err.to_string()))?;
+    tokio::fs::create_dir_all(settings::config_dir()?)
+        .await
+        .map_err(|err| write_error(err.to_string()))?;
+    write_atomically(&path, templates_toml)
+        .await
+        .map_err(|err| write_error(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(prompt: &str) -> Template {
+        Template {
+            name: "Summary".to_string(),
+            prompt: prompt.to_string(),
+        }
+    }
+
+    #[test]
+    fn variables_are_found_once_each_in_order() {
+        let summary = template("Summarise this {{ kind }} in {{count}} points, one per {{kind}}");
+        assert_eq!(summary.variables(), vec!["kind", "count"]);
+        assert!(template("let map = {{}};").variables().is_empty());
+        assert_eq!(template("{{ {{name}} }}").variables(), vec!["name"]);
+    }
+
+    #[test]
+    fn variables_are_filled_in() {
+        let summary = template("Summarise this {{ kind }} in {{count}} points: {{text}}");
+        assert_eq!(
+            summary.fill(&[
+                ("kind".to_string(), "article".to_string()),
+                ("count".to_string(), "{{3}}".to_string()),
+            ]),
+            "Summarise this article in {{3}} points: {{text}}"
+        );
+    }
+
+    #[test]
+    fn templates_survive_a_roundtrip() {
+        let templates = vec![template("Translate into {{language}}:\n\n{{text}}")];
+        let templates_toml = toml::to_string_pretty(&TemplatesFile {
+            templates: templates.clone(),
+        })
+        .unwrap();
+        assert_eq!(
+            toml::from_str::<TemplatesFile>(&templates_toml)
+                .unwrap()
+                .templates,
+            templates
+        );
+    }
+}
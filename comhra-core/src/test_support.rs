@@ -0,0 +1,29 @@
+//! Helpers shared by the tests of the modules that store conversations
+
+use std::path::PathBuf;
+
+use crate::{ChatMessage, MessageRole};
+
+/// An empty dir for a test to save in, named after it so tests running at once don't share one
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("comhra-core-test-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Where the encryption key file is looked for instead of the user's data dir, so whether
+/// encryption is on where the tests run doesn't change what they see
+pub(crate) fn key_file() -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "comhra-core-test-{}-encryption.json",
+        std::process::id()
+    ))
+}
+
+pub(crate) fn chat_message(role: MessageRole, content: &str) -> ChatMessage {
+    ChatMessage {
+        role,
+        content: content.to_string(),
+        images: None,
+    }
+}
@@ -0,0 +1,110 @@
+//! When messages were sent and conversations last used, described relative to now
+//!
+//! Dates are written in UTC, as there's no time zone database to find the local time with.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// How long ago something happened, rounded down to the largest unit that fits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ago {
+    JustNow,
+    Minutes(u64),
+    Hours(u64),
+    Yesterday,
+    Days(u64),
+    /// More than a week ago, with the date it happened, e.g. `2024-10-02`
+    On(String),
+}
+
+pub fn ago(time: SystemTime, now: SystemTime) -> Ago {
+    // Times in the future, e.g. from another device with its clock ahead, count as just now
+    let elapsed = now.duration_since(time).unwrap_or_default().as_secs();
+    match elapsed {
+        elapsed if elapsed < MINUTE => Ago::JustNow,
+        elapsed if elapsed < HOUR => Ago::Minutes(elapsed / MINUTE),
+        elapsed if elapsed < DAY => Ago::Hours(elapsed / HOUR),
+        elapsed if elapsed < 2 * DAY => Ago::Yesterday,
+        elapsed if elapsed < 7 * DAY => Ago::Days(elapsed / DAY),
+        _ => Ago::On(date(time)),
+    }
+}
+
+fn since_epoch(time: SystemTime) -> Duration {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// The date in UTC, e.g. `2024-10-02`
+pub fn date(time: SystemTime) -> String {
+    let (year, month, day) = civil_from_days((since_epoch(time).as_secs() / DAY) as i64);
+    format!("{year}-{month:02}-{day:02}")
+}
+
+/// The date and time to the minute in UTC, e.g. `2024-10-02 18:40 UTC`
+pub fn date_time(time: SystemTime) -> String {
+    let seconds_today = since_epoch(time).as_secs() % DAY;
+    format!(
+        "{} {:02}:{:02} UTC",
+        date(time),
+        seconds_today / HOUR,
+        seconds_today % HOUR / MINUTE
+    )
+}
+
+/// The year, month and day a number of days after the Unix epoch, from Howard Hinnant's
+/// `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so the leap day comes last
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_described_relative_to_now() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_727_894_400);
+        let before = |seconds: u64| now - Duration::from_secs(seconds);
+        assert_eq!(ago(before(30), now), Ago::JustNow);
+        assert_eq!(ago(now + Duration::from_secs(30), now), Ago::JustNow);
+        assert_eq!(ago(before(5 * MINUTE + 59), now), Ago::Minutes(5));
+        assert_eq!(ago(before(2 * HOUR), now), Ago::Hours(2));
+        assert_eq!(ago(before(DAY + HOUR), now), Ago::Yesterday);
+        assert_eq!(ago(before(3 * DAY), now), Ago::Days(3));
+        assert_eq!(
+            ago(before(30 * DAY), now),
+            Ago::On("2024-09-02".to_string())
+        );
+    }
+
+    #[test]
+    fn dates_are_written_in_utc() {
+        assert_eq!(
+            date_time(UNIX_EPOCH + Duration::from_secs(1_727_894_400)),
+            "2024-10-02 18:40 UTC"
+        );
+        assert_eq!(
+            date_time(UNIX_EPOCH + Duration::from_secs(951_782_700)),
+            "2000-02-29 00:05 UTC"
+        );
+        assert_eq!(date(UNIX_EPOCH), "1970-01-01");
+    }
+}
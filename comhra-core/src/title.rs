@@ -0,0 +1,64 @@
+//! Short titles for conversations, written by a model once the first response is in
+
+use crate::backend::{Backend, Provider};
+use crate::conversation::GenerationParams;
+use crate::reasoning;
+use crate::{ChatMessage, Error, MessageRole, Result};
+
+/// Asked after the conversation so far, so the model has the whole exchange to go on
+const TITLE_INSTRUCTION: &str = "Write a title of at most six words for this conversation. \
+    Reply with only the title, without quotes or a full stop.";
+
+/// Asks the model for a title summing up the conversation
+pub async fn generate(
+    backend: Backend,
+    model: String,
+    conversation: Vec<ChatMessage>,
+) -> Result<String> {
+    // The system prompt could tell the model to answer in a way that doesn't suit a title
+    let mut messages: Vec<ChatMessage> = conversation
+        .into_iter()
+        .filter(|chat_message| chat_message.role != MessageRole::System)
+        .collect();
+    messages.push(ChatMessage::user(TITLE_INSTRUCTION.to_string()));
+    let response = backend
+        .chat(model, messages, GenerationParams::default())
+        .await?;
+    clean(&response)
+        .ok_or_else(|| Error::Backend("the model didn't reply with a title".to_string()))
+}
+
+/// Takes the title out of the model's reply, which often wraps it in quotes or markdown, or
+/// thinks out loud before it
+fn clean(response: &str) -> Option<String> {
+    let title = reasoning::answer(response)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+    let title = title.trim_start_matches(['#', '*']).trim();
+    let title = title.strip_prefix("Title:").unwrap_or(title);
+    let title = title
+        .trim_matches(|c: char| c.is_whitespace() || "\"'`*“”".contains(c))
+        .trim_end_matches(['.', '!'])
+        .trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_are_cleaned_up() {
+        assert_eq!(
+            clean("\"Sourdough Starter Tips\"\n").as_deref(),
+            Some("Sourdough Starter Tips")
+        );
+        assert_eq!(
+            clean("<think>\nThey asked about bread.\n</think>\n\n**Title:** Baking Bread.")
+                .as_deref(),
+            Some("Baking Bread")
+        );
+        assert_eq!(clean("  \n"), None);
+    }
+}
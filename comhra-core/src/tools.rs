@@ -0,0 +1,373 @@
+//! Tools a model can call while writing a response, with Ollama's tool calling API: a calculator,
+//! the current time, reading local files and running shell commands
+//!
+//! A tool's result, or what went wrong running it, is sent back to the model as text for it to
+//! carry on with, so failures here aren't [`Error`](crate::Error)s.
+
+use std::fmt;
+use std::process::Stdio;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::process::Command;
+
+use crate::time;
+
+/// Characters of a file or a command's output sent back to the model, so one large file doesn't
+/// fill its context window
+const MAX_RESULT_LENGTH: usize = 8_000;
+
+/// Calls a model can make while writing one response, so a model that keeps calling tools still
+/// gets to answer
+pub const MAX_CALLS_PER_RESPONSE: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tool {
+    Calculator,
+    CurrentTime,
+    ReadFile,
+    /// Only run once the user has seen the command and confirmed it
+    RunCommand,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 4] = [
+        Tool::Calculator,
+        Tool::CurrentTime,
+        Tool::ReadFile,
+        Tool::RunCommand,
+    ];
+
+    /// The name the model calls it by
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Calculator => "calculator",
+            Tool::CurrentTime => "current_time",
+            Tool::ReadFile => "read_file",
+            Tool::RunCommand => "run_command",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Tool> {
+        Tool::ALL.into_iter().find(|tool| tool.name() == name)
+    }
+
+    pub fn needs_confirmation(self) -> bool {
+        self == Tool::RunCommand
+    }
+
+    /// The tool as it's described to the model, with a JSON schema of its arguments
+    pub fn definition(self) -> Value {
+        let (description, parameters) = match self {
+            Tool::Calculator => (
+                "Evaluate an arithmetic expression, e.g. `(2 + 3) * sqrt(16) ^ 2`. Supports + - * \
+                 / % ^, parentheses, pi, e and the functions sqrt, abs, ln, log, sin, cos and tan.",
+                json!({
+                    "expression": {"type": "string", "description": "The expression to evaluate"}
+                }),
+            ),
+            Tool::CurrentTime => ("Get the current date and time in UTC.", json!({})),
+            Tool::ReadFile => (
+                "Read a text file on the user's computer.",
+                json!({
+                    "path": {"type": "string", "description": "Absolute path of the file"}
+                }),
+            ),
+            Tool::RunCommand => (
+                "Run a shell command on the user's computer, once they've confirmed it, and get \
+                 its output.",
+                json!({
+                    "command": {"type": "string", "description": "The command to run"}
+                }),
+            ),
+        };
+        let required: Vec<&String> = parameters
+            .as_object()
+            .map(|properties| properties.keys().collect())
+            .unwrap_or_default();
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name(),
+                "description": description,
+                "parameters": {
+                    "type": "object",
+                    "properties": parameters,
+                    "required": required,
+                },
+            },
+        })
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A tool the model asked to have run, as Ollama sends it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+impl ToolCall {
+    pub fn tool(&self) -> Option<Tool> {
+        Tool::from_name(&self.name)
+    }
+
+    fn argument(&self, name: &str) -> Option<&str> {
+        self.arguments.get(name).and_then(Value::as_str)
+    }
+
+    /// What the tool was asked to work on, e.g. the expression or the command, to show the user
+    pub fn input(&self) -> String {
+        let argument = match self.tool() {
+            Some(Tool::Calculator) => self.argument("expression"),
+            Some(Tool::ReadFile) => self.argument("path"),
+            Some(Tool::RunCommand) => self.argument("command"),
+            Some(Tool::CurrentTime) => Some(""),
+            None => None,
+        };
+        argument.map_or_else(|| self.arguments.to_string(), str::to_string)
+    }
+}
+
+/// A call a model made to a tool, with what the tool gave back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolExchange {
+    pub call: ToolCall,
+    pub result: String,
+}
+
+/// Runs the tool a model called, which for commands should only be once the user has confirmed
+pub async fn run(call: ToolCall) -> ToolExchange {
+    let result = match call.tool() {
+        Some(Tool::Calculator) => call
+            .argument("expression")
+            .ok_or_else(|| "no expression was given".to_string())
+            .and_then(calculate)
+            .map(|value| value.to_string()),
+        Some(Tool::CurrentTime) => Ok(time::date_time(SystemTime::now())),
+        Some(Tool::ReadFile) => match call.argument("path") {
+            Some(path) => tokio::fs::read_to_string(path)
+                .await
+                .map(truncate)
+                .map_err(|err| format!("couldn't read {path}: {err}")),
+            None => Err("no path was given".to_string()),
+        },
+        Some(Tool::RunCommand) => match call.argument("command") {
+            Some(command) => run_command(command).await,
+            None => Err("no command was given".to_string()),
+        },
+        None => Err(format!("there's no tool called {}", call.name)),
+    };
+    ToolExchange {
+        call,
+        result: result.unwrap_or_else(|err| format!("Error: {err}")),
+    }
+}
+
+/// The result sent back for a command the user chose not to run
+pub fn declined(call: ToolCall) -> ToolExchange {
+    ToolExchange {
+        call,
+        result: "The user declined to run this.".to_string(),
+    }
+}
+
+async fn run_command(command: &str) -> Result<String, String> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let output = Command::new(shell)
+        .args([flag, command])
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|err| format!("couldn't run {shell}: {err}"))?;
+    let mut result = String::from_utf8_lossy(&output.stdout).into_owned();
+    result.push_str(&String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        result.push_str(&format!("\n({})", output.status));
+    }
+    Ok(truncate(result))
+}
+
+fn truncate(mut text: String) -> String {
+    if let Some((cut, _)) = text.char_indices().nth(MAX_RESULT_LENGTH) {
+        text.truncate(cut);
+        text.push_str("\n… (cut off)");
+    }
+    text
+}
+
+/// Evaluates an arithmetic expression, with the usual precedence and `^` for powers
+pub fn calculate(expression: &str) -> Result<f64, String> {
+    let mut parser = Parser {
+        chars: expression.chars().filter(|c| !c.is_whitespace()).collect(),
+        position: 0,
+    };
+    let value = parser.sum()?;
+    match parser.peek() {
+        None if value.is_finite() => Ok(value),
+        None => Err("the result isn't a finite number".to_string()),
+        Some(c) => Err(format!("unexpected '{c}'")),
+    }
+}
+
+/// Recursive descent over an expression, with each method reading one level of precedence
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let is_next = self.peek() == Some(c);
+        if is_next {
+            self.position += 1;
+        }
+        is_next
+    }
+
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.power()?;
+        loop {
+            if self.eat('*') {
+                value *= self.power()?;
+            } else if self.eat('/') {
+                value /= self.power()?;
+            } else if self.eat('%') {
+                value %= self.power()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Powers group to the right, so `2^3^2` is `2^9`, and bind tighter than a leading minus
+    fn power(&mut self) -> Result<f64, String> {
+        if self.eat('-') {
+            return self.power().map(|value| -value);
+        }
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Ok(base.powf(self.power()?));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64, String> {
+        if self.eat('(') {
+            let value = self.sum()?;
+            return if self.eat(')') {
+                Ok(value)
+            } else {
+                Err("a bracket isn't closed".to_string())
+            };
+        }
+        let start = self.position;
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.position += 1;
+                }
+                let number: String = self.chars[start..self.position].iter().collect();
+                number
+                    .parse()
+                    .map_err(|_| format!("{number} isn't a number"))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric()) {
+                    self.position += 1;
+                }
+                let name: String = self.chars[start..self.position].iter().collect();
+                let function: fn(f64) -> f64 = match name.as_str() {
+                    "pi" => return Ok(std::f64::consts::PI),
+                    "e" => return Ok(std::f64::consts::E),
+                    "sqrt" => f64::sqrt,
+                    "abs" => f64::abs,
+                    "ln" => f64::ln,
+                    "log" => f64::log10,
+                    "sin" => f64::sin,
+                    "cos" => f64::cos,
+                    "tan" => f64::tan,
+                    _ => return Err(format!("there's no function called {name}")),
+                };
+                if !self.eat('(') {
+                    return Err(format!("{name} needs brackets around what it's applied to"));
+                }
+                let value = self.sum()?;
+                if !self.eat(')') {
+                    return Err("a bracket isn't closed".to_string());
+                }
+                Ok(function(value))
+            }
+            Some(c) => Err(format!("unexpected '{c}'")),
+            None => Err("the expression ends too soon".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions_are_calculated_with_precedence() {
+        assert_eq!(calculate("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(calculate("(1 + 2) * 3"), Ok(9.0));
+        assert_eq!(calculate("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(calculate("-2^2"), Ok(-4.0));
+        assert_eq!(calculate("10 % 4 - sqrt(16) / 2"), Ok(0.0));
+        assert_eq!(calculate("abs(-1.5)"), Ok(1.5));
+        assert!(calculate("1 / 0").is_err());
+        assert!(calculate("(1 + 2").is_err());
+        assert!(calculate("2 +").is_err());
+        assert!(calculate("foo(2)").is_err());
+    }
+
+    #[test]
+    fn tools_are_called_by_name() {
+        for tool in Tool::ALL {
+            assert_eq!(Tool::from_name(tool.name()), Some(tool));
+            assert_eq!(tool.definition()["function"]["name"], tool.name());
+        }
+        let call: ToolCall = serde_json::from_value(json!({
+            "name": "calculator",
+            "arguments": {"expression": "6 * 7"}
+        }))
+        .unwrap();
+        assert_eq!(call.input(), "6 * 7");
+        assert_eq!(
+            Tool::RunCommand.definition()["function"]["parameters"]["required"],
+            json!(["command"])
+        );
+    }
+}
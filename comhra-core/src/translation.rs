@@ -0,0 +1,168 @@
+//! Languages responses can be asked for in: translating a response into one with another request
+//! to the model, and telling the model to always answer a conversation in one
+
+use std::fmt;
+
+use crate::backend::{Backend, Provider};
+use crate::conversation::GenerationParams;
+use crate::reasoning;
+use crate::{ChatMessage, Error, MessageRole, Result};
+
+/// A language models are asked to write in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    /// The language's name in English, which is what models are told to write in
+    pub name: &'static str,
+    /// What the language calls itself, shown to choose it by
+    pub native_name: &'static str,
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.native_name)
+    }
+}
+
+/// The languages offered, Ireland's and Britain's first, then the most widely spoken ones
+pub const LANGUAGES: [Language; 18] = [
+    Language {
+        name: "English",
+        native_name: "English",
+    },
+    Language {
+        name: "Irish",
+        native_name: "Gaeilge",
+    },
+    Language {
+        name: "Scottish Gaelic",
+        native_name: "Gàidhlig",
+    },
+    Language {
+        name: "Welsh",
+        native_name: "Cymraeg",
+    },
+    Language {
+        name: "French",
+        native_name: "Français",
+    },
+    Language {
+        name: "German",
+        native_name: "Deutsch",
+    },
+    Language {
+        name: "Spanish",
+        native_name: "Español",
+    },
+    Language {
+        name: "Italian",
+        native_name: "Italiano",
+    },
+    Language {
+        name: "Portuguese",
+        native_name: "Português",
+    },
+    Language {
+        name: "Dutch",
+        native_name: "Nederlands",
+    },
+    Language {
+        name: "Polish",
+        native_name: "Polski",
+    },
+    Language {
+        name: "Ukrainian",
+        native_name: "Українська",
+    },
+    Language {
+        name: "Russian",
+        native_name: "Русский",
+    },
+    Language {
+        name: "Arabic",
+        native_name: "العربية",
+    },
+    Language {
+        name: "Hindi",
+        native_name: "हिन्दी",
+    },
+    Language {
+        name: "Chinese",
+        native_name: "中文",
+    },
+    Language {
+        name: "Japanese",
+        native_name: "日本語",
+    },
+    Language {
+        name: "Korean",
+        native_name: "한국어",
+    },
+];
+
+/// The conversation with an instruction to answer in the language added to its system prompt, or
+/// as one if it doesn't have one
+pub fn with_response_language(
+    mut conversation: Vec<ChatMessage>,
+    language: &str,
+) -> Vec<ChatMessage> {
+    let instruction =
+        format!("Always respond in {language}, whatever language the user writes in.");
+    match conversation.first_mut() {
+        Some(system_message) if system_message.role == MessageRole::System => {
+            system_message.content = format!("{}\n\n{instruction}", system_message.content);
+        }
+        _ => conversation.insert(0, ChatMessage::system(instruction)),
+    }
+    conversation
+}
+
+/// Asks the model to translate the text, keeping its formatting
+pub async fn translate(
+    backend: Backend,
+    model: String,
+    text: String,
+    language: String,
+) -> Result<String> {
+    let messages = vec![
+        ChatMessage::system(format!(
+            "Translate the user's message into {language}. Keep its markdown formatting, and \
+            leave code, names and links as they are. Reply with only the translation."
+        )),
+        ChatMessage::user(text),
+    ];
+    let response = backend
+        .chat(model, messages, GenerationParams::default())
+        .await?;
+    let translation = reasoning::answer(&response).trim();
+    if translation.is_empty() {
+        return Err(Error::Backend(
+            "the model didn't reply with a translation".to_string(),
+        ));
+    }
+    Ok(translation.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_language_is_added_to_the_system_prompt() {
+        let conversation = with_response_language(
+            vec![
+                ChatMessage::system("You're a baker.".to_string()),
+                ChatMessage::user("Hello".to_string()),
+            ],
+            "Irish",
+        );
+        assert_eq!(
+            conversation[0].content,
+            "You're a baker.\n\nAlways respond in Irish, whatever language the user writes in."
+        );
+        assert_eq!(conversation.len(), 2);
+        let conversation =
+            with_response_language(vec![ChatMessage::user("Hello".to_string())], "Welsh");
+        assert_eq!(conversation[0].role, MessageRole::System);
+        assert_eq!(conversation[1].content, "Hello");
+    }
+}
@@ -0,0 +1,154 @@
+//! How much the app's been used, counted from the messages saved across every conversation:
+//! messages sent each day, and the responses, tokens and time each model has taken
+//!
+//! Only what's still saved is counted, so deleted conversations and replaced responses aren't.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::conversation::ResponseStats;
+use crate::{storage, time, MessageRole, Result};
+
+/// Number of days messages are counted for, up to and including today
+pub const DAYS: u32 = 30;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What's stored about a message apart from its contents
+pub(crate) struct MessageRecord {
+    pub(crate) role: MessageRole,
+    /// The model the conversation was using when the message was saved
+    pub(crate) model: Option<String>,
+    pub(crate) stats: Option<ResponseStats>,
+    pub(crate) sent_at: SystemTime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Usage {
+    /// Prompts and responses sent on each of the last [`DAYS`] days by their date in UTC, oldest
+    /// first
+    pub messages_per_day: Vec<(String, u32)>,
+    /// The models responses were written by, the most used first
+    pub models: Vec<ModelUsage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelUsage {
+    pub model: String,
+    pub responses: u32,
+    pub generated_tokens: u64,
+    /// Responses the server reported stats for, which the time is totalled over
+    timed_responses: u32,
+    total_time: Duration,
+}
+
+impl ModelUsage {
+    /// Average time from sending a prompt to the whole response arriving, including loading the
+    /// model, or `None` if the server never said
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.timed_responses > 0).then(|| self.total_time / self.timed_responses)
+    }
+}
+
+/// Counts up the messages saved in the conversations in `dir`
+pub async fn load(dir: PathBuf) -> Result<Usage> {
+    let records = storage::in_database(dir, |database| database.message_records()).await?;
+    Ok(tally(&records, SystemTime::now()))
+}
+
+fn tally(records: &[MessageRecord], now: SystemTime) -> Usage {
+    let mut messages_per_day: Vec<(String, u32)> = (0..DAYS)
+        .rev()
+        .map(|days_ago| (time::date(now - DAY * days_ago), 0))
+        .collect();
+    let mut models: Vec<ModelUsage> = vec![];
+    for record in records {
+        if record.role == MessageRole::System {
+            continue;
+        }
+        let date = time::date(record.sent_at);
+        if let Some((_date, count)) = messages_per_day.iter_mut().find(|(day, _)| *day == date) {
+            *count += 1;
+        }
+        // Responses saved before the model was stored with them can't be put down to one
+        let (MessageRole::Assistant, Some(model)) = (&record.role, &record.model) else {
+            continue;
+        };
+        let index = match models.iter().position(|usage| usage.model == *model) {
+            Some(index) => index,
+            None => {
+                models.push(ModelUsage {
+                    model: model.clone(),
+                    ..ModelUsage::default()
+                });
+                models.len() - 1
+            }
+        };
+        let usage = &mut models[index];
+        usage.responses += 1;
+        if let Some(stats) = &record.stats {
+            usage.generated_tokens += u64::from(stats.generated_tokens);
+            usage.timed_responses += 1;
+            usage.total_time += stats.total_time();
+        }
+    }
+    models.sort_by(|a, b| {
+        b.responses
+            .cmp(&a.responses)
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    Usage {
+        messages_per_day,
+        models,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        role: MessageRole,
+        model: &str,
+        generated_tokens: u32,
+        days_ago: u32,
+    ) -> MessageRecord {
+        MessageRecord {
+            role,
+            model: Some(model.to_string()),
+            stats: (generated_tokens > 0).then(|| ResponseStats {
+                generated_tokens,
+                total_nanos: 2_000_000_000,
+                ..ResponseStats::default()
+            }),
+            sent_at: SystemTime::UNIX_EPOCH + DAY * (100 - days_ago),
+        }
+    }
+
+    #[test]
+    fn messages_are_counted_by_day_and_model() {
+        let now = SystemTime::UNIX_EPOCH + DAY * 100;
+        let records = [
+            record(MessageRole::User, "llama3", 0, 0),
+            record(MessageRole::Assistant, "llama3", 10, 0),
+            record(MessageRole::User, "mistral", 0, 1),
+            record(MessageRole::Assistant, "mistral", 5, 1),
+            record(MessageRole::Assistant, "mistral", 0, 1),
+            record(MessageRole::System, "mistral", 0, 1),
+            record(MessageRole::User, "llama3", 0, 45),
+        ];
+        let usage = tally(&records, now);
+        assert_eq!(usage.messages_per_day.len(), DAYS as usize);
+        assert_eq!(usage.messages_per_day[0].0, time::date(now - DAY * 29));
+        assert_eq!(usage.messages_per_day[28].1, 3);
+        assert_eq!(usage.messages_per_day[29], (time::date(now), 2));
+        assert_eq!(usage.models[0].model, "mistral");
+        assert_eq!(usage.models[0].responses, 2);
+        assert_eq!(usage.models[0].generated_tokens, 5);
+        assert_eq!(
+            usage.models[0].average_latency(),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(usage.models[1].model, "llama3");
+    }
+}
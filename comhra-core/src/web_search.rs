@@ -0,0 +1,262 @@
+//! Searching the web before answering: the prompt is searched for with a SearxNG instance or
+//! Brave's search API, the top results' pages are fetched and stripped down to their text, and
+//! that's sent to the model along with the prompt for it to cite by number
+
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::settings::{SearchEngine, WebSearch};
+use crate::{ChatMessage, Error, Result};
+
+/// Number of results whose pages are sent with each prompt
+pub const RESULT_COUNT: usize = 3;
+
+/// Characters of each page sent to the model, so one long page doesn't fill its context window
+const MAX_PAGE_LENGTH: usize = 4_000;
+
+/// How long the search and each page get before they're given up on
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const BRAVE_API_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+
+/// Elements whose contents aren't part of a page's text
+const SKIPPED_ELEMENTS: [&str; 7] = [
+    "head", "script", "style", "noscript", "svg", "nav", "footer",
+];
+
+/// Elements that start on a line of their own
+const BLOCK_ELEMENTS: [&str; 22] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "p",
+    "pre",
+    "section",
+    "td",
+    "tr",
+];
+
+/// Put before the pages so the model knows where they came from and how to cite them
+const CONTEXT_INSTRUCTION: &str = "The user's next message was searched for on the web. Use \
+    these results to answer it if they're relevant, citing the ones you use by their number in \
+    square brackets, like [1]. If they don't help, answer as you would without them.";
+
+/// A search result whose page was sent to the model along with a prompt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebSource {
+    pub title: String,
+    pub url: String,
+    /// The page's text, or the search engine's snippet of it if it couldn't be fetched
+    pub excerpt: String,
+}
+
+/// Searches for the prompt and fetches the top results' pages
+pub async fn search(web_search: &WebSearch, prompt: &str) -> Result<Vec<WebSource>> {
+    let client = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|err| Error::WebSearch(err.to_string()))?;
+    let request = match web_search.engine {
+        SearchEngine::Searxng => client
+            .get(format!("{}/search", web_search.url.trim_end_matches('/')))
+            .query(&[("q", prompt), ("format", "json")]),
+        SearchEngine::Brave => client
+            .get(BRAVE_API_URL)
+            .query(&[("q", prompt)])
+            .header(ACCEPT, "application/json")
+            .header("X-Subscription-Token", &web_search.api_key),
+    };
+    let results: Value = request
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|err| Error::WebSearch(err.to_string()))?
+        .json()
+        .await
+        .map_err(|err| Error::WebSearch(err.to_string()))?;
+    let mut sources = parse_results(web_search.engine, &results);
+    sources.truncate(RESULT_COUNT);
+    let pages = join_all(
+        sources
+            .iter()
+            .map(|source| fetch_page(&client, &source.url)),
+    )
+    .await;
+    for (source, page) in sources.iter_mut().zip(pages) {
+        if let Some(page) = page.filter(|page| !page.is_empty()) {
+            source.excerpt = page;
+        }
+    }
+    Ok(sources)
+}
+
+/// The results in a search engine's JSON reply, in the order it ranked them
+fn parse_results(engine: SearchEngine, results: &Value) -> Vec<WebSource> {
+    let (results, snippet_field) = match engine {
+        SearchEngine::Searxng => (&results["results"], "content"),
+        SearchEngine::Brave => (&results["web"]["results"], "description"),
+    };
+    results
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|result| {
+            let url = result["url"].as_str()?;
+            Some(WebSource {
+                title: result["title"].as_str().unwrap_or(url).to_string(),
+                url: url.to_string(),
+                // Brave marks the words searched for in its snippets
+                excerpt: strip_html(result[snippet_field].as_str().unwrap_or_default()),
+            })
+        })
+        .collect()
+}
+
+/// A page's text, `None` if it couldn't be fetched or isn't HTML or text
+async fn fetch_page(client: &reqwest::Client, url: &str) -> Option<String> {
+    let response = client.get(url).send().await.ok()?.error_for_status().ok()?;
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let body = response.text().await.ok()?;
+    let text = if content_type.contains("html") {
+        strip_html(&body)
+    } else if content_type.starts_with("text/") {
+        body
+    } else {
+        return None;
+    };
+    Some(text.chars().take(MAX_PAGE_LENGTH).collect())
+}
+
+/// The text of some HTML, a line for each block like a paragraph or list item
+pub fn strip_html(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let tag = &rest[start + 1..];
+        let Some(end) = tag.find('>') else {
+            rest = "";
+            break;
+        };
+        let is_closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        rest = &tag[end + 1..];
+        if !is_closing && SKIPPED_ELEMENTS.contains(&name.as_str()) {
+            // Lowercasing ASCII leaves where everything is in the string as it was
+            rest = match rest.to_ascii_lowercase().find(&format!("</{name}")) {
+                Some(closing_tag) => &rest[closing_tag..],
+                None => "",
+            };
+        }
+        if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            text.push('\n');
+        }
+    }
+    text.push_str(rest);
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A system message with the results, numbered for the model to cite, to go before the prompt
+/// they were found for
+pub fn context_message(sources: &[WebSource]) -> ChatMessage {
+    let mut context = CONTEXT_INSTRUCTION.to_string();
+    for (index, source) in sources.iter().enumerate() {
+        context.push_str(&format!(
+            "\n\n[{}] {} ({}):\n{}",
+            index + 1,
+            source.title,
+            source.url,
+            source.excerpt
+        ));
+    }
+    ChatMessage::system(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn pages_are_stripped_to_their_text() {
+        let html = "<html><head><title>Bread</title><style>p { color: red }</style></head>\
+            <body><nav><a href=\"/\">Home</a></nav><h1>Soda  bread</h1><p>Flour &amp; \
+            <b>buttermilk</b>.</p><SCRIPT>track();</SCRIPT><ul><li>Mix</li><li>Bake</li></ul>\
+            </body></html>";
+        assert_eq!(
+            strip_html(html),
+            "Soda bread\nFlour & buttermilk.\nMix\nBake"
+        );
+        assert_eq!(strip_html("No tags at all"), "No tags at all");
+        assert_eq!(strip_html("Cut off <a hre"), "Cut off");
+    }
+
+    #[test]
+    fn results_are_read_from_each_engine() {
+        let searxng = json!({"results": [
+            {"url": "https://example.ie/bread", "title": "Bread", "content": "All about bread"},
+            {"title": "No link"},
+        ]});
+        assert_eq!(
+            parse_results(SearchEngine::Searxng, &searxng),
+            [WebSource {
+                title: "Bread".to_string(),
+                url: "https://example.ie/bread".to_string(),
+                excerpt: "All about bread".to_string(),
+            }]
+        );
+        let brave = json!({"web": {"results": [
+            {
+                "url": "https://example.ie",
+                "title": "Example",
+                "description": "<strong>Soda</strong> bread",
+            },
+        ]}});
+        assert_eq!(
+            parse_results(SearchEngine::Brave, &brave)[0].excerpt,
+            "Soda bread"
+        );
+        assert!(parse_results(SearchEngine::Brave, &searxng).is_empty());
+    }
+}
@@ -0,0 +1,435 @@
+## Top bar
+
+toggle-sidebar = Toggle Sidebar
+new-chat = New Chat
+select-model = Select Model
+tasks-button = Tasks ({ $count })
+background-tasks = Background Tasks
+history = History
+logs = Logs
+
+## Models
+
+pull-model-placeholder = Model to pull from the Ollama library, e.g. llama3.2
+pull-model = Pull
+pulling-model = Pulling { $model }: { $status }
+create-model = Create Model
+new-model-name = Name
+new-model-name-placeholder = Name for the new model, e.g. baker
+new-model-base = Based on
+new-model-system-prompt-placeholder = Instructions the model always follows, e.g. "Answer as a patient code reviewer"
+modelfile-preview = Modelfile
+creating-model = Creating { $model }: { $status }
+conversation-model-missing = This conversation was answered with { $model }, which isn't on the server
+model-parameters = { $count } parameters
+model-modified = Modified { $date }
+confirm-delete-model = Delete this model from the server?
+
+## Sidebar
+
+conversations = Conversations
+conversation-summary =
+    Messages: { $count }
+    { $preview }
+search-conversations = Search conversations
+no-search-results = No conversations match
+conversation-title = Conversation title
+rename = Rename
+duplicate = Duplicate
+open-in-new-window = Open in New Window
+organize = Organize
+folder-placeholder = Folder (leave blank for none)
+tags-placeholder = Tags, separated by commas
+pin = Pin
+unpin = Unpin
+archive = Archive
+unarchive = Unarchive
+pinned = Pinned
+archived = Archived ({ $count })
+delete = Delete
+confirm-delete = Delete this conversation?
+
+## Background tasks
+
+no-background-tasks = No background tasks running
+job-queued = Queued
+job-index-conversations = { $count ->
+    [one] Indexing { $count } conversation
+   *[other] Indexing { $count } conversations
+}
+job-index-knowledge = Indexing the documents in { $folder }
+job-embed-conversations = Embedding conversations to find related ones
+job-crashed = Background job crashed: { $error }
+
+## Chat
+
+prompt-placeholder = Enter your chat, Shift+Enter for a new line, Up for the prompts sent before
+paste-selection = Paste Selection
+paste-selection-tooltip = Paste the primary selection
+attach-image = Attach Image
+attach-image-tooltip = Attach a PNG or JPEG for vision models like llava, or drop one on the window. Text, code and CSV files dropped on the window are included in the prompt.
+paste-image = Paste Image
+screenshot = Screenshot
+screenshot-tooltip = Drag out part of the screen to attach, to ask a vision model about it. Images can also be pasted into the prompt with Ctrl+V.
+dictate = Dictate
+dictate-tooltip = Say the prompt instead of typing it, transcribed on this computer
+listening = Listening…
+listening-tooltip = Stop and transcribe what was said
+transcribing = Transcribing…
+templates = Templates
+json-mode = JSON mode
+json-mode-tooltip = Ask for the reply as JSON, optionally matching a JSON schema
+web-search = Web
+web-search-tooltip = Search the web for each prompt and give the model the top results
+web-search-tooltip-not-set-up = Set up a search engine in the settings to search the web
+searching-web = Searching the web…
+json-schema-placeholder = JSON schema, or leave empty for any JSON
+invalid-schema = The schema can't be used: { $error }
+reply-not-json = This reply isn't valid JSON
+reply-doesnt-match-schema = This reply doesn't match the schema
+reasoning = Reasoning
+reasoning-in-progress = Thinking…
+no-templates = No templates yet. Write a prompt with {"{{"}variables{"}}"} in double braces and save it as one.
+template-name = Template name
+save-template = Save Prompt as Template
+save-template-tooltip = Saves what's in the prompt box, with anything in double braces filled in each time it's used
+insert-template = Insert
+context-usage = ~{ $used } of { $length } tokens
+context-usage-tooltip = An estimate of how much of the model's context window the conversation fills
+summarize-context = Summarise older messages
+summarizing = Summarising…
+tool-call = Called { $tool }
+tool-result = Result
+tool-confirm = Wants to call { $tool }:
+run-tool = Run
+decline-tool = Don't Run
+image-path = Path of the image to attach
+attach = Attach
+couldnt-paste-image = The image on the clipboard couldn't be attached
+file-truncated = { $name } is over { $size }KB, so only its start will be sent
+file-chip-truncated = { $name } (truncated)
+load-earlier-messages = Load earlier messages ({ $count } more)
+jump-to-latest = Jump to latest
+role-user = User
+role-assistant = Assistant
+role-system = System
+time-just-now = just now
+time-minutes-ago = { $count } min ago
+time-hours-ago = { $count } h ago
+time-yesterday = yesterday
+time-days-ago = { $count } days ago
+edit-message = Edit
+send-edited-message = Send
+regenerate = Regenerate
+delete-message = Delete
+read-aloud = Read Aloud
+translate = Translate
+translating = Translating into { $language }…
+translation = Translation into { $language }
+stop-reading = Stop Reading
+version-count = { $position } of { $count }
+copy = Copy
+copy-as = Copy as
+copy-markdown = Markdown
+copy-plain-text = Plain text
+copy-code = Code only
+stats-tokens = { $count ->
+    [one] { $count } token
+   *[other] { $count } tokens
+}
+stats-speed = { $speed } tokens/s
+stats-conversation-total = { $count ->
+    [one] { $count } token in the conversation so far
+   *[other] { $count } tokens in the conversation so far
+}
+sources = Sources:
+web-sources = From the web:
+
+## Notices
+
+retry = Retry
+open-file = Open File
+copy-details = Copy Details
+dismiss = Dismiss
+couldnt-open-file = Couldn't open { $path }: { $error }
+clipboard-error = Couldn't use the clipboard: { $error }
+recovery-prompt = Comhrá didn't close cleanly last time. Restore your unsent prompt and any response that was being generated?
+restore = Restore
+discard = Discard
+ask-about-clipboard = Ask about this? “{ $snippet }”
+ask = Ask
+
+## Desktop notifications
+
+notification-response-ready = Response ready: { $title }
+notification-generation-failed = Generation failed: { $title }
+notification-open = Open
+new-conversation = New conversation
+
+## System prompts
+
+system-prompt = System Prompt
+system-prompt-placeholder = Instructions the model follows for the whole conversation, e.g. "Answer as a patient code reviewer"
+persona = Persona
+start-with-persona = Start with a persona
+persona-name = Persona name
+save-persona = Save as Persona
+delete-persona = Delete Persona
+apply = Apply
+
+## Generation params
+
+params = Parameters
+param-temperature = Temperature
+param-top-p = Top P
+param-top-k = Top K
+param-repeat-penalty = Repeat penalty
+param-seed = Seed
+param-num-ctx = Context length
+param-default = Model default
+response-language = Respond in
+any-language = Any language
+reset = Reset
+
+## Knowledge
+
+knowledge = Knowledge
+knowledge-explanation = Attach a folder of text, Markdown or PDF files and the passages most relevant to each prompt are sent along with it
+knowledge-attached = Answers will draw on this folder once it's indexed with the embedding model from the settings
+knowledge-indexed = { $count ->
+    [one] { $count } passage indexed
+   *[other] { $count } passages indexed
+}
+knowledge-folder = Folder of documents
+reindex = Index Again
+detach = Detach
+not-a-folder = it isn't a folder
+related = Related
+related-conversations = Related conversations
+find-related = Find Related
+finding-related = Embedding your conversations to find related ones…
+no-related = No other conversations are about the same things
+related-unsaved = Related conversations are found once this one is saved
+related-similarity = { $percent }%
+embeddings-playground = Embeddings Playground
+playground-explanation = Write some texts to see how alike { $model } finds each of them to each of the others, from -1 to 1
+playground-text = Text to compare
+add-text = Add Text
+compare = Compare
+
+## Settings
+
+settings = Settings
+open-settings-file = Open Settings File
+setting-server-url = Server for new conversations
+saved-servers = Saved servers, to switch the open conversation between from the toolbar
+server-name = Name
+server-username = Username
+server-password = Password
+server-api-key = API key
+server-logins-unencrypted = Passwords and API keys are saved in the settings file as they are, unencrypted even when conversations are
+server-kind-ollama = Ollama
+server-kind-openai = OpenAI-compatible
+remove = Remove
+add-server = Add Server
+server-connecting = connecting…
+server-connected = connected
+server-unreachable = can't be reached
+prompt-queued = Queued to send: { $prompt }
+unqueue-prompt = Edit
+generating-in-background = Writing a response…
+server-unreachable-banner = Couldn't reach the server at { $server }. Check that it's running, or choose another server in the settings.
+setting-default-model = Default model
+no-default-model = Pick one on launch
+setting-theme = Theme
+setting-zoom = Zoom
+theme-system = Follow the system (light or dark)
+theme-custom = Custom
+palette-background = Background
+palette-text = Text
+palette-primary = Accent
+palette-success = Success
+palette-danger = Danger
+setting-language = Language
+system-language = System language
+setting-conversations-dir = Conversations folder
+setting-show-sidebar = Show the sidebar
+setting-encrypt-conversations = Encrypt conversations with a passphrase
+setting-git-history = Keep every version of conversations in git
+setting-watch-clipboard = Offer to ask about text copied in other apps
+setting-minimize-to-tray = Keep running in the system tray when the window is closed
+setting-quick-chat-shortcut = Global shortcut for quick chat
+setting-summarize-context = Summarise the earliest messages automatically once the context window is filling up
+setting-include-reasoning = Include the reasoning models write before answering when copying and exporting
+setting-share-url = Host to upload shared conversations to, or empty to save them as files
+setting-search-engine = Web search engine
+setting-searxng-url = SearxNG URL
+setting-brave-api-key = Brave Search API key
+setting-tools = Tools models can call, on Ollama servers
+tool-calculator = Calculator
+tool-current-time = Current time
+tool-read-file = Reading files
+tool-run-command = Shell commands, once confirmed
+setting-auto-title = Have a model title conversations after their first response
+setting-title-model = Model for titles
+conversation-model = The conversation's model
+setting-embedding-model = Model for embedding knowledge folders
+setting-math-renderer = Command rendering equations to SVG, e.g. tex2svg from mathjax-node-cli, or empty to show them as text
+setting-markdown-memory-budget = Memory for formatted messages, beyond which those furthest out of view are formatted again when scrolled back to
+megabytes = { $count } MB
+setting-speech-engine = Text-to-speech engine
+setting-voice = Voice
+default-voice = espeak-ng's default
+setting-piper-model = Piper voice model
+setting-audio-player = Command to play audio with
+setting-whisper-model = whisper.cpp model for dictation
+setting-whisper-command = whisper.cpp command
+setting-recorder = Command to record from the microphone with
+
+## Profiles
+
+choose-profile = Choose a Profile
+default-profile = Default
+new-profile = New profile name
+create-profile = Create
+
+## Encryption
+
+unlock-title = Unlock Conversations
+unlock-explanation = Your conversations are encrypted. Enter your passphrase to read them.
+unlock = Unlock
+encrypt-title = Encrypt Conversations
+encrypt-explanation = Choose a passphrase to encrypt your conversations with. It can't be recovered if you forget it, and conversation titles stay readable in their file names. Earlier versions kept in the conversations' history are deleted, as they weren't encrypted.
+encrypt = Encrypt
+passphrase = Passphrase
+cancel = Cancel
+
+## Sync conflicts
+
+conflict = "{ $title }" was changed in two places at once, e.g. on two synced devices
+merge-both = Merge Both
+keep-original = Keep Original
+keep-copy = Keep Copy
+open-copy = Open Copy
+database-conflict = A sync tool saved a copy of the conversations database, "{ $file }", because it was changed on two devices at once. Conversations saved in the copy aren't shown.
+open-folder = Open Folder
+
+## History
+
+history-save-commit = { $count ->
+    [one] Save "{ $title }" with { $count } message
+   *[other] Save "{ $title }" with { $count } messages
+}
+history-restore-commit = Restore "{ $title }" to the version from { $date }
+restore-version = Restore This Version
+no-versions = No versions of this conversation have been recorded yet
+select-version = Select a version to preview it
+close = Close
+
+## Code blocks
+
+save-code-block = Save As…
+open-in-editor = Open in Editor
+save-path = File to save to
+save = Save
+saved-to = Saved to { $path }
+couldnt-open-editor = Couldn't open an editor: { $error }
+
+## Exporting
+
+export = Export
+export-tooltip = Save the conversation as Markdown, as a web page anyone can open, or as a PDF
+export-markdown = Markdown
+export-html = Web Page
+export-pdf = PDF
+share = Share
+sharing = Sharing…
+share-tooltip = Upload the conversation as a web page and copy the link to it
+share-tooltip-no-host = Save the conversation as a web page to send on, or set a host to upload it to in the settings
+share-link-copied = Link copied: { $link }
+
+## Importing
+
+import = Import
+import-explanation = Bring in your conversations from ChatGPT's conversations.json export, an Open WebUI export, a JSON list of messages, or the Ollama CLI's history file
+import-path = File to import
+conversations-imported = { $count ->
+    [one] { $count } conversation imported
+   *[other] { $count } conversations imported
+}
+
+## Tray and quick chat
+
+quick-chat = Quick Chat
+quick-chat-placeholder = Ask anything, then press Enter
+tray-show-window = Show Comhrá
+tray-quit = Quit
+
+## Benchmark
+
+benchmark = Benchmark
+benchmark-prompts = Prompts
+benchmark-prompts-placeholder = One prompt per line
+benchmark-models = Models
+run-benchmark = Run
+stop = Stop
+benchmark-running = Running { $model }, { $remaining } more to go
+benchmark-model = Model
+benchmark-prompt = Prompt
+benchmark-time-to-first-token = First Token
+benchmark-total-time = Total Time
+benchmark-tokens-per-second = Tokens/s
+benchmark-output = Output
+export-csv = Export CSV
+export-json = Export JSON
+batch = Batch
+batch-file = Or load them from a file
+batch-file-placeholder = Path to a text or CSV file
+batch-csv-column = CSV column
+batch-load = Load
+batch-loaded = { $count ->
+    [one] { $count } prompt from { $file }
+   *[other] { $count } prompts from { $file }
+}
+run-batch = Run against { $model }
+batch-running = Running against { $model }, { $done } of { $total } done
+
+## Usage
+
+usage = Usage
+usage-messages-per-day = Messages over the last { $days } days
+usage-day = { $count ->
+    [one] { $date }: { $count } message
+   *[other] { $date }: { $count } messages
+}
+usage-models = Models
+usage-responses = Responses
+usage-generated-tokens = Tokens generated
+usage-average-latency = Average time
+usage-no-responses = No responses yet
+
+## Logs
+
+open-log-folder = Open Log Folder
+no-logs = No log files have been written yet
+couldnt-read-file = Couldn't read { $path }: { $error }
+
+## Errors
+
+error-backend = Couldn't reach Ollama: { $details }
+error-stream = The response stream from Ollama broke off
+error-no-app-dir = Couldn't find a directory to store the app's files in
+error-read = Couldn't open { $path }: { $details }
+error-write = Couldn't save { $path }: { $details }
+error-corrupt = { $path } isn't a valid conversation file: { $details }
+error-locked = Conversations are encrypted and need to be unlocked first
+error-wrong-passphrase = That passphrase doesn't unlock the conversations
+error-history = Conversation history isn't available: { $details }
+error-invalid-profile = “{ $name }” can't be used as a profile name
+error-speech = Couldn't read the response aloud: { $details }
+error-dictation = Couldn't take dictation: { $details }
+error-math = Couldn't render an equation: { $details }
+error-share = Couldn't share the conversation: { $details }
+error-web-search = Couldn't search the web: { $details }
+error-screenshot = Couldn't take a screenshot: { $details }
+error-no-model = No model was given, pass one with --model or set a default model
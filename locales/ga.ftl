@@ -0,0 +1,456 @@
+### Counted nouns are left as they are after 1, lenited after 2 to 6 and eclipsed after 7 to 10,
+### which are the one, two, few and many plural categories for Irish, and left as they are after
+### any other count, including 11 and over
+
+## Barra uachtair
+
+toggle-sidebar = Taispeáin nó Folaigh an Barra Taoibh
+new-chat = Comhrá Nua
+select-model = Roghnaigh Samhail
+tasks-button = Tascanna ({ $count })
+background-tasks = Tascanna Cúlra
+history = Stair
+logs = Logaí
+
+## Samhlacha
+
+pull-model-placeholder = Samhail le tarraingt ó leabharlann Ollama, m.sh. llama3.2
+pull-model = Tarraing
+pulling-model = { $model } á tharraingt: { $status }
+create-model = Cruthaigh Samhail
+new-model-name = Ainm
+new-model-name-placeholder = Ainm don tsamhail nua, m.sh. báicéir
+new-model-base = Bunaithe ar
+new-model-system-prompt-placeholder = Treoracha a leanann an tsamhail i gcónaí, m.sh. "Freagair mar athbhreithneoir cóid foighneach"
+modelfile-preview = Modelfile
+creating-model = { $model } á chruthú: { $status }
+conversation-model-missing = Freagraíodh an comhrá seo le { $model }, nach bhfuil ar an bhfreastalaí
+model-parameters = { $count } paraiméadar
+model-modified = Athraithe { $date }
+confirm-delete-model = An bhfuil tú ag iarraidh an tsamhail seo a scriosadh ón bhfreastalaí?
+
+## Barra taoibh
+
+conversations = Comhráite
+conversation-summary =
+    Teachtaireachtaí: { $count }
+    { $preview }
+search-conversations = Cuardaigh na comhráite
+no-search-results = Níl comhrá ar bith ag teacht leis
+conversation-title = Teideal an chomhrá
+rename = Athainmnigh
+duplicate = Dúblaigh
+open-in-new-window = Oscail i bhFuinneog Nua
+organize = Eagraigh
+folder-placeholder = Fillteán (fág bán é mura bhfuil ceann uait)
+tags-placeholder = Clibeanna, scartha le camóga
+pin = Pionnáil
+unpin = Díphionnáil
+archive = Cartlannaigh
+unarchive = Bain as an gcartlann
+pinned = Pionnáilte
+archived = Cartlannaithe ({ $count })
+delete = Scrios
+confirm-delete = An bhfuil tú ag iarraidh an comhrá seo a scriosadh?
+
+## Tascanna cúlra
+
+no-background-tasks = Níl aon tasc cúlra ar siúl
+job-queued = Sa scuaine
+job-index-conversations = { $count ->
+    [one] { $count } comhrá á innéacsú
+    [two] { $count } chomhrá á n-innéacsú
+    [few] { $count } chomhrá á n-innéacsú
+    [many] { $count } gcomhrá á n-innéacsú
+   *[other] { $count } comhrá á n-innéacsú
+}
+job-index-knowledge = Na cáipéisí in { $folder } á n-innéacsú
+job-embed-conversations = Comhráite á leabú chun cinn ghaolmhara a aimsiú
+job-crashed = Thuairteáil an tasc cúlra: { $error }
+
+## Comhrá
+
+prompt-placeholder = Scríobh do theachtaireacht, Shift+Enter le haghaidh líne nua, Suas le haghaidh na leideanna a seoladh cheana
+paste-selection = Greamaigh an Roghnúchán
+paste-selection-tooltip = Greamaigh an príomhroghnúchán
+attach-image = Ceangail Íomhá
+attach-image-tooltip = Ceangail PNG nó JPEG do shamhlacha radhairc cosúil le llava, nó scaoil ceann ar an bhfuinneog. Cuirtear comhaid téacs, cóid agus CSV a scaoiltear ar an bhfuinneog san áireamh sa leid.
+paste-image = Greamaigh Íomhá
+screenshot = Gabháil Scáileáin
+screenshot-tooltip = Tarraing amach cuid den scáileán le ceangal, chun ceist a chur ar shamhail radhairc faoi. Is féidir íomhánna a ghreamú sa leid le Ctrl+V freisin.
+dictate = Deachtaigh
+dictate-tooltip = Abair an leid in ionad í a chlóscríobh, tras-scríofa ar an ríomhaire seo
+listening = Ag éisteacht…
+listening-tooltip = Stop agus tras-scríobh a ndúradh
+transcribing = Ag tras-scríobh…
+templates = Teimpléid
+json-mode = Mód JSON
+json-mode-tooltip = Iarr an freagra mar JSON, a mheaitseálann scéimre JSON más mian leat
+web-search = Gréasán
+web-search-tooltip = Cuardaigh an gréasán do gach leid agus tabhair na torthaí is fearr don tsamhail
+web-search-tooltip-not-set-up = Socraigh inneall cuardaigh sna socruithe chun an gréasán a chuardach
+searching-web = Ag cuardach an ghréasáin…
+json-schema-placeholder = Scéimre JSON, nó fág folamh le haghaidh JSON ar bith
+invalid-schema = Ní féidir an scéimre a úsáid: { $error }
+reply-not-json = Níl an freagra seo ina JSON bailí
+reply-doesnt-match-schema = Ní mheaitseálann an freagra seo an scéimre
+reasoning = Réasúnaíocht
+reasoning-in-progress = Ag smaoineamh…
+no-templates = Níl aon teimpléad fós. Scríobh leid le {"{{"}athróga{"}}"} idir lúibíní dúbailte agus sábháil mar cheann í.
+template-name = Ainm an teimpléid
+save-template = Sábháil an Leid mar Theimpléad
+save-template-tooltip = Sábhálann sé a bhfuil sa bhosca leide, agus líontar aon rud idir lúibíní dúbailte gach uair a úsáidtear é
+insert-template = Cuir Isteach
+context-usage = ~{ $used } as { $length } comhartha
+context-usage-tooltip = Meastachán ar an méid d'fhuinneog chomhthéacs na samhla a líonann an comhrá
+summarize-context = Déan achoimre ar theachtaireachtaí níos sine
+summarizing = Achoimre á déanamh…
+tool-call = Glaodh ar { $tool }
+tool-result = Toradh
+tool-confirm = Ag iarraidh glaoch ar { $tool }:
+run-tool = Rith
+decline-tool = Ná Rith
+image-path = Conair na híomhá le ceangal
+attach = Ceangail
+couldnt-paste-image = Níorbh fhéidir an íomhá ar an ngearrthaisce a cheangal
+file-truncated = Tá { $name } os cionn { $size }KB, mar sin ní sheolfar ach a thús
+file-chip-truncated = { $name } (giorraithe)
+load-earlier-messages = Lódáil teachtaireachtaí níos luaithe ({ $count } eile)
+jump-to-latest = Léim go dtí an ceann is déanaí
+role-user = Úsáideoir
+role-assistant = Cúntóir
+role-system = Córas
+time-just-now = díreach anois
+time-minutes-ago = { $count } nóiméad ó shin
+time-hours-ago = { $count } uair ó shin
+time-yesterday = inné
+time-days-ago = { $count } lá ó shin
+edit-message = Cuir in Eagar
+send-edited-message = Seol
+regenerate = Athghin
+delete-message = Scrios
+read-aloud = Léigh Os Ard
+translate = Aistrigh
+translating = Á aistriú ({ $language })…
+translation = Aistriúchán ({ $language })
+stop-reading = Stop ag Léamh
+version-count = { $position } as { $count }
+copy = Cóipeáil
+copy-as = Cóipeáil mar
+copy-markdown = Markdown
+copy-plain-text = Gnáth-théacs
+copy-code = Cód amháin
+stats-tokens = { $count ->
+    [one] { $count } comhartha
+    [two] { $count } chomhartha
+    [few] { $count } chomhartha
+    [many] { $count } gcomhartha
+   *[other] { $count } comhartha
+}
+stats-speed = { $speed } comhartha/s
+stats-conversation-total = { $count ->
+    [one] { $count } comhartha sa chomhrá go dtí seo
+    [two] { $count } chomhartha sa chomhrá go dtí seo
+    [few] { $count } chomhartha sa chomhrá go dtí seo
+    [many] { $count } gcomhartha sa chomhrá go dtí seo
+   *[other] { $count } comhartha sa chomhrá go dtí seo
+}
+sources = Foinsí:
+web-sources = Ón ngréasán:
+
+## Fógraí
+
+retry = Bain Triail Eile As
+open-file = Oscail an Comhad
+copy-details = Cóipeáil na Sonraí
+dismiss = Dún
+couldnt-open-file = Níorbh fhéidir { $path } a oscailt: { $error }
+clipboard-error = Níorbh fhéidir an ghearrthaisce a úsáid: { $error }
+recovery-prompt = Níor dúnadh Comhrá i gceart an uair dheireanach. An bhfuil fonn ort do theachtaireacht neamhsheolta agus aon fhreagra a bhí á ghiniúint a aisghabháil?
+restore = Aisghabh
+discard = Caith Uait
+ask-about-clipboard = Ceist a chur faoi seo? “{ $snippet }”
+ask = Fiafraigh
+
+## Fógraí deisce
+
+notification-response-ready = Freagra réidh: { $title }
+notification-generation-failed = Theip ar an nginiúint: { $title }
+notification-open = Oscail
+new-conversation = Comhrá nua
+
+## Leideanna córais
+
+system-prompt = Leid Chórais
+system-prompt-placeholder = Treoracha a leanann an tsamhail don chomhrá ar fad, m.sh. "Freagair mar athbhreithneoir cóid foighneach"
+persona = Pearsa
+start-with-persona = Tosaigh le pearsa
+persona-name = Ainm na pearsan
+save-persona = Sábháil mar Phearsa
+delete-persona = Scrios an Phearsa
+apply = Cuir i bhFeidhm
+
+## Paraiméadair ghiniúna
+
+params = Paraiméadair
+param-temperature = Teocht
+param-top-p = Top P
+param-top-k = Top K
+param-repeat-penalty = Pionós athrá
+param-seed = Síol
+param-num-ctx = Fad an chomhthéacs
+param-default = Réamhshocrú na samhla
+response-language = Freagair i
+any-language = Teanga ar bith
+reset = Athshocraigh
+
+## Knowledge
+
+knowledge = Eolas
+knowledge-explanation = Ceangail fillteán de chomhaid téacs, Markdown nó PDF agus seolfar na sleachta is ábhartha do gach leid in éineacht leis
+knowledge-attached = Bainfear úsáid as an bhfillteán seo i bhfreagraí nuair a bheidh sé innéacsaithe leis an tsamhail leabaithe ó na socruithe
+knowledge-indexed = { $count ->
+    [one] { $count } sliocht innéacsaithe
+    [two] { $count } shliocht innéacsaithe
+    [few] { $count } shliocht innéacsaithe
+   *[other] { $count } sliocht innéacsaithe
+}
+knowledge-folder = Fillteán cáipéisí
+reindex = Innéacsaigh Arís
+detach = Dícheangail
+not-a-folder = ní fillteán é
+related = Gaolmhar
+related-conversations = Comhráite gaolmhara
+find-related = Aimsigh Cinn Ghaolmhara
+finding-related = Do chomhráite á leabú chun cinn ghaolmhara a aimsiú…
+no-related = Níl aon chomhrá eile faoi na rudaí céanna
+related-unsaved = Aimseofar comhráite gaolmhara nuair a bheidh an ceann seo sábháilte
+related-similarity = { $percent }%
+embeddings-playground = Clós Súgartha Leabuithe
+playground-explanation = Scríobh roinnt téacsanna le feiceáil cé chomh cosúil lena chéile a mheasann { $model } iad, ó -1 go 1
+playground-text = Téacs le comparáid
+add-text = Cuir Téacs Leis
+compare = Cuir i gComparáid
+
+## Socruithe
+
+settings = Socruithe
+open-settings-file = Oscail Comhad na Socruithe
+setting-server-url = Freastalaí do chomhráite nua
+saved-servers = Freastalaithe sábháilte, le malartú an chomhrá oscailte eatarthu ón mbarra uirlisí
+server-name = Ainm
+server-username = Ainm úsáideora
+server-password = Pasfhocal
+server-api-key = Eochair API
+server-logins-unencrypted = Sábháiltear pasfhocail agus eochracha API sa chomhad socruithe mar atá siad, gan chriptiú fiú nuair a bhíonn na comhráite criptithe
+server-kind-ollama = Ollama
+server-kind-openai = Comhoiriúnach le OpenAI
+remove = Bain
+add-server = Cuir Freastalaí Leis
+server-connecting = ag ceangal…
+server-connected = ceangailte
+server-unreachable = ní féidir teacht air
+prompt-queued = Sa scuaine le seoladh: { $prompt }
+unqueue-prompt = Cuir in Eagar
+generating-in-background = Freagra á scríobh…
+server-unreachable-banner = Níorbh fhéidir an freastalaí ag { $server } a bhaint amach. Seiceáil go bhfuil sé ar siúl, nó roghnaigh freastalaí eile sna socruithe.
+setting-default-model = Samhail réamhshocraithe
+no-default-model = Roghnaigh ceann ag an tosú
+setting-theme = Téama
+setting-zoom = Zúmáil
+theme-system = Lean an córas (geal nó dorcha)
+theme-custom = Saincheaptha
+palette-background = Cúlra
+palette-text = Téacs
+palette-primary = Aiceann
+palette-success = Rath
+palette-danger = Contúirt
+setting-language = Teanga
+system-language = Teanga an chórais
+setting-conversations-dir = Fillteán na gcomhráite
+setting-show-sidebar = Taispeáin an barra taoibh
+setting-encrypt-conversations = Criptigh na comhráite le pasfhrása
+setting-git-history = Coinnigh gach leagan de na comhráite in git
+setting-watch-clipboard = Tairg ceist a chur faoi théacs a cóipeáladh in aipeanna eile
+setting-minimize-to-tray = Lean ar aghaidh sa tráidire córais nuair a dhúntar an fhuinneog
+setting-quick-chat-shortcut = Aicearra domhanda don chomhrá tapa
+setting-summarize-context = Déan achoimre go huathoibríoch ar na teachtaireachtaí is luaithe nuair atá an fhuinneog chomhthéacs ag líonadh
+setting-include-reasoning = Cuir an réasúnaíocht a scríobhann samhlacha roimh fhreagairt san áireamh agus ag cóipeáil nó ag easpórtáil
+setting-share-url = Óstach le comhráite roinnte a uaslódáil chuige, nó folamh lena sábháil mar chomhaid
+setting-search-engine = Inneall cuardaigh gréasáin
+setting-searxng-url = URL SearxNG
+setting-brave-api-key = Eochair API Brave Search
+setting-tools = Uirlisí ar féidir le samhlacha glaoch orthu, ar fhreastalaithe Ollama
+tool-calculator = Áireamhán
+tool-current-time = An t-am anois
+tool-read-file = Comhaid a léamh
+tool-run-command = Orduithe blaoisce, nuair a dheimhnítear iad
+setting-auto-title = Iarr ar shamhail teideal a chur ar chomhráite tar éis a gcéad fhreagra
+setting-title-model = Samhail do theidil
+conversation-model = Samhail an chomhrá
+setting-embedding-model = Samhail le fillteáin eolais a leabú
+setting-math-renderer = Ordú a thaispeánann cothromóidí mar SVG, m.sh. tex2svg ó mathjax-node-cli, nó folamh chun iad a thaispeáint mar théacs
+setting-markdown-memory-budget = Cuimhne do theachtaireachtaí formáidithe, thar a bhformáidítear arís na cinn is faide ó radharc nuair a scrollaítear ar ais chucu
+megabytes = { $count } MB
+setting-speech-engine = Inneall téacs-go-caint
+setting-voice = Guth
+default-voice = Guth réamhshocraithe espeak-ng
+setting-piper-model = Samhail ghutha Piper
+setting-audio-player = Ordú le fuaim a sheinm
+setting-whisper-model = Samhail whisper.cpp don deachtú
+setting-whisper-command = Ordú whisper.cpp
+setting-recorder = Ordú le taifeadadh ón micreafón
+
+## Próifílí
+
+choose-profile = Roghnaigh Próifíl
+default-profile = Réamhshocrú
+new-profile = Ainm na próifíle nua
+create-profile = Cruthaigh
+
+## Criptiú
+
+unlock-title = Díghlasáil na Comhráite
+unlock-explanation = Tá do chomhráite criptithe. Cuir isteach do phasfhrása chun iad a léamh.
+unlock = Díghlasáil
+encrypt-title = Criptigh na Comhráite
+encrypt-explanation = Roghnaigh pasfhrása chun do chomhráite a chriptiú. Ní féidir é a aisghabháil má dhéanann tú dearmad air, agus beidh teidil na gcomhráite fós le léamh in ainmneacha na gcomhad. Scriosfar na leaganacha roimhe seo atá i stair na gcomhráite, ós rud é nach raibh siad criptithe.
+encrypt = Criptigh
+passphrase = Pasfhrása
+cancel = Cealaigh
+
+## Coimhlintí sioncronaithe
+
+conflict = Athraíodh "{ $title }" in dhá áit ag an am céanna, m.sh. ar dhá ghléas sioncronaithe
+merge-both = Cumaisc an Dá Cheann
+keep-original = Coinnigh an Bunleagan
+keep-copy = Coinnigh an Chóip
+open-copy = Oscail an Chóip
+database-conflict = Shábháil uirlis sioncronaithe cóip de bhunachar sonraí na gcomhráite, "{ $file }", mar athraíodh é ar dhá ghléas ag an am céanna. Ní thaispeántar na comhráite a sábháladh sa chóip.
+open-folder = Oscail an Fillteán
+
+## Stair
+
+history-save-commit = { $count ->
+    [one] Sábháil "{ $title }" le { $count } teachtaireacht
+    [two] Sábháil "{ $title }" le { $count } theachtaireacht
+    [few] Sábháil "{ $title }" le { $count } theachtaireacht
+    [many] Sábháil "{ $title }" le { $count } dteachtaireacht
+   *[other] Sábháil "{ $title }" le { $count } teachtaireacht
+}
+history-restore-commit = Aisghabh "{ $title }" go dtí an leagan ó { $date }
+restore-version = Aisghabh an Leagan Seo
+no-versions = Níl aon leagan den chomhrá seo taifeadta fós
+select-version = Roghnaigh leagan chun réamhamharc a fháil air
+close = Dún
+
+## Bloic chóid
+
+save-code-block = Sábháil Mar…
+open-in-editor = Oscail in Eagarthóir
+save-path = An comhad ina sábhálfar é
+save = Sábháil
+saved-to = Sábháilte i { $path }
+couldnt-open-editor = Níorbh fhéidir eagarthóir a oscailt: { $error }
+
+## Easpórtáil
+
+export = Easpórtáil
+export-tooltip = Sábháil an comhrá mar Markdown, mar leathanach gréasáin is féidir le duine ar bith a oscailt, nó mar PDF
+export-markdown = Markdown
+export-html = Leathanach Gréasáin
+export-pdf = PDF
+share = Roinn
+sharing = Á roinnt…
+share-tooltip = Uaslódáil an comhrá mar leathanach gréasáin agus cóipeáil an nasc chuige
+share-tooltip-no-host = Sábháil an comhrá mar leathanach gréasáin le seoladh ar aghaidh, nó socraigh óstach lena uaslódáil chuige sna socruithe
+share-link-copied = Nasc cóipeáilte: { $link }
+
+## Iompórtáil
+
+import = Iompórtáil
+import-explanation = Tabhair isteach do chomhráite ó easpórtáil conversations.json ChatGPT, ó easpórtáil Open WebUI, ó liosta teachtaireachtaí JSON, nó ó chomhad staire an Ollama CLI
+import-path = An comhad le hiompórtáil
+conversations-imported = { $count ->
+    [one] { $count } comhrá iompórtáilte
+    [two] { $count } chomhrá iompórtáilte
+    [few] { $count } chomhrá iompórtáilte
+    [many] { $count } gcomhrá iompórtáilte
+   *[other] { $count } comhrá iompórtáilte
+}
+
+## Tráidire agus comhrá tapa
+
+quick-chat = Comhrá Tapa
+quick-chat-placeholder = Cuir ceist, ansin brúigh Enter
+tray-show-window = Taispeáin Comhrá
+tray-quit = Scoir
+
+## Tagarmharc
+
+benchmark = Tagarmharc
+benchmark-prompts = Leideanna
+benchmark-prompts-placeholder = Leid amháin ar gach líne
+benchmark-models = Samhlacha
+run-benchmark = Rith
+stop = Stad
+benchmark-running = { $model } á rith, { $remaining } eile le déanamh
+benchmark-model = Samhail
+benchmark-prompt = Leid
+benchmark-time-to-first-token = An Chéad Chomhartha
+benchmark-total-time = Am Iomlán
+benchmark-tokens-per-second = Comharthaí/s
+benchmark-output = Aschur
+export-csv = Easpórtáil CSV
+export-json = Easpórtáil JSON
+batch = Baisc
+batch-file = Nó luchtaigh iad ó chomhad
+batch-file-placeholder = Cosán chuig comhad téacs nó CSV
+batch-csv-column = Colún CSV
+batch-load = Luchtaigh
+batch-loaded = { $count } leid ó { $file }
+run-batch = Rith in aghaidh { $model }
+batch-running = Á rith in aghaidh { $model }, { $done } as { $total } déanta
+
+## Úsáid
+
+usage = Úsáid
+usage-messages-per-day = Teachtaireachtaí le { $days } lá anuas
+usage-day = { $count ->
+    [one] { $date }: { $count } teachtaireacht
+    [two] { $date }: { $count } theachtaireacht
+    [few] { $date }: { $count } theachtaireacht
+    [many] { $date }: { $count } dteachtaireacht
+   *[other] { $date }: { $count } teachtaireacht
+}
+usage-models = Samhlacha
+usage-responses = Freagraí
+usage-generated-tokens = Comharthaí ginte
+usage-average-latency = Meán-am
+usage-no-responses = Níl aon fhreagra fós
+
+## Logaí
+
+open-log-folder = Oscail Fillteán na Logaí
+no-logs = Níor scríobhadh aon logchomhad fós
+couldnt-read-file = Níorbh fhéidir { $path } a léamh: { $error }
+
+## Earráidí
+
+error-backend = Níorbh fhéidir Ollama a bhaint amach: { $details }
+error-stream = Briseadh an sruth freagartha ó Ollama
+error-no-app-dir = Níorbh fhéidir fillteán a aimsiú chun comhaid an aip a stóráil ann
+error-read = Níorbh fhéidir { $path } a oscailt: { $details }
+error-write = Níorbh fhéidir { $path } a shábháil: { $details }
+error-corrupt = Ní comhad comhrá bailí é { $path }: { $details }
+error-locked = Tá na comhráite criptithe agus caithfear iad a dhíghlasáil ar dtús
+error-wrong-passphrase = Ní dhíghlasálann an pasfhrása sin na comhráite
+error-history = Níl stair an chomhrá ar fáil: { $details }
+error-invalid-profile = Ní féidir “{ $name }” a úsáid mar ainm próifíle
+error-speech = Níorbh fhéidir an freagra a léamh os ard: { $details }
+error-dictation = Níorbh fhéidir an deachtú a ghlacadh: { $details }
+error-math = Níorbh fhéidir cothromóid a thaispeáint: { $details }
+error-share = Níorbh fhéidir an comhrá a roinnt: { $details }
+error-web-search = Níorbh fhéidir an gréasán a chuardach: { $details }
+error-screenshot = Níorbh fhéidir gabháil scáileáin a dhéanamh: { $details }
+error-no-model = Níor tugadh aon samhail, tabhair ceann le --model nó socraigh samhail réamhshocraithe
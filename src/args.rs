@@ -0,0 +1,184 @@
+use std::ffi::OsString;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+
+/// Command line arguments for launching the app
+#[derive(Debug)]
+pub struct Args {
+    /// Log at debug level instead of info
+    pub verbose: bool,
+    /// Profile to use, otherwise it's picked on launch if any profiles have been created
+    pub profile: Option<String>,
+    pub command: Command,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    /// Open the app, or hand the activation to the instance that's already open
+    Gui(Activation),
+    /// `comhra ask "question"`, answering on stdout without opening a window
+    Ask {
+        prompt: String,
+        /// Uses the default model from the settings if not given
+        model: Option<String>,
+        /// Name of a conversation to continue, or start if it doesn't exist yet
+        conversation: Option<String>,
+    },
+}
+
+/// What a launch asks the app to open, forwarded to the running instance if there is one
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Activation {
+    /// Text to put in the prompt box, starting a new conversation unless a file is opened
+    pub prompt: Option<String>,
+    /// Conversation files to open, the last one ending up on screen
+    pub files: Vec<PathBuf>,
+    /// Model to select, over the one the opened conversation was last answered with
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Start a new conversation even without a prompt, e.g. from a launcher's "New Chat" action
+    #[serde(default)]
+    pub new: bool,
+    /// Send the prompt as soon as a model is selected instead of just filling it in
+    #[serde(default)]
+    pub send: bool,
+    /// Open the quick chat window, e.g. from a shortcut set up in a desktop without global
+    /// shortcuts support
+    #[serde(default)]
+    pub quick_chat: bool,
+}
+
+impl Args {
+    pub fn parse() -> Result<Self, pico_args::Error> {
+        Self::parse_from(std::env::args_os().skip(1).collect(), piped_input())
+    }
+
+    /// Parses the arguments given after the app's name, with anything piped in on stdin
+    fn parse_from(
+        raw_args: Vec<OsString>,
+        piped_input: Option<String>,
+    ) -> Result<Self, pico_args::Error> {
+        let mut args = pico_args::Arguments::from_vec(raw_args);
+        // Taken out first so its value isn't mistaken for the subcommand
+        let profile = args.opt_value_from_str("--profile")?;
+        let mut raw_args: Vec<OsString> = args.finish();
+        let subcommand_position = raw_args
+            .iter()
+            .position(|arg| !arg.to_string_lossy().starts_with('-'));
+        let is_ask = subcommand_position.is_some_and(|position| raw_args[position] == "ask");
+        if let Some(position) = subcommand_position.filter(|_| is_ask) {
+            raw_args.remove(position);
+        }
+        let mut args = pico_args::Arguments::from_vec(raw_args);
+        let verbose = args.contains(["-v", "--verbose"]);
+        if is_ask {
+            let model = args.opt_value_from_str(["-m", "--model"])?;
+            let conversation = args.opt_value_from_str(["-c", "--conversation"])?;
+            let prompt: String = args.free_from_str()?;
+            return Ok(Self {
+                verbose,
+                profile,
+                command: Command::Ask {
+                    prompt: with_piped_input(Some(prompt), piped_input).unwrap_or_default(),
+                    model,
+                    conversation,
+                },
+            });
+        }
+        let prompt = with_piped_input(args.opt_value_from_str("--prompt")?, piped_input);
+        let model = args.opt_value_from_str(["-m", "--model"])?;
+        let new = args.contains("--new");
+        let send = args.contains("--send");
+        let quick_chat = args.contains("--quick-chat");
+        // `--conversation <path>` is the same as passing the file on its own, but ends up on screen
+        let conversation: Option<PathBuf> = args.opt_value_from_str("--conversation")?;
+        let files = args
+            .finish()
+            .into_iter()
+            .map(PathBuf::from)
+            .chain(conversation)
+            .map(|file| {
+                // The running instance may have a different working directory
+                std::path::absolute(&file).unwrap_or(file)
+            })
+            .collect();
+        Ok(Self {
+            verbose,
+            profile,
+            command: Command::Gui(Activation {
+                prompt,
+                files,
+                model,
+                new,
+                send,
+                quick_chat,
+            }),
+        })
+    }
+}
+
+impl Activation {
+    /// Whether the launch didn't ask for anything to be opened or selected, so the last session
+    /// should be restored
+    pub fn is_empty(&self) -> bool {
+        self.prompt.is_none() && self.files.is_empty() && self.model.is_none() && !self.new
+    }
+}
+
+/// Whatever was piped in on stdin, e.g. the diff in
+/// `git diff | comhra --prompt "write a commit message"`
+fn piped_input() -> Option<String> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return None;
+    }
+    let mut piped_input = String::new();
+    if stdin.lock().read_to_string(&mut piped_input).is_err() || piped_input.trim().is_empty() {
+        return None;
+    }
+    Some(piped_input)
+}
+
+/// Adds what was piped in to the prompt
+fn with_piped_input(prompt: Option<String>, piped_input: Option<String>) -> Option<String> {
+    match (prompt, piped_input) {
+        (Some(prompt), Some(piped_input)) => Some(format!("{prompt}\n\n{piped_input}")),
+        (prompt, piped_input) => prompt.or(piped_input),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], piped_input: Option<&str>) -> Args {
+        let raw_args = args.iter().map(OsString::from).collect();
+        Args::parse_from(raw_args, piped_input.map(str::to_string)).unwrap()
+    }
+
+    #[test]
+    fn the_profile_isnt_taken_for_the_subcommand() {
+        let args = parse(&["--profile", "work", "ask", "-m", "llama3", "Hi"], None);
+        assert_eq!(args.profile.as_deref(), Some("work"));
+        let Command::Ask { prompt, model, .. } = args.command else {
+            panic!("expected ask, got {:?}", args.command);
+        };
+        assert_eq!(prompt, "Hi");
+        assert_eq!(model.as_deref(), Some("llama3"));
+    }
+
+    #[test]
+    fn piped_input_is_added_to_the_prompt() {
+        let args = parse(&["--prompt", "Summarise this", "--send"], Some("diff"));
+        let Command::Gui(activation) = args.command else {
+            panic!("expected the gui, got {:?}", args.command);
+        };
+        assert_eq!(activation.prompt.as_deref(), Some("Summarise this\n\ndiff"));
+        assert!(activation.send);
+        assert!(!activation.is_empty());
+        let Command::Gui(activation) = parse(&[], Some("diff")).command else {
+            panic!("expected the gui");
+        };
+        assert_eq!(activation.prompt.as_deref(), Some("diff"));
+    }
+}
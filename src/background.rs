@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use comhra_core::backend::Backend;
+use comhra_core::knowledge;
+use comhra_core::related::{self, ConversationEmbeddings};
+use comhra_core::storage::{self, ConversationSummary};
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream, StreamExt};
+
+use crate::i18n::{self, tr};
+
+pub type JobId = usize;
+
+/// Work that runs on the background worker instead of competing with the UI
+#[derive(Debug, Clone)]
+pub enum Job {
+    IndexConversations(Vec<PathBuf>),
+    /// Embeds the documents in a conversation's knowledge folder with the embedding model
+    IndexKnowledge {
+        backend: Backend,
+        dir: PathBuf,
+        model: String,
+    },
+    /// Embeds the conversations saved since they were last embedded, to find related ones
+    EmbedConversations {
+        backend: Backend,
+        conversations_dir: PathBuf,
+        /// Every conversation, with when it was last saved
+        conversations: Vec<(PathBuf, SystemTime)>,
+        model: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum JobOutput {
+    /// A summary for each indexed conversation, or `None` if the file no longer exists
+    ConversationIndex(Vec<(PathBuf, Option<ConversationSummary>)>),
+    /// The knowledge folder that was indexed, with how many passages it was split into
+    KnowledgeIndexed(PathBuf, usize),
+    ConversationsEmbedded(ConversationEmbeddings),
+}
+
+#[derive(Debug, Clone)]
+pub enum WorkerEvent {
+    Ready(WorkerHandle),
+    Started(JobId),
+    Progress(JobId, f32),
+    Finished(JobId, Result<JobOutput, String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerHandle(mpsc::UnboundedSender<(JobId, Job)>);
+
+impl WorkerHandle {
+    pub fn queue(&self, id: JobId, job: Job) {
+        let _ = self.0.unbounded_send((id, job));
+    }
+}
+
+/// Progress of a job as shown in the background tasks popover
+pub struct JobStatus {
+    pub id: JobId,
+    pub name: String,
+    pub progress: Option<f32>,
+}
+
+impl Job {
+    pub fn name(&self) -> String {
+        match self {
+            Job::IndexConversations(paths) => {
+                tr!("job-index-conversations", count = paths.len())
+            }
+            Job::IndexKnowledge { dir, .. } => tr!(
+                "job-index-knowledge",
+                folder = dir
+                    .file_name()
+                    .unwrap_or(dir.as_os_str())
+                    .to_string_lossy()
+                    .into_owned()
+            ),
+            Job::EmbedConversations { .. } => tr!("job-embed-conversations"),
+        }
+    }
+
+    async fn run(
+        self,
+        id: JobId,
+        mut output: mpsc::Sender<WorkerEvent>,
+    ) -> Result<JobOutput, String> {
+        match self {
+            Job::IndexConversations(paths) => {
+                let mut index = Vec::with_capacity(paths.len());
+                for (count, path) in paths.iter().enumerate() {
+                    let summary = match storage::load_conversation(path.clone()).await {
+                        Ok(conversation) => Some(ConversationSummary::new(&conversation)),
+                        Err(_) => storage::conversation_exists(path)
+                            .await
+                            .then(|| ConversationSummary::new(&Default::default())),
+                    };
+                    index.push((path.clone(), summary));
+                    let _ = output
+                        .send(WorkerEvent::Progress(
+                            id,
+                            (count + 1) as f32 / paths.len() as f32,
+                        ))
+                        .await;
+                }
+                Ok(JobOutput::ConversationIndex(index))
+            }
+            Job::IndexKnowledge {
+                backend,
+                dir,
+                model,
+            } => {
+                let index = knowledge::index(&backend, &dir, &model, |progress| {
+                    let _ = output.try_send(WorkerEvent::Progress(id, progress));
+                })
+                .await
+                .map_err(|err| i18n::error_message(&err))?;
+                Ok(JobOutput::KnowledgeIndexed(dir, index.chunk_count()))
+            }
+            Job::EmbedConversations {
+                backend,
+                conversations_dir,
+                conversations,
+                model,
+            } => {
+                let embeddings = related::index(
+                    &backend,
+                    &conversations_dir,
+                    conversations,
+                    &model,
+                    |progress| {
+                        let _ = output.try_send(WorkerEvent::Progress(id, progress));
+                    },
+                )
+                .await
+                .map_err(|err| i18n::error_message(&err))?;
+                Ok(JobOutput::ConversationsEmbedded(embeddings))
+            }
+        }
+    }
+}
+
+/// Runs queued jobs one at a time on their own tokio tasks, reporting their progress
+pub fn run_worker() -> impl Stream<Item = WorkerEvent> {
+    iced::stream::channel(100, |mut output| async move {
+        let (sender, mut receiver) = mpsc::unbounded();
+        let _ = output.send(WorkerEvent::Ready(WorkerHandle(sender))).await;
+        while let Some((id, job)) = receiver.next().await {
+            let _ = output.send(WorkerEvent::Started(id)).await;
+            let result = tokio::spawn(job.run(id, output.clone()))
+                .await
+                .unwrap_or_else(|err| Err(tr!("job-crashed", error = err.to_string())));
+            let _ = output.send(WorkerEvent::Finished(id, result)).await;
+        }
+    })
+}
@@ -0,0 +1,101 @@
+use std::io::Write;
+use std::process::ExitCode;
+
+use comhra_core::backend::{Backend, Provider};
+use comhra_core::conversation::Conversation;
+use comhra_core::{crypto, settings, storage, ChatMessage, Error, MessageRole};
+use iced::futures::StreamExt;
+
+use crate::i18n;
+
+/// Environment variable holding the passphrase when conversations are encrypted
+const PASSPHRASE_VARIABLE: &str = "COMHRA_PASSPHRASE";
+
+/// Answers a single prompt on stdout, optionally continuing a saved conversation,
+/// returning the exit code for the process
+pub fn ask(prompt: String, model: Option<String>, conversation: Option<String>) -> ExitCode {
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("Couldn't start the async runtime: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match runtime.block_on(run_ask(prompt, model, conversation)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", i18n::error_message(&err));
+            // The same code as other mistakes in the arguments
+            match err {
+                Error::NoModel => ExitCode::from(2),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+async fn run_ask(
+    prompt: String,
+    model: Option<String>,
+    conversation_name: Option<String>,
+) -> Result<(), Error> {
+    let settings = settings::load().await?;
+    i18n::set_language(settings.language.as_deref());
+    let model_name = model
+        .or(settings.default_model.clone())
+        .ok_or(Error::NoModel)?;
+    let conversation_file = match conversation_name {
+        Some(conversation_name) => Some(
+            settings
+                .conversations_dir()?
+                .join(storage::conversation_file_name(&conversation_name)),
+        ),
+        None => None,
+    };
+    if conversation_file.is_some() && crypto::is_enabled() {
+        let passphrase = std::env::var(PASSPHRASE_VARIABLE).map_err(|_| Error::Locked)?;
+        crypto::unlock(passphrase).await?;
+    }
+    let mut conversation = Conversation::default();
+    if let Some(conversation_file) = conversation_file.as_ref() {
+        if storage::conversation_exists(conversation_file).await {
+            conversation = storage::load_conversation(conversation_file.clone()).await?;
+        }
+    }
+    let backend = Backend::new(&settings.server_for(conversation.server.as_deref()))?;
+    conversation.messages.push(ChatMessage {
+        role: MessageRole::User,
+        content: prompt,
+        images: None,
+    });
+
+    let mut stream = backend
+        .chat_stream(
+            model_name,
+            conversation.messages.clone(),
+            conversation.params,
+        )
+        .await?;
+    let mut response = String::new();
+    let mut stdout = std::io::stdout();
+    while let Some(response_chunk) = stream.next().await {
+        let response_chunk = response_chunk?;
+        print!("{}", response_chunk.content);
+        let _ = stdout.flush();
+        response.push_str(&response_chunk.content);
+    }
+    println!();
+
+    if let Some(conversation_file) = conversation_file {
+        conversation.messages.push(ChatMessage {
+            role: MessageRole::Assistant,
+            content: response,
+            images: None,
+        });
+        storage::save_conversation(conversation_file, conversation).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{CodeBlockKind, Event, Parser, Tag, TagEnd};
+
+/// A code block from a message, for saving or opening it somewhere other than the chat
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    /// The language given after the opening fence, empty if there wasn't one
+    pub language: String,
+    pub code: String,
+}
+
+/// Finds the code blocks that aren't nested in a list, in the order iced's markdown lays them out
+pub fn extract(markdown: &str) -> Vec<CodeBlock> {
+    let mut code_blocks = vec![];
+    let mut list_depth = 0;
+    let mut current_block: Option<CodeBlock> = None;
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::List(_)) => list_depth += 1,
+            Event::End(TagEnd::List(_)) => list_depth -= 1,
+            Event::Start(Tag::CodeBlock(kind)) if list_depth == 0 => {
+                current_block = Some(CodeBlock {
+                    language: match kind {
+                        CodeBlockKind::Fenced(language) => language.to_string(),
+                        CodeBlockKind::Indented => String::new(),
+                    },
+                    code: String::new(),
+                })
+            }
+            Event::Text(text) => {
+                if let Some(code_block) = current_block.as_mut() {
+                    code_block.code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => code_blocks.extend(current_block.take()),
+            _ => {}
+        }
+    }
+    code_blocks
+}
+
+impl CodeBlock {
+    /// The block's language without anything else given after the fence
+    pub fn language_name(&self) -> &str {
+        // Fences can carry more than the language, e.g. "rust,ignore"
+        self.language.split([',', ' ']).next().unwrap_or_default()
+    }
+
+    /// File extension for the block's language, e.g. `rs` for `rust`
+    pub fn extension(&self) -> &str {
+        let language = self.language_name();
+        match language.to_lowercase().as_str() {
+            "rust" => "rs",
+            "python" | "py" => "py",
+            "javascript" | "js" => "js",
+            "typescript" | "ts" => "ts",
+            "bash" | "sh" | "shell" | "zsh" => "sh",
+            "c++" | "cpp" => "cpp",
+            "c#" | "csharp" => "cs",
+            "markdown" | "md" => "md",
+            "yaml" | "yml" => "yaml",
+            "kotlin" => "kt",
+            "ruby" => "rb",
+            "haskell" => "hs",
+            "" | "text" | "plaintext" => "txt",
+            "c" | "go" | "java" | "json" | "toml" | "html" | "css" | "sql" | "lua" | "php"
+            | "swift" | "xml" => language,
+            _ if language.len() <= 4 && language.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                language
+            }
+            _ => "txt",
+        }
+    }
+
+    /// Where to suggest saving the block, in the current directory
+    pub fn suggested_path(&self) -> PathBuf {
+        std::env::current_dir()
+            .unwrap_or_default()
+            .join(format!("code.{}", self.extension()))
+    }
+}
+
+/// Writes the block to a temporary file and opens it with `$VISUAL` or `$EDITOR`, or the
+/// desktop's default app for the file type if neither is set
+pub fn open_in_editor(code_block: &CodeBlock) -> Result<(), String> {
+    let mut hasher = DefaultHasher::new();
+    code_block.code.hash(&mut hasher);
+    let path = std::env::temp_dir().join(format!(
+        "comhra-{:x}.{}",
+        hasher.finish(),
+        code_block.extension()
+    ));
+    std::fs::write(&path, &code_block.code).map_err(|err| err.to_string())?;
+    spawn_editor(&path).map_err(|err| err.to_string())
+}
+
+fn spawn_editor(path: &Path) -> std::io::Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "xdg-open".to_string());
+    // The editor can come with its own arguments, e.g. "code --wait"
+    let mut editor_args = editor.split_whitespace();
+    let program = editor_args.next().unwrap_or("xdg-open");
+    std::process::Command::new(program)
+        .args(editor_args)
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+}
@@ -0,0 +1,311 @@
+//! Everything kept for a conversation, for the open one and each one still being generated for in
+//! the background, and the prompts queued for them until they can be sent
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+use comhra_core::backend::Backend;
+use comhra_core::context::ContextSummary;
+use comhra_core::conversation::{Branches, GenerationParams, Listing, ResponseStats};
+use comhra_core::knowledge::Citation;
+use comhra_core::settings::Server;
+use comhra_core::structured::OutputFormat;
+use comhra_core::tools::{ToolCall, ToolExchange};
+use comhra_core::web_search::WebSource;
+use comhra_core::{ChatMessage, Image, LocalModel};
+use iced::widget::{button, container, row, text};
+use iced::{Center, Element, Length, Task};
+
+use crate::i18n::tr;
+use crate::notifications;
+use crate::{App, ConnectionStatus, Message, ParsedMarkdown, StreamedMarkdown, Translation};
+
+/// A prompt waiting for the server to come back or for the response before it to finish, sent once
+/// it has and its conversation is open or generating in the background
+pub struct QueuedPrompt {
+    pub conversation: Option<PathBuf>,
+    pub content: String,
+    pub images: Vec<Image>,
+}
+
+/// A conversation with everything its responses are written into and it's saved with, kept for
+/// the open one and for each one switched away from while it was generating
+#[derive(Default)]
+pub struct ConversationState {
+    /// The client for the conversation's server
+    pub backend: Backend,
+    /// The server the conversation carries on with, new ones starting on the default one
+    pub server: Server,
+    pub current_model: Option<LocalModel>,
+    /// Where it's saved, or `None` for a new conversation that hasn't been yet
+    pub current_conversation: Option<PathBuf>,
+    /// When the conversation was last loaded or saved, to notice a sync tool changing it
+    pub conversation_modified: Option<SystemTime>,
+    /// Messages in the conversation, with their parsed markdown unless it was unloaded to save
+    /// memory
+    pub chats_list: Vec<(ChatMessage, Option<Arc<ParsedMarkdown>>)>,
+    /// Older messages of the conversation that haven't been parsed and shown yet
+    pub unloaded_chats: Vec<ChatMessage>,
+    pub is_generating: bool,
+    /// When the response being generated was asked for, to only notify about slow ones
+    pub generation_started: Option<Instant>,
+    /// Stops the response being generated
+    pub generation: Option<iced::task::Handle>,
+    pub stream_buffer: String,
+    /// Markdown parsed so far of the response being streamed
+    pub streamed_markdown: StreamedMarkdown,
+    pub has_unsaved_changes: bool,
+    /// Sampling options the conversation's responses are generated with
+    pub generation_params: GenerationParams,
+    /// Statistics of the conversation's responses, by the index of the response in the
+    /// conversation, like everything else kept about its messages
+    pub response_stats: BTreeMap<usize, ResponseStats>,
+    /// Folder the conversation is filed under
+    pub conversation_folder: Option<String>,
+    pub conversation_tags: Vec<String>,
+    pub conversation_listing: Listing,
+    /// Other versions of the conversation from a message on, by the message's index in the
+    /// whole conversation
+    pub branches: BTreeMap<usize, Branches>,
+    /// Folder of documents the conversation's prompts are answered from
+    pub knowledge_dir: Option<PathBuf>,
+    /// Passages each response was given from the knowledge folder
+    pub citations: BTreeMap<usize, Vec<Citation>>,
+    /// Passages found for the response being generated, filed under it once it's finished
+    pub pending_citations: Vec<Citation>,
+    /// Pages from the web each response was given
+    pub web_sources: BTreeMap<usize, Vec<WebSource>>,
+    /// Pages found for the response being generated, filed under it once it's finished
+    pub pending_web_sources: Vec<WebSource>,
+    /// Search the web for each prompt sent, to give the model the results along with it
+    pub search_web: bool,
+    /// The prompt being answered is still being searched for
+    pub is_searching_web: bool,
+    /// Tools the model called while writing each response
+    pub tool_calls: BTreeMap<usize, Vec<ToolExchange>>,
+    /// Tool calls made for the response being generated, sent back with their results until it's
+    /// finished
+    pub pending_tool_exchanges: Vec<ToolExchange>,
+    /// Calls the model has asked for that haven't been run yet, the first waiting for the user to
+    /// confirm it if it needs confirming
+    pub queued_tool_calls: Vec<ToolCall>,
+    /// Schema the responses to the next prompts are asked to match while JSON mode is on, left
+    /// empty for any JSON
+    pub structured_output: Option<String>,
+    /// What the response being generated was asked to be written as, filed under it once it's
+    /// finished
+    pub pending_format: Option<OutputFormat>,
+    /// What each response asked to be written as JSON was asked to be
+    pub formats: BTreeMap<usize, OutputFormat>,
+    /// Objects and arrays folded away in responses shown as JSON, by the hash of the response and
+    /// where they are in it
+    pub collapsed_json: HashSet<(u64, String)>,
+    /// Responses whose reasoning is unfolded, by their index in the conversation, as the response
+    /// being written changes with each chunk
+    pub expanded_reasoning: HashSet<usize>,
+    /// Language the conversation's responses are asked to be written in, by its English name
+    pub response_language: Option<String>,
+    /// Translations asked for of responses, by the hash of the response
+    pub translations: HashMap<u64, Translation>,
+    /// When each of the conversation's messages was sent
+    pub sent_times: BTreeMap<usize, SystemTime>,
+    /// Summary of the conversation's earliest messages, sent in their place
+    pub context_summary: Option<ContextSummary>,
+    pub is_summarizing_context: bool,
+}
+
+impl App {
+    /// Keeps a prompt for the open conversation to be sent once the server can be reached and the
+    /// response before it has finished
+    pub fn queue_prompt(&mut self, content: String, images: Vec<Image>) {
+        self.queued_prompts.push(QueuedPrompt {
+            conversation: self.conversation.current_conversation.clone(),
+            content,
+            images,
+        });
+    }
+
+    /// Takes a queued prompt back out of the queue and into the composer, in front of anything
+    /// that's been written since
+    pub fn unqueue_prompt(&mut self, index: usize) {
+        if index < self.queued_prompts.len() {
+            let queued_prompt = self.queued_prompts.remove(index);
+            let prompt = self.prompt_text();
+            if prompt.trim().is_empty() {
+                self.set_prompt(&queued_prompt.content);
+            } else {
+                self.set_prompt(&format!("{}\n\n{prompt}", queued_prompt.content));
+            }
+            self.attachments.extend(queued_prompt.images);
+        }
+    }
+
+    /// Sends the first prompt queued for the open conversation while the server was away, once it's
+    /// back and nothing else is being generated
+    pub fn send_queued_prompt(&mut self) -> Task<Message> {
+        if self.connection != ConnectionStatus::Connected
+            || self.conversation.current_model.is_none()
+            || self.conversation.is_generating
+        {
+            return Task::none();
+        }
+        let Some(index) = self.queued_prompts.iter().position(|queued_prompt| {
+            queued_prompt.conversation == self.conversation.current_conversation
+        }) else {
+            return Task::none();
+        };
+        let queued_prompt = self.queued_prompts.remove(index);
+        self.send_message(queued_prompt.content, queued_prompt.images)
+    }
+
+    /// Handles a message for a conversation's response, in whichever conversation it's for, open
+    /// or generating in the background
+    pub fn update_in_conversation(
+        &mut self,
+        conversation: PathBuf,
+        message: Message,
+    ) -> Task<Message> {
+        let conversation = self.renamed(conversation);
+        if self.conversation.current_conversation.as_ref() == Some(&conversation) {
+            return self
+                .update(message)
+                .map(move |message| in_conversation(&conversation, message));
+        }
+        // It was closed once its response had finished, so it's loaded again from its
+        // file when it's next opened
+        let Some(mut background) = self.background_conversations.remove(&conversation) else {
+            return Task::none();
+        };
+        // It's handled as if it were open, without scrolling the one that is
+        let is_following_stream = std::mem::replace(&mut self.is_following_stream, false);
+        let toast_count = self.toasts.len();
+        let task = self.in_state(&mut background, |app| app.update(message));
+        self.is_following_stream = is_following_stream;
+        // Retrying a response that failed retries it in its own conversation
+        for toast in self.toasts.iter_mut().skip(toast_count) {
+            for (_label, action) in &mut toast.actions {
+                *action = in_conversation(&conversation, action.clone());
+            }
+        }
+        self.background_conversations
+            .insert(conversation.clone(), background);
+        task.map(move |message| in_conversation(&conversation, message))
+    }
+
+    /// Keeps the response being generated for the open conversation going while another one's
+    /// opened, leaving an empty conversation with the same model, server and toggles in its place
+    pub fn send_to_background(&mut self) {
+        let Some(conversation) = self
+            .conversation
+            .current_conversation
+            .clone()
+            .filter(|_| self.conversation.is_generating)
+        else {
+            return;
+        };
+        let open = ConversationState {
+            current_model: self.conversation.current_model.clone(),
+            backend: self.conversation.backend.clone(),
+            server: self.conversation.server.clone(),
+            search_web: self.conversation.search_web,
+            structured_output: self.conversation.structured_output.clone(),
+            ..ConversationState::default()
+        };
+        let background = std::mem::replace(&mut self.conversation, open);
+        self.background_conversations
+            .insert(conversation, background);
+    }
+
+    /// Handles something for a conversation other than the open one, with its state in place of
+    /// the open one's until it's done
+    pub fn in_state<T>(
+        &mut self,
+        state: &mut ConversationState,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        std::mem::swap(&mut self.conversation, state);
+        let result = f(self);
+        std::mem::swap(&mut self.conversation, state);
+        result
+    }
+
+    /// The path a conversation has now, following it through being renamed
+    fn renamed(&self, conversation: PathBuf) -> PathBuf {
+        let mut conversation = conversation;
+        // Bounded, as a name can be taken again by another conversation once it's renamed
+        for _ in 0..self.renamed_conversations.len() {
+            let is_known = self.conversation.current_conversation.as_ref() == Some(&conversation)
+                || self.background_conversations.contains_key(&conversation);
+            match self.renamed_conversations.get(&conversation) {
+                Some(new_path) if !is_known => conversation = new_path.clone(),
+                _ => break,
+            }
+        }
+        conversation
+    }
+
+    /// Whether a response is being generated for the conversation, open or in the background
+    pub fn is_generating_in(&self, conversation: &Path) -> bool {
+        match self.background_conversations.get(conversation) {
+            Some(background) => background.is_generating,
+            None => {
+                self.conversation.is_generating
+                    && self.conversation.current_conversation.as_deref() == Some(conversation)
+            }
+        }
+    }
+
+    /// The prompts queued for the open conversation, each with a button to take it back out
+    pub fn view_queued_prompts(&self) -> impl Iterator<Item = Element<'_, Message>> {
+        self.queued_prompts
+            .iter()
+            .enumerate()
+            .filter(|(_index, queued_prompt)| {
+                queued_prompt.conversation == self.conversation.current_conversation
+            })
+            .map(|(index, queued_prompt)| {
+                container(
+                    row![
+                        text(tr!(
+                            "prompt-queued",
+                            prompt = notifications::snippet(&queued_prompt.content)
+                        ))
+                        .size(14)
+                        .width(Length::Fill),
+                        button(text(tr!("unqueue-prompt")).size(14))
+                            .on_press(Message::UnqueuePrompt(index))
+                            .style(button::secondary),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                )
+                .padding([5, 10])
+                .style(container::rounded_box)
+                .into()
+            })
+    }
+}
+
+/// Marks a message about the response being generated for a conversation to be handled by that
+/// conversation, so it still gets there once another's been opened
+pub fn in_conversation(conversation: &Path, message: Message) -> Message {
+    match message {
+        Message::StartGeneration
+        | Message::ToggleIsGenerating
+        | Message::HandleStreamResponse(_)
+        | Message::FlushStreamBuffer
+        | Message::SourcesFound(..)
+        | Message::ContextSummarized(_)
+        | Message::ToolCallsRequested(_)
+        | Message::ToolCallFinished(_)
+        | Message::GenerationFailed(_)
+        | Message::RetryGeneration
+        | Message::SaveConversation
+        | Message::GenerationFinished => {
+            Message::InConversation(conversation.to_path_buf(), Box::new(message))
+        }
+        message => message,
+    }
+}
@@ -0,0 +1,200 @@
+//! The desktop's own dialogs for choosing where to save a file or which file to open
+//!
+//! They're asked for through the XDG desktop portal's file chooser, so there are none on other
+//! platforms or on desktops without the portal, and the path's typed in instead.
+
+use std::path::PathBuf;
+
+/// What came of asking the desktop for a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Picked {
+    File(PathBuf),
+    Cancelled,
+    /// There's no dialog to ask with, so the path needs typing in
+    NoDialog,
+}
+
+/// Asks where to save a file, starting from the suggested path
+#[cfg(target_os = "linux")]
+pub async fn save_file(title: String, suggested: PathBuf) -> Picked {
+    linux::save_file(&title, &suggested)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::warn!("Couldn't show the save dialog: {err}");
+            Picked::NoDialog
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn save_file(_title: String, _suggested: PathBuf) -> Picked {
+    Picked::NoDialog
+}
+
+/// Asks for a file to open, of one of the MIME types, e.g. `image/png`
+#[cfg(target_os = "linux")]
+pub async fn open_file(title: String, mime_types: &'static [&'static str]) -> Picked {
+    linux::open_file(&title, mime_types)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::warn!("Couldn't show the open dialog: {err}");
+            Picked::NoDialog
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn open_file(_title: String, _mime_types: &'static [&'static str]) -> Picked {
+    Picked::NoDialog
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use iced::futures::StreamExt;
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+    use super::Picked;
+
+    /// Counts the dialogs asked for, so each request's token is different
+    static REQUEST_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[zbus::proxy(
+        interface = "org.freedesktop.portal.FileChooser",
+        default_service = "org.freedesktop.portal.Desktop",
+        default_path = "/org/freedesktop/portal/desktop"
+    )]
+    trait FileChooser {
+        fn open_file(
+            &self,
+            parent_window: &str,
+            title: &str,
+            options: HashMap<&str, Value<'_>>,
+        ) -> zbus::Result<OwnedObjectPath>;
+
+        fn save_file(
+            &self,
+            parent_window: &str,
+            title: &str,
+            options: HashMap<&str, Value<'_>>,
+        ) -> zbus::Result<OwnedObjectPath>;
+    }
+
+    enum Dialog {
+        Open,
+        Save,
+    }
+
+    pub async fn save_file(title: &str, suggested: &Path) -> zbus::Result<Picked> {
+        let mut options = HashMap::new();
+        if let Some(name) = suggested.file_name().and_then(|name| name.to_str()) {
+            options.insert("current_name", Value::from(name));
+        }
+        if let Some(folder) = suggested
+            .parent()
+            .filter(|folder| !folder.as_os_str().is_empty())
+        {
+            // The portal takes the folder as a null-terminated byte string
+            let mut folder = folder.as_os_str().as_bytes().to_vec();
+            folder.push(0);
+            options.insert("current_folder", Value::from(folder));
+        }
+        pick(Dialog::Save, title, options).await
+    }
+
+    pub async fn open_file(title: &str, mime_types: &[&str]) -> zbus::Result<Picked> {
+        // Filters are by name, with each pattern's kind, 1 being a MIME type
+        let patterns: Vec<(u32, &str)> =
+            mime_types.iter().map(|mime_type| (1, *mime_type)).collect();
+        let filters = vec![(title, patterns)];
+        pick(
+            Dialog::Open,
+            title,
+            HashMap::from([("filters", Value::from(filters))]),
+        )
+        .await
+    }
+
+    /// Shows the dialog and waits for the file picked in it
+    async fn pick(
+        dialog: Dialog,
+        title: &str,
+        mut options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<Picked> {
+        let connection = zbus::Connection::session().await?;
+        let portal = FileChooserProxy::new(&connection).await?;
+        // The portal names the request after the connection and a token of the app's choosing
+        let sender = connection
+            .unique_name()
+            .map(|name| name.trim_start_matches(':').replace('.', "_"))
+            .unwrap_or_default();
+        let token = format!(
+            "comhra{}_{}",
+            std::process::id(),
+            REQUEST_COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let request = zbus::Proxy::new(
+            &connection,
+            "org.freedesktop.portal.Desktop",
+            format!("/org/freedesktop/portal/desktop/request/{sender}/{token}"),
+            "org.freedesktop.portal.Request",
+        )
+        .await?;
+        // Subscribed before asking so the answer can't be missed
+        let mut responses = request.receive_signal("Response").await?;
+        options.insert("handle_token", Value::from(token.as_str()));
+        match dialog {
+            Dialog::Open => portal.open_file("", title, options).await?,
+            Dialog::Save => portal.save_file("", title, options).await?,
+        };
+        let (response, mut results): (u32, HashMap<String, OwnedValue>) =
+            match responses.next().await {
+                Some(message) => message.body().deserialize()?,
+                None => return Ok(Picked::Cancelled),
+            };
+        match response {
+            0 => {}
+            1 => return Ok(Picked::Cancelled),
+            _ => {
+                return Err(zbus::Error::Failure(
+                    "the file chooser closed without a file".to_string(),
+                ))
+            }
+        }
+        let uris = match results.remove("uris") {
+            Some(uris) => Vec::<String>::try_from(uris)?,
+            None => vec![],
+        };
+        Ok(uris
+            .first()
+            .and_then(|uri| file_path(uri))
+            .map_or(Picked::Cancelled, Picked::File))
+    }
+
+    /// The path of a `file://` URI, with its percent-encoded bytes decoded
+    fn file_path(uri: &str) -> Option<PathBuf> {
+        let mut rest = uri.strip_prefix("file://")?.as_bytes();
+        let mut path = Vec::with_capacity(rest.len());
+        while let Some((&byte, after)) = rest.split_first() {
+            let escaped = after
+                .get(..2)
+                .filter(|_| byte == b'%')
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escaped {
+                Some(escaped) => {
+                    path.push(escaped);
+                    rest = &after[2..];
+                }
+                None => {
+                    path.push(byte);
+                    rest = after;
+                }
+            }
+        }
+        Some(PathBuf::from(OsString::from_vec(path)))
+    }
+}
@@ -0,0 +1,175 @@
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use comhra_core::time::{self, Ago};
+use comhra_core::Error;
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+/// Translations of the UI by language code, with the language's own name for it, English first
+/// as it's used for anything missing
+const LANGUAGES: [(&str, &str, &str); 2] = [
+    ("en", "English", include_str!("../locales/en.ftl")),
+    ("ga", "Gaeilge", include_str!("../locales/ga.ftl")),
+];
+
+/// The chosen language's bundle followed by the English one
+static BUNDLES: RwLock<Vec<FluentBundle<FluentResource>>> = RwLock::new(Vec::new());
+
+/// Looks up a translation by its id in the UI's language, with named arguments if it takes any
+///
+/// `tr!("tasks-button", count = jobs.len())`
+macro_rules! tr {
+    ($id:literal) => {
+        $crate::i18n::translate($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::i18n::translate($id, Some(&args))
+    }};
+}
+pub(crate) use tr;
+
+fn bundle(language: &str, ftl: &str) -> FluentBundle<FluentResource> {
+    let language_id: LanguageIdentifier = language.parse().unwrap_or_default();
+    let mut bundle = FluentBundle::new_concurrent(vec![language_id]);
+    // Unicode isolation marks around arguments show up as boxes in iced's text rendering
+    bundle.set_use_isolating(false);
+    let resource = FluentResource::try_new(ftl.to_string()).unwrap_or_else(|(resource, errors)| {
+        tracing::warn!("Errors in the {language} translation: {errors:?}");
+        resource
+    });
+    if let Err(errors) = bundle.add_resource(resource) {
+        tracing::warn!("Errors in the {language} translation: {errors:?}");
+    }
+    bundle
+}
+
+/// Switches the UI to a language code from the settings, or the system's language if `None`,
+/// falling back to English if there's no translation for it
+pub fn set_language(language: Option<&str>) {
+    let system_locale = sys_locale::get_locale();
+    let requested = language.or(system_locale.as_deref()).unwrap_or("en");
+    // Locales like `ga-IE` or `ga_IE.UTF-8` are matched by their language alone
+    let requested = requested
+        .split(['-', '_', '.'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let mut bundles: Vec<FluentBundle<FluentResource>> = LANGUAGES
+        .iter()
+        .filter(|(code, _name, _ftl)| *code == requested && *code != "en")
+        .map(|(code, _name, ftl)| bundle(code, ftl))
+        .collect();
+    bundles.push(bundle(LANGUAGES[0].0, LANGUAGES[0].2));
+    if let Ok(mut current_bundles) = BUNDLES.write() {
+        *current_bundles = bundles;
+    }
+}
+
+/// Codes and names of the languages the UI is translated into
+pub fn languages() -> impl Iterator<Item = (&'static str, &'static str)> {
+    LANGUAGES.iter().map(|(code, name, _ftl)| (*code, *name))
+}
+
+pub fn translate(id: &str, args: Option<&FluentArgs>) -> String {
+    let Ok(bundles) = BUNDLES.read() else {
+        return id.to_string();
+    };
+    for bundle in bundles.iter() {
+        let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+            continue;
+        };
+        let mut errors = vec![];
+        let translation = bundle.format_pattern(pattern, args, &mut errors);
+        if !errors.is_empty() {
+            tracing::debug!("Errors formatting {id}: {errors:?}");
+        }
+        return translation.into_owned();
+    }
+    id.to_string()
+}
+
+/// How long ago something happened, e.g. "2 h ago", or the date if it was over a week ago
+pub fn time_ago(when: SystemTime) -> String {
+    match time::ago(when, SystemTime::now()) {
+        Ago::JustNow => tr!("time-just-now"),
+        Ago::Minutes(count) => tr!("time-minutes-ago", count = count),
+        Ago::Hours(count) => tr!("time-hours-ago", count = count),
+        Ago::Yesterday => tr!("time-yesterday"),
+        Ago::Days(count) => tr!("time-days-ago", count = count),
+        Ago::On(date) => date,
+    }
+}
+
+/// The error in the UI's language
+pub fn error_message(err: &Error) -> String {
+    match err {
+        Error::Backend(details) => tr!("error-backend", details = details.as_str()),
+        Error::Stream => tr!("error-stream"),
+        Error::NoAppDir => tr!("error-no-app-dir"),
+        Error::Read { path, message } => tr!(
+            "error-read",
+            path = path.display().to_string(),
+            details = message.as_str()
+        ),
+        Error::Write { path, message } => tr!(
+            "error-write",
+            path = path.display().to_string(),
+            details = message.as_str()
+        ),
+        Error::Corrupt { path, message } => tr!(
+            "error-corrupt",
+            path = path.display().to_string(),
+            details = message.as_str()
+        ),
+        Error::Locked => tr!("error-locked"),
+        Error::WrongPassphrase => tr!("error-wrong-passphrase"),
+        Error::History(details) => tr!("error-history", details = details.as_str()),
+        Error::InvalidProfile(name) => tr!("error-invalid-profile", name = name.as_str()),
+        Error::Speech(details) => tr!("error-speech", details = details.as_str()),
+        Error::Dictation(details) => tr!("error-dictation", details = details.as_str()),
+        Error::Math(details) => tr!("error-math", details = details.as_str()),
+        Error::Share(details) => tr!("error-share", details = details.as_str()),
+        Error::WebSearch(details) => tr!("error-web-search", details = details.as_str()),
+        Error::Screenshot(details) => tr!("error-screenshot", details = details.as_str()),
+        Error::NoModel => tr!("error-no-model"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_translation_parses_and_has_every_message() {
+        let (_code, _name, english) = LANGUAGES[0];
+        let ids: Vec<&str> = english
+            .lines()
+            .filter(|line| line.starts_with(|c: char| c.is_ascii_lowercase()))
+            .filter_map(|line| Some(line.split_once('=')?.0.trim()))
+            .collect();
+        assert!(ids.contains(&"encrypt-title"));
+        for (code, _name, ftl) in LANGUAGES {
+            assert!(
+                FluentResource::try_new(ftl.to_string()).is_ok(),
+                "{code} has errors"
+            );
+            let bundle = bundle(code, ftl);
+            for id in &ids {
+                assert!(bundle.has_message(id), "{code} is missing {id}");
+            }
+        }
+    }
+
+    #[test]
+    fn locales_are_matched_by_their_language() {
+        set_language(Some("ga_IE.UTF-8"));
+        assert_eq!(translate("encrypt", None), "Criptigh");
+        set_language(Some("xx"));
+        assert_eq!(translate("encrypt", None), "Encrypt");
+        assert_eq!(translate("no-such-message", None), "no-such-message");
+    }
+}
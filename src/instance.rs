@@ -0,0 +1,150 @@
+//! Local socket that later launches and external tools use to drive the running instance
+//!
+//! A client connects, writes one JSON [`Request`], shuts down its write half and reads one JSON
+//! [`Response`] back, e.g. `{"method":"NewPromptFromText","params":"Summarise this"}`.
+
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use comhra_core::{profile, storage};
+use iced::futures::channel::{mpsc, oneshot};
+use iced::futures::{SinkExt, Stream};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixListener;
+
+use crate::args::Activation;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params")]
+pub enum Request {
+    /// Sent by a later launch of the app, with the prompt and files it was given
+    Activate(Activation),
+    OpenConversation(PathBuf),
+    /// Starts a new conversation and sends the text if a model is selected
+    NewPromptFromText(String),
+    GetLastResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", content = "value")]
+pub enum Response {
+    Done,
+    /// The latest assistant message in the open conversation, if there is one
+    LastResponse(Option<String>),
+}
+
+/// Sends the app's answer back to whoever made the request
+#[derive(Debug, Clone)]
+pub struct Responder(Arc<Mutex<Option<oneshot::Sender<Response>>>>);
+
+impl Responder {
+    /// Only the first response is sent, later ones are ignored
+    pub fn respond(&self, response: Response) {
+        if let Some(sender) = self.0.lock().ok().and_then(|mut sender| sender.take()) {
+            let _ = sender.send(response);
+        }
+    }
+}
+
+fn socket_path() -> Option<PathBuf> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .or_else(|| storage::data_dir().ok())?;
+    // Each profile has its own instance
+    Some(runtime_dir.join(match profile::current() {
+        Some(name) => format!("comhra-{name}.sock"),
+        None => "comhra.sock".to_string(),
+    }))
+}
+
+/// Sends a request to the running instance, returning `None` if there isn't one
+pub fn send(request: &Request) -> Option<Response> {
+    let mut stream = UnixStream::connect(socket_path()?).ok()?;
+    let request_json = serde_json::to_vec(request).ok()?;
+    stream.write_all(&request_json).ok()?;
+    stream.shutdown(Shutdown::Write).ok()?;
+    let mut response_json = vec![];
+    stream.read_to_end(&mut response_json).ok()?;
+    serde_json::from_slice(&response_json).ok()
+}
+
+/// Hands the activation to an instance that's already running, returning whether there was one
+pub fn forward(activation: &Activation) -> bool {
+    send(&Request::Activate(activation.clone())).is_some()
+}
+
+/// Listens for requests from later launches and external tools
+pub fn listen() -> impl Stream<Item = (Request, Responder)> {
+    iced::stream::channel(10, |output| async move {
+        let Some(path) = socket_path() else {
+            return;
+        };
+        // Nothing answered on the socket on launch, so anything left there is from a crashed instance
+        let _ = std::fs::remove_file(&path);
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                tracing::warn!(
+                    "Couldn't listen for other launches on {}: {err}",
+                    path.display()
+                );
+                return;
+            }
+        };
+        while let Ok((stream, _address)) = listener.accept().await {
+            // Answered on its own task so a client that never finishes writing can't block others
+            tokio::spawn(handle_connection(stream, output.clone()));
+        }
+    })
+}
+
+async fn handle_connection(
+    mut stream: tokio::net::UnixStream,
+    mut output: mpsc::Sender<(Request, Responder)>,
+) {
+    let mut request_json = vec![];
+    if stream.read_to_end(&mut request_json).await.is_err() {
+        return;
+    }
+    let request = match serde_json::from_slice(&request_json) {
+        Ok(request) => request,
+        Err(err) => {
+            tracing::warn!("Ignoring a malformed request: {err}");
+            return;
+        }
+    };
+    let (sender, receiver) = oneshot::channel();
+    let responder = Responder(Arc::new(Mutex::new(Some(sender))));
+    if output.send((request, responder)).await.is_err() {
+        return;
+    }
+    let Ok(response) = receiver.await else {
+        return;
+    };
+    if let Ok(response_json) = serde_json::to_vec(&response) {
+        let _ = stream.write_all(&response_json).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_read_as_documented() {
+        let request: Request =
+            serde_json::from_str(r#"{"method":"NewPromptFromText","params":"Summarise this"}"#)
+                .unwrap();
+        assert!(matches!(request, Request::NewPromptFromText(text) if text == "Summarise this"));
+        let request: Request = serde_json::from_str(r#"{"method":"GetLastResponse"}"#).unwrap();
+        assert!(matches!(request, Request::GetLastResponse));
+        assert_eq!(
+            serde_json::to_string(&Response::LastResponse(Some("Hi".to_string()))).unwrap(),
+            r#"{"result":"LastResponse","value":"Hi"}"#
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+
+use comhra_core::storage;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::EnvFilter;
+
+use crate::i18n::tr;
+
+/// Number of daily log files kept before the oldest is deleted
+const MAX_LOG_FILES: usize = 7;
+
+pub fn logs_dir() -> Option<PathBuf> {
+    storage::data_dir()
+        .ok()
+        .map(|data_dir| data_dir.join("logs/"))
+}
+
+/// Starts writing logs to a daily rotated file, returning the guard that flushes it when dropped
+///
+/// `RUST_LOG` overrides the level, otherwise it's debug when verbose and info when not.
+pub fn init(verbose: bool) -> Option<WorkerGuard> {
+    let logs_dir = logs_dir()?;
+    std::fs::create_dir_all(&logs_dir).ok()?;
+    let file_appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .max_log_files(MAX_LOG_FILES)
+        .filename_prefix("comhra")
+        .filename_suffix("log")
+        .build(logs_dir)
+        .ok()?;
+    let (writer, guard) = tracing_appender::non_blocking(file_appender);
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        EnvFilter::new(if verbose {
+            "comhra_iced=debug,comhra_core=debug"
+        } else {
+            "comhra_iced=info,comhra_core=info"
+        })
+    });
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_ansi(false)
+        .init();
+    Some(guard)
+}
+
+/// Reads the most recently written log file
+pub async fn read_latest_log(logs_dir: PathBuf) -> Result<String, String> {
+    let latest_log = latest_log_file(&logs_dir).ok_or_else(|| tr!("no-logs"))?;
+    tokio::fs::read_to_string(&latest_log).await.map_err(|err| {
+        tr!(
+            "couldnt-read-file",
+            path = latest_log.display().to_string(),
+            error = err.to_string()
+        )
+    })
+}
+
+fn latest_log_file(logs_dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(logs_dir)
+        .ok()?
+        .filter_map(|read_dir| read_dir.ok())
+        .filter_map(|dir_entry| {
+            let modified = dir_entry.metadata().ok()?.modified().ok()?;
+            Some((dir_entry.path(), modified))
+        })
+        .max_by_key(|(_path, modified)| *modified)
+        .map(|(path, _modified)| path)
+}
@@ -1,424 +1,9437 @@
-use std::fs;
-use std::os::linux::fs::MetadataExt;
-use std::path::PathBuf;
+mod args;
+mod background;
+mod cli;
+mod code_blocks;
+mod conversations;
+mod dialogs;
+mod i18n;
+mod instance;
+mod logging;
+mod notifications;
+mod shortcuts;
+mod tray;
+mod windows;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use arboard::Clipboard;
+use args::{Activation, Args, Command};
+use background::{Job, JobId, JobOutput, JobStatus, WorkerEvent, WorkerHandle};
+use code_blocks::CodeBlock;
+use comhra_core::backend::{Backend, Provider, ResponseChunk, ResponseStream};
+use comhra_core::batch::{self, BatchResult};
+use comhra_core::benchmark::{self, BenchmarkResult};
+use comhra_core::composer::{self, EditRun, PromptHistory, UndoHistory};
+use comhra_core::context::{self, ContextSummary};
+use comhra_core::conversation::{
+    self, Branch, Conversation, GenerationParams, Listing, ResponseStats,
+};
+use comhra_core::copy::{self, CopyFormat};
+use comhra_core::crypto;
+use comhra_core::dictation;
+use comhra_core::export;
+use comhra_core::files::{self, FileAttachment};
+use comhra_core::history::{self, Version};
+use comhra_core::images;
+use comhra_core::import;
+use comhra_core::knowledge::{self, Citation};
+use comhra_core::markdown_cache::{self, Lru};
+use comhra_core::math;
+use comhra_core::modelfile;
+use comhra_core::models::{self, CreateModelStatus, ModelDetails, ModelFamily, PullModelStatus};
+use comhra_core::personas::{self, Persona};
+use comhra_core::profile;
+use comhra_core::reasoning;
+use comhra_core::recovery::{self, RecoveryState};
+use comhra_core::related::{self, ConversationEmbeddings};
+use comhra_core::screenshot;
+use comhra_core::search::SearchIndex;
+use comhra_core::session::{self, Session};
+use comhra_core::settings::{
+    self, CustomPalette, Dictation, SearchEngine, Server, ServerKind, Settings, Speech, TtsEngine,
+    WebSearch,
+};
+use comhra_core::share;
+use comhra_core::speech::{self, Voice};
+use comhra_core::storage::{self, Conflict, ConversationSummary, Resolution, SaveOutcome};
+use comhra_core::streamed;
+use comhra_core::structured::{self, OutputFormat};
+use comhra_core::templates::{self, Template};
+use comhra_core::time;
+use comhra_core::title;
+use comhra_core::tools::{self, Tool, ToolCall, ToolExchange};
+use comhra_core::translation::{self, Language};
+use comhra_core::usage::{self, Usage};
+use comhra_core::web_search::{self, WebSource};
+use comhra_core::{ChatMessage, Error, Image, LocalModel, MessageRole};
+use conversations::{in_conversation, ConversationState, QueuedPrompt};
+use dialogs::Picked;
+use i18n::tr;
+use iced::futures::channel::oneshot;
+use iced::futures::{stream, Stream, StreamExt};
+use iced::keyboard::{key, Key, Modifiers};
 use iced::widget::svg::Handle;
+use iced::widget::text_editor::{Binding, Edit, KeyPress, Motion};
 use iced::widget::{
-    button, column, container, markdown, row, scrollable, text, text_input, Row, Space, Svg,
-    Tooltip,
+    button, checkbox, column, container, markdown, pick_list, progress_bar, row, scrollable,
+    slider, stack, text, text_editor, text_input, Column, Row, Space, Svg, Tooltip,
 };
-use iced::{Center, Element, Length, Subscription, Task, Theme};
+use iced::{color, Center, Color, Element, Length, Subscription, Task, Theme};
 use iced_aw::Spinner;
-use ollama_rs::generation::chat::request::ChatMessageRequest;
-use ollama_rs::generation::chat::{ChatMessage, MessageRole};
-use ollama_rs::models::LocalModel;
-use ollama_rs::Ollama;
+use instance::{Request, Responder, Response};
+use notify::{Event, RecursiveMode, Watcher};
+use windows::Windows;
 
-pub fn main() -> iced::Result {
-    iced::application("Comhrá", App::update, App::view)
-        .subscription(App::subscription)
-        .theme(App::theme)
-        .run_with(App::new)
+pub fn main() -> ExitCode {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::from(2);
+        }
+    };
+    // Profiles are only picked in the window, so the command line and later launches without
+    // `--profile` use the default one
+    let choose_profile = match args.profile {
+        Some(name) => {
+            if let Err(err) = profile::select(Some(name)) {
+                eprintln!("{err}");
+                return ExitCode::from(2);
+            }
+            false
+        }
+        None => {
+            matches!(args.command, Command::Gui(_))
+                && profile::list().is_ok_and(|profiles| !profiles.is_empty())
+        }
+    };
+    let _log_guard = logging::init(args.verbose);
+    i18n::set_language(None);
+    let activation = match args.command {
+        Command::Gui(activation) => activation,
+        Command::Ask {
+            prompt,
+            model,
+            conversation,
+        } => return cli::ask(prompt, model, conversation),
+    };
+    if !choose_profile && instance::forward(&activation) {
+        tracing::info!("Handed the launch over to the instance that's already running");
+        return ExitCode::SUCCESS;
+    }
+    tracing::info!("Starting Comhrá {}", env!("CARGO_PKG_VERSION"));
+    // Returned rather than exiting so the log guard is dropped and its last lines written
+    match iced::daemon(Windows::title, Windows::update, Windows::view)
+        .subscription(Windows::subscription)
+        .theme(Windows::theme)
+        .scale_factor(Windows::scale_factor)
+        .run_with(move || Windows::new(activation, choose_profile))
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            tracing::error!("{err}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
+/// How long changes to a conversation can go unsaved, e.g. while a response is streaming
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How often chunks of a streaming response are added to it, batching those that arrive in
+/// between so its markdown isn't parsed again for each one
+const STREAM_FLUSH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How often the clipboard is checked for newly copied text when watching it
+const CLIPBOARD_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the system's light or dark preference is checked when the theme follows it
+const SYSTEM_THEME_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often unsent prompts and responses being generated are checkpointed for crash recovery
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the server is checked on, to notice it going away or coming back
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of messages parsed at a time when opening or scrolling back through a conversation
+const CONVERSATION_PAGE_SIZE: usize = 30;
+
+/// Number of off-screen messages kept laid out either side of the visible ones
+const VIRTUALIZATION_BUFFER: usize = 5;
+
+/// Number of lines the composer grows to before it scrolls instead
+const COMPOSER_MAX_LINES: usize = 10;
+
+/// Height of the composer once it's grown to its maximum number of lines
+const COMPOSER_MAX_HEIGHT: f32 = 220.0;
+
+/// Size of the thumbnails of images attached to the prompt being written
+const ATTACHMENT_THUMBNAIL_SIZE: u32 = 64;
+
+/// Size of the thumbnails of images sent with a message in the chat
+const CHAT_THUMBNAIL_SIZE: u32 = 160;
+
+/// Distance from the bottom of the chat, in pixels, that still counts as being scrolled to it
+const CHAT_BOTTOM_THRESHOLD: f32 = 40.0;
+
 #[derive(Default)]
 struct App {
-    ollama: Ollama,
-    prompt: String,
-    current_model: Option<LocalModel>,
-    current_conversation: Option<PathBuf>,
-    chats_list: Vec<(ChatMessage, Vec<markdown::Item>)>,
+    /// The open conversation, with everything its responses are written into and it's saved with
+    conversation: ConversationState,
+    prompt: text_editor::Content,
+    /// Changes to the prompt being written, to undo and redo
+    prompt_undo: UndoHistory,
+    /// Prompts sent before, to go back through with the arrow keys
+    prompt_history: PromptHistory,
+    /// Unsent prompts of conversations other than the open one, `None` being a new conversation
+    drafts: HashMap<Option<PathBuf>, String>,
     models_list: Vec<LocalModel>,
     conversations_list: Vec<PathBuf>,
+    /// Where conversations are stored, once the settings have been loaded
+    conversations_dir: Option<PathBuf>,
     show_sidebar: bool,
-    is_generating: bool,
+    settings: Settings,
+    /// The settings as last read from the settings file
+    loaded_settings: Option<Settings>,
+    chat_viewport: Option<(f32, f32)>,
+    /// Scroll position in the chat, from 0 at the top to 1 at the bottom
+    chat_scroll_offset: f32,
+    /// Whether the system is set to a dark theme, for the theme that follows it
+    is_system_dark: bool,
+    /// Whether the chat is scrolled to the newest message, or close enough to it
+    is_chat_at_bottom: bool,
+    /// Whether the chat keeps scrolling down to show the response as it streams in, which stops
+    /// once it's scrolled up and starts again once it's back at the bottom
+    is_following_stream: bool,
+    /// Where to scroll the chat once enough of the conversation restored from the last session has
+    /// loaded, as the number of messages loaded and the scroll offset
+    pending_scroll: Option<(usize, f32)>,
+    /// The model selected in the last session, preferred over the default model
+    session_model: Option<String>,
+    /// Model the open conversation was last answered with, waiting to be selected until the
+    /// server's models are listed or it's been pulled
+    pending_model: Option<String>,
+    /// Model the app was launched with, selected once the conversation it opened is loaded
+    launch_model: Option<String>,
+    /// Whether the sidebar was open in the last session, preferred over the setting on launch
+    session_sidebar: Option<bool>,
+    toasts: Vec<Toast>,
+    clipboard: Option<Clipboard>,
+    /// Contents of the latest log file while the logs screen is open
+    log_view: Option<String>,
+    /// What was last written to the recovery file, so unchanged state isn't written again
+    last_checkpoint: RecoveryState,
+    /// A response recovered after a crash, to be put back once its conversation loads
+    recovered_response: Option<String>,
+    /// Total size in bytes of message content whose parsed markdown is kept in memory
+    markdown_memory_budget: usize,
+    markdown_cache: MarkdownCache,
+    /// Displayed equations rendered to SVG, by a hash of their TeX, with `None` for those still
+    /// being rendered or that couldn't be
+    math_images: HashMap<u64, Option<Handle>>,
+    worker: Option<WorkerHandle>,
+    /// Jobs queued before the background worker started
+    pending_jobs: Vec<(JobId, Job)>,
+    next_job_id: JobId,
+    background_jobs: Vec<JobStatus>,
+    show_background_jobs: bool,
+    conversation_index: HashMap<PathBuf, ConversationSummary>,
+    /// When each listed conversation was last saved, so only those saved since are indexed again
+    conversation_times: HashMap<PathBuf, SystemTime>,
+    /// Responses that finish while the window is in the background raise a desktop notification
+    is_window_focused: bool,
+    /// The window the app is shown in, once it's been opened
+    window: Option<iced::window::Id>,
+    /// Whether this is a conversation opened in a window of its own, which leaves the session,
+    /// settings and everything else shared between windows to the main one
+    detached: bool,
+    /// Set when quitting from the tray, so closing the main window quits rather than hiding it
+    quitting: bool,
+    /// Shown instead of the chat while a passphrase is needed to encrypt or unlock conversations
+    passphrase_screen: Option<PassphraseScreen>,
+    passphrase: String,
+    /// Files and prompt from launching the app, opened once conversations are unlocked
+    pending_activation: Option<Activation>,
+    /// Conversations edited separately on two devices, waiting for the user to pick what to keep
+    conflicts: Vec<Conflict>,
+    /// Copies of the conversations database sync tools made, which can only be warned about
+    database_conflicts: Vec<PathBuf>,
+    /// Earlier versions of the open conversation while browsing its history
+    history_view: Option<HistoryView>,
+    /// Contents being saved to a file, e.g. a code block or an exported conversation, with the
+    /// path typed in for it
+    save_as: Option<(Vec<u8>, String)>,
+    /// Whether the desktop's save dialog is open for them, rather than the path being typed in
+    is_save_dialog_open: bool,
+    /// Whether the open conversation is being uploaded to be shared
+    is_sharing: bool,
+    /// Path typed in for another app's export, while the import panel is open
+    import_panel: Option<String>,
+    /// Whether to send the prompt given on launch as soon as a model is selected
+    send_when_ready: bool,
+    /// The text on the clipboard when it was last checked, so each copy is only offered once
+    last_clipboard_text: Option<String>,
+    /// Shown instead of the chat while comparing models
+    benchmark_view: Option<BenchmarkView>,
+    batch_view: Option<BatchView>,
+    /// Shown instead of the chat once the usage across every conversation has been counted
+    usage_view: Option<Usage>,
+    /// Shown on launch until a profile is picked, if any profiles have been created
+    profile_picker: Option<ProfilePicker>,
+    /// The settings being edited, shown instead of the chat until they're saved or discarded
+    settings_draft: Option<Settings>,
+    /// Whether the Ollama server answered when the models were last listed or it was last checked
+    connection: ConnectionStatus,
+    /// Prompts sent while the server couldn't be reached or a response was being generated, in
+    /// order, each sent once it's back and the response before it has finished
+    queued_prompts: Vec<QueuedPrompt>,
+    /// Conversations switched away from while they were generating, by their path, kept until
+    /// they're opened again
+    background_conversations: HashMap<PathBuf, ConversationState>,
+    /// The paths conversations were renamed to by the ones they had, for what was still being
+    /// generated for them under the old one
+    renamed_conversations: HashMap<PathBuf, PathBuf>,
+    /// Conversations being saved, by their path, so the next save of one waits for the last
+    saves_in_flight: HashMap<PathBuf, SaveInFlight>,
+    personas: Vec<Persona>,
+    templates: Vec<Template>,
+    /// The saved prompt templates, shown above the composer to pick one to insert
+    template_panel: Option<TemplatePanel>,
+    /// The system prompt being edited for the open conversation
+    system_prompt_editor: Option<SystemPromptEditor>,
+    /// The generation params as typed in, while the params panel is open
+    params_panel: Option<ParamsPanel>,
+    /// What's being done to a conversation from its entry in the sidebar
+    sidebar_action: Option<SidebarAction>,
+    /// Text searched for in every conversation, which replaces the sidebar with the results
+    search_query: String,
+    /// Message to scroll to once the conversation opened from a search result has loaded, by its
+    /// index in the conversation
+    pending_jump: Option<usize>,
+    /// A user message being edited to send again, by its index in `chats_list`
+    chat_editor: Option<(usize, text_editor::Content)>,
+    /// Images to send with the prompt being written, for vision models
+    attachments: Vec<Image>,
+    /// Text files whose contents are sent inline with the prompt being written
+    file_attachments: Vec<FileAttachment>,
+    /// Path typed in for an image to attach, when there is no file dialog to pick it with
+    attach_path: Option<String>,
+    /// Parameter counts and quantization of the installed models, by name
+    model_details: HashMap<String, ModelFamily>,
+    /// Name typed in for a model to pull from the registry
+    pull_model_name: String,
+    model_pull: Option<ModelPull>,
+    /// Shown instead of the chat while a model is being derived from an installed one
+    model_builder: Option<ModelBuilder>,
+    /// Installed model waiting for the user to confirm deleting it
+    confirm_delete_model: Option<String>,
+    /// Only conversations with this tag are listed in the sidebar
+    tag_filter: Option<String>,
+    /// Whether the archived conversations are listed in the sidebar
+    show_archived: bool,
+    /// Path typed in for the knowledge folder, while the knowledge panel is open
+    knowledge_panel: Option<String>,
+    /// Number of passages each knowledge folder was split into when it was last indexed
+    indexed_knowledge: HashMap<PathBuf, usize>,
+    /// Whether the conversations related to the open one are shown above the composer
+    show_related: bool,
+    /// Summaries of the conversations embedded to find the ones related to the open one, once
+    /// they've been embedded
+    conversation_embeddings: Option<ConversationEmbeddings>,
+    /// The job embedding the conversations, while it's queued or running
+    embedding_job: Option<JobId>,
+    /// Shown instead of the chat while comparing texts with the embedding model
+    embeddings_playground: Option<EmbeddingsPlayground>,
+    /// The response being read aloud, by its hash, and the handle that stops reading it
+    speaking: Option<(u64, iced::task::Handle)>,
+    /// Voices espeak-ng has installed, listed when the settings are opened
+    voices: Vec<Voice>,
+    /// The prompt being dictated, if the microphone is on or what was said is being transcribed
+    dictation: Option<DictationState>,
+}
+
+/// What a model came back with when asked for a response
+enum Reply {
+    Stream(ResponseStream),
+    /// Tools to run before it carries on with the response
+    ToolCalls(Vec<ToolCall>),
+}
+
+enum DictationState {
+    /// Recording, until something is sent to stop it
+    Listening(oneshot::Sender<()>),
+    Transcribing,
+}
+
+/// A model being downloaded from the Ollama registry
+struct ModelPull {
+    model_name: String,
+    /// What the server says it's doing, e.g. "pulling manifest"
+    status: String,
+    /// How much of the current layer has downloaded, if it's downloading one
+    progress: Option<f32>,
+}
+
+/// Texts being compared by how alike the embedding model finds them
+struct EmbeddingsPlayground {
+    texts: Vec<String>,
+    /// How alike each text is to each of the others, once they've been compared
+    similarities: Option<Vec<Vec<f32>>>,
+    is_comparing: bool,
+}
+
+/// A model being made from an installed one with its own system prompt and params
+struct ModelBuilder {
+    name: String,
+    base: Option<String>,
+    system_prompt: text_editor::Content,
+    /// Params as they're typed, like in the params panel
+    inputs: [String; GenerationParam::ALL.len()],
+    params: GenerationParams,
+    /// What the server says it's doing once it's been asked to create the model, e.g. "writing
+    /// manifest"
+    status: Option<String>,
+}
+
+impl ModelBuilder {
+    fn modelfile(&self) -> String {
+        modelfile::build(
+            self.base.as_deref().unwrap_or_default(),
+            &self.system_prompt.text(),
+            &self.params,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SidebarAction {
+    /// Rename, duplicate and delete buttons shown under the conversation
+    Menu(PathBuf),
+    /// The conversation's title being edited in place
+    Rename(PathBuf, String),
+    /// Asking to make sure before the conversation is deleted
+    ConfirmDelete(PathBuf),
+    /// The conversation's folder and comma separated tags being edited
+    Organize(PathBuf, String, String),
+}
+
+impl SidebarAction {
+    fn path(&self) -> &PathBuf {
+        match self {
+            SidebarAction::Menu(path)
+            | SidebarAction::Rename(path, _)
+            | SidebarAction::ConfirmDelete(path)
+            | SidebarAction::Organize(path, ..) => path,
+        }
+    }
+}
+
+struct SystemPromptEditor {
+    system_prompt: text_editor::Content,
+    /// Name to save the system prompt under as a persona
+    persona_name: String,
+}
+
+#[derive(Default)]
+struct TemplatePanel {
+    /// Name to save the prompt being written under as a template
+    template_name: String,
+    /// The template picked, with what's been typed in for each of its variables
+    filling: Option<(Template, Vec<(String, String)>)>,
+}
+
+/// Text typed in for each generation param, kept separately so a half typed number isn't lost
+struct ParamsPanel {
+    inputs: [String; GenerationParam::ALL.len()],
+}
+
+impl ParamsPanel {
+    fn new(params: GenerationParams) -> Self {
+        Self {
+            inputs: GenerationParam::ALL.map(|param| param.get(params).unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GenerationParam {
+    Temperature,
+    TopP,
+    TopK,
+    RepeatPenalty,
+    Seed,
+    NumCtx,
+}
+
+impl GenerationParam {
+    const ALL: [Self; 6] = [
+        Self::Temperature,
+        Self::TopP,
+        Self::TopK,
+        Self::RepeatPenalty,
+        Self::Seed,
+        Self::NumCtx,
+    ];
+
+    fn label(self) -> String {
+        match self {
+            Self::Temperature => tr!("param-temperature"),
+            Self::TopP => tr!("param-top-p"),
+            Self::TopK => tr!("param-top-k"),
+            Self::RepeatPenalty => tr!("param-repeat-penalty"),
+            Self::Seed => tr!("param-seed"),
+            Self::NumCtx => tr!("param-num-ctx"),
+        }
+    }
+
+    fn get(self, params: GenerationParams) -> Option<String> {
+        match self {
+            Self::Temperature => params.temperature.map(|value| value.to_string()),
+            Self::TopP => params.top_p.map(|value| value.to_string()),
+            Self::TopK => params.top_k.map(|value| value.to_string()),
+            Self::RepeatPenalty => params.repeat_penalty.map(|value| value.to_string()),
+            Self::Seed => params.seed.map(|value| value.to_string()),
+            Self::NumCtx => params.num_ctx.map(|value| value.to_string()),
+        }
+    }
+
+    /// Sets the param from its text, leaving it to the model's default if the text is empty,
+    /// and returns whether the text was valid
+    fn set(self, params: &mut GenerationParams, input: &str) -> bool {
+        fn parse<T: std::str::FromStr>(input: &str, value: &mut Option<T>) -> bool {
+            let input = input.trim();
+            if input.is_empty() {
+                *value = None;
+                return true;
+            }
+            match input.parse() {
+                Ok(parsed) => {
+                    *value = Some(parsed);
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+        match self {
+            Self::Temperature => parse(input, &mut params.temperature),
+            Self::TopP => parse(input, &mut params.top_p),
+            Self::TopK => parse(input, &mut params.top_k),
+            Self::RepeatPenalty => parse(input, &mut params.repeat_penalty),
+            Self::Seed => parse(input, &mut params.seed),
+            Self::NumCtx => parse(input, &mut params.num_ctx),
+        }
+    }
+}
+
+/// A conversation being saved
+///
+/// Saves of the same conversation can't overlap, or the later one would find the file changed by
+/// the earlier and save itself as a conflicting copy.
+struct SaveInFlight {
+    /// When the file was last modified before this save, which it's saved over
+    last_modified: Option<SystemTime>,
+    /// The conversation as it's been changed since, saved once this save's finished
+    queued: Option<Conversation>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum ConnectionStatus {
+    #[default]
+    Connecting,
+    Connected,
+    Unreachable,
+}
+
+/// An option in a settings dropdown that can be left unset, e.g. to use the system's language
+#[derive(Debug, Clone, PartialEq)]
+struct Choice {
+    value: Option<String>,
+    label: String,
+}
+
+impl std::fmt::Display for Choice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.label)
+    }
+}
+
+/// A format in the export dropdown
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ExportChoice(export::Format);
+
+impl std::fmt::Display for ExportChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&match self.0 {
+            export::Format::Markdown => tr!("export-markdown"),
+            export::Format::Html => tr!("export-html"),
+            export::Format::Pdf => tr!("export-pdf"),
+        })
+    }
+}
+
+/// A text-to-speech engine in the settings' dropdown
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TtsEngineChoice(TtsEngine);
+
+impl std::fmt::Display for TtsEngineChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self.0 {
+            TtsEngine::EspeakNg => "espeak-ng",
+            TtsEngine::Piper => "Piper",
+        })
+    }
+}
+
+/// A way of copying a message in its copy menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CopyFormatChoice(CopyFormat);
+
+impl std::fmt::Display for CopyFormatChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&match self.0 {
+            CopyFormat::Markdown => tr!("copy-markdown"),
+            CopyFormat::PlainText => tr!("copy-plain-text"),
+            CopyFormat::Code => tr!("copy-code"),
+        })
+    }
+}
+
+/// A search engine in the settings' dropdown
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SearchEngineChoice(SearchEngine);
+
+impl std::fmt::Display for SearchEngineChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self.0 {
+            SearchEngine::Searxng => "SearxNG",
+            SearchEngine::Brave => "Brave Search",
+        })
+    }
+}
+
+/// A kind of server in the saved servers' dropdowns
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ServerKindChoice(ServerKind);
+
+impl std::fmt::Display for ServerKindChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&match self.0 {
+            ServerKind::Ollama => tr!("server-kind-ollama"),
+            ServerKind::OpenAi => tr!("server-kind-openai"),
+        })
+    }
+}
+
+struct ProfilePicker {
+    profiles: Vec<String>,
+    /// Name typed in for creating a new profile
+    new_profile: String,
+}
+
+struct BenchmarkView {
+    /// One prompt per line
+    prompts: text_editor::Content,
+    selected_models: Vec<String>,
+    /// Model and prompt pairs still to run, in order
+    queue: Vec<(String, String)>,
+    /// The model and prompt being run right now
+    running: Option<(String, String)>,
+    results: Vec<BenchmarkResult>,
+    /// Column the results are sorted by, and whether it's ascending
+    sort: (BenchmarkColumn, bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BenchmarkColumn {
+    Model,
+    Prompt,
+    TimeToFirstToken,
+    TotalTime,
+    TokensPerSecond,
+}
+
+impl BenchmarkView {
+    fn sort_results(&mut self) {
+        let (sort_column, is_ascending) = self.sort;
+        self.results.sort_by(|a, b| {
+            let ordering = match sort_column {
+                BenchmarkColumn::Model => a.model.cmp(&b.model),
+                BenchmarkColumn::Prompt => a.prompt.cmp(&b.prompt),
+                BenchmarkColumn::TimeToFirstToken => {
+                    a.time_to_first_token.cmp(&b.time_to_first_token)
+                }
+                BenchmarkColumn::TotalTime => a.total_time.cmp(&b.total_time),
+                BenchmarkColumn::TokensPerSecond => a
+                    .tokens_per_second
+                    .unwrap_or_default()
+                    .total_cmp(&b.tokens_per_second.unwrap_or_default()),
+            };
+            if is_ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+    }
+}
+
+struct BatchView {
+    /// One prompt per line, unless prompts were loaded from a file
+    prompts: text_editor::Content,
+    /// Path typed in for a file of prompts to load
+    file: String,
+    /// Column of a CSV file the prompts are in, or empty for the first one
+    csv_column: String,
+    /// Prompts loaded from a file, with its name, used instead of the ones typed in
+    loaded: Option<(String, Vec<String>)>,
+    /// Model the batch is run against, the one selected when it was started
+    model: String,
+    /// Prompts still to run, in order
+    queue: Vec<String>,
+    running: Option<String>,
+    results: Vec<BatchResult>,
+}
+
+/// The kinds of file a batch's results can be exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BatchExport {
+    Csv,
+    Json,
+}
+
+/// A response translated into another language, shown under it
+struct Translation {
+    /// What the language calls itself
+    language: String,
+    /// The translation and its parsed markdown, once it's in
+    text: Option<(String, ParsedMarkdown)>,
+}
+
+struct HistoryView {
+    versions: Vec<Version>,
+    /// The version being previewed, by its index in `versions`, once it's loaded
+    preview: Option<(usize, Vec<ChatMessage>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PassphraseScreen {
+    /// Conversations are encrypted and the app was just launched
+    Unlock,
+    /// Encryption was just turned on in the settings
+    Choose,
+}
+
+/// A dismissable notice shown above the prompt, with buttons to act on it
+struct Toast {
+    message: String,
+    actions: Vec<(String, Message)>,
 }
 
 #[derive(Debug, Clone)]
 enum Message {
-    SetModelsList(Vec<LocalModel>),
-    SetConversationsList(Vec<PathBuf>),
+    LoadModelsList,
+    CheckConnection,
+    ConnectionChecked(Result<(), Error>),
+    /// Takes a queued prompt back out of the queue and puts it in the composer
+    UnqueuePrompt(usize),
+    /// Something to do with a conversation's response, handled by that conversation whether it's
+    /// open or generating in the background
+    InConversation(PathBuf, Box<Message>),
+    SetModelsList(Result<Vec<LocalModel>, Error>),
+    SetConversationsList(Result<Vec<(PathBuf, SystemTime)>, Error>),
     SetConversationFile(Option<PathBuf>),
     SetModel(Option<LocalModel>),
+    /// The installed models, listed again after pulling or deleting one
+    ModelsRefreshed(Result<Vec<LocalModel>, Error>),
+    ModelDetailsLoaded(Result<Vec<ModelDetails>, Error>),
+    UpdatePullModelName(String),
+    PullModel,
+    PullStatus(Result<PullModelStatus, Error>),
+    ShowModelBuilder,
+    CloseModelBuilder,
+    UpdateNewModelName(String),
+    SetNewModelBase(String),
+    EditNewModelSystemPrompt(text_editor::Action),
+    UpdateNewModelParam(GenerationParam, String),
+    CreateModel,
+    CreateStatus(Result<CreateModelStatus, Error>),
+    CreateFinished,
+    PullFinished,
+    /// Pulls the model the open conversation was answered with, selecting it once it's pulled
+    PullMissingModel(String),
+    ConfirmDeleteModel(Option<String>),
+    DeleteModel(String),
+    ModelDeleted(Result<(), Error>),
     ToggleSidebar,
+    KeyPressed(Key, Modifiers),
+    StopGeneration,
     LinkClicked(markdown::Url),
     CopyChat(String),
-    UpdatePrompt(String),
+    CopyChatAs(String, CopyFormat),
+    CheckClipboard,
+    EditSystemPrompt,
+    SystemPromptAction(text_editor::Action),
+    UpdatePersonaName(String),
+    ApplySystemPrompt,
+    CancelSystemPrompt,
+    UsePersona(Persona),
+    SavePersona,
+    DeletePersona(String),
+    PersonasLoaded(Result<Vec<Persona>, Error>),
+    PersonasSaved(Result<(), Error>),
+    ToggleTemplates,
+    ToggleStructuredOutput,
+    UpdateOutputSchema(String),
+    /// Folds or unfolds an object or array in a response shown as JSON
+    ToggleJsonNode(u64, String),
+    /// Unfolds or folds away the reasoning before a response
+    ToggleReasoning(usize),
+    UpdateTemplateName(String),
+    SaveTemplate,
+    DeleteTemplate(String),
+    UseTemplate(Template),
+    UpdateTemplateVariable(usize, String),
+    InsertTemplate,
+    TemplatesLoaded(Result<Vec<Template>, Error>),
+    TemplatesSaved(Result<(), Error>),
+    ToggleParamsPanel,
+    ToggleConversationMenu(PathBuf),
+    SetSidebarAction(Option<SidebarAction>),
+    UpdateRenameTitle(String),
+    RenameConversation,
+    /// A conversation's old path, with its new one and when it was last saved
+    ConversationRenamed(PathBuf, Result<(PathBuf, Option<SystemTime>), Error>),
+    ConversationImported(PathBuf, Result<PathBuf, Error>),
+    ToggleImportPanel,
+    UpdateImportPath(String),
+    ImportConversations,
+    ConversationsImported(Result<usize, Error>),
+    UpdateOrganizeFolder(String),
+    UpdateOrganizeTags(String),
+    OrganizeConversation,
+    ConversationOrganized(PathBuf, Option<String>, Vec<String>, Result<(), Error>),
+    FilterByTag(Option<String>),
+    SetListing(PathBuf, Listing),
+    ListingSet(PathBuf, Listing, Result<(), Error>),
+    ToggleArchived,
+    /// Shows another version of the conversation from a message on, by the message's index and
+    /// the version's position
+    SwitchVersion(usize, usize),
+    ToggleKnowledgePanel,
+    UpdateKnowledgePath(String),
+    AttachKnowledge,
+    DetachKnowledge,
+    IndexKnowledge,
+    ToggleRelated,
+    /// Embeds the conversations saved since they were last embedded, to find the related ones
+    EmbedConversations,
+    ShowEmbeddingsPlayground,
+    CloseEmbeddingsPlayground,
+    UpdatePlaygroundText(usize, String),
+    AddPlaygroundText,
+    RemovePlaygroundText(usize),
+    ComparePlaygroundTexts,
+    PlaygroundCompared(Result<Vec<Vec<f32>>, Error>),
+    /// Passages from the knowledge folder sent along with the prompt being answered
+    SourcesFound(Vec<Citation>, Vec<WebSource>),
+    ToggleWebSearch,
+    OpenLink(String),
+    /// The title for a conversation, unless it was renamed or deleted while it was being written
+    TitleGenerated(PathBuf, Result<Option<String>, Error>),
+    UpdateSearch(String),
+    EditChat(usize),
+    ChatEditorAction(text_editor::Action),
+    ResendEditedChat,
+    CancelChatEdit,
+    DeleteChat(usize),
+    ShowAttachImage,
+    AttachPathPicked(Picked),
+    UpdateAttachPath(String),
+    ConfirmAttachImage,
+    CancelAttachImage,
+    AttachImage(PathBuf),
+    AttachFile(PathBuf),
+    FileAttached(Result<FileAttachment, Error>),
+    RemoveFileAttachment(usize),
+    PasteImage,
+    /// Ctrl+V in the composer, which pastes text or attaches an image, whichever's copied
+    PasteIntoPrompt,
+    CaptureScreenshot,
+    ScreenshotCaptured(Result<Option<Image>, Error>),
+    ImageAttached(Result<Image, Error>),
+    RemoveAttachment(usize),
+    OpenSearchResult(PathBuf, Option<usize>),
+    DuplicateConversation(PathBuf),
+    /// Opens a conversation in a window of its own, which the windows around the app see to
+    OpenInNewWindow(PathBuf),
+    ConversationDuplicated(Result<PathBuf, Error>),
+    DeleteConversation(PathBuf),
+    ConversationDeleted(PathBuf, Result<(), Error>),
+    UpdateGenerationParam(GenerationParam, String),
+    ResetGenerationParams,
+    ShowSettings,
+    UpdateSettingsDraft(Settings),
+    SaveSettings(Settings),
+    SettingsSaved(Settings, Result<(), Error>),
+    SwitchServer(Server),
+    CloseSettings,
+    ShowBenchmark,
+    CloseBenchmark,
+    EditBenchmarkPrompts(text_editor::Action),
+    ToggleBenchmarkModel(String, bool),
+    RunBenchmark,
+    StopBenchmark,
+    BenchmarkRan(Result<BenchmarkResult, Error>),
+    SortBenchmark(BenchmarkColumn),
+    ExportBenchmark,
+    ShowBatch,
+    CloseBatch,
+    EditBatchPrompts(text_editor::Action),
+    UpdateBatchFile(String),
+    UpdateBatchColumn(String),
+    LoadBatchFile,
+    BatchFileLoaded(Result<Vec<String>, Error>),
+    ClearBatchFile,
+    RunBatch,
+    StopBatch,
+    BatchRan(BatchResult),
+    ExportBatch(BatchExport),
+    ShowUsage,
+    UsageLoaded(Result<Usage, Error>),
+    CloseUsage,
+    AskAboutClipboard(String),
+    #[cfg(target_os = "linux")]
+    PastePrimarySelection,
+    OpenFile(PathBuf),
+    EditPrompt(text_editor::Action),
+    UndoPrompt,
+    RedoPrompt,
+    /// Goes back to the prompt sent before the one in the composer
+    PreviousPrompt,
+    NextPrompt,
+    PromptHistoryLoaded(Result<Vec<String>, Error>),
+    MarkdownCacheLoaded(Result<Lru<String>, Error>),
+    PromptHistorySaved(Result<(), Error>),
     SubmitPrompt,
+    /// The path a new conversation was given, once a title no other conversation has was found
+    NewConversationNamed(Result<PathBuf, Error>),
+    StartGeneration,
+    GenerationFailed(Error),
+    RetryGeneration,
     SaveConversation,
+    ConversationSaved(PathBuf, Result<SaveOutcome, Error>),
     LoadConversation,
-    HandleStreamResponse(String),
+    ConversationLoaded(Result<(Conversation, Option<SystemTime>), Error>),
+    DismissToast(usize),
+    Autosave,
+    LoadEarlierMessages,
+    /// A chunk of the response, and the statistics the server sends with the last one
+    HandleStreamResponse(Result<ResponseChunk, Error>),
+    FlushStreamBuffer,
+    ChatScrolled(scrollable::Viewport),
+    CheckSystemTheme,
+    JumpToLatest,
+    SummarizeContext,
+    ToolCallsRequested(Vec<ToolCall>),
+    ConfirmToolCall,
+    DeclineToolCall,
+    ToolCallFinished(ToolExchange),
+    MathRendered(u64, Result<Vec<u8>, Error>),
+    ContextSummarized(Result<ContextSummary, Error>),
+    ReadAloud(String),
+    SetResponseLanguage(Option<String>),
+    TranslateResponse(String, Language),
+    Translated(u64, Result<String, Error>),
+    CloseTranslation(u64),
+    StopSpeaking,
+    SpeechFinished(u64, Result<(), Error>),
+    VoicesListed(Result<Vec<Voice>, Error>),
+    StartDictation,
+    StopDictation,
+    DictationTranscribed(Result<String, Error>),
     NewChat,
     NewChatButtonPressed,
     LoadConversationList,
+    ConversationsMoved(Result<(), Error>),
+    ConversationFilesChanged(Vec<PathBuf>),
+    BackgroundWorker(WorkerEvent),
+    ToggleBackgroundJobs,
+    Checkpoint,
+    CheckpointSaved(Result<(), Error>),
+    RecoveryLoaded(Option<RecoveryState>),
+    RestoreRecovery(RecoveryState),
+    DiscardRecovery,
+    Activated(Activation),
+    IpcRequest(Request, Responder),
+    Tray(tray::Event),
+    /// Opens the quick chat window, which the windows around the app see to
+    OpenQuickChat,
+    WindowFocusChanged(bool),
+    GenerationFinished,
+    NotificationClicked(Option<PathBuf>),
+    UpdateNewProfile(String),
+    ChooseProfile(Option<String>),
+    UpdatePassphrase(String),
+    SubmitPassphrase,
+    CancelPassphrase,
+    Unlocked(Result<(), Error>),
+    EncryptionChanged(Result<(), Error>),
+    LoadConflicts,
+    /// Conflicting copies of conversations, and of the database itself
+    SetConflicts(Result<(Vec<Conflict>, Vec<PathBuf>), Error>),
+    ResolveConflict(Conflict, Resolution),
+    ConflictResolved(Conflict, Result<(), Error>),
+    HistoryCommitted(Result<(), Error>),
+    ShowHistory,
+    HistoryLoaded(Result<Vec<Version>, Error>),
+    PreviewVersion(usize),
+    VersionLoaded(usize, Result<Conversation, Error>),
+    RestoreVersion(Version),
+    VersionRestored(Result<(), Error>),
+    CloseHistory,
+    SaveCodeBlock(CodeBlock),
+    /// Exports a saved conversation, or the open one if there's no path
+    ExportConversation(Option<PathBuf>, export::Format),
+    /// Uploads the open conversation as a web page and copies the link to it, or saves the page
+    /// if there's nowhere set to upload it to
+    ShareConversation,
+    ConversationShared(Result<String, Error>),
+    ConversationExported(String, export::Format, Result<Vec<u8>, Error>),
+    SavePathPicked(Picked),
+    UpdateSavePath(String),
+    ConfirmSave,
+    CancelSave,
+    Saved(Result<PathBuf, Error>),
+    OpenCodeBlock(CodeBlock),
+    SettingsFileChanged,
+    SettingsLoaded(Result<Settings, Error>),
+    ShowLogs,
+    LogLoaded(Result<String, String>),
+    CloseLogs,
     ToggleIsGenerating,
+    SessionLoaded(Option<Session>),
+    CloseRequested(iced::window::Id),
 }
 
 impl App {
-    fn new() -> (Self, Task<Message>) {
-        let ollama = Ollama::default();
+    fn new(activation: Activation, choose_profile: bool) -> (Self, Task<Message>) {
+        let mut app = Self::blank();
+        if choose_profile {
+            app.profile_picker = Some(ProfilePicker {
+                profiles: profile::list().unwrap_or_default(),
+                new_profile: String::new(),
+            });
+            app.pending_activation = Some(activation);
+            return (app, Task::none());
+        }
+        let task = app.start(activation);
+        (app, task)
+    }
+
+    /// A window of its own for a conversation, starting out with this window's settings and
+    /// model, and generating responses separately from it
+    fn detach(&self, conversation: PathBuf) -> (Self, Task<Message>) {
+        let mut app = Self::blank();
+        app.detached = true;
+        app.conversation.backend = self.conversation.backend.clone();
+        app.conversation.server = self.conversation.server.clone();
+        app.connection = self.connection;
+        app.settings = self.settings.clone();
+        app.loaded_settings = self.loaded_settings.clone();
+        app.conversations_dir = self.conversations_dir.clone();
+        app.conversations_list = self.conversations_list.clone();
+        app.conversation_index = self.conversation_index.clone();
+        app.conversation_times = self.conversation_times.clone();
+        app.models_list = self.models_list.clone();
+        app.conversation.current_model = self.conversation.current_model.clone();
+        app.personas = self.personas.clone();
+        app.templates = self.templates.clone();
+        app.prompt_history = PromptHistory::new(self.prompt_history.sent_prompts().to_vec());
+        // There's less room for it side by side with the main window
+        app.show_sidebar = false;
         (
-            Self {
-                ollama: ollama.clone(),
-                prompt: String::new(),
-                models_list: vec![],
-                conversations_list: vec![],
-                show_sidebar: true,
-                current_model: None,
-                current_conversation: None,
-                chats_list: vec![],
-                is_generating: false,
-            },
+            app,
+            Task::done(Message::SetConversationFile(Some(conversation))),
+        )
+    }
+
+    /// Everything before a profile's settings and conversations are loaded
+    fn blank() -> Self {
+        Self {
+            conversation: ConversationState::default(),
+            prompt: text_editor::Content::new(),
+            prompt_undo: UndoHistory::default(),
+            prompt_history: PromptHistory::default(),
+            drafts: HashMap::new(),
+            models_list: vec![],
+            conversations_list: vec![],
+            conversations_dir: None,
+            show_sidebar: true,
+            settings: Settings::default(),
+            loaded_settings: None,
+            chat_viewport: None,
+            chat_scroll_offset: 1.0,
+            is_system_dark: system_prefers_dark(),
+            is_chat_at_bottom: true,
+            is_following_stream: true,
+            pending_scroll: None,
+            session_model: None,
+            pending_model: None,
+            launch_model: None,
+            session_sidebar: None,
+            toasts: vec![],
+            clipboard: None,
+            log_view: None,
+            last_checkpoint: RecoveryState::default(),
+            recovered_response: None,
+            markdown_memory_budget: Settings::default().markdown_memory_budget_bytes(),
+            markdown_cache: MarkdownCache::new(Settings::default().markdown_memory_budget_bytes()),
+            math_images: HashMap::new(),
+            worker: None,
+            pending_jobs: vec![],
+            next_job_id: 0,
+            background_jobs: vec![],
+            show_background_jobs: false,
+            conversation_index: HashMap::new(),
+            conversation_times: HashMap::new(),
+            is_window_focused: true,
+            window: None,
+            detached: false,
+            quitting: false,
+            passphrase_screen: None,
+            passphrase: String::new(),
+            pending_activation: None,
+            conflicts: vec![],
+            database_conflicts: vec![],
+            history_view: None,
+            save_as: None,
+            is_save_dialog_open: false,
+            is_sharing: false,
+            import_panel: None,
+            send_when_ready: false,
+            last_clipboard_text: None,
+            benchmark_view: None,
+            batch_view: None,
+            usage_view: None,
+            profile_picker: None,
+            settings_draft: None,
+            connection: ConnectionStatus::Connecting,
+            queued_prompts: vec![],
+            background_conversations: HashMap::new(),
+            renamed_conversations: HashMap::new(),
+            saves_in_flight: HashMap::new(),
+            personas: vec![],
+            templates: vec![],
+            template_panel: None,
+            system_prompt_editor: None,
+            params_panel: None,
+            sidebar_action: None,
+            search_query: String::new(),
+            pending_jump: None,
+            chat_editor: None,
+            attachments: vec![],
+            file_attachments: vec![],
+            attach_path: None,
+            model_details: HashMap::new(),
+            pull_model_name: String::new(),
+            model_pull: None,
+            model_builder: None,
+            confirm_delete_model: None,
+            tag_filter: None,
+            show_archived: false,
+            knowledge_panel: None,
+            indexed_knowledge: HashMap::new(),
+            show_related: false,
+            conversation_embeddings: None,
+            embedding_job: None,
+            embeddings_playground: None,
+            speaking: None,
+            voices: vec![],
+            dictation: None,
+        }
+    }
+
+    /// Loads the selected profile's settings and opens what the app was launched with, once
+    /// conversations are unlocked
+    fn start(&mut self, activation: Activation) -> Task<Message> {
+        let load_personas = Task::batch([
+            Task::perform(personas::load(), Message::PersonasLoaded),
+            Task::perform(templates::load(), Message::TemplatesLoaded),
+        ]);
+        if crypto::is_enabled() && !crypto::is_unlocked() {
+            self.passphrase_screen = Some(PassphraseScreen::Unlock);
+            self.pending_activation = Some(activation);
+            Task::batch([Task::done(Message::SettingsFileChanged), load_personas])
+        } else {
             Task::batch([
+                Task::done(Message::SettingsFileChanged).chain(Self::restore(activation)),
+                load_personas,
+            ])
+        }
+    }
+
+    fn title(&self) -> String {
+        if self.detached {
+            return format!("{} – Comhrá", self.conversation_title());
+        }
+        match profile::current() {
+            Some(name) => format!("Comhrá – {name}"),
+            None => "Comhrá".to_string(),
+        }
+    }
+
+    /// Opens what the app was launched with, or otherwise whatever was open when it last closed,
+    /// and offers to recover anything lost in a crash
+    fn restore(activation: Activation) -> Task<Message> {
+        Task::batch([
+            Task::perform(recovery::load(), Message::RecoveryLoaded),
+            Task::perform(composer::load_history(), Message::PromptHistoryLoaded),
+            Task::perform(markdown_cache::load(), Message::MarkdownCacheLoaded),
+            if activation.quick_chat {
+                Task::done(Message::OpenQuickChat)
+            } else {
+                Task::none()
+            },
+            if activation.is_empty() {
                 Task::perform(
-                    async move { ollama.list_local_models().await.unwrap() },
-                    Message::SetModelsList,
-                ),
-                Task::done(Message::LoadConversationList),
-            ]),
-        )
+                    async {
+                        let mut session = session::take().await?;
+                        // Left out if it was deleted since, e.g. by a sync
+                        if let Some(path) = session.conversation.take() {
+                            if storage::conversation_exists(&path).await {
+                                session.conversation = Some(path);
+                            }
+                        }
+                        Some(session)
+                    },
+                    Message::SessionLoaded,
+                )
+            } else {
+                Task::done(Message::Activated(activation))
+            },
+        ])
+    }
+
+    /// Whether closing the window leaves the app running in the tray instead of quitting
+    fn hides_on_close(&self) -> bool {
+        !self.detached && self.settings.minimize_to_tray && !self.quitting
+    }
+
+    /// Brings the window back from the tray if it was hidden there, and focuses it
+    fn show_window(&self) -> Task<Message> {
+        self.window.map_or_else(Task::none, |id| {
+            iced::window::change_mode(id, iced::window::Mode::Windowed)
+                .chain(iced::window::gain_focus(id))
+        })
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
-            Message::SetModelsList(models_list) => self.models_list = models_list,
-            Message::SetConversationsList(conversations_list) => {
-                self.conversations_list = conversations_list
+            Message::LoadModelsList => {
+                self.connection = ConnectionStatus::Connecting;
+                let backend = self.conversation.backend.clone();
+                return Task::perform(
+                    async move { backend.list_models().await },
+                    Message::SetModelsList,
+                );
+            }
+            Message::SetModelsList(result) => match result {
+                Ok(models_list) => {
+                    self.connection = ConnectionStatus::Connected;
+                    self.models_list = models_list;
+                    self.select_conversation_model();
+                    self.select_default_model();
+                    return Task::batch([
+                        self.send_if_ready(),
+                        self.send_queued_prompt(),
+                        self.load_model_details(),
+                    ]);
+                }
+                Err(err) => {
+                    self.connection = ConnectionStatus::Unreachable;
+                    // The model picker has a banner for this that stays until it's fixed
+                    if self.conversation.current_model.is_some() {
+                        self.show_error(err, Some(Message::LoadModelsList));
+                    } else {
+                        tracing::warn!("{err}");
+                    }
+                }
+            },
+            Message::CheckConnection => {
+                // The models being listed will say whether it's reachable
+                if self.connection == ConnectionStatus::Connecting {
+                    return Task::none();
+                }
+                let backend = self.conversation.backend.clone();
+                return Task::perform(
+                    async move { backend.ping().await },
+                    Message::ConnectionChecked,
+                );
+            }
+            Message::ConnectionChecked(result) => match (result, self.connection) {
+                (Ok(()), ConnectionStatus::Unreachable) => {
+                    tracing::info!("The server can be reached again");
+                    return self.update(Message::LoadModelsList);
+                }
+                (Err(err), ConnectionStatus::Connected) => {
+                    tracing::warn!("The server can't be reached: {err}");
+                    self.connection = ConnectionStatus::Unreachable;
+                }
+                _ => {}
+            },
+            Message::UnqueuePrompt(index) => self.unqueue_prompt(index),
+            Message::InConversation(conversation, message) => {
+                return self.update_in_conversation(conversation, *message);
             }
+            Message::SetConversationsList(result) => match result {
+                Ok(conversation_times) => {
+                    let saved_since_indexed: Vec<PathBuf> = conversation_times
+                        .iter()
+                        .filter(|(path, modified)| {
+                            self.conversation_times.get(path) != Some(modified)
+                        })
+                        .map(|(path, _modified)| path.clone())
+                        .collect();
+                    if !saved_since_indexed.is_empty() {
+                        self.queue_job(Job::IndexConversations(saved_since_indexed));
+                    }
+                    self.conversations_list = conversation_times
+                        .iter()
+                        .map(|(path, _modified)| path.clone())
+                        .collect();
+                    self.conversation_times = conversation_times.into_iter().collect();
+                    self.conversation_index
+                        .retain(|path, _summary| self.conversation_times.contains_key(path));
+                    self.background_conversations.retain(|path, background| {
+                        background.is_generating || self.conversation_times.contains_key(path)
+                    });
+                    // Conversations saved since are embedded again while related ones are shown,
+                    // unless they couldn't be embedded in the first place
+                    if self.show_related && self.conversation_embeddings.is_some() {
+                        return self.update(Message::EmbedConversations);
+                    }
+                }
+                Err(err) => self.show_error(err, Some(Message::LoadConversationList)),
+            },
             Message::SetConversationFile(conversation) => {
-                self.current_conversation = conversation.clone();
+                self.system_prompt_editor = None;
+                self.switch_draft(conversation.clone());
+                if conversation != self.conversation.current_conversation {
+                    self.send_to_background();
+                }
+                let background = conversation
+                    .as_ref()
+                    .and_then(|conversation| self.background_conversations.remove(conversation));
+                // What's still being written, or couldn't be saved, is picked up where it's got to
+                // rather than loaded from its file
+                if let Some(background) = background
+                    .filter(|background| background.is_generating || background.has_unsaved_changes)
+                {
+                    let previous = std::mem::replace(&mut self.conversation, background);
+                    self.chat_editor = None;
+                    self.is_following_stream = true;
+                    if self.conversation.server != previous.server {
+                        return Task::batch([
+                            Task::done(Message::LoadModelsList),
+                            scroll_chat_to_bottom(),
+                        ]);
+                    }
+                    return scroll_chat_to_bottom();
+                }
+                self.conversation.current_conversation = conversation.clone();
                 if conversation.is_some() {
                     return Task::done(Message::LoadConversation);
                 }
             }
-            Message::SetModel(model) => self.current_model = model,
-            Message::ToggleSidebar => self.show_sidebar = !self.show_sidebar,
-            Message::LinkClicked(url) => {
-                println!("The following url was clicked: {url}");
+            Message::SetModel(model) => {
+                self.conversation.current_model = model;
+                return self.send_if_ready();
             }
-            Message::CopyChat(s) => Clipboard::new().unwrap().set_text(s).unwrap(),
-            Message::UpdatePrompt(s) => self.prompt = s,
-            Message::SubmitPrompt => {
-                let mut reload_conversation_list = false;
-                if self.current_conversation.is_none() {
-                    let mut conversation_file =
-                        dirs::config_dir().expect("Couldn't find config dir");
-                    conversation_file.push("github.com.leo030303.comhra/");
-                    conversation_file.push("conversations/");
-                    let mut filename = match self.prompt.split_at_checked(40) {
-                        Some((title, _)) => title.to_string(),
-                        None => self.prompt.clone(),
-                    };
-                    filename.push_str(".json");
-                    conversation_file.push(filename);
-                    self.current_conversation = Some(conversation_file);
-                    reload_conversation_list = true;
-                };
-                let markdown_items = markdown::parse(&self.prompt).collect();
-                self.chats_list.push((
-                    ChatMessage {
-                        role: MessageRole::User,
-                        content: self.prompt.clone(),
-                        images: None,
-                    },
-                    markdown_items,
-                ));
-                self.chats_list.push((
-                    ChatMessage {
-                        role: MessageRole::Assistant,
-                        content: String::new(),
-                        images: None,
+            Message::ModelsRefreshed(result) => match result {
+                Ok(models_list) => {
+                    self.models_list = models_list;
+                    self.select_conversation_model();
+                    return self.load_model_details();
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::ModelDetailsLoaded(result) => match result {
+                Ok(model_details) => {
+                    self.model_details = model_details
+                        .into_iter()
+                        .map(|model| (model.name, model.details))
+                        .collect();
+                }
+                // The models can still be used without their details
+                Err(err) => tracing::warn!("Couldn't load the models' details: {err}"),
+            },
+            Message::UpdatePullModelName(model_name) => self.pull_model_name = model_name,
+            Message::PullModel => {
+                let model_name = self.pull_model_name.trim().to_string();
+                if model_name.is_empty() || self.model_pull.is_some() {
+                    return Task::none();
+                }
+                self.model_pull = Some(ModelPull {
+                    model_name: model_name.clone(),
+                    status: String::new(),
+                    progress: None,
+                });
+                let backend = self.conversation.backend.clone();
+                return Task::future(async move { backend.pull_model(model_name).await }).then(
+                    |result| match result {
+                        Ok(stream) => Task::run(stream, Message::PullStatus)
+                            .chain(Task::done(Message::PullFinished)),
+                        Err(err) => Task::done(Message::PullStatus(Err(err)))
+                            .chain(Task::done(Message::PullFinished)),
                     },
-                    vec![],
-                ));
-                let conversation: Vec<ChatMessage> = self
-                    .chats_list
-                    .iter()
-                    .map(|(chat_message, _markdown_items)| chat_message.clone())
-                    .collect();
-                let chat_request =
-                    ChatMessageRequest::new(self.current_model.clone().unwrap().name, conversation);
-                let ollama = self.ollama.clone();
-                self.prompt = String::new();
-                return Task::done(Message::ToggleIsGenerating)
-                    .chain(
-                        Task::future(async move {
-                            ollama.send_chat_messages_stream(chat_request).await
-                        })
-                        .and_then(move |stream| {
-                            Task::run(stream, |stream_responses| {
-                                let parsed_response =
-                                    stream_responses.unwrap().message.unwrap().content;
-                                Message::HandleStreamResponse(parsed_response)
-                            })
-                            .chain(Task::done(Message::SaveConversation))
-                            .chain({
-                                if reload_conversation_list {
-                                    Task::done(Message::LoadConversationList)
-                                } else {
-                                    Task::none()
-                                }
-                            })
-                        }),
-                    )
-                    .chain(Task::done(Message::ToggleIsGenerating));
+                );
             }
-            Message::SaveConversation => {
-                if let Some(current_conversation) = self.current_conversation.as_ref() {
-                    fs::write(
-                        current_conversation,
-                        serde_json::to_string(
-                            &self
-                                .chats_list
-                                .iter()
-                                .map(|(chat_message, _markdown_items)| chat_message.clone())
-                                .collect::<Vec<ChatMessage>>(),
-                        )
-                        .unwrap(),
-                    )
-                    .unwrap()
+            Message::PullStatus(result) => match result {
+                Ok(status) => {
+                    if let Some(model_pull) = self.model_pull.as_mut() {
+                        model_pull.progress = models::pull_progress(&status);
+                        model_pull.status = status.message;
+                    }
+                }
+                Err(err) => {
+                    // The name is kept so the pull can be retried, e.g. after fixing a typo
+                    self.model_pull = None;
+                    self.pending_model = None;
+                    self.show_error(err, Some(Message::PullModel));
+                }
+            },
+            Message::PullFinished => {
+                // A pull that failed has already been cleared
+                if self.model_pull.take().is_some() {
+                    self.pull_model_name.clear();
                 }
+                return self.refresh_models();
             }
-            Message::LoadConversation => {
-                if let Ok(conversation_json) =
-                    fs::read_to_string(self.current_conversation.as_ref().unwrap())
-                {
-                    let conversation: Vec<ChatMessage> =
-                        serde_json::from_str(&conversation_json).unwrap_or_default();
-                    self.chats_list = conversation
-                        .into_iter()
-                        .map(|chat_message| {
-                            let markdown_items = markdown::parse(&chat_message.content)
-                                .collect::<Vec<markdown::Item>>();
-                            (chat_message, markdown_items)
-                        })
-                        .collect();
-                };
+            Message::PullMissingModel(model_name) => {
+                self.pending_model = Some(model_name.clone());
+                self.pull_model_name = model_name;
+                return self.update(Message::PullModel);
             }
-            Message::HandleStreamResponse(next_chunk) => {
-                let (chat_message, markdown_vec) = self.chats_list.last_mut().unwrap();
-                chat_message.content.push_str(&next_chunk);
-                let markdown_items =
-                    markdown::parse(&chat_message.content).collect::<Vec<markdown::Item>>();
-                markdown_vec.clear();
-                markdown_vec.extend(markdown_items);
+            Message::ShowModelBuilder => {
+                self.model_builder = Some(ModelBuilder {
+                    name: String::new(),
+                    base: self
+                        .conversation
+                        .current_model
+                        .as_ref()
+                        .map(|model| model.name.clone()),
+                    system_prompt: text_editor::Content::new(),
+                    inputs: Default::default(),
+                    params: GenerationParams::default(),
+                    status: None,
+                });
             }
-            Message::NewChat => {
-                self.current_conversation = None;
-                self.chats_list = vec![];
+            Message::CloseModelBuilder => self.model_builder = None,
+            Message::UpdateNewModelName(name) => {
+                if let Some(model_builder) = self.model_builder.as_mut() {
+                    model_builder.name = name;
+                }
             }
-            Message::NewChatButtonPressed => {
-                return Task::done(Message::SaveConversation).chain(Task::done(Message::NewChat))
+            Message::SetNewModelBase(base) => {
+                if let Some(model_builder) = self.model_builder.as_mut() {
+                    model_builder.base = Some(base);
+                }
             }
-            Message::LoadConversationList => {
+            Message::EditNewModelSystemPrompt(action) => {
+                if let Some(model_builder) = self.model_builder.as_mut() {
+                    model_builder.system_prompt.perform(action);
+                }
+            }
+            Message::UpdateNewModelParam(param, input) => {
+                if let Some(model_builder) = self.model_builder.as_mut() {
+                    param.set(&mut model_builder.params, &input);
+                    model_builder.inputs[param as usize] = input;
+                }
+            }
+            Message::CreateModel => {
+                let Some(model_builder) = self.model_builder.as_mut() else {
+                    return Task::none();
+                };
+                let model_name = model_builder.name.trim().to_string();
+                if model_name.is_empty()
+                    || model_builder.base.is_none()
+                    || model_builder.status.is_some()
+                {
+                    return Task::none();
+                }
+                let modelfile = model_builder.modelfile();
+                model_builder.status = Some(String::new());
+                let backend = self.conversation.backend.clone();
+                return Task::future(
+                    async move { backend.create_model(model_name, modelfile).await },
+                )
+                .then(|result| match result {
+                    Ok(stream) => Task::run(stream, Message::CreateStatus)
+                        .chain(Task::done(Message::CreateFinished)),
+                    Err(err) => Task::done(Message::CreateStatus(Err(err)))
+                        .chain(Task::done(Message::CreateFinished)),
+                });
+            }
+            Message::CreateStatus(result) => match result {
+                Ok(status) => {
+                    if let Some(model_builder) = self.model_builder.as_mut() {
+                        model_builder.status = Some(status.message);
+                    }
+                }
+                Err(err) => {
+                    // The builder's kept open so the model can be fixed up and created again
+                    if let Some(model_builder) = self.model_builder.as_mut() {
+                        model_builder.status = None;
+                    }
+                    self.show_error(err, Some(Message::CreateModel));
+                }
+            },
+            Message::CreateFinished => {
+                // A model that failed to be created has already had its status cleared
+                if self
+                    .model_builder
+                    .as_ref()
+                    .is_some_and(|model_builder| model_builder.status.is_some())
+                {
+                    self.model_builder = None;
+                }
+                return self.refresh_models();
+            }
+            Message::ConfirmDeleteModel(model_name) => self.confirm_delete_model = model_name,
+            Message::DeleteModel(model_name) => {
+                self.confirm_delete_model = None;
+                let backend = self.conversation.backend.clone();
                 return Task::perform(
-                    async {
-                        let mut config_dir = dirs::config_dir().expect("Couldn't find config dir");
-                        config_dir.push("github.com.leo030303.comhra/");
-                        config_dir.push("conversations/");
-                        if !config_dir.exists() {
-                            fs::create_dir_all(&config_dir).expect("Error making the config dir");
-                        };
-                        let mut conversations_list: Vec<PathBuf> = fs::read_dir(config_dir)
-                            .unwrap()
-                            .map(|read_dir| read_dir.unwrap().path())
-                            .collect();
-                        conversations_list.sort_unstable_by(|a, b| {
-                            a.metadata()
-                                .unwrap()
-                                .st_mtime()
-                                .partial_cmp(&b.metadata().unwrap().st_mtime())
-                                .unwrap()
-                                .reverse()
-                        });
-                        conversations_list
-                    },
-                    Message::SetConversationsList,
+                    async move { backend.delete_model(model_name).await },
+                    Message::ModelDeleted,
                 );
             }
-            Message::ToggleIsGenerating => self.is_generating = !self.is_generating,
-        };
-        Task::none()
-    }
-
-    fn subscription(&self) -> Subscription<Message> {
-        Subscription::none()
-    }
-
-    fn view(&self) -> Element<Message> {
-        row![if self.current_model.is_none() {
-            column![scrollable(
-                column(self.models_list.iter().map(|model| {
-                    button(
-                        text(&model.name)
-                            .width(Length::Fixed(250.0))
-                            .align_x(Center)
-                            .size(20),
-                    )
-                    .on_press(Message::SetModel(Some(model.clone())))
-                    .into()
-                }))
-                .spacing(10)
-            )]
-            .padding(30)
-            .align_x(Center)
-            .width(Length::Fill)
-        } else {
-            column![
-                row![
-                    Tooltip::new(
-                        button(Svg::new(Handle::from_memory(include_bytes!(
-                            "../icons/toggle-sidebar.svg"
-                        ))))
-                        .height(Length::Fill)
-                        .on_press(Message::ToggleSidebar)
-                        .style(if self.show_sidebar {
-                            button::secondary
-                        } else {
-                            button::primary
+            Message::ModelDeleted(result) => match result {
+                Ok(()) => return self.refresh_models(),
+                Err(err) => self.show_error(err, None),
+            },
+            Message::ToggleSidebar => self.show_sidebar = !self.show_sidebar,
+            Message::KeyPressed(key, modifiers) => {
+                if self.passphrase_screen.is_some() {
+                    return Task::none();
+                }
+                match shortcuts::action(&self.settings.shortcuts, &key, modifiers) {
+                    Some(shortcuts::Action::NewChat) => {
+                        return Task::done(Message::NewChatButtonPressed)
+                    }
+                    Some(shortcuts::Action::ToggleSidebar) => {
+                        return Task::done(Message::ToggleSidebar)
+                    }
+                    Some(shortcuts::Action::Search) => {
+                        self.show_sidebar = true;
+                        return text_input::focus(search_input_id());
+                    }
+                    Some(shortcuts::Action::Submit)
+                        if self.conversation.current_model.is_some() =>
+                    {
+                        return Task::done(Message::SubmitPrompt)
+                    }
+                    Some(shortcuts::Action::StopGeneration) => {
+                        return Task::done(Message::StopGeneration)
+                    }
+                    Some(shortcuts::Action::NextConversation) => return self.cycle_conversation(1),
+                    Some(shortcuts::Action::PreviousConversation) => {
+                        return self.cycle_conversation(-1)
+                    }
+                    Some(shortcuts::Action::ZoomIn) => return self.zoom(self.settings.zoomed(1)),
+                    Some(shortcuts::Action::ZoomOut) => return self.zoom(self.settings.zoomed(-1)),
+                    Some(shortcuts::Action::ResetZoom) => return self.zoom(1.0),
+                    Some(shortcuts::Action::Submit) | None => {}
+                }
+            }
+            Message::LinkClicked(url) => {
+                tracing::info!("The following url was clicked: {url}");
+            }
+            Message::CopyChat(s) => {
+                // Text copied from the app isn't offered back by the clipboard watcher
+                self.last_clipboard_text = Some(s.clone());
+                if let Err(err) = self.clipboard().and_then(|clipboard| clipboard.set_text(s)) {
+                    self.clipboard_error(err);
+                }
+            }
+            Message::CopyChatAs(content, format) => {
+                return self.update(Message::CopyChat(copy::text(&content, format)));
+            }
+            Message::CheckClipboard => {
+                let clipboard_text =
+                    match self.clipboard().and_then(|clipboard| clipboard.get_text()) {
+                        Ok(clipboard_text) => clipboard_text,
+                        // Empty or non-text clipboards are errors too, so they aren't worth a toast
+                        Err(err) => {
+                            tracing::debug!("Couldn't read the clipboard: {err}");
+                            return Task::none();
+                        }
+                    };
+                if clipboard_text.trim().is_empty()
+                    || self.last_clipboard_text.as_ref() == Some(&clipboard_text)
+                {
+                    return Task::none();
+                }
+                let previous_text = self.last_clipboard_text.replace(clipboard_text.clone());
+                // Whatever was already copied when watching started isn't new, and copies made
+                // while the window is focused are most likely from the app itself
+                if previous_text.is_none() || self.is_window_focused {
+                    return Task::none();
+                }
+                self.dismiss_clipboard_prompt();
+                self.toasts.push(Toast {
+                    message: tr!(
+                        "ask-about-clipboard",
+                        snippet = notifications::snippet(&clipboard_text)
+                    ),
+                    actions: vec![(tr!("ask"), Message::AskAboutClipboard(clipboard_text))],
+                });
+            }
+            Message::AskAboutClipboard(clipboard_text) => {
+                self.dismiss_clipboard_prompt();
+                return Task::done(Message::Activated(Activation {
+                    prompt: Some(format!("{clipboard_text}\n\n")),
+                    ..Default::default()
+                }));
+            }
+            #[cfg(target_os = "linux")]
+            Message::PastePrimarySelection => {
+                use arboard::{GetExtLinux, LinuxClipboardKind};
+                match self.clipboard().and_then(|clipboard| {
+                    clipboard
+                        .get()
+                        .clipboard(LinuxClipboardKind::Primary)
+                        .text()
+                }) {
+                    Ok(selection) => self
+                        .prompt
+                        .perform(text_editor::Action::Edit(Edit::Paste(Arc::new(selection)))),
+                    Err(err) => self.clipboard_error(err),
+                }
+            }
+            Message::OpenFile(path) => {
+                if let Err(err) = std::process::Command::new("xdg-open").arg(&path).spawn() {
+                    self.toasts.push(Toast {
+                        message: tr!(
+                            "couldnt-open-file",
+                            path = path.display().to_string(),
+                            error = err.to_string()
+                        ),
+                        actions: vec![],
+                    });
+                }
+            }
+            Message::EditPrompt(action) => {
+                if let text_editor::Action::Edit(edit) = &action {
+                    let run = match edit {
+                        text_editor::Edit::Insert(c) if !c.is_whitespace() => Some(EditRun::Typing),
+                        text_editor::Edit::Backspace | text_editor::Edit::Delete => {
+                            Some(EditRun::Deleting)
+                        }
+                        _ => None,
+                    };
+                    self.prompt_undo.edited(&self.prompt_text(), run);
+                    // A sent prompt that's edited is the prompt being written now
+                    self.prompt_history.stop_browsing();
+                }
+                self.prompt.perform(action);
+            }
+            Message::UndoPrompt => {
+                if let Some(prompt) = self.prompt_undo.undo(&self.prompt_text()) {
+                    self.set_prompt(&prompt);
+                }
+            }
+            Message::RedoPrompt => {
+                if let Some(prompt) = self.prompt_undo.redo(&self.prompt_text()) {
+                    self.set_prompt(&prompt);
+                }
+            }
+            Message::PreviousPrompt => {
+                let current = self.prompt_text();
+                if let Some(prompt) = self.prompt_history.previous(&current).map(str::to_string) {
+                    self.set_prompt(&prompt);
+                }
+            }
+            Message::NextPrompt => {
+                if let Some(prompt) = self.prompt_history.next() {
+                    self.set_prompt(&prompt);
+                }
+            }
+            Message::PromptHistoryLoaded(result) => match result {
+                Ok(sent_prompts) => {
+                    // Anything sent while it loaded is newer
+                    let mut prompt_history = PromptHistory::new(sent_prompts);
+                    for prompt in self.prompt_history.sent_prompts() {
+                        prompt_history.push(prompt);
+                    }
+                    self.prompt_history = prompt_history;
+                }
+                Err(err) => tracing::warn!("Couldn't load the prompt history: {err}"),
+            },
+            // Anything prepared while it loaded is prepared again if it's needed
+            Message::MarkdownCacheLoaded(result) => match result {
+                Ok(mut prepared) => {
+                    prepared.set_budget(self.markdown_memory_budget);
+                    self.markdown_cache.prepared = prepared;
+                }
+                Err(err) => tracing::warn!("Couldn't load the markdown cache: {err}"),
+            },
+            Message::PromptHistorySaved(result) => {
+                if let Err(err) = result {
+                    tracing::warn!("Couldn't save the prompt history: {err}");
+                }
+            }
+            Message::SubmitPrompt => {
+                let prompt = self.prompt_text();
+                if prompt.trim().is_empty() {
+                    return Task::none();
+                }
+                if let Err(err) = self.output_format() {
+                    self.toasts.push(Toast {
+                        message: tr!("invalid-schema", error = err),
+                        actions: vec![],
+                    });
+                    return Task::none();
+                }
+                // A new conversation is named first, so it doesn't take another one's title, and
+                // the prompt's sent once it has been
+                if self.conversation.current_conversation.is_none() {
+                    let Some(conversations_dir) = self.conversations_dir.clone() else {
+                        self.show_error(Error::NoAppDir, None);
+                        return Task::none();
+                    };
+                    return Task::perform(
+                        storage::new_conversation_path(conversations_dir, prompt),
+                        Message::NewConversationNamed,
+                    );
+                }
+                // Sending can be undone to get the prompt back, e.g. to send it somewhere else
+                self.prompt_undo.edited(&prompt, None);
+                self.prompt_history.push(&prompt);
+                self.prompt = text_editor::Content::new();
+                let save_history = Task::perform(
+                    composer::save_history(self.prompt_history.sent_prompts().to_vec()),
+                    Message::PromptHistorySaved,
+                );
+                let images = std::mem::take(&mut self.attachments);
+                let content = files::inline(&prompt, &std::mem::take(&mut self.file_attachments));
+                // Sent once the response being generated has finished, rather than mixed in with it
+                if self.connection == ConnectionStatus::Unreachable
+                    || self.conversation.is_generating
+                {
+                    self.queue_prompt(content, images);
+                    return save_history;
+                }
+                return Task::batch([save_history, self.send_message(content, images)]);
+            }
+            Message::NewConversationNamed(result) => match result {
+                // Left alone if another conversation was opened in the meantime
+                Ok(path) if self.conversation.current_conversation.is_none() => {
+                    self.conversation.current_conversation = Some(path);
+                    return Task::done(Message::SubmitPrompt);
+                }
+                Ok(_path) => {}
+                Err(err) => self.show_error(err, None),
+            },
+            Message::EditChat(index) => {
+                if let Some((chat_message, _markdown_items)) =
+                    self.conversation.chats_list.get(index)
+                {
+                    self.chat_editor = Some((
+                        index,
+                        text_editor::Content::with_text(&chat_message.content),
+                    ));
+                }
+            }
+            Message::ChatEditorAction(action) => {
+                if let Some((_index, content)) = self.chat_editor.as_mut() {
+                    content.perform(action);
+                }
+            }
+            Message::ResendEditedChat => {
+                let Some((index, content)) = self.chat_editor.take() else {
+                    return Task::none();
+                };
+                let content = content.text().trim_end().to_string();
+                if content.trim().is_empty() || self.conversation.is_generating {
+                    return Task::none();
+                }
+                let images = self
+                    .conversation
+                    .chats_list
+                    .get(index)
+                    .and_then(|(chat_message, _markdown_items)| chat_message.images.clone())
+                    .unwrap_or_default();
+                // Everything after the edited message was in reply to what it said before, so it's
+                // kept as another version to go back to
+                self.change_branches(index, Branch::keep_version);
+                return self.send_message(content, images);
+            }
+            Message::CancelChatEdit => self.chat_editor = None,
+            Message::ShowAttachImage => {
+                return Task::perform(
+                    dialogs::open_file(tr!("attach-image"), images::MIME_TYPES),
+                    Message::AttachPathPicked,
+                );
+            }
+            Message::AttachPathPicked(picked) => match picked {
+                Picked::File(path) => return Task::done(attach_message(path)),
+                Picked::Cancelled => {}
+                Picked::NoDialog => self.attach_path = Some(String::new()),
+            },
+            Message::UpdateAttachPath(path) => self.attach_path = Some(path),
+            Message::ConfirmAttachImage => {
+                if let Some(path) = self.attach_path.take() {
+                    return Task::done(attach_message(PathBuf::from(path.trim())));
+                }
+            }
+            Message::CancelAttachImage => self.attach_path = None,
+            Message::AttachImage(path) => {
+                return Task::perform(images::load(path), Message::ImageAttached);
+            }
+            Message::AttachFile(path) => {
+                return Task::perform(files::load(path), Message::FileAttached);
+            }
+            Message::FileAttached(result) => match result {
+                Ok(file) => {
+                    if file.is_truncated {
+                        self.toasts.push(Toast {
+                            message: tr!(
+                                "file-truncated",
+                                name = file.name.as_str(),
+                                size = files::MAX_FILE_SIZE / 1024
+                            ),
+                            actions: vec![],
+                        });
+                    }
+                    self.file_attachments.push(file);
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::RemoveFileAttachment(index) => {
+                if index < self.file_attachments.len() {
+                    self.file_attachments.remove(index);
+                }
+            }
+            Message::PasteImage => {
+                match self.clipboard().and_then(|clipboard| clipboard.get_image()) {
+                    Ok(image) => self.attach_clipboard_image(image),
+                    Err(err) => self.clipboard_error(err),
+                }
+            }
+            Message::PasteIntoPrompt => {
+                let clipboard = match self.clipboard() {
+                    Ok(clipboard) => clipboard,
+                    Err(err) => {
+                        self.clipboard_error(err);
+                        return Task::none();
+                    }
+                };
+                // Text is pasted as the editor would, and an image is only looked for without any
+                let pasted = match clipboard.get_text() {
+                    Err(arboard::Error::ContentNotAvailable) => clipboard.get_image().map(Err),
+                    text => text.map(Ok),
+                };
+                match pasted {
+                    Ok(Ok(text)) => {
+                        return self.update(Message::EditPrompt(text_editor::Action::Edit(
+                            Edit::Paste(Arc::new(text)),
+                        )))
+                    }
+                    Ok(Err(image)) => self.attach_clipboard_image(image),
+                    // Nothing's been copied
+                    Err(arboard::Error::ContentNotAvailable) => {}
+                    Err(err) => self.clipboard_error(err),
+                }
+            }
+            Message::CaptureScreenshot => {
+                return Task::perform(screenshot::capture_region(), Message::ScreenshotCaptured);
+            }
+            Message::ScreenshotCaptured(result) => match result {
+                Ok(Some(image)) => self.attachments.push(image),
+                // Picking the region was cancelled
+                Ok(None) => {}
+                Err(err) => self.show_error(err, Some(Message::CaptureScreenshot)),
+            },
+            Message::ImageAttached(result) => match result {
+                Ok(image) => self.attachments.push(image),
+                Err(err) => self.show_error(err, None),
+            },
+            Message::RemoveAttachment(index) => {
+                if index < self.attachments.len() {
+                    self.attachments.remove(index);
+                }
+            }
+            Message::DeleteChat(index) => {
+                if index >= self.conversation.chats_list.len() || self.conversation.is_generating {
+                    return Task::none();
+                }
+                self.change_branches(index, Branch::remove);
+                // The responses after it have moved up
+                self.conversation.expanded_reasoning.clear();
+                return Task::done(Message::SaveConversation);
+            }
+            Message::StartGeneration => {
+                let Some(model) = self.conversation.current_model.as_ref() else {
+                    return Task::none();
+                };
+                let model_name = model.name.clone();
+                tracing::debug!("Generating a response with {model_name}");
+                let conversation = self.full_conversation();
+                let summary = self.conversation.context_summary.clone();
+                let summarize_covers = self
+                    .settings
+                    .summarize_context
+                    .then(|| self.summary_needed(&conversation))
+                    .flatten();
+                let params = self.conversation.generation_params;
+                let backend = self.conversation.backend.clone();
+                let knowledge_dir = self.conversation.knowledge_dir.clone();
+                let response_language = self.conversation.response_language.clone();
+                let web_search = Some(self.settings.web_search.clone())
+                    .filter(|web_search| self.conversation.search_web && web_search.is_set_up());
+                self.conversation.is_searching_web = web_search.is_some();
+                // A model that keeps calling tools is left to answer with what it has
+                let offered_tools = if self.conversation.backend.supports_tools()
+                    && self.conversation.pending_tool_exchanges.len()
+                        < tools::MAX_CALLS_PER_RESPONSE
+                {
+                    self.settings.tools.clone()
+                } else {
+                    vec![]
+                };
+                let tool_exchanges = self.conversation.pending_tool_exchanges.clone();
+                let format = self
+                    .conversation
+                    .pending_format
+                    .clone()
+                    .filter(|_| self.conversation.backend.supports_structured_output());
+                self.conversation.pending_citations.clear();
+                self.conversation.pending_web_sources.clear();
+                let (generation, handle) = Task::done(Message::ToggleIsGenerating)
+                    .chain(
+                        Task::future(async move {
+                            let new_summary = match summarize_covers {
+                                Some(covers) => match context::summarize(
+                                    backend.clone(),
+                                    model_name.clone(),
+                                    conversation.clone(),
+                                    covers,
+                                    summary.clone(),
+                                )
+                                .await
+                                {
+                                    Ok(new_summary) => Some(new_summary),
+                                    // Sent as it is, to be summarised again next time
+                                    Err(err) => {
+                                        tracing::warn!("Couldn't summarise the context: {err}");
+                                        None
+                                    }
+                                },
+                                None => None,
+                            };
+                            let mut conversation = context::with_summary(
+                                &conversation,
+                                new_summary.as_ref().or(summary.as_ref()),
+                            );
+                            if let Some(language) = response_language {
+                                conversation =
+                                    translation::with_response_language(conversation, &language);
+                            }
+                            let prompt_index = conversation
+                                .iter()
+                                .rposition(|chat_message| chat_message.role == MessageRole::User);
+                            let citations = match (knowledge_dir, prompt_index) {
+                                (Some(knowledge_dir), Some(prompt_index)) => {
+                                    let citations = knowledge::retrieve(
+                                        &backend,
+                                        &knowledge_dir,
+                                        &conversation[prompt_index].content,
+                                    )
+                                    .await?;
+                                    if !citations.is_empty() {
+                                        conversation.insert(
+                                            prompt_index,
+                                            knowledge::context_message(&citations),
+                                        );
+                                    }
+                                    citations
+                                }
+                                _ => vec![],
+                            };
+                            let web_sources = match (web_search, prompt_index) {
+                                (Some(web_search), Some(prompt_index)) => {
+                                    // After the passages from documents, if there are any
+                                    let prompt_index =
+                                        prompt_index + usize::from(!citations.is_empty());
+                                    let web_sources = web_search::search(
+                                        &web_search,
+                                        &conversation[prompt_index].content,
+                                    )
+                                    .await?;
+                                    if !web_sources.is_empty() {
+                                        conversation.insert(
+                                            prompt_index,
+                                            web_search::context_message(&web_sources),
+                                        );
+                                    }
+                                    web_sources
+                                }
+                                _ => vec![],
+                            };
+                            let sources = (citations, web_sources);
+                            if offered_tools.is_empty()
+                                && tool_exchanges.is_empty()
+                                && format.is_none()
+                            {
+                                let stream = backend
+                                    .chat_stream(model_name, conversation, params)
+                                    .await?;
+                                return Ok::<_, Error>((
+                                    sources,
+                                    new_summary,
+                                    Reply::Stream(stream),
+                                ));
+                            }
+                            let tool_reply = backend
+                                .chat_with_tools(
+                                    model_name,
+                                    conversation,
+                                    tool_exchanges,
+                                    offered_tools,
+                                    format,
+                                    params,
+                                )
+                                .await?;
+                            if !tool_reply.calls.is_empty() {
+                                return Ok((
+                                    sources,
+                                    new_summary,
+                                    Reply::ToolCalls(tool_reply.calls),
+                                ));
+                            }
+                            let chunk = ResponseChunk {
+                                content: tool_reply.content,
+                                stats: Some(tool_reply.stats),
+                            };
+                            // Replies offered tools or asked for as JSON come whole, so they arrive
+                            // in one chunk
+                            let stream: ResponseStream =
+                                Box::pin(stream::iter([Ok::<_, Error>(chunk)]));
+                            Ok((sources, new_summary, Reply::Stream(stream)))
+                        })
+                        .then(|result| match result {
+                            Ok(((citations, web_sources), new_summary, reply)) => {
+                                let found = new_summary
+                                    .map_or_else(Task::none, |new_summary| {
+                                        Task::done(Message::ContextSummarized(Ok(new_summary)))
+                                    })
+                                    .chain(Task::done(Message::SourcesFound(
+                                        citations,
+                                        web_sources,
+                                    )));
+                                match reply {
+                                    // Still generating while they run
+                                    Reply::ToolCalls(calls) => {
+                                        found.chain(Task::done(Message::ToolCallsRequested(calls)))
+                                    }
+                                    Reply::Stream(stream) => found
+                                        .chain(Task::run(stream, Message::HandleStreamResponse))
+                                        .chain(Task::done(Message::FlushStreamBuffer))
+                                        .chain(Task::done(Message::ToggleIsGenerating))
+                                        // Saved once generation is over so the finished response
+                                        // gets recorded in the conversation's history
+                                        .chain(Task::done(Message::SaveConversation))
+                                        .chain(Task::done(Message::GenerationFinished)),
+                                }
+                            }
+                            Err(err) => Task::done(Message::GenerationFailed(err))
+                                .chain(Task::done(Message::ToggleIsGenerating)),
+                        }),
+                    )
+                    .abortable();
+                self.conversation.generation = Some(handle);
+                // It carries on into its own conversation if another's opened while it's written
+                return match self.conversation.current_conversation.clone() {
+                    Some(conversation) => {
+                        generation.map(move |message| in_conversation(&conversation, message))
+                    }
+                    None => generation,
+                };
+            }
+            Message::StopGeneration => {
+                let Some(generation) = self.conversation.generation.take() else {
+                    return Task::none();
+                };
+                // What's been generated so far is kept, as it would be if the model had stopped
+                generation.abort();
+                let _ = self.update(Message::FlushStreamBuffer);
+                self.conversation.queued_tool_calls.clear();
+                self.file_pending_citations();
+                self.conversation.is_generating = false;
+                self.conversation.is_searching_web = false;
+                self.finish_streamed_markdown();
+                self.record_response_time();
+                return Task::done(Message::SaveConversation);
+            }
+            Message::GenerationFailed(err) => {
+                let notification = self.notify_if_unfocused(
+                    tr!(
+                        "notification-generation-failed",
+                        title = self.conversation_title()
+                    ),
+                    i18n::error_message(&err),
+                );
+                self.show_error(err, Some(Message::RetryGeneration));
+                return notification;
+            }
+            Message::RetryGeneration => {
+                // Asked for in the same format as the response it replaces
+                if let Some(format) = self
+                    .message_count()
+                    .checked_sub(1)
+                    .and_then(|index| self.conversation.formats.get(&index))
+                {
+                    self.conversation.pending_format = Some(format.clone());
+                }
+                if let Some((chat_message, markdown_items)) =
+                    self.conversation.chats_list.last_mut()
+                {
+                    if chat_message.role == MessageRole::Assistant {
+                        // A response that broke off before it said anything isn't worth keeping
+                        if chat_message.content.is_empty() {
+                            *markdown_items = Some(Arc::default());
+                        } else {
+                            self.change_branches(
+                                self.conversation.chats_list.len() - 1,
+                                Branch::keep_version,
+                            );
+                            self.conversation.chats_list.push((
+                                ChatMessage {
+                                    role: MessageRole::Assistant,
+                                    content: String::new(),
+                                    images: None,
+                                },
+                                Some(vec![]),
+                            ));
+                        }
+                        return Task::done(Message::StartGeneration);
+                    }
+                }
+            }
+            Message::SwitchVersion(index, version) => {
+                if self.conversation.is_generating {
+                    return Task::none();
+                }
+                self.change_branches(index, |conversation, index| {
+                    conversation.switch_version(index, version)
+                });
+                return Task::done(Message::SaveConversation);
+            }
+            Message::ToggleKnowledgePanel => {
+                self.knowledge_panel = match self.knowledge_panel {
+                    Some(_) => None,
+                    None => Some(
+                        self.conversation
+                            .knowledge_dir
+                            .as_ref()
+                            .map(|dir| dir.display().to_string())
+                            .unwrap_or_default(),
+                    ),
+                };
+            }
+            Message::UpdateKnowledgePath(path) => self.knowledge_panel = Some(path),
+            Message::AttachKnowledge => {
+                let Some(path) = self.knowledge_panel.as_ref() else {
+                    return Task::none();
+                };
+                let path = PathBuf::from(path.trim());
+                if !path.is_dir() {
+                    self.show_error(
+                        Error::Read {
+                            path,
+                            message: tr!("not-a-folder"),
+                        },
+                        None,
+                    );
+                    return Task::none();
+                }
+                self.conversation.knowledge_dir = Some(path);
+                self.conversation.has_unsaved_changes = true;
+                return Task::done(Message::IndexKnowledge);
+            }
+            Message::DetachKnowledge => {
+                self.conversation.knowledge_dir = None;
+                self.knowledge_panel = Some(String::new());
+                self.conversation.has_unsaved_changes = true;
+            }
+            Message::IndexKnowledge => {
+                if let Some(dir) = self.conversation.knowledge_dir.clone() {
+                    self.queue_job(Job::IndexKnowledge {
+                        backend: self.conversation.backend.clone(),
+                        dir,
+                        model: self.settings.embedding_model.clone(),
+                    });
+                }
+            }
+            Message::ToggleRelated => {
+                self.show_related = !self.show_related;
+                if self.show_related {
+                    return self.update(Message::EmbedConversations);
+                }
+            }
+            Message::EmbedConversations => {
+                let Some(conversations_dir) = self.conversations_dir.clone() else {
+                    return Task::none();
+                };
+                let is_up_to_date =
+                    self.conversation_embeddings
+                        .as_ref()
+                        .is_some_and(|embeddings| {
+                            embeddings.model == self.settings.embedding_model
+                                && self.conversation_times.iter().all(|(path, modified)| {
+                                    embeddings.embedded_at(path) == Some(*modified)
+                                })
+                        });
+                if self.embedding_job.is_some() || is_up_to_date {
+                    return Task::none();
+                }
+                self.embedding_job = Some(
+                    self.queue_job(Job::EmbedConversations {
+                        backend: self.conversation.backend.clone(),
+                        conversations_dir,
+                        conversations: self
+                            .conversation_times
+                            .iter()
+                            .map(|(path, modified)| (path.clone(), *modified))
+                            .collect(),
+                        model: self.settings.embedding_model.clone(),
+                    }),
+                );
+            }
+            Message::ShowEmbeddingsPlayground => {
+                self.embeddings_playground = Some(EmbeddingsPlayground {
+                    texts: vec![String::new(); 2],
+                    similarities: None,
+                    is_comparing: false,
+                });
+            }
+            Message::CloseEmbeddingsPlayground => self.embeddings_playground = None,
+            Message::UpdatePlaygroundText(index, text) => {
+                if let Some(playground) = self.embeddings_playground.as_mut() {
+                    if let Some(playground_text) = playground.texts.get_mut(index) {
+                        *playground_text = text;
+                        playground.similarities = None;
+                    }
+                }
+            }
+            Message::AddPlaygroundText => {
+                if let Some(playground) = self.embeddings_playground.as_mut() {
+                    playground.texts.push(String::new());
+                    playground.similarities = None;
+                }
+            }
+            Message::RemovePlaygroundText(index) => {
+                if let Some(playground) = self.embeddings_playground.as_mut() {
+                    // There's nothing to compare with fewer than two
+                    if index < playground.texts.len() && playground.texts.len() > 2 {
+                        playground.texts.remove(index);
+                        playground.similarities = None;
+                    }
+                }
+            }
+            Message::ComparePlaygroundTexts => {
+                let Some(playground) = self.embeddings_playground.as_mut() else {
+                    return Task::none();
+                };
+                if playground.texts.iter().any(|text| text.trim().is_empty()) {
+                    return Task::none();
+                }
+                playground.is_comparing = true;
+                return Task::perform(
+                    related::compare(
+                        self.conversation.backend.clone(),
+                        self.settings.embedding_model.clone(),
+                        playground.texts.clone(),
+                    ),
+                    Message::PlaygroundCompared,
+                );
+            }
+            Message::PlaygroundCompared(result) => {
+                let Some(playground) = self.embeddings_playground.as_mut() else {
+                    return Task::none();
+                };
+                playground.is_comparing = false;
+                match result {
+                    Ok(similarities) => playground.similarities = Some(similarities),
+                    Err(err) => self.show_error(err, Some(Message::ComparePlaygroundTexts)),
+                }
+            }
+            Message::SourcesFound(citations, web_sources) => {
+                self.conversation.pending_citations = citations;
+                self.conversation.pending_web_sources = web_sources;
+                self.conversation.is_searching_web = false;
+            }
+            Message::ToggleWebSearch => {
+                self.conversation.search_web = !self.conversation.search_web
+            }
+            Message::OpenLink(url) => {
+                if let Err(err) = std::process::Command::new("xdg-open").arg(&url).spawn() {
+                    self.toasts.push(Toast {
+                        message: tr!("couldnt-open-file", path = url, error = err.to_string()),
+                        actions: vec![],
+                    });
+                }
+            }
+            Message::SaveConversation => {
+                self.conversation.has_unsaved_changes = false;
+                if let Some(current_conversation) = self.conversation.current_conversation.clone() {
+                    return self.save_conversation(
+                        current_conversation,
+                        self.saved_conversation(),
+                        self.conversation.conversation_modified,
+                    );
+                }
+            }
+            Message::ConversationSaved(path, result) => {
+                let Some(SaveInFlight {
+                    last_modified,
+                    queued: Some(queued),
+                }) = self.saves_in_flight.remove(&path)
+                else {
+                    return self.conversation_saved(path, result);
+                };
+                match result {
+                    // The save after it is the one kept in the history
+                    Ok(SaveOutcome::Saved(modified)) => {
+                        self.set_conversation_modified(&path, modified);
+                        return self.save_conversation(path, queued, modified);
+                    }
+                    // Saved over the same conflicting copy, or tried again
+                    result => {
+                        let queued_save =
+                            self.save_conversation(path.clone(), queued, last_modified);
+                        return self.conversation_saved(path, result).chain(queued_save);
+                    }
+                }
+            }
+            Message::LoadConversation => {
+                if let Some(current_conversation) = self.conversation.current_conversation.clone() {
+                    return Task::perform(
+                        storage::load_conversation_with_modified_time(current_conversation),
+                        Message::ConversationLoaded,
+                    );
+                }
+            }
+            Message::ConversationLoaded(result) => match result {
+                Ok((mut conversation, modified)) => {
+                    self.conversation.conversation_modified = modified;
+                    if let Some(partial_response) = self.recovered_response.take() {
+                        restore_partial_response(&mut conversation.messages, partial_response);
+                        self.conversation.has_unsaved_changes = true;
+                    }
+                    self.set_generation_params(conversation.params);
+                    self.chat_editor = None;
+                    self.conversation.response_stats = conversation.stats;
+                    self.conversation.conversation_folder = conversation.folder;
+                    self.conversation.conversation_tags = conversation.tags;
+                    self.conversation.conversation_listing = conversation.listing;
+                    self.conversation.branches = conversation.branches;
+                    self.conversation.citations = conversation.citations;
+                    self.conversation.web_sources = conversation.web_sources;
+                    self.conversation.sent_times = conversation.sent_at;
+                    self.conversation.context_summary = conversation.summary;
+                    self.conversation.tool_calls = conversation.tool_calls;
+                    self.conversation.pending_tool_exchanges.clear();
+                    self.conversation.formats = conversation.formats;
+                    self.conversation.pending_format = None;
+                    self.conversation.collapsed_json.clear();
+                    self.conversation.expanded_reasoning.clear();
+                    self.conversation.translations.clear();
+                    self.conversation.response_language = conversation.response_language;
+                    self.stop_speaking();
+                    self.conversation.knowledge_dir = conversation.knowledge_dir;
+                    // Indexing again only embeds the documents that changed since
+                    let index_knowledge = self
+                        .conversation
+                        .knowledge_dir
+                        .as_ref()
+                        .is_some_and(|dir| !self.indexed_knowledge.contains_key(dir));
+                    if let Some(knowledge_panel) = self.knowledge_panel.as_mut() {
+                        *knowledge_panel = self
+                            .conversation
+                            .knowledge_dir
+                            .as_ref()
+                            .map(|dir| dir.display().to_string())
+                            .unwrap_or_default();
+                    }
+                    let server = self.settings.server_for(conversation.server.as_deref());
+                    // A server being switched to has its models listed once it's switched
+                    self.pending_model = self.launch_model.take().or(conversation.model);
+                    if server == self.conversation.server {
+                        self.select_conversation_model();
+                    }
+                    self.conversation.unloaded_chats = conversation.messages;
+                    self.conversation.chats_list = vec![];
+                    if index_knowledge {
+                        let _ = self.update(Message::IndexKnowledge);
+                    }
+                    return self
+                        .use_server(server)
+                        .chain(Task::done(Message::LoadEarlierMessages));
+                }
+                Err(err) => self.show_error(err, Some(Message::LoadConversation)),
+            },
+            Message::DismissToast(index) => {
+                self.toasts.remove(index);
+            }
+            Message::LoadEarlierMessages => {
+                let page_start = self
+                    .conversation
+                    .unloaded_chats
+                    .len()
+                    .saturating_sub(CONVERSATION_PAGE_SIZE);
+                let earlier_chats: Vec<(ChatMessage, Option<Arc<ParsedMarkdown>>)> = self
+                    .conversation
+                    .unloaded_chats
+                    .drain(page_start..)
+                    .map(|chat_message| (chat_message, None))
+                    .collect();
+                self.conversation.chats_list.splice(0..0, earlier_chats);
+                self.enforce_markdown_memory_budget();
+                if let Some(message_index) = self.pending_jump {
+                    if self.conversation.unloaded_chats.len() > message_index {
+                        return Task::done(Message::LoadEarlierMessages);
+                    }
+                    self.pending_jump = None;
+                    // Messages are laid out at their estimated heights until they're scrolled
+                    // into view, so that's where the message will be
+                    let height_above: f32 = self.conversation.chats_list
+                        [..message_index - self.conversation.unloaded_chats.len()]
+                        .iter()
+                        .map(|(chat_message, _markdown_items)| estimated_chat_height(chat_message))
+                        .sum();
+                    return scrollable::scroll_to(
+                        chat_scrollable_id(),
+                        scrollable::AbsoluteOffset {
+                            x: 0.0,
+                            y: height_above,
+                        },
+                    );
+                }
+                if let Some((loaded_messages, scroll_offset)) = self.pending_scroll {
+                    if self.conversation.chats_list.len() < loaded_messages
+                        && !self.conversation.unloaded_chats.is_empty()
+                    {
+                        return Task::done(Message::LoadEarlierMessages);
+                    }
+                    self.pending_scroll = None;
+                    return scrollable::snap_to(
+                        chat_scrollable_id(),
+                        scrollable::RelativeOffset {
+                            x: 0.0,
+                            y: scroll_offset,
+                        },
+                    );
+                }
+            }
+            Message::HandleStreamResponse(result) => match result {
+                Ok(response_chunk) => {
+                    self.conversation
+                        .stream_buffer
+                        .push_str(&response_chunk.content);
+                    if let Some(stats) = response_chunk.stats {
+                        // The response is complete with the last chunk, so it's flushed now to
+                        // file the statistics under its final text
+                        let _ = self.update(Message::FlushStreamBuffer);
+                        if let Some(index) = self.message_count().checked_sub(1) {
+                            self.conversation.response_stats.insert(index, stats);
+                        }
+                        self.file_pending_citations();
+                    }
+                }
+                Err(err) => {
+                    let notification = self.notify_if_unfocused(
+                        tr!(
+                            "notification-generation-failed",
+                            title = self.conversation_title()
+                        ),
+                        i18n::error_message(&err),
+                    );
+                    self.show_error(err, Some(Message::RetryGeneration));
+                    return notification;
+                }
+            },
+            Message::FlushStreamBuffer => {
+                if self.conversation.stream_buffer.is_empty() {
+                    return Task::none();
+                }
+                let Some((chat_message, markdown_items)) = self.conversation.chats_list.last_mut()
+                else {
+                    return Task::none();
+                };
+                self.conversation.has_unsaved_changes = true;
+                chat_message
+                    .content
+                    .push_str(&self.conversation.stream_buffer);
+                self.conversation.stream_buffer.clear();
+                // Reasoning is shown as it was written rather than as markdown
+                let answer = reasoning::answer(&chat_message.content);
+                *markdown_items = Some(Arc::new(if self.conversation.is_generating {
+                    self.conversation.streamed_markdown.parse(answer)
+                } else {
+                    parse_markdown(answer)
+                }));
+                self.enforce_markdown_memory_budget();
+                if self.conversation.is_generating && self.is_following_stream {
+                    return scroll_chat_to_bottom();
+                }
+            }
+            Message::CheckSystemTheme => self.is_system_dark = system_prefers_dark(),
+            Message::SetResponseLanguage(response_language) => {
+                self.conversation.response_language = response_language;
+                self.conversation.has_unsaved_changes = true;
+            }
+            Message::TranslateResponse(content, language) => {
+                let Some(model) = self.conversation.current_model.as_ref() else {
+                    return Task::none();
+                };
+                let hash = content_hash(&content);
+                self.conversation.translations.insert(
+                    hash,
+                    Translation {
+                        language: language.native_name.to_string(),
+                        text: None,
+                    },
+                );
+                return Task::perform(
+                    translation::translate(
+                        self.conversation.backend.clone(),
+                        model.name.clone(),
+                        // The reasoning is left out, as it's the model thinking
+                        reasoning::answer(&content).to_string(),
+                        language.name.to_string(),
+                    ),
+                    move |result| Message::Translated(hash, result),
+                );
+            }
+            Message::Translated(hash, result) => match result {
+                Ok(translated) => {
+                    if let Some(translation) = self.conversation.translations.get_mut(&hash) {
+                        let markdown_items = parse_markdown(&translated);
+                        translation.text = Some((translated, markdown_items));
+                    }
+                }
+                Err(err) => {
+                    self.conversation.translations.remove(&hash);
+                    self.show_error(err, None);
+                }
+            },
+            Message::CloseTranslation(hash) => {
+                self.conversation.translations.remove(&hash);
+            }
+            Message::ReadAloud(content) => {
+                // Only one response is read at a time
+                self.stop_speaking();
+                let hash = content_hash(&content);
+                // The reasoning is skipped, as it's the model thinking rather than answering
+                let answer = reasoning::answer(&content);
+                let (speak, handle) = Task::perform(
+                    speech::speak(self.settings.speech.clone(), speech::plain_text(answer)),
+                    move |result| Message::SpeechFinished(hash, result),
+                )
+                .abortable();
+                self.speaking = Some((hash, handle));
+                return speak;
+            }
+            Message::StopSpeaking => self.stop_speaking(),
+            Message::SpeechFinished(hash, result) => {
+                if self
+                    .speaking
+                    .as_ref()
+                    .is_some_and(|(speaking, _handle)| *speaking == hash)
+                {
+                    self.speaking = None;
+                }
+                if let Err(err) = result {
+                    self.show_error(err, None);
+                }
+            }
+            Message::VoicesListed(result) => match result {
+                Ok(voices) => self.voices = voices,
+                // Without espeak-ng the voice can still be typed in, e.g. before installing it
+                Err(err) => tracing::warn!("Couldn't list the espeak-ng voices: {err}"),
+            },
+            Message::StartDictation => {
+                let (stop, stopped) = oneshot::channel();
+                self.dictation = Some(DictationState::Listening(stop));
+                let settings = self.settings.dictation.clone();
+                return Task::perform(
+                    async move {
+                        let samples = dictation::record(&settings, async move {
+                            // Dropping the sender stops the recording too
+                            let _ = stopped.await;
+                        })
+                        .await?;
+                        dictation::transcribe(settings, samples).await
+                    },
+                    Message::DictationTranscribed,
+                );
+            }
+            Message::StopDictation => {
+                if let Some(DictationState::Listening(stop)) = self.dictation.take() {
+                    let _ = stop.send(());
+                    self.dictation = Some(DictationState::Transcribing);
+                }
+            }
+            Message::DictationTranscribed(result) => {
+                self.dictation = None;
+                match result {
+                    // Put where the cursor is, so a prompt can be part typed and part dictated
+                    Ok(transcript) if !transcript.is_empty() => self
+                        .prompt
+                        .perform(text_editor::Action::Edit(Edit::Paste(Arc::new(transcript)))),
+                    Ok(_) => {}
+                    Err(err) => self.show_error(err, None),
+                }
+            }
+            Message::ToolCallsRequested(calls) => {
+                self.conversation.queued_tool_calls.extend(calls);
+                return self.run_next_tool_call();
+            }
+            Message::ConfirmToolCall => {
+                if self.conversation.queued_tool_calls.is_empty() {
+                    return Task::none();
+                }
+                let call = self.conversation.queued_tool_calls.remove(0);
+                return self.run_tool_call(call);
+            }
+            Message::DeclineToolCall => {
+                if self.conversation.queued_tool_calls.is_empty() {
+                    return Task::none();
+                }
+                let call = self.conversation.queued_tool_calls.remove(0);
+                self.conversation
+                    .pending_tool_exchanges
+                    .push(tools::declined(call));
+                return self.run_next_tool_call();
+            }
+            Message::ToolCallFinished(tool_exchange) => {
+                self.conversation.pending_tool_exchanges.push(tool_exchange);
+                return self.run_next_tool_call();
+            }
+            Message::MathRendered(hash, result) => match result {
+                Ok(svg) => {
+                    self.math_images
+                        .insert(hash, Some(Handle::from_memory(svg)));
+                }
+                // Left as text, as it would be if there were no renderer
+                Err(err) => tracing::warn!("{err}"),
+            },
+            Message::SummarizeContext => {
+                let Some(model) = self.conversation.current_model.as_ref() else {
+                    return Task::none();
+                };
+                let conversation = self.full_conversation();
+                let Some(covers) = self.summary_needed(&conversation) else {
+                    return Task::none();
+                };
+                self.conversation.is_summarizing_context = true;
+                return Task::perform(
+                    context::summarize(
+                        self.conversation.backend.clone(),
+                        model.name.clone(),
+                        conversation,
+                        covers,
+                        self.conversation.context_summary.clone(),
+                    ),
+                    Message::ContextSummarized,
+                );
+            }
+            Message::ContextSummarized(result) => {
+                self.conversation.is_summarizing_context = false;
+                match result {
+                    Ok(context_summary) => {
+                        self.conversation.context_summary = Some(context_summary);
+                        self.conversation.has_unsaved_changes = true;
+                    }
+                    Err(err) => self.show_error(err, Some(Message::SummarizeContext)),
+                }
+            }
+            Message::JumpToLatest => {
+                self.is_following_stream = true;
+                return scroll_chat_to_bottom();
+            }
+            Message::NewChat => {
+                self.system_prompt_editor = None;
+                self.switch_draft(None);
+                self.send_to_background();
+                self.chat_editor = None;
+                self.set_generation_params(GenerationParams::default());
+                self.conversation.current_conversation = None;
+                self.conversation.conversation_modified = None;
+                self.conversation.chats_list = vec![];
+                self.conversation.unloaded_chats = vec![];
+                self.conversation.response_stats.clear();
+                self.conversation.branches.clear();
+                self.conversation.citations.clear();
+                self.conversation.web_sources.clear();
+                self.conversation.sent_times.clear();
+                self.conversation.context_summary = None;
+                self.conversation.tool_calls.clear();
+                self.conversation.pending_tool_exchanges.clear();
+                self.conversation.formats.clear();
+                self.conversation.pending_format = None;
+                self.conversation.collapsed_json.clear();
+                self.conversation.expanded_reasoning.clear();
+                self.conversation.translations.clear();
+                self.conversation.response_language = None;
+                self.stop_speaking();
+                self.conversation.knowledge_dir = None;
+                if let Some(knowledge_panel) = self.knowledge_panel.as_mut() {
+                    knowledge_panel.clear();
+                }
+                self.conversation.conversation_folder = None;
+                self.conversation.conversation_tags = vec![];
+                self.conversation.conversation_listing = Listing::default();
+                self.pending_model = None;
+                if let Some(default_model) = self
+                    .settings
+                    .default_model
+                    .as_ref()
+                    .and_then(|name| self.models_list.iter().find(|model| model.name == *name))
+                {
+                    self.conversation.current_model = Some(default_model.clone());
+                }
+                return self.use_server(self.settings.server());
+            }
+            Message::NewChatButtonPressed => {
+                return Task::done(Message::SaveConversation).chain(Task::done(Message::NewChat))
+            }
+            Message::LoadConversationList => {
+                let conversations_dir = self.conversations_dir.clone().ok_or(Error::NoAppDir);
+                return Task::perform(
+                    async move { storage::conversation_times(&conversations_dir?).await },
+                    Message::SetConversationsList,
+                )
+                .chain(Task::done(Message::LoadConflicts));
+            }
+            Message::ConversationsMoved(result) => {
+                if let Err(err) = result {
+                    self.show_error(err, None);
+                }
+                return Task::done(Message::LoadConversationList);
+            }
+            Message::ConversationFilesChanged(paths) => {
+                // Anything saving to the database, this window included, changes its files, and
+                // listing the conversations again picks out which ones were saved
+                if paths.iter().any(|path| {
+                    storage::is_database_file(path)
+                        || storage::is_database_conflict(path)
+                        || storage::conflict_original(path).is_some()
+                }) {
+                    return Task::done(Message::LoadConversationList);
+                }
+            }
+            Message::BackgroundWorker(event) => match event {
+                WorkerEvent::Ready(worker) => {
+                    for (id, job) in self.pending_jobs.drain(..) {
+                        worker.queue(id, job);
+                    }
+                    self.worker = Some(worker);
+                }
+                WorkerEvent::Started(id) => self.set_job_progress(id, 0.0),
+                WorkerEvent::Progress(id, progress) => self.set_job_progress(id, progress),
+                WorkerEvent::Finished(id, result) => {
+                    self.background_jobs
+                        .retain(|job_status| job_status.id != id);
+                    if self.embedding_job == Some(id) {
+                        self.embedding_job = None;
+                    }
+                    match result {
+                        Ok(JobOutput::KnowledgeIndexed(dir, chunk_count)) => {
+                            self.indexed_knowledge.insert(dir, chunk_count);
+                        }
+                        Ok(JobOutput::ConversationsEmbedded(embeddings)) => {
+                            self.conversation_embeddings = Some(embeddings);
+                        }
+                        Ok(JobOutput::ConversationIndex(index)) => {
+                            for (path, summary) in index {
+                                match summary {
+                                    Some(summary) => {
+                                        self.conversation_index.insert(path, summary);
+                                    }
+                                    None => {
+                                        self.conversation_index.remove(&path);
+                                    }
+                                }
+                            }
+                        }
+                        Err(err) => self.toasts.push(Toast {
+                            message: err,
+                            actions: vec![],
+                        }),
+                    }
+                }
+            },
+            Message::ToggleBackgroundJobs => self.show_background_jobs = !self.show_background_jobs,
+            Message::ChatScrolled(viewport) => {
+                let offset = viewport.absolute_offset().y;
+                // The viewport also changes as the response grows, which only scrolling up moves
+                // it back from
+                let is_scrolled_up = self
+                    .chat_viewport
+                    .is_some_and(|(previous_offset, _height)| offset < previous_offset);
+                self.is_chat_at_bottom =
+                    viewport.content_bounds().height - offset - viewport.bounds().height
+                        < CHAT_BOTTOM_THRESHOLD;
+                if self.is_chat_at_bottom {
+                    self.is_following_stream = true;
+                } else if is_scrolled_up {
+                    self.is_following_stream = false;
+                }
+                self.chat_viewport = Some((offset, viewport.bounds().height));
+                self.chat_scroll_offset = viewport.relative_offset().y;
+                self.enforce_markdown_memory_budget();
+                let render_math = self.render_visible_math();
+                if viewport.relative_offset().y == 0.0
+                    && !self.conversation.unloaded_chats.is_empty()
+                {
+                    return render_math.chain(Task::done(Message::LoadEarlierMessages));
+                }
+                return render_math;
+            }
+            Message::Autosave => {
+                if self.conversation.has_unsaved_changes {
+                    return Task::done(Message::SaveConversation);
+                }
+            }
+            Message::Checkpoint => {
+                let recovery_state = RecoveryState {
+                    conversation: self.conversation.current_conversation.clone(),
+                    draft: self.prompt_text(),
+                    partial_response: self
+                        .conversation
+                        .chats_list
+                        .last()
+                        .filter(|_| self.conversation.is_generating)
+                        .map(|(chat_message, _markdown_items)| {
+                            format!(
+                                "{}{}",
+                                chat_message.content, self.conversation.stream_buffer
+                            )
+                        }),
+                };
+                if recovery_state != self.last_checkpoint {
+                    self.last_checkpoint = recovery_state.clone();
+                    return Task::perform(recovery::save(recovery_state), Message::CheckpointSaved);
+                }
+            }
+            Message::CheckpointSaved(result) => {
+                if let Err(err) = result {
+                    tracing::warn!("Couldn't checkpoint work in progress: {err}");
+                }
+            }
+            Message::RecoveryLoaded(recovery_state) => {
+                if let Some(recovery_state) = recovery_state {
+                    self.toasts.push(Toast {
+                        message: tr!("recovery-prompt"),
+                        actions: vec![
+                            (tr!("restore"), Message::RestoreRecovery(recovery_state)),
+                            (tr!("discard"), Message::DiscardRecovery),
+                        ],
+                    });
+                }
+            }
+            Message::RestoreRecovery(recovery_state) => {
+                self.dismiss_recovery_prompt();
+                self.set_draft(recovery_state.conversation.clone(), recovery_state.draft);
+                self.recovered_response = recovery_state.partial_response;
+                if let Some(conversation) = recovery_state.conversation {
+                    return Task::done(Message::SetConversationFile(Some(conversation)));
+                }
+            }
+            Message::DiscardRecovery => {
+                self.dismiss_recovery_prompt();
+                return Task::perform(
+                    recovery::save(RecoveryState::default()),
+                    Message::CheckpointSaved,
+                );
+            }
+            Message::Activated(activation) => {
+                if activation.quick_chat {
+                    return Task::done(Message::OpenQuickChat);
+                }
+                let focus_window = self.show_window();
+                let open_conversation = match activation.files.last() {
+                    Some(file) => {
+                        self.launch_model = activation.model.clone();
+                        self.open_conversation_file(file.clone())
+                    }
+                    // A prompt on its own, or `--new`, starts a conversation of its own
+                    None if activation.new || activation.prompt.is_some() => {
+                        let save_task = self.update(Message::SaveConversation);
+                        let _ = self.update(Message::NewChat);
+                        save_task
+                    }
+                    None => Task::none(),
+                };
+                // Selected once the models are listed, if they aren't yet
+                if let (None, Some(model)) = (activation.files.last(), activation.model) {
+                    self.pending_model = Some(model);
+                    self.select_conversation_model();
+                }
+                if let Some(prompt) = activation.prompt {
+                    // Opening a file happens after this, so the prompt is kept as its draft
+                    let conversation = activation
+                        .files
+                        .last()
+                        .cloned()
+                        .or(self.conversation.current_conversation.clone());
+                    self.set_draft(conversation, prompt);
+                    self.send_when_ready = activation.send;
+                }
+                return Task::batch([focus_window, open_conversation, self.send_if_ready()]);
+            }
+            Message::IpcRequest(request, responder) => {
+                let response = match request {
+                    Request::GetLastResponse => Response::LastResponse(
+                        self.conversation
+                            .chats_list
+                            .iter()
+                            .rev()
+                            .find(|(chat_message, _)| chat_message.role == MessageRole::Assistant)
+                            .map(|(chat_message, _)| chat_message.content.clone()),
+                    ),
+                    _ => Response::Done,
+                };
+                responder.respond(response);
+                return match request {
+                    Request::Activate(activation) => Task::done(Message::Activated(activation)),
+                    Request::OpenConversation(path) => Task::done(Message::Activated(Activation {
+                        files: vec![path],
+                        ..Activation::default()
+                    })),
+                    Request::NewPromptFromText(text) => {
+                        Task::done(Message::Activated(Activation {
+                            prompt: Some(text),
+                            send: true,
+                            ..Activation::default()
+                        }))
+                    }
+                    Request::GetLastResponse => Task::none(),
+                };
+            }
+            Message::Tray(event) => {
+                return match event {
+                    tray::Event::ShowWindow => self.show_window(),
+                    tray::Event::QuickChat => Task::done(Message::OpenQuickChat),
+                    tray::Event::Quit => {
+                        self.quitting = true;
+                        self.window
+                            .map_or_else(Task::none, |id| Task::done(Message::CloseRequested(id)))
+                    }
+                };
+            }
+            Message::OpenQuickChat => {}
+            Message::WindowFocusChanged(is_focused) => self.is_window_focused = is_focused,
+            Message::GenerationFinished => {
+                let was_slow = self
+                    .conversation
+                    .generation_started
+                    .take()
+                    .is_some_and(|started| started.elapsed() >= notifications::MIN_GENERATION_TIME);
+                let notification = match self.conversation.chats_list.last() {
+                    Some((chat_message, _markdown_items)) if was_slow => self.notify_if_unfocused(
+                        tr!(
+                            "notification-response-ready",
+                            title = self.conversation_title()
+                        ),
+                        notifications::first_line(reasoning::answer(&chat_message.content)),
+                    ),
+                    _ => Task::none(),
+                };
+                return Task::batch([
+                    notification,
+                    self.generate_title(),
+                    self.render_visible_math(),
+                ]);
+            }
+            Message::UpdateSearch(search_query) => self.search_query = search_query,
+            Message::OpenSearchResult(path, message_index) => {
+                self.pending_jump = message_index;
+                return Task::done(Message::SetConversationFile(Some(path)));
+            }
+            Message::TitleGenerated(path, result) => match result {
+                Ok(Some(title)) => return self.rename_conversation(path, title),
+                Ok(None) => {}
+                Err(err) => tracing::warn!("Couldn't generate a conversation title: {err}"),
+            },
+            Message::NotificationClicked(conversation) => {
+                if let Some(conversation) = conversation {
+                    return Task::done(Message::Activated(Activation {
+                        files: vec![conversation],
+                        ..Activation::default()
+                    }));
+                }
+            }
+            Message::UpdateNewProfile(name) => {
+                if let Some(profile_picker) = self.profile_picker.as_mut() {
+                    profile_picker.new_profile = name;
+                }
+            }
+            Message::ChooseProfile(name) => {
+                if let Err(err) = profile::select(name) {
+                    self.show_error(err, None);
+                    return Task::none();
+                }
+                let activation = self.pending_activation.take().unwrap_or_default();
+                if instance::forward(&activation) {
+                    tracing::info!("Handed the launch over to the profile's running instance");
+                    return iced::exit();
+                }
+                self.profile_picker = None;
+                return self.start(activation);
+            }
+            Message::UpdatePassphrase(passphrase) => self.passphrase = passphrase,
+            Message::SubmitPassphrase => {
+                let passphrase = self.passphrase.clone();
+                return match self.passphrase_screen {
+                    Some(PassphraseScreen::Unlock) => {
+                        Task::perform(crypto::unlock(passphrase), Message::Unlocked)
+                    }
+                    Some(PassphraseScreen::Choose) => match self.conversations_dir.clone() {
+                        Some(conversations_dir) => Task::perform(
+                            crypto::enable(passphrase, conversations_dir),
+                            Message::EncryptionChanged,
+                        ),
+                        None => {
+                            self.show_error(Error::NoAppDir, None);
+                            Task::none()
+                        }
+                    },
+                    None => Task::none(),
+                };
+            }
+            Message::CancelPassphrase => {
+                self.passphrase_screen = None;
+                self.passphrase.clear();
+            }
+            Message::Unlocked(result) => match result {
+                Ok(()) => {
+                    self.passphrase_screen = None;
+                    self.passphrase.clear();
+                    return Task::batch([
+                        Self::restore(self.pending_activation.take().unwrap_or_default()),
+                        Task::done(Message::LoadConversationList),
+                        self.sync_encryption(),
+                    ]);
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::EncryptionChanged(result) => {
+                match result {
+                    Ok(()) => {
+                        self.passphrase_screen = None;
+                        self.passphrase.clear();
+                    }
+                    Err(err) => self.show_error(err, None),
+                }
+                return Task::done(Message::LoadConversationList);
+            }
+            Message::LoadConflicts => {
+                let conversations_dir = self.conversations_dir.clone().ok_or(Error::NoAppDir);
+                return Task::perform(
+                    async move {
+                        let conversations_dir = conversations_dir?;
+                        Ok((
+                            storage::list_conflicts(&conversations_dir)?,
+                            storage::list_database_conflicts(&conversations_dir)?,
+                        ))
+                    },
+                    Message::SetConflicts,
+                );
+            }
+            Message::SetConflicts(result) => match result {
+                Ok((conflicts, database_conflicts)) => {
+                    self.conflicts = conflicts;
+                    self.database_conflicts = database_conflicts;
+                }
+                Err(err) => self.show_error(err, Some(Message::LoadConflicts)),
+            },
+            Message::ResolveConflict(conflict, resolution) => {
+                self.conflicts.retain(|other| *other != conflict);
+                return Task::perform(
+                    storage::resolve_conflict(conflict.clone(), resolution),
+                    move |result| Message::ConflictResolved(conflict.clone(), result),
+                );
+            }
+            Message::ConflictResolved(conflict, result) => {
+                if let Err(err) = result {
+                    self.show_error(err, None);
+                }
+                // Pick up the resolved version if it's the one on screen
+                if self.conversation.current_conversation.as_ref() == Some(&conflict.original)
+                    && !self.conversation.is_generating
+                {
+                    return Task::done(Message::LoadConversation)
+                        .chain(Task::done(Message::LoadConflicts));
+                }
+                return Task::done(Message::LoadConflicts);
+            }
+            Message::HistoryCommitted(result) => {
+                if let Err(err) = result {
+                    self.show_error(err, None);
+                }
+            }
+            Message::ShowHistory => {
+                if let (Some(conversations_dir), Some(conversation)) = (
+                    self.conversations_dir.clone(),
+                    self.conversation.current_conversation.clone(),
+                ) {
+                    return Task::perform(
+                        history::versions(conversations_dir, conversation),
+                        Message::HistoryLoaded,
+                    );
+                }
+            }
+            Message::HistoryLoaded(result) => match result {
+                Ok(versions) => {
+                    self.history_view = Some(HistoryView {
+                        versions,
+                        preview: None,
+                    })
+                }
+                Err(err) => self.show_error(err, Some(Message::ShowHistory)),
+            },
+            Message::PreviewVersion(index) => {
+                let Some(version) = self
+                    .history_view
+                    .as_ref()
+                    .and_then(|history_view| history_view.versions.get(index))
+                else {
+                    return Task::none();
+                };
+                if let (Some(conversations_dir), Some(conversation)) = (
+                    self.conversations_dir.clone(),
+                    self.conversation.current_conversation.clone(),
+                ) {
+                    return Task::perform(
+                        history::load_version(
+                            conversations_dir,
+                            conversation,
+                            version.commit.clone(),
+                        ),
+                        move |result| Message::VersionLoaded(index, result),
+                    );
+                }
+            }
+            Message::VersionLoaded(index, result) => match result {
+                Ok(conversation) => {
+                    if let Some(history_view) = self.history_view.as_mut() {
+                        history_view.preview = Some((index, conversation.messages));
+                    }
+                }
+                Err(err) => self.show_error(err, Some(Message::PreviewVersion(index))),
+            },
+            Message::RestoreVersion(version) => {
+                if let (Some(conversations_dir), Some(conversation)) = (
+                    self.conversations_dir.clone(),
+                    self.conversation.current_conversation.clone(),
+                ) {
+                    self.history_view = None;
+                    let commit_message = tr!(
+                        "history-restore-commit",
+                        title = self.conversation_title(),
+                        date = version.date.clone()
+                    );
+                    return Task::perform(
+                        history::restore_version(
+                            conversations_dir,
+                            conversation,
+                            version,
+                            commit_message,
+                        ),
+                        Message::VersionRestored,
+                    );
+                }
+            }
+            Message::VersionRestored(result) => match result {
+                Ok(()) => return Task::done(Message::LoadConversation),
+                Err(err) => self.show_error(err, None),
+            },
+            Message::CloseHistory => self.history_view = None,
+            Message::SaveCodeBlock(code_block) => {
+                let path = code_block.suggested_path();
+                return self.ask_where_to_save(code_block.code.into_bytes(), path);
+            }
+            Message::ExportConversation(path, format) => {
+                self.sidebar_action = None;
+                let copy_label = tr!("copy");
+                let include_reasoning = self.settings.include_reasoning;
+                // The open conversation is exported as shown, including anything not saved yet
+                let path = path
+                    .filter(|path| self.conversation.current_conversation.as_ref() != Some(path));
+                let Some(path) = path else {
+                    let title = self.conversation_title();
+                    let conversation = self.full_conversation();
+                    // Highlighting a long conversation takes a moment, so it's kept off the UI
+                    // thread
+                    return Task::perform(
+                        {
+                            let title = title.clone();
+                            async move {
+                                Ok(export::export(
+                                    format,
+                                    &title,
+                                    &conversation,
+                                    role_name,
+                                    &copy_label,
+                                    include_reasoning,
+                                ))
+                            }
+                        },
+                        move |result| Message::ConversationExported(title.clone(), format, result),
+                    );
+                };
+                let title = path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                return Task::perform(
+                    {
+                        let title = title.clone();
+                        async move {
+                            let conversation = storage::load_conversation(path).await?;
+                            Ok(export::export(
+                                format,
+                                &title,
+                                &conversation.messages,
+                                role_name,
+                                &copy_label,
+                                include_reasoning,
+                            ))
+                        }
+                    },
+                    move |result| Message::ConversationExported(title.clone(), format, result),
+                );
+            }
+            Message::ShareConversation => {
+                if self.settings.share_url.trim().is_empty() {
+                    return Task::done(Message::ExportConversation(None, export::Format::Html));
+                }
+                self.is_sharing = true;
+                let share_url = self.settings.share_url.clone();
+                let title = self.conversation_title();
+                let conversation = self.full_conversation();
+                let copy_label = tr!("copy");
+                let include_reasoning = self.settings.include_reasoning;
+                return Task::perform(
+                    async move {
+                        let page = export::export(
+                            export::Format::Html,
+                            &title,
+                            &conversation,
+                            role_name,
+                            &copy_label,
+                            include_reasoning,
+                        );
+                        share::upload(&share_url, &title, page).await
+                    },
+                    Message::ConversationShared,
+                );
+            }
+            Message::ConversationShared(result) => {
+                self.is_sharing = false;
+                match result {
+                    Ok(link) => {
+                        self.toasts.push(Toast {
+                            message: tr!("share-link-copied", link = link.as_str()),
+                            actions: vec![],
+                        });
+                        return Task::done(Message::CopyChat(link));
+                    }
+                    Err(err) => self.show_error(err, Some(Message::ShareConversation)),
+                }
+            }
+            Message::ConversationExported(title, format, result) => match result {
+                Ok(contents) => {
+                    let path = std::env::current_dir()
+                        .unwrap_or_default()
+                        .join(format!("{title}.{}", format.extension()));
+                    return self.ask_where_to_save(contents, path);
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::SavePathPicked(picked) => {
+                self.is_save_dialog_open = false;
+                match picked {
+                    Picked::File(path) => {
+                        if let Some((contents, _path)) = self.save_as.take() {
+                            return Task::perform(save_to_file(contents, path), Message::Saved);
+                        }
+                    }
+                    Picked::Cancelled => self.save_as = None,
+                    // The path's typed in instead
+                    Picked::NoDialog => {}
+                }
+            }
+            Message::UpdateSavePath(path) => {
+                if let Some((_contents, current_path)) = self.save_as.as_mut() {
+                    *current_path = path;
+                }
+            }
+            Message::ConfirmSave => {
+                if let Some((contents, path)) = self.save_as.take() {
+                    return Task::perform(
+                        save_to_file(contents, PathBuf::from(path)),
+                        Message::Saved,
+                    );
+                }
+            }
+            Message::CancelSave => self.save_as = None,
+            Message::Saved(result) => match result {
+                Ok(path) => self.toasts.push(Toast {
+                    message: tr!("saved-to", path = path.display().to_string()),
+                    actions: vec![(tr!("open-file"), Message::OpenFile(path))],
+                }),
+                Err(err) => self.show_error(err, None),
+            },
+            Message::OpenCodeBlock(code_block) => {
+                if let Err(err) = code_blocks::open_in_editor(&code_block) {
+                    self.toasts.push(Toast {
+                        message: tr!("couldnt-open-editor", error = err),
+                        actions: vec![],
+                    });
+                }
+            }
+            Message::SettingsFileChanged => {
+                return Task::perform(settings::load(), Message::SettingsLoaded);
+            }
+            Message::SettingsLoaded(result) => match result {
+                Ok(settings) => return self.apply_settings(settings),
+                Err(err) => {
+                    self.show_error(err, Some(Message::SettingsFileChanged));
+                    // Carry on with the default settings if they couldn't be loaded on launch,
+                    // without writing them over the file so it can still be fixed
+                    if self.loaded_settings.is_none() {
+                        return self.apply_settings(Settings::default());
+                    }
+                }
+            },
+            Message::EditSystemPrompt => {
+                self.system_prompt_editor = Some(SystemPromptEditor {
+                    system_prompt: text_editor::Content::with_text(
+                        self.system_prompt().unwrap_or_default(),
+                    ),
+                    persona_name: String::new(),
+                });
+            }
+            Message::SystemPromptAction(action) => {
+                if let Some(editor) = self.system_prompt_editor.as_mut() {
+                    editor.system_prompt.perform(action);
+                }
+            }
+            Message::UpdatePersonaName(name) => {
+                if let Some(editor) = self.system_prompt_editor.as_mut() {
+                    editor.persona_name = name;
+                }
+            }
+            Message::ApplySystemPrompt => {
+                if let Some(editor) = self.system_prompt_editor.take() {
+                    return self.set_system_prompt(editor.system_prompt.text());
+                }
+            }
+            Message::CancelSystemPrompt => self.system_prompt_editor = None,
+            Message::UsePersona(persona) => match self.system_prompt_editor.as_mut() {
+                Some(editor) => {
+                    editor.system_prompt = text_editor::Content::with_text(&persona.system_prompt);
+                    editor.persona_name = persona.name;
+                }
+                None => return self.set_system_prompt(persona.system_prompt),
+            },
+            Message::SavePersona => {
+                let Some(editor) = self.system_prompt_editor.as_ref() else {
+                    return Task::none();
+                };
+                let persona = Persona {
+                    name: editor.persona_name.trim().to_string(),
+                    system_prompt: editor.system_prompt.text().trim().to_string(),
+                };
+                match self
+                    .personas
+                    .iter_mut()
+                    .find(|saved_persona| saved_persona.name == persona.name)
+                {
+                    Some(saved_persona) => *saved_persona = persona,
+                    None => self.personas.push(persona),
+                }
+                return Task::perform(
+                    personas::save(self.personas.clone()),
+                    Message::PersonasSaved,
+                );
+            }
+            Message::ToggleParamsPanel => {
+                self.params_panel = match self.params_panel {
+                    Some(_) => None,
+                    None => Some(ParamsPanel::new(self.conversation.generation_params)),
+                };
+            }
+            Message::UpdateGenerationParam(param, input) => {
+                let Some(params_panel) = self.params_panel.as_mut() else {
+                    return Task::none();
+                };
+                let mut generation_params = self.conversation.generation_params;
+                if param.set(&mut generation_params, &input)
+                    && generation_params != self.conversation.generation_params
+                {
+                    self.conversation.generation_params = generation_params;
+                    self.conversation.has_unsaved_changes = true;
+                }
+                params_panel.inputs[param as usize] = input;
+            }
+            Message::ToggleConversationMenu(path) => {
+                self.sidebar_action = match self.sidebar_action.take() {
+                    Some(sidebar_action) if *sidebar_action.path() == path => None,
+                    _ => Some(SidebarAction::Menu(path)),
+                };
+            }
+            Message::SetSidebarAction(sidebar_action) => self.sidebar_action = sidebar_action,
+            Message::UpdateRenameTitle(title) => {
+                if let Some(SidebarAction::Rename(_, rename_title)) = self.sidebar_action.as_mut() {
+                    *rename_title = title;
+                }
+            }
+            Message::RenameConversation => {
+                let Some(SidebarAction::Rename(path, title)) = self.sidebar_action.take() else {
+                    return Task::none();
+                };
+                if title.trim().is_empty() {
+                    return Task::none();
+                }
+                return self.rename_conversation(path, title);
+            }
+            Message::ConversationRenamed(old_path, result) => match result {
+                Ok((new_path, modified)) => {
+                    if self.conversation.current_conversation.as_ref() == Some(&old_path) {
+                        self.conversation.conversation_modified = modified;
+                        self.conversation.current_conversation = Some(new_path.clone());
+                    }
+                    if let Some(mut background) = self.background_conversations.remove(&old_path) {
+                        background.conversation_modified = modified;
+                        background.current_conversation = Some(new_path.clone());
+                        self.background_conversations
+                            .insert(new_path.clone(), background);
+                    }
+                    // Whatever's still being generated for it is sent on to its new name
+                    self.renamed_conversations.remove(&new_path);
+                    self.renamed_conversations
+                        .insert(old_path.clone(), new_path.clone());
+                    if let Some(draft) = self.drafts.remove(&Some(old_path.clone())) {
+                        self.drafts.insert(Some(new_path.clone()), draft);
+                    }
+                    if let Some(summary) = self.conversation_index.remove(&old_path) {
+                        self.conversation_index.insert(new_path, summary);
+                    }
+                    return Task::done(Message::LoadConversationList);
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::UpdateOrganizeFolder(folder) => {
+                if let Some(SidebarAction::Organize(_, organize_folder, _)) =
+                    self.sidebar_action.as_mut()
+                {
+                    *organize_folder = folder;
+                }
+            }
+            Message::UpdateOrganizeTags(tags) => {
+                if let Some(SidebarAction::Organize(_, _, organize_tags)) =
+                    self.sidebar_action.as_mut()
+                {
+                    *organize_tags = tags;
+                }
+            }
+            Message::OrganizeConversation => {
+                let Some(SidebarAction::Organize(path, folder, tags)) = self.sidebar_action.take()
+                else {
+                    return Task::none();
+                };
+                let folder = conversation::parse_folder(&folder);
+                let tags = conversation::parse_tags(&tags);
+                // The open conversation is saved with its folder and tags, so writing them to the
+                // file separately would be undone by the next save
+                if self.conversation.current_conversation.as_ref() == Some(&path) {
+                    self.conversation.conversation_folder = folder.clone();
+                    self.conversation.conversation_tags = tags.clone();
+                    return Task::done(Message::SaveConversation).chain(Task::done(
+                        Message::ConversationOrganized(path, folder, tags, Ok(())),
+                    ));
+                }
+                return Task::perform(
+                    storage::organize_conversation(path.clone(), folder.clone(), tags.clone()),
+                    move |result| {
+                        Message::ConversationOrganized(
+                            path.clone(),
+                            folder.clone(),
+                            tags.clone(),
+                            result,
+                        )
+                    },
+                );
+            }
+            Message::ConversationOrganized(path, folder, tags, result) => match result {
+                Ok(()) => {
+                    if let Some(summary) = self.conversation_index.get_mut(&path) {
+                        summary.folder = folder;
+                        summary.tags = tags;
+                    }
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::FilterByTag(tag) => self.tag_filter = tag,
+            Message::SetListing(path, listing) => {
+                self.sidebar_action = None;
+                // Like its folder and tags, the open conversation's listing is saved with it
+                if self.conversation.current_conversation.as_ref() == Some(&path) {
+                    self.conversation.conversation_listing = listing;
+                    return Task::done(Message::SaveConversation)
+                        .chain(Task::done(Message::ListingSet(path, listing, Ok(()))));
+                }
+                return Task::perform(storage::set_listing(path.clone(), listing), move |result| {
+                    Message::ListingSet(path.clone(), listing, result)
+                });
+            }
+            Message::ListingSet(path, listing, result) => match result {
+                Ok(()) => {
+                    if let Some(summary) = self.conversation_index.get_mut(&path) {
+                        summary.listing = listing;
+                    }
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::ToggleArchived => self.show_archived = !self.show_archived,
+            Message::ConversationImported(file, result) => match result {
+                Ok(path) => {
+                    if let Some(draft) = self.drafts.remove(&Some(file)) {
+                        self.drafts.insert(Some(path.clone()), draft);
+                    }
+                    return Task::done(Message::SetConversationFile(Some(path)));
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::ToggleImportPanel => {
+                self.import_panel = match self.import_panel {
+                    Some(_) => None,
+                    None => Some(
+                        import::ollama_history_file()
+                            .map(|path| path.display().to_string())
+                            .unwrap_or_default(),
+                    ),
+                };
+            }
+            Message::UpdateImportPath(path) => self.import_panel = Some(path),
+            Message::ImportConversations => {
+                let (Some(path), Some(conversations_dir)) =
+                    (self.import_panel.take(), self.conversations_dir.clone())
+                else {
+                    return Task::none();
+                };
+                return Task::perform(
+                    import::import_file(PathBuf::from(path.trim()), conversations_dir),
+                    Message::ConversationsImported,
+                );
+            }
+            Message::ConversationsImported(result) => match result {
+                Ok(count) => {
+                    self.toasts.push(Toast {
+                        message: tr!("conversations-imported", count = count),
+                        actions: vec![],
+                    });
+                    return Task::done(Message::LoadConversationList);
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::OpenInNewWindow(_) => self.sidebar_action = None,
+            Message::DuplicateConversation(path) => {
+                self.sidebar_action = None;
+                return Task::perform(
+                    storage::duplicate_conversation(path),
+                    Message::ConversationDuplicated,
+                );
+            }
+            Message::ConversationDuplicated(result) => match result {
+                Ok(_) => return Task::done(Message::LoadConversationList),
+                Err(err) => self.show_error(err, None),
+            },
+            Message::DeleteConversation(path) => {
+                self.sidebar_action = None;
+                return Task::perform(storage::delete_conversation(path.clone()), move |result| {
+                    Message::ConversationDeleted(path.clone(), result)
+                });
+            }
+            Message::ConversationDeleted(path, result) => match result {
+                Ok(()) => {
+                    if self.conversation.current_conversation.as_ref() == Some(&path) {
+                        self.conversation.has_unsaved_changes = false;
+                        let _ = self.update(Message::NewChat);
+                    }
+                    self.drafts.remove(&Some(path.clone()));
+                    self.conversation_index.remove(&path);
+                    return Task::done(Message::LoadConversationList);
+                }
+                Err(err) => self.show_error(err, None),
+            },
+            Message::ResetGenerationParams => {
+                if self.conversation.generation_params != GenerationParams::default() {
+                    self.conversation.has_unsaved_changes = true;
+                }
+                self.set_generation_params(GenerationParams::default());
+            }
+            Message::DeletePersona(name) => {
+                self.personas.retain(|persona| persona.name != name);
+                return Task::perform(
+                    personas::save(self.personas.clone()),
+                    Message::PersonasSaved,
+                );
+            }
+            Message::PersonasLoaded(result) => match result {
+                Ok(personas) => self.personas = personas,
+                Err(err) => self.show_error(err, None),
+            },
+            Message::PersonasSaved(result) => {
+                if let Err(err) = result {
+                    self.show_error(err, None);
+                }
+            }
+            Message::ToggleTemplates => {
+                self.template_panel = match self.template_panel {
+                    Some(_) => None,
+                    None => Some(TemplatePanel::default()),
+                };
+            }
+            Message::ToggleStructuredOutput => {
+                self.conversation.structured_output = match self.conversation.structured_output {
+                    Some(_) => None,
+                    None => Some(String::new()),
+                };
+            }
+            Message::UpdateOutputSchema(schema) => {
+                self.conversation.structured_output = Some(schema)
+            }
+            Message::ToggleJsonNode(response, path) => {
+                if !self
+                    .conversation
+                    .collapsed_json
+                    .remove(&(response, path.clone()))
+                {
+                    self.conversation.collapsed_json.insert((response, path));
+                }
+            }
+            Message::ToggleReasoning(index) => {
+                if !self.conversation.expanded_reasoning.remove(&index) {
+                    self.conversation.expanded_reasoning.insert(index);
+                }
+            }
+            Message::UpdateTemplateName(name) => {
+                if let Some(template_panel) = self.template_panel.as_mut() {
+                    template_panel.template_name = name;
+                }
+            }
+            Message::SaveTemplate => {
+                let Some(template_panel) = self.template_panel.as_mut() else {
+                    return Task::none();
+                };
+                let template = Template {
+                    name: template_panel.template_name.trim().to_string(),
+                    prompt: self.prompt_text(),
+                };
+                template_panel.template_name.clear();
+                match self
+                    .templates
+                    .iter_mut()
+                    .find(|saved_template| saved_template.name == template.name)
+                {
+                    Some(saved_template) => *saved_template = template,
+                    None => self.templates.push(template),
+                }
+                return Task::perform(
+                    templates::save(self.templates.clone()),
+                    Message::TemplatesSaved,
+                );
+            }
+            Message::DeleteTemplate(name) => {
+                self.templates.retain(|template| template.name != name);
+                return Task::perform(
+                    templates::save(self.templates.clone()),
+                    Message::TemplatesSaved,
+                );
+            }
+            Message::UseTemplate(template) => {
+                let variables = template.variables();
+                if variables.is_empty() {
+                    self.template_panel = None;
+                    self.prompt
+                        .perform(text_editor::Action::Edit(Edit::Paste(Arc::new(
+                            template.prompt,
+                        ))));
+                    return Task::none();
+                }
+                if let Some(template_panel) = self.template_panel.as_mut() {
+                    let values = variables
+                        .into_iter()
+                        .map(|variable| (variable, String::new()))
+                        .collect();
+                    template_panel.filling = Some((template, values));
+                }
+            }
+            Message::UpdateTemplateVariable(index, value) => {
+                if let Some((_template, values)) = self
+                    .template_panel
+                    .as_mut()
+                    .and_then(|template_panel| template_panel.filling.as_mut())
+                {
+                    if let Some((_variable, old_value)) = values.get_mut(index) {
+                        *old_value = value;
+                    }
+                }
+            }
+            Message::InsertTemplate => {
+                let Some((template, values)) = self
+                    .template_panel
+                    .take()
+                    .and_then(|template_panel| template_panel.filling)
+                else {
+                    return Task::none();
+                };
+                self.prompt
+                    .perform(text_editor::Action::Edit(Edit::Paste(Arc::new(
+                        template.fill(&values),
+                    ))));
+            }
+            Message::TemplatesLoaded(result) => match result {
+                Ok(templates) => self.templates = templates,
+                Err(err) => self.show_error(err, None),
+            },
+            Message::TemplatesSaved(result) => {
+                if let Err(err) = result {
+                    self.show_error(err, None);
+                }
+            }
+            Message::ShowSettings => {
+                self.settings_draft = Some(self.settings.clone());
+                if self.voices.is_empty() {
+                    return Task::perform(speech::voices(), Message::VoicesListed);
+                }
+            }
+            Message::UpdateSettingsDraft(settings) => self.settings_draft = Some(settings),
+            Message::SaveSettings(settings) => {
+                return Task::perform(settings::save(settings.clone()), move |result| {
+                    Message::SettingsSaved(settings.clone(), result)
+                });
+            }
+            Message::SettingsSaved(settings, result) => match result {
+                // Applied straight away rather than waiting for the settings file to be noticed
+                Ok(()) => {
+                    if self.settings_draft.as_ref() == Some(&settings) {
+                        self.settings_draft = None;
+                    }
+                    return self.apply_settings(settings);
+                }
+                Err(err) => self.show_error(err, Some(Message::SaveSettings(settings))),
+            },
+            Message::SwitchServer(server) => return self.use_server(server),
+            Message::CloseSettings => self.settings_draft = None,
+            Message::ShowBenchmark => {
+                self.benchmark_view = Some(BenchmarkView {
+                    prompts: text_editor::Content::with_text(self.prompt_text().trim()),
+                    selected_models: self
+                        .models_list
+                        .iter()
+                        .map(|model| model.name.clone())
+                        .collect(),
+                    queue: vec![],
+                    running: None,
+                    results: vec![],
+                    sort: (BenchmarkColumn::TokensPerSecond, false),
+                });
+            }
+            Message::CloseBenchmark => self.benchmark_view = None,
+            Message::EditBenchmarkPrompts(action) => {
+                if let Some(benchmark_view) = self.benchmark_view.as_mut() {
+                    benchmark_view.prompts.perform(action);
+                }
+            }
+            Message::ToggleBenchmarkModel(model_name, is_selected) => {
+                if let Some(benchmark_view) = self.benchmark_view.as_mut() {
+                    benchmark_view
+                        .selected_models
+                        .retain(|name| *name != model_name);
+                    if is_selected {
+                        benchmark_view.selected_models.push(model_name);
+                    }
+                }
+            }
+            Message::RunBenchmark => {
+                let Some(benchmark_view) = self.benchmark_view.as_mut() else {
+                    return Task::none();
+                };
+                let prompts = benchmark_view.prompts.text();
+                // Every prompt runs on each model in turn, so models are compared on equal terms
+                // even if the run is stopped early
+                benchmark_view.queue = prompts
+                    .lines()
+                    .map(str::trim)
+                    .filter(|prompt| !prompt.is_empty())
+                    .flat_map(|prompt| {
+                        self.models_list
+                            .iter()
+                            .filter(|model| benchmark_view.selected_models.contains(&model.name))
+                            .map(|model| (model.name.clone(), prompt.to_string()))
+                    })
+                    .collect();
+                benchmark_view.results.clear();
+                return self.run_next_benchmark();
+            }
+            Message::StopBenchmark => {
+                if let Some(benchmark_view) = self.benchmark_view.as_mut() {
+                    benchmark_view.queue.clear();
+                }
+            }
+            Message::BenchmarkRan(result) => {
+                let Some(benchmark_view) = self.benchmark_view.as_mut() else {
+                    return Task::none();
+                };
+                benchmark_view.running = None;
+                match result {
+                    Ok(result) => {
+                        benchmark_view.results.push(result);
+                        benchmark_view.sort_results();
+                    }
+                    Err(err) => self.show_error(err, None),
+                }
+                return self.run_next_benchmark();
+            }
+            Message::SortBenchmark(column) => {
+                if let Some(benchmark_view) = self.benchmark_view.as_mut() {
+                    benchmark_view.sort = match benchmark_view.sort {
+                        (sort_column, is_ascending) if sort_column == column => {
+                            (column, !is_ascending)
+                        }
+                        _ => (column, true),
+                    };
+                    benchmark_view.sort_results();
+                }
+            }
+            Message::ExportBenchmark => {
+                if let Some(benchmark_view) = self.benchmark_view.as_ref() {
+                    let path = std::env::current_dir()
+                        .unwrap_or_default()
+                        .join("benchmark.csv");
+                    let contents = benchmark::to_csv(&benchmark_view.results).into_bytes();
+                    return self.ask_where_to_save(contents, path);
+                }
+            }
+            Message::ShowBatch => {
+                self.batch_view = Some(BatchView {
+                    prompts: text_editor::Content::with_text(self.prompt_text().trim()),
+                    file: String::new(),
+                    csv_column: "prompt".to_string(),
+                    loaded: None,
+                    model: String::new(),
+                    queue: vec![],
+                    running: None,
+                    results: vec![],
+                });
+            }
+            Message::CloseBatch => self.batch_view = None,
+            Message::EditBatchPrompts(action) => {
+                if let Some(batch_view) = self.batch_view.as_mut() {
+                    batch_view.prompts.perform(action);
+                }
+            }
+            Message::UpdateBatchFile(file) => {
+                if let Some(batch_view) = self.batch_view.as_mut() {
+                    batch_view.file = file;
+                }
+            }
+            Message::UpdateBatchColumn(csv_column) => {
+                if let Some(batch_view) = self.batch_view.as_mut() {
+                    batch_view.csv_column = csv_column;
+                }
+            }
+            Message::LoadBatchFile => {
+                let Some(batch_view) = self.batch_view.as_ref() else {
+                    return Task::none();
+                };
+                let path = PathBuf::from(batch_view.file.trim());
+                return Task::perform(
+                    batch::load_prompts(path, batch_view.csv_column.clone()),
+                    Message::BatchFileLoaded,
+                );
+            }
+            Message::BatchFileLoaded(result) => match result {
+                Ok(prompts) => {
+                    if let Some(batch_view) = self.batch_view.as_mut() {
+                        let file_name = Path::new(batch_view.file.trim())
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned();
+                        batch_view.loaded = Some((file_name, prompts));
+                    }
+                }
+                Err(err) => self.show_error(err, Some(Message::LoadBatchFile)),
+            },
+            Message::ClearBatchFile => {
+                if let Some(batch_view) = self.batch_view.as_mut() {
+                    batch_view.loaded = None;
+                }
+            }
+            Message::RunBatch => {
+                let Some(model) = self.conversation.current_model.as_ref() else {
+                    return Task::none();
+                };
+                let Some(batch_view) = self.batch_view.as_mut() else {
+                    return Task::none();
+                };
+                batch_view.queue = match &batch_view.loaded {
+                    Some((_file_name, prompts)) => prompts.clone(),
+                    None => batch::prompts_from_lines(&batch_view.prompts.text()),
+                };
+                batch_view.model = model.name.clone();
+                batch_view.results.clear();
+                return self.run_next_batch_prompt();
+            }
+            Message::StopBatch => {
+                if let Some(batch_view) = self.batch_view.as_mut() {
+                    batch_view.queue.clear();
+                }
+            }
+            Message::BatchRan(result) => {
+                let Some(batch_view) = self.batch_view.as_mut() else {
+                    return Task::none();
+                };
+                batch_view.running = None;
+                batch_view.results.push(result);
+                return self.run_next_batch_prompt();
+            }
+            Message::ExportBatch(format) => {
+                if let Some(batch_view) = self.batch_view.as_ref() {
+                    let (contents, extension) = match format {
+                        BatchExport::Csv => (batch::to_csv(&batch_view.results), "csv"),
+                        BatchExport::Json => (batch::to_json(&batch_view.results), "json"),
+                    };
+                    let path = std::env::current_dir()
+                        .unwrap_or_default()
+                        .join(format!("batch.{extension}"));
+                    return self.ask_where_to_save(contents.into_bytes(), path);
+                }
+            }
+            Message::ShowUsage => {
+                let Some(conversations_dir) = self.conversations_dir.clone() else {
+                    self.show_error(Error::NoAppDir, None);
+                    return Task::none();
+                };
+                return Task::perform(usage::load(conversations_dir), Message::UsageLoaded);
+            }
+            Message::UsageLoaded(result) => match result {
+                Ok(usage) => self.usage_view = Some(usage),
+                Err(err) => self.show_error(err, Some(Message::ShowUsage)),
+            },
+            Message::CloseUsage => self.usage_view = None,
+            Message::ShowLogs => {
+                let Some(logs_dir) = logging::logs_dir() else {
+                    self.show_error(Error::NoAppDir, None);
+                    return Task::none();
+                };
+                return Task::perform(logging::read_latest_log(logs_dir), Message::LogLoaded);
+            }
+            Message::LogLoaded(result) => match result {
+                Ok(log) => self.log_view = Some(log),
+                Err(err) => self.toasts.push(Toast {
+                    message: err,
+                    actions: vec![],
+                }),
+            },
+            Message::CloseLogs => self.log_view = None,
+            Message::ToggleIsGenerating => {
+                self.conversation.is_generating = !self.conversation.is_generating;
+                if self.conversation.is_generating {
+                    self.conversation.generation_started = Some(Instant::now());
+                } else {
+                    self.conversation.generation = None;
+                    self.conversation.is_searching_web = false;
+                    self.record_response_time();
+                    self.finish_streamed_markdown();
+                    return self.send_queued_prompt();
+                }
+            }
+            Message::SessionLoaded(session) => {
+                let Some(session) = session else {
+                    return Task::none();
+                };
+                let conversation = session.conversation;
+                if self.prompt_text().is_empty() {
+                    self.set_draft(conversation.clone(), session.draft);
+                }
+                self.session_model = session.model;
+                if let Some(show_sidebar) = session.show_sidebar {
+                    self.session_sidebar = Some(show_sidebar);
+                    self.show_sidebar = show_sidebar;
+                }
+                self.select_default_model();
+                if let Some(conversation) = conversation {
+                    self.pending_scroll = Some((session.loaded_messages, session.scroll_offset));
+                    return Task::done(Message::SetConversationFile(Some(conversation)));
+                }
+            }
+            Message::CloseRequested(id) => {
+                // Anything being generated carries on, and is autosaved, while it's in the tray
+                if self.hides_on_close() {
+                    return iced::window::change_mode(id, iced::window::Mode::Hidden);
+                }
+                // Nothing from the last session has been restored while still locked
+                if self.passphrase_screen == Some(PassphraseScreen::Unlock)
+                    || self.profile_picker.is_some()
+                {
+                    return iced::window::close(id);
+                }
+                // Whatever's been generated is kept, as if generation had been stopped first,
+                // including in the conversations generating in the background
+                let background_saves: Vec<_> = std::mem::take(&mut self.background_conversations)
+                    .into_values()
+                    .filter(|background| background.is_generating || background.has_unsaved_changes)
+                    .filter_map(|mut background| {
+                        self.in_state(&mut background, |app| {
+                            let _ = app.update(Message::StopGeneration);
+                            app.conversation.current_conversation.clone().map(|path| {
+                                storage::save_conversation_unless_changed(
+                                    path,
+                                    app.saved_conversation(),
+                                    app.conversation.conversation_modified,
+                                )
+                            })
+                        })
+                    })
+                    .collect();
+                let was_generating = self.conversation.is_generating;
+                if was_generating {
+                    let _ = self.update(Message::StopGeneration);
+                }
+                let session = Session {
+                    conversation: self.conversation.current_conversation.clone(),
+                    model: self
+                        .conversation
+                        .current_model
+                        .as_ref()
+                        .map(|model| model.name.clone()),
+                    draft: self.prompt_text(),
+                    loaded_messages: self.conversation.chats_list.len(),
+                    scroll_offset: self.chat_scroll_offset,
+                    show_sidebar: Some(self.show_sidebar),
+                };
+                // Checkpointed instead if the conversation can't be saved, to be offered back
+                let recovery_state = RecoveryState {
+                    conversation: self.conversation.current_conversation.clone(),
+                    draft: String::new(),
+                    partial_response: self
+                        .conversation
+                        .chats_list
+                        .last()
+                        .filter(|_| was_generating)
+                        .map(|(chat_message, _markdown_items)| chat_message.content.clone()),
+                };
+                let unsaved_conversation = self
+                    .conversation
+                    .current_conversation
+                    .clone()
+                    .filter(|_| self.conversation.has_unsaved_changes || was_generating)
+                    .map(|path| {
+                        storage::save_conversation_unless_changed(
+                            path,
+                            self.saved_conversation(),
+                            self.conversation.conversation_modified,
+                        )
+                    });
+                // Only the main window's conversation is reopened on launch
+                if self.detached {
+                    return Task::future(async move {
+                        for save in unsaved_conversation.into_iter().chain(background_saves) {
+                            if let Err(err) = save.await {
+                                tracing::warn!("Couldn't save the conversation on closing: {err}");
+                            }
+                        }
+                    })
+                    .discard()
+                    .chain(iced::window::close(id));
+                }
+                let prepared_markdown = self.markdown_cache.prepared.clone();
+                return Task::future(async move {
+                    for save in background_saves {
+                        if let Err(err) = save.await {
+                            tracing::warn!("Couldn't save the conversation on closing: {err}");
+                        }
+                    }
+                    let saved = match unsaved_conversation {
+                        Some(save) => save.await.map(drop),
+                        None => Ok(()),
+                    };
+                    // The session has the draft, so there's nothing left for the checkpoint to
+                    // recover once the conversation's saved
+                    let checkpoint = match saved {
+                        Ok(()) => RecoveryState::default(),
+                        Err(err) => {
+                            tracing::warn!("Couldn't save the conversation on closing: {err}");
+                            recovery_state
+                        }
+                    };
+                    if let Err(err) = recovery::save(checkpoint).await {
+                        tracing::warn!("Couldn't checkpoint work in progress: {err}");
+                    }
+                    if let Err(err) = session::save(session).await {
+                        tracing::warn!("Couldn't save the session: {err}");
+                    }
+                    if let Err(err) = markdown_cache::save(prepared_markdown).await {
+                        tracing::warn!("Couldn't save the markdown cache: {err}");
+                    }
+                })
+                .discard()
+                .chain(iced::window::close(id));
+            }
+        };
+        Task::none()
+    }
+
+    /// What the window listens for, other than its own events and close requests which come
+    /// through the windows around the app
+    fn subscription(&self) -> Subscription<Message> {
+        // Everything else depends on the profile's directories
+        if self.profile_picker.is_some() {
+            return Subscription::none();
+        }
+        Subscription::batch([
+            if self.conversation.is_generating {
+                // Chunks are buffered as they arrive and only applied every so often
+                iced::time::every(STREAM_FLUSH_INTERVAL).map(|_| Message::FlushStreamBuffer)
+            } else {
+                Subscription::none()
+            },
+            iced::time::every(HEALTH_CHECK_INTERVAL).map(|_| Message::CheckConnection),
+            if self.conversation.has_unsaved_changes {
+                iced::time::every(AUTOSAVE_INTERVAL).map(|_| Message::Autosave)
+            } else {
+                Subscription::none()
+            },
+            if self.settings.theme == settings::SYSTEM_THEME {
+                iced::time::every(SYSTEM_THEME_POLL_INTERVAL).map(|_| Message::CheckSystemTheme)
+            } else {
+                Subscription::none()
+            },
+            match self.conversations_dir.clone() {
+                Some(conversations_dir) => Subscription::run_with_id(
+                    conversations_dir.clone(),
+                    watch_dir(Ok(conversations_dir)).map(Message::ConversationFilesChanged),
+                ),
+                None => Subscription::none(),
+            },
+            Subscription::run(background::run_worker).map(Message::BackgroundWorker),
+            // Settings changes are passed on from the main window to the others
+            if self.detached {
+                Subscription::none()
+            } else {
+                Subscription::batch([
+                    iced::time::every(CHECKPOINT_INTERVAL).map(|_| Message::Checkpoint),
+                    if self.settings.watch_clipboard {
+                        iced::time::every(CLIPBOARD_POLL_INTERVAL).map(|_| Message::CheckClipboard)
+                    } else {
+                        Subscription::none()
+                    },
+                    Subscription::run(watch_settings_file),
+                    Subscription::run(instance::listen)
+                        .map(|(request, responder)| Message::IpcRequest(request, responder)),
+                    if self.settings.minimize_to_tray {
+                        Subscription::run(tray::icon).map(Message::Tray)
+                    } else {
+                        Subscription::none()
+                    },
+                    if self.settings.shortcuts.quick_chat.is_empty() {
+                        Subscription::none()
+                    } else {
+                        let shortcut = self.settings.shortcuts.quick_chat.clone();
+                        Subscription::run_with_id(
+                            shortcut.clone(),
+                            tray::global_shortcut(shortcut).map(|()| Message::OpenQuickChat),
+                        )
+                    },
+                ])
+            },
+        ])
+    }
+
+    /// What an event in the app's window means for it, if anything
+    fn window_event(event: iced::Event, status: iced::event::Status) -> Option<Message> {
+        match event {
+            iced::Event::Window(iced::window::Event::Focused) => {
+                Some(Message::WindowFocusChanged(true))
+            }
+            iced::Event::Window(iced::window::Event::Unfocused) => {
+                Some(Message::WindowFocusChanged(false))
+            }
+            iced::Event::Window(iced::window::Event::FileDropped(path)) => {
+                Some(attach_message(path))
+            }
+            // Keys a focused widget used are left to it, except Escape which text inputs use to
+            // unfocus and is still wanted to stop a response. Plain typing is never a shortcut, so
+            // it isn't sent on.
+            iced::Event::Keyboard(iced::keyboard::Event::KeyPressed { key, modifiers, .. })
+                if (status == iced::event::Status::Ignored
+                    || key == Key::Named(key::Named::Escape))
+                    && (matches!(key, Key::Named(_))
+                        || modifiers.control()
+                        || modifiers.alt()
+                        || modifiers.logo()) =>
+            {
+                Some(Message::KeyPressed(key, modifiers))
+            }
+            _ => None,
+        }
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        if let Some(profile_picker) = self.profile_picker.as_ref() {
+            return self.view_profile_picker(profile_picker);
+        }
+        if let Some(passphrase_screen) = self.passphrase_screen {
+            return self.view_passphrase_screen(passphrase_screen);
+        }
+        if let Some(log) = self.log_view.as_ref() {
+            return self.view_logs(log);
+        }
+        if let Some(settings) = self.settings_draft.as_ref() {
+            return self.view_settings(settings);
+        }
+        if let Some(benchmark_view) = self.benchmark_view.as_ref() {
+            return self.view_benchmark(benchmark_view);
+        }
+        if let Some(batch_view) = self.batch_view.as_ref() {
+            return self.view_batch(batch_view);
+        }
+        if let Some(model_builder) = self.model_builder.as_ref() {
+            return self.view_model_builder(model_builder);
+        }
+        if let Some(usage) = self.usage_view.as_ref() {
+            return self.view_usage(usage);
+        }
+        if let Some(playground) = self.embeddings_playground.as_ref() {
+            return self.view_embeddings_playground(playground);
+        }
+        if let Some(history_view) = self.history_view.as_ref() {
+            return self.view_history(history_view);
+        }
+        row![if self.conversation.current_model.is_none() {
+            self.view_model_manager()
+        } else {
+            column![
+                row![
+                    Tooltip::new(
+                        button(Svg::new(Handle::from_memory(include_bytes!(
+                            "../icons/toggle-sidebar.svg"
+                        ))))
+                        .height(Length::Fill)
+                        .on_press(Message::ToggleSidebar)
+                        .style(if self.show_sidebar {
+                            button::secondary
+                        } else {
+                            button::primary
+                        })
+                        .width(Length::Fixed(50.0)),
+                        text(tr!("toggle-sidebar")),
+                        iced::widget::tooltip::Position::Bottom
+                    ),
+                    Tooltip::new(
+                        button(Svg::new(Handle::from_memory(include_bytes!(
+                            "../icons/add.svg"
+                        ))))
+                        .height(Length::Fill)
+                        .on_press(Message::NewChatButtonPressed)
+                        .width(Length::Fixed(50.0)),
+                        text(tr!("new-chat")),
+                        iced::widget::tooltip::Position::Bottom
+                    ),
+                    button(
+                        text(tr!("select-model"))
+                            .width(Length::Fill)
+                            .align_x(Center)
+                    )
+                    .on_press(Message::SetModel(None))
+                    .height(Length::Fill)
+                    .width(Length::Fixed(170.0)),
+                    text(
+                        self.conversation
+                            .current_model
+                            .clone()
+                            .map(|model| model.name)
+                            .unwrap_or_default()
+                    )
+                    .width(Length::Fill)
+                    .align_x(Center)
+                    .size(24),
+                    self.view_server_picker(),
+                    Tooltip::new(
+                        button(
+                            text(tr!("tasks-button", count = self.background_jobs.len()))
+                                .width(Length::Fill)
+                                .align_x(Center)
+                        )
+                        .on_press(Message::ToggleBackgroundJobs)
+                        .style(if self.show_background_jobs {
+                            button::primary
+                        } else {
+                            button::secondary
+                        })
+                        .height(Length::Fill)
+                        .width(Length::Fixed(100.0)),
+                        text(tr!("background-tasks")),
+                        iced::widget::tooltip::Position::Bottom
+                    ),
+                ]
+                .push_maybe(
+                    (self.settings.git_history && self.conversation.current_conversation.is_some())
+                        .then(|| {
+                            button(text(tr!("history")).width(Length::Fill).align_x(Center))
+                                .on_press(Message::ShowHistory)
+                                .style(button::secondary)
+                                .height(Length::Fill)
+                                .width(Length::Fixed(80.0))
+                        })
+                )
+                .push_maybe((!self.conversation.chats_list.is_empty()).then(|| {
+                    Tooltip::new(
+                        export_pick_list(None),
+                        text(tr!("export-tooltip")),
+                        iced::widget::tooltip::Position::Bottom,
+                    )
+                }))
+                .push_maybe((!self.conversation.chats_list.is_empty()).then(|| {
+                    Tooltip::new(
+                        button(
+                            text(if self.is_sharing {
+                                tr!("sharing")
+                            } else {
+                                tr!("share")
+                            })
+                            .width(Length::Fill)
+                            .align_x(Center),
+                        )
+                        .on_press_maybe((!self.is_sharing).then_some(Message::ShareConversation))
+                        .style(button::secondary)
+                        .height(Length::Fill)
+                        .width(Length::Fixed(90.0)),
+                        text(if self.settings.share_url.trim().is_empty() {
+                            tr!("share-tooltip-no-host")
+                        } else {
+                            tr!("share-tooltip")
+                        }),
+                        iced::widget::tooltip::Position::Bottom,
+                    )
+                }))
+                .push(
+                    button(
+                        text(tr!("system-prompt"))
+                            .width(Length::Fill)
+                            .align_x(Center)
+                    )
+                    .on_press(Message::EditSystemPrompt)
+                    .style(if self.system_prompt().is_some() {
+                        button::primary
+                    } else {
+                        button::secondary
+                    })
+                    .height(Length::Fill)
+                    .width(Length::Fixed(130.0))
+                )
+                .push(
+                    button(text(tr!("params")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ToggleParamsPanel)
+                        .style(if self.params_panel.is_some() {
+                            button::primary
+                        } else {
+                            button::secondary
+                        })
+                        .height(Length::Fill)
+                        .width(Length::Fixed(100.0))
+                )
+                .push(
+                    button(text(tr!("knowledge")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ToggleKnowledgePanel)
+                        .style(if self.conversation.knowledge_dir.is_some() {
+                            button::primary
+                        } else {
+                            button::secondary
+                        })
+                        .height(Length::Fill)
+                        .width(Length::Fixed(100.0))
+                )
+                .push(
+                    button(text(tr!("related")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ToggleRelated)
+                        .style(if self.show_related {
+                            button::primary
+                        } else {
+                            button::secondary
+                        })
+                        .height(Length::Fill)
+                        .width(Length::Fixed(80.0))
+                )
+                .push(
+                    button(text(tr!("import")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ToggleImportPanel)
+                        .style(if self.import_panel.is_some() {
+                            button::primary
+                        } else {
+                            button::secondary
+                        })
+                        .height(Length::Fill)
+                        .width(Length::Fixed(80.0))
+                )
+                .push(
+                    button(text(tr!("settings")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ShowSettings)
+                        .style(button::secondary)
+                        .height(Length::Fill)
+                        .width(Length::Fixed(90.0))
+                )
+                .push(
+                    button(text(tr!("benchmark")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ShowBenchmark)
+                        .style(button::secondary)
+                        .height(Length::Fill)
+                        .width(Length::Fixed(100.0))
+                )
+                .push(
+                    button(text(tr!("batch")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ShowBatch)
+                        .style(button::secondary)
+                        .height(Length::Fill)
+                        .width(Length::Fixed(70.0))
+                )
+                .push(
+                    button(text(tr!("usage")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ShowUsage)
+                        .style(button::secondary)
+                        .height(Length::Fill)
+                        .width(Length::Fixed(70.0))
+                )
+                .push(
+                    button(text(tr!("logs")).width(Length::Fill).align_x(Center))
+                        .on_press(Message::ShowLogs)
+                        .style(button::secondary)
+                        .height(Length::Fill)
+                        .width(Length::Fixed(70.0))
+                )
+                .height(Length::Fixed(30.0)),
+                self.view_background_jobs(),
+                row![
+                    self.view_sidebar(),
+                    column![
+                        self.view_chat_list(),
+                        self.view_conflicts(),
+                        self.view_save_as(),
+                        self.view_import_panel(),
+                        self.view_toasts(),
+                        self.view_system_prompt(),
+                        self.view_params_panel(),
+                        self.view_knowledge_panel(),
+                        self.view_related_panel(),
+                        self.view_composer(),
+                    ]
+                    .width(Length::FillPortion(2))
+                    .padding(10)
+                ]
+            ]
+        }]
+        .into()
+    }
+
+    /// Applies settings loaded from the settings file, only touching what changed in it so
+    /// state changed in the UI since isn't overwritten by every reload
+    fn apply_settings(&mut self, settings: Settings) -> Task<Message> {
+        let previous_settings = self.loaded_settings.replace(settings.clone());
+        let previous_settings = previous_settings.as_ref();
+        if previous_settings.map(|previous| &previous.language) != Some(&settings.language) {
+            i18n::set_language(settings.language.as_deref());
+        }
+        if previous_settings.map(|previous| previous.show_sidebar) != Some(settings.show_sidebar) {
+            self.show_sidebar = match previous_settings {
+                None => self.session_sidebar.unwrap_or(settings.show_sidebar),
+                Some(_) => settings.show_sidebar,
+            };
+        }
+        let server = match previous_settings {
+            // The open conversation follows the default server if it was on it, and otherwise
+            // picks up changes to the login of the server it's on
+            Some(previous) if previous.server() != self.conversation.server => {
+                settings.server_for(Some(&self.conversation.server.url))
+            }
+            _ => settings.server(),
+        };
+        let conversations_dir_task = match settings.conversations_dir() {
+            Ok(conversations_dir) => self.set_conversations_dir(conversations_dir),
+            Err(err) => {
+                self.show_error(err, None);
+                Task::none()
+            }
+        };
+        self.settings = settings;
+        let markdown_memory_budget = self.settings.markdown_memory_budget_bytes();
+        if markdown_memory_budget != self.markdown_memory_budget {
+            self.markdown_memory_budget = markdown_memory_budget;
+            self.markdown_cache.set_budget(markdown_memory_budget);
+            self.enforce_markdown_memory_budget();
+        }
+        if server != self.conversation.server {
+            match Backend::new(&server) {
+                Ok(backend) => {
+                    self.conversation.backend = backend;
+                    self.conversation.server = server;
+                    return conversations_dir_task.chain(Task::done(Message::LoadModelsList));
+                }
+                Err(err) => self.show_error(err, None),
+            }
+        }
+        self.select_default_model();
+        Task::batch([
+            conversations_dir_task.chain(self.sync_encryption()),
+            self.send_if_ready(),
+        ])
+    }
+
+    /// Asks for a passphrase if encryption was turned on, or decrypts everything if it was turned
+    /// off, once conversations are unlocked
+    fn sync_encryption(&mut self) -> Task<Message> {
+        if self.passphrase_screen == Some(PassphraseScreen::Unlock) || self.detached {
+            return Task::none();
+        }
+        if self.settings.encrypt_conversations && !crypto::is_enabled() {
+            self.passphrase_screen = Some(PassphraseScreen::Choose);
+        } else if !self.settings.encrypt_conversations && crypto::is_enabled() {
+            if let Some(conversations_dir) = self.conversations_dir.clone() {
+                return Task::perform(
+                    crypto::disable(conversations_dir),
+                    Message::EncryptionChanged,
+                );
+            }
+        }
+        Task::none()
+    }
+
+    /// Switches to storing conversations in a different directory, moving them all over to it
+    fn set_conversations_dir(&mut self, conversations_dir: PathBuf) -> Task<Message> {
+        if self.conversations_dir.as_ref() == Some(&conversations_dir) {
+            return Task::none();
+        }
+        let previous_dir = self.conversations_dir.replace(conversations_dir.clone());
+        if let Some(current_conversation) = self.conversation.current_conversation.as_mut() {
+            if let Some(file_name) = current_conversation.file_name() {
+                *current_conversation = conversations_dir.join(file_name);
+            }
+        }
+        // The main window moves the conversations over, and the others list them once it has
+        if self.detached {
+            return Task::none();
+        }
+        Task::perform(
+            async move {
+                match previous_dir {
+                    Some(previous_dir) => {
+                        storage::move_conversations(previous_dir, conversations_dir).await?
+                    }
+                    None => storage::migrate_legacy_conversations(conversations_dir).await?,
+                };
+                Ok(())
+            },
+            Message::ConversationsMoved,
+        )
+    }
+
+    /// Carries on the open conversation with `server`, listing its models if it's a different one
+    fn use_server(&mut self, server: Server) -> Task<Message> {
+        if server == self.conversation.server {
+            return Task::none();
+        }
+        match Backend::new(&server) {
+            Ok(backend) => {
+                self.conversation.backend = backend;
+                self.conversation.server = server;
+                Task::done(Message::LoadModelsList)
+            }
+            Err(err) => {
+                self.show_error(err, None);
+                Task::none()
+            }
+        }
+    }
+
+    /// Sends a prompt given on launch once there's a model to send it to
+    fn send_if_ready(&mut self) -> Task<Message> {
+        if !self.send_when_ready
+            || self.conversation.current_model.is_none()
+            || self.conversation.is_generating
+        {
+            return Task::none();
+        }
+        self.send_when_ready = false;
+        if self.prompt_text().is_empty() {
+            return Task::none();
+        }
+        Task::done(Message::SubmitPrompt)
+    }
+
+    fn refresh_models(&self) -> Task<Message> {
+        let backend = self.conversation.backend.clone();
+        Task::perform(
+            async move { backend.list_models().await },
+            Message::ModelsRefreshed,
+        )
+    }
+
+    fn load_model_details(&self) -> Task<Message> {
+        let backend = self.conversation.backend.clone();
+        Task::perform(
+            async move { backend.model_details().await },
+            Message::ModelDetailsLoaded,
+        )
+    }
+
+    /// Opens the conversation a step up or down the sidebar from the open one, wrapping around
+    /// at the ends
+    fn cycle_conversation(&self, step: isize) -> Task<Message> {
+        if self.conversations_list.is_empty() {
+            return Task::none();
+        }
+        let conversation_count = self.conversations_list.len() as isize;
+        let next_index = match self
+            .conversation
+            .current_conversation
+            .as_ref()
+            .and_then(|current| {
+                self.conversations_list
+                    .iter()
+                    .position(|conversation| conversation == current)
+            }) {
+            Some(index) => (index as isize + step).rem_euclid(conversation_count),
+            None if step > 0 => 0,
+            None => conversation_count - 1,
+        };
+        let next_conversation = self.conversations_list[next_index as usize].clone();
+        Task::done(Message::SaveConversation).chain(Task::done(Message::SetConversationFile(Some(
+            next_conversation,
+        ))))
+    }
+
+    /// Selects the model the open conversation was last answered with, offering to pull it if the
+    /// server doesn't have it
+    fn select_conversation_model(&mut self) {
+        // Left waiting until there are models to pick from
+        if self.models_list.is_empty() {
+            return;
+        }
+        let Some(model_name) = self.pending_model.take() else {
+            return;
+        };
+        match self
+            .models_list
+            .iter()
+            .find(|model| model.name == model_name)
+        {
+            Some(model) => self.conversation.current_model = Some(model.clone()),
+            None => self.toasts.push(Toast {
+                message: tr!("conversation-model-missing", model = model_name.as_str()),
+                actions: vec![(tr!("pull-model"), Message::PullMissingModel(model_name))],
+            }),
+        }
+    }
+
+    fn select_default_model(&mut self) {
+        if self.conversation.current_model.is_some() {
+            return;
+        }
+        if let Some(default_model) = self
+            .session_model
+            .as_ref()
+            .or(self.settings.default_model.as_ref())
+        {
+            self.conversation.current_model = self
+                .models_list
+                .iter()
+                .find(|model| model.name == *default_model)
+                .cloned();
+        }
+    }
+
+    /// Shows an error as a toast, offering to retry if given the message that failed
+    fn show_error(&mut self, err: Error, retry: Option<Message>) {
+        tracing::warn!("{err}");
+        let mut actions = vec![];
+        if let Some(retry) = retry {
+            actions.push((tr!("retry"), retry));
+        }
+        if let Some(path) = err.path() {
+            actions.push((tr!("open-file"), Message::OpenFile(path.clone())));
+        }
+        actions.push((tr!("copy-details"), Message::CopyChat(format!("{err:?}"))));
+        self.toasts.push(Toast {
+            message: i18n::error_message(&err),
+            actions,
+        });
+    }
+
+    fn conversation_title(&self) -> String {
+        self.conversation
+            .current_conversation
+            .as_ref()
+            .and_then(|conversation| conversation.file_stem())
+            .map(|title| title.to_string_lossy().into_owned())
+            .unwrap_or_else(|| tr!("new-conversation"))
+    }
+
+    /// Raises a desktop notification about the current conversation if the window isn't focused,
+    /// jumping back to the conversation when it's clicked
+    fn notify_if_unfocused(&self, summary: String, body: String) -> Task<Message> {
+        if self.is_window_focused {
+            return Task::none();
+        }
+        Task::perform(
+            notifications::notify(
+                summary,
+                body,
+                tr!("notification-open"),
+                self.conversation.current_conversation.clone(),
+            ),
+            Message::NotificationClicked,
+        )
+    }
+
+    /// The clipboard is kept open for the app's lifetime, as on Wayland copied text
+    /// disappears as soon as the handle that set it is dropped
+    fn clipboard(&mut self) -> Result<&mut Clipboard, arboard::Error> {
+        match self.clipboard {
+            Some(ref mut clipboard) => Ok(clipboard),
+            None => Ok(self.clipboard.insert(Clipboard::new()?)),
+        }
+    }
+
+    fn dismiss_recovery_prompt(&mut self) {
+        self.toasts.retain(|toast| {
+            !toast
+                .actions
+                .iter()
+                .any(|(_label, message)| matches!(message, Message::DiscardRecovery))
+        });
+    }
+
+    /// Starts the next model and prompt in the benchmark's queue, if there's one left
+    fn run_next_benchmark(&mut self) -> Task<Message> {
+        let Some(benchmark_view) = self.benchmark_view.as_mut() else {
+            return Task::none();
+        };
+        if benchmark_view.queue.is_empty() {
+            return Task::none();
+        }
+        let (model_name, prompt) = benchmark_view.queue.remove(0);
+        benchmark_view.running = Some((model_name.clone(), prompt.clone()));
+        Task::perform(
+            benchmark::run(self.conversation.backend.clone(), model_name, prompt),
+            Message::BenchmarkRan,
+        )
+    }
+
+    /// Starts the next prompt in the batch's queue, if there's one left, with the open
+    /// conversation's system prompt and parameters
+    fn run_next_batch_prompt(&mut self) -> Task<Message> {
+        let system_prompt = self.system_prompt().map(str::to_string);
+        let params = self.conversation.generation_params;
+        let Some(batch_view) = self.batch_view.as_mut() else {
+            return Task::none();
+        };
+        if batch_view.queue.is_empty() {
+            return Task::none();
+        }
+        let prompt = batch_view.queue.remove(0);
+        batch_view.running = Some(prompt.clone());
+        Task::perform(
+            batch::run(
+                self.conversation.backend.clone(),
+                batch_view.model.clone(),
+                system_prompt,
+                prompt,
+                params,
+            ),
+            Message::BatchRan,
+        )
+    }
+
+    fn dismiss_clipboard_prompt(&mut self) {
+        self.toasts.retain(|toast| {
+            !toast
+                .actions
+                .iter()
+                .any(|(_label, message)| matches!(message, Message::AskAboutClipboard(_)))
+        });
+    }
+
+    /// Attaches an image copied to the clipboard, which hands it over as raw pixels, as a PNG
+    fn attach_clipboard_image(&mut self, image: arboard::ImageData) {
+        let mut png = std::io::Cursor::new(vec![]);
+        let encoded = image::RgbaImage::from_raw(
+            image.width as u32,
+            image.height as u32,
+            image.bytes.into_owned(),
+        )
+        .map(|pixels| pixels.write_to(&mut png, image::ImageFormat::Png));
+        match encoded {
+            Some(Ok(())) => self.attachments.push(images::from_bytes(png.get_ref())),
+            _ => self.toasts.push(Toast {
+                message: tr!("couldnt-paste-image"),
+                actions: vec![],
+            }),
+        }
+    }
+
+    fn clipboard_error(&mut self, err: arboard::Error) {
+        tracing::warn!("Clipboard error: {err}");
+        // Start over with a fresh handle next time in case this one is broken
+        self.clipboard = None;
+        self.toasts.push(Toast {
+            message: tr!("clipboard-error", error = err.to_string()),
+            actions: vec![],
+        });
+    }
+
+    fn queue_job(&mut self, job: Job) -> JobId {
+        let id = self.next_job_id;
+        self.next_job_id += 1;
+        self.background_jobs.push(JobStatus {
+            id,
+            name: job.name(),
+            progress: None,
+        });
+        match self.worker.as_ref() {
+            Some(worker) => worker.queue(id, job),
+            None => self.pending_jobs.push((id, job)),
+        }
+        id
+    }
+
+    fn set_job_progress(&mut self, id: JobId, progress: f32) {
+        if let Some(job_status) = self
+            .background_jobs
+            .iter_mut()
+            .find(|job_status| job_status.id == id)
+        {
+            job_status.progress = Some(progress);
+        }
+    }
+
+    /// Parses the streamed response again as a whole, as parsing it a block at a time can split
+    /// up what belongs together, e.g. the items of a list with blank lines between them
+    fn finish_streamed_markdown(&mut self) {
+        if std::mem::take(&mut self.conversation.streamed_markdown).is_empty() {
+            return;
+        }
+        if let Some((chat_message, markdown_items)) = self.conversation.chats_list.last_mut() {
+            *markdown_items = Some(self.markdown_cache.parse(&chat_message.content));
+        }
+    }
+
+    /// Parses the messages around the viewport, then unloads the parsed markdown of the messages
+    /// furthest from it until the conversation fits within the memory budget again
+    fn enforce_markdown_memory_budget(&mut self) {
+        let (visible_start, visible_end) = self.visible_chat_range();
+        for (chat_message, markdown_items) in
+            &mut self.conversation.chats_list[visible_start..visible_end]
+        {
+            if markdown_items.is_none() {
+                *markdown_items = Some(self.markdown_cache.parse(&chat_message.content));
+            }
+        }
+        let mut parsed_bytes: usize = self
+            .conversation
+            .chats_list
+            .iter()
+            .filter(|(_chat_message, markdown_items)| markdown_items.is_some())
+            .map(|(chat_message, _markdown_items)| chat_message.content.len())
+            .sum();
+        let furthest_first =
+            (0..visible_start).chain((visible_end..self.conversation.chats_list.len()).rev());
+        for index in furthest_first {
+            if parsed_bytes <= self.markdown_memory_budget {
+                break;
+            }
+            let (chat_message, markdown_items) = &mut self.conversation.chats_list[index];
+            if markdown_items.take().is_some() {
+                parsed_bytes -= chat_message.content.len();
+            }
+        }
+    }
+
+    /// Number of messages in the current conversation, including ones not loaded into the view
+    /// yet, which is the index the next one's added at
+    fn message_count(&self) -> usize {
+        self.conversation.unloaded_chats.len() + self.conversation.chats_list.len()
+    }
+
+    /// Every message in the current conversation, including ones not loaded into the view yet
+    fn full_conversation(&self) -> Vec<ChatMessage> {
+        self.conversation
+            .unloaded_chats
+            .iter()
+            .cloned()
+            .chain(
+                self.conversation
+                    .chats_list
+                    .iter()
+                    .map(|(chat_message, _markdown_items)| chat_message.clone()),
+            )
+            .collect()
+    }
+
+    /// Has a model title the open conversation if its first response just finished
+    fn generate_title(&self) -> Task<Message> {
+        let conversation = self.full_conversation();
+        let is_first_response = conversation
+            .iter()
+            .filter(|chat_message| chat_message.role != MessageRole::System)
+            .count()
+            == 2;
+        let model = self.settings.title_model.clone().or(self
+            .conversation
+            .current_model
+            .as_ref()
+            .map(|model| model.name.clone()));
+        let (Some(path), Some(model)) = (self.conversation.current_conversation.clone(), model)
+        else {
+            return Task::none();
+        };
+        if !self.settings.auto_title || !is_first_response {
+            return Task::none();
+        }
+        tracing::debug!("Generating a title for {} with {model}", path.display());
+        let backend = self.conversation.backend.clone();
+        let conversation_path = path.clone();
+        Task::perform(
+            async move {
+                let title = title::generate(backend, model, conversation).await?;
+                // Left alone if it was renamed or deleted while the title was being written
+                Ok(storage::conversation_exists(&conversation_path)
+                    .await
+                    .then_some(title))
+            },
+            move |result| Message::TitleGenerated(path.clone(), result),
+        )
+    }
+
+    /// Saves a conversation unless it's changed on disk since `last_modified`, once the save
+    /// already under way for it has finished if there is one
+    fn save_conversation(
+        &mut self,
+        path: PathBuf,
+        conversation: Conversation,
+        last_modified: Option<SystemTime>,
+    ) -> Task<Message> {
+        if let Some(save) = self.saves_in_flight.get_mut(&path) {
+            save.queued = Some(conversation);
+            return Task::none();
+        }
+        self.saves_in_flight.insert(
+            path.clone(),
+            SaveInFlight {
+                last_modified,
+                queued: None,
+            },
+        );
+        Task::perform(
+            storage::save_conversation_unless_changed(path.clone(), conversation, last_modified),
+            move |result| Message::ConversationSaved(path.clone(), result),
+        )
+    }
+
+    fn conversation_saved(
+        &mut self,
+        path: PathBuf,
+        result: Result<SaveOutcome, Error>,
+    ) -> Task<Message> {
+        match result {
+            Ok(SaveOutcome::Saved(modified)) => {
+                self.set_conversation_modified(&path, modified);
+                // Autosaves mid-response aren't worth keeping, the finished one is saved after
+                let is_open = self.conversation.current_conversation.as_ref() == Some(&path);
+                if self.settings.git_history && is_open && !self.conversation.is_generating {
+                    if let Some(conversations_dir) = self.conversations_dir.clone() {
+                        let title = path
+                            .file_stem()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned();
+                        let message_count = self.conversation.chats_list.len()
+                            + self.conversation.unloaded_chats.len();
+                        return Task::perform(
+                            history::commit(
+                                conversations_dir,
+                                path,
+                                tr!("history-save-commit", title = title, count = message_count),
+                            ),
+                            Message::HistoryCommitted,
+                        );
+                    }
+                }
+            }
+            Ok(SaveOutcome::Conflicted(conflict_path)) => {
+                tracing::info!(
+                    "Conversation changed on disk, saved as {} instead",
+                    conflict_path.display()
+                );
+                return Task::done(Message::LoadConflicts);
+            }
+            Err(err) => {
+                self.show_error(err, Some(in_conversation(&path, Message::SaveConversation)))
+            }
+        }
+        Task::none()
+    }
+
+    /// Keeps when a conversation's file was last modified by the app, open or in the background
+    fn set_conversation_modified(&mut self, path: &Path, modified: Option<SystemTime>) {
+        if self.conversation.current_conversation.as_deref() == Some(path) {
+            self.conversation.conversation_modified = modified;
+        } else if let Some(background) = self.background_conversations.get_mut(path) {
+            background.conversation_modified = modified;
+        }
+    }
+
+    /// Asks where to save the contents with the desktop's save dialog, starting from `path`, or
+    /// has the path typed in if there isn't one
+    fn ask_where_to_save(&mut self, contents: Vec<u8>, path: PathBuf) -> Task<Message> {
+        self.save_as = Some((contents, path.display().to_string()));
+        self.is_save_dialog_open = true;
+        Task::perform(
+            dialogs::save_file(tr!("save"), path),
+            Message::SavePathPicked,
+        )
+    }
+
+    fn rename_conversation(&mut self, path: PathBuf, title: String) -> Task<Message> {
+        // Unsaved changes are saved before the rename, or they'd be saved under the old name
+        // afterwards
+        let unsaved_conversation = (self.conversation.current_conversation.as_ref() == Some(&path)
+            && self.conversation.has_unsaved_changes)
+            .then(|| {
+                self.conversation.has_unsaved_changes = false;
+                storage::save_conversation_unless_changed(
+                    path.clone(),
+                    self.saved_conversation(),
+                    self.conversation.conversation_modified,
+                )
+            });
+        let old_path = path.clone();
+        Task::perform(
+            async move {
+                if let Some(save) = unsaved_conversation {
+                    save.await?;
+                }
+                let new_path = storage::rename_conversation(path, title).await?;
+                let modified = storage::modified_time(&new_path).await;
+                Ok((new_path, modified))
+            },
+            move |result| Message::ConversationRenamed(old_path.clone(), result),
+        )
+    }
+
+    /// What the responses to prompts sent now are asked to be written as, or why the schema typed
+    /// in can't be used
+    fn output_format(&self) -> Result<Option<OutputFormat>, String> {
+        match self
+            .conversation
+            .structured_output
+            .as_deref()
+            .map(str::trim)
+        {
+            None => Ok(None),
+            Some("") => Ok(Some(OutputFormat::Json)),
+            Some(schema) => {
+                structured::parse_schema(schema).map(|schema| Some(OutputFormat::Schema(schema)))
+            }
+        }
+    }
+
+    /// Adds a user message to the conversation along with an empty response for the model to fill
+    fn send_message(&mut self, content: String, images: Vec<Image>) -> Task<Message> {
+        self.conversation.pending_tool_exchanges.clear();
+        self.conversation.pending_format = self.output_format().ok().flatten();
+        let markdown_items = self.markdown_cache.parse(&content);
+        self.conversation
+            .sent_times
+            .insert(self.message_count(), SystemTime::now());
+        self.conversation.chats_list.push((
+            ChatMessage {
+                role: MessageRole::User,
+                content,
+                images: (!images.is_empty()).then_some(images),
+            },
+            Some(markdown_items),
+        ));
+        self.conversation.chats_list.push((
+            ChatMessage {
+                role: MessageRole::Assistant,
+                content: String::new(),
+                images: None,
+            },
+            Some(vec![]),
+        ));
+        self.is_following_stream = true;
+        scroll_chat_to_bottom().chain(Task::done(Message::StartGeneration))
+    }
+
+    /// The prompt being written, with its lines exactly as typed
+    fn prompt_text(&self) -> String {
+        self.prompt
+            .lines()
+            .map(|line| line.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Replaces the prompt being written, with the cursor at its end to carry on typing
+    fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = text_editor::Content::with_text(prompt);
+        self.prompt
+            .perform(text_editor::Action::Move(Motion::DocumentEnd));
+    }
+
+    /// Puts a draft in the composer if it's for the open conversation, or keeps it until that
+    /// conversation is opened
+    fn set_draft(&mut self, conversation: Option<PathBuf>, draft: String) {
+        if conversation == self.conversation.current_conversation {
+            self.set_prompt(&draft);
+        } else {
+            self.drafts.insert(conversation, draft);
+        }
+    }
+
+    /// Opens a conversation, first copying it into the conversations dir if it's a file from
+    /// somewhere else
+    fn open_conversation_file(&self, path: PathBuf) -> Task<Message> {
+        match self.conversations_dir.clone() {
+            Some(conversations_dir)
+                if path.parent() != Some(conversations_dir.as_path()) && path.is_file() =>
+            {
+                Task::perform(
+                    storage::import_conversation_file(path.clone(), conversations_dir),
+                    move |result| Message::ConversationImported(path.clone(), result),
+                )
+            }
+            _ => Task::done(Message::SetConversationFile(Some(path))),
+        }
+    }
+
+    /// Keeps the open conversation's unsent prompt and brings back the one for the conversation
+    /// being opened
+    fn switch_draft(&mut self, conversation: Option<PathBuf>) {
+        let draft = self.prompt_text();
+        if draft.is_empty() {
+            self.drafts.remove(&self.conversation.current_conversation);
+        } else {
+            self.drafts
+                .insert(self.conversation.current_conversation.clone(), draft);
+        }
+        let draft = self.drafts.remove(&conversation).unwrap_or_default();
+        self.set_prompt(&draft);
+        self.prompt_undo.clear();
+        self.prompt_history.stop_browsing();
+    }
+
+    /// The current conversation as it's saved, with the params its responses are generated with
+    fn saved_conversation(&self) -> Conversation {
+        let messages = self.full_conversation();
+        Conversation {
+            params: self.conversation.generation_params,
+            stats: self.conversation.response_stats.clone(),
+            messages,
+            folder: self.conversation.conversation_folder.clone(),
+            tags: self.conversation.conversation_tags.clone(),
+            listing: self.conversation.conversation_listing,
+            model: self
+                .conversation
+                .current_model
+                .as_ref()
+                .map(|model| model.name.clone()),
+            branches: self.conversation.branches.clone(),
+            knowledge_dir: self.conversation.knowledge_dir.clone(),
+            citations: self.conversation.citations.clone(),
+            web_sources: self.conversation.web_sources.clone(),
+            server: Some(self.conversation.server.url.clone()),
+            sent_at: self.conversation.sent_times.clone(),
+            summary: self.conversation.context_summary.clone(),
+            tool_calls: self.conversation.tool_calls.clone(),
+            formats: self.conversation.formats.clone(),
+            response_language: self.conversation.response_language.clone(),
+        }
+    }
+
+    /// Tokens the model can take in, as far as the app knows
+    fn context_length(&self) -> u32 {
+        self.conversation
+            .generation_params
+            .num_ctx
+            .unwrap_or(context::DEFAULT_CONTEXT_LENGTH)
+    }
+
+    /// How many of the earliest messages need summarising to fit in the context window, if it's
+    /// more than the summary there is already covers
+    fn summary_needed(&self, conversation: &[ChatMessage]) -> Option<usize> {
+        let covers = context::messages_to_summarize(conversation, self.context_length())?;
+        let summarized = self
+            .conversation
+            .context_summary
+            .as_ref()
+            .filter(|context_summary| context_summary.applies_to(conversation))
+            .map_or(0, |context_summary| context_summary.covers);
+        (covers > summarized).then_some(covers)
+    }
+
+    /// Stops reading a response aloud, if one is being read
+    fn stop_speaking(&mut self) {
+        if let Some((_hash, speaking)) = self.speaking.take() {
+            speaking.abort();
+        }
+    }
+
+    /// Records the last response as having arrived now, once it's finished
+    fn record_response_time(&mut self) {
+        if let Some((chat_message, _markdown_items)) = self.conversation.chats_list.last() {
+            if chat_message.role == MessageRole::Assistant && !chat_message.content.is_empty() {
+                self.conversation
+                    .sent_times
+                    .insert(self.message_count() - 1, SystemTime::now());
+            }
+        }
+    }
+
+    /// Files the passages and pages the response being generated was given and the tools it
+    /// called under it, now its text is final
+    fn file_pending_citations(&mut self) {
+        let Some(index) = self.message_count().checked_sub(1) else {
+            return;
+        };
+        if !self.conversation.pending_citations.is_empty() {
+            self.conversation.citations.insert(
+                index,
+                std::mem::take(&mut self.conversation.pending_citations),
+            );
+        }
+        if !self.conversation.pending_web_sources.is_empty() {
+            self.conversation.web_sources.insert(
+                index,
+                std::mem::take(&mut self.conversation.pending_web_sources),
+            );
+        }
+        if !self.conversation.pending_tool_exchanges.is_empty() {
+            self.conversation.tool_calls.insert(
+                index,
+                std::mem::take(&mut self.conversation.pending_tool_exchanges),
+            );
+        }
+        // Kept for regenerating the response
+        if let Some(format) = self.conversation.pending_format.clone() {
+            self.conversation.formats.insert(index, format);
+        }
+    }
+
+    /// Renders the displayed equations in the messages around the viewport that haven't been
+    /// rendered yet
+    fn render_visible_math(&mut self) -> Task<Message> {
+        if self.settings.math_renderer.trim().is_empty() {
+            return Task::none();
+        }
+        let (visible_start, visible_end) = self.visible_chat_range();
+        let mut renders = vec![];
+        for (chat_message, _markdown_items) in
+            &self.conversation.chats_list[visible_start..visible_end]
+        {
+            for tex in math::display_math(&chat_message.content) {
+                let hash = content_hash(&tex);
+                if self.math_images.contains_key(&hash) {
+                    continue;
+                }
+                self.math_images.insert(hash, None);
+                renders.push(Task::perform(
+                    math::render(self.settings.math_renderer.clone(), tex),
+                    move |result| Message::MathRendered(hash, result),
+                ));
+            }
+        }
+        Task::batch(renders)
+    }
+
+    /// Runs the model's next tool call unless it's waiting to be confirmed, or carries on
+    /// generating the response once they've all been run
+    fn run_next_tool_call(&mut self) -> Task<Message> {
+        let Some(call) = self.conversation.queued_tool_calls.first() else {
+            // Generation is toggled back on as it starts again
+            return Task::done(Message::ToggleIsGenerating)
+                .chain(Task::done(Message::StartGeneration));
+        };
+        if call.tool().is_some_and(Tool::needs_confirmation) {
+            return Task::none();
+        }
+        let call = self.conversation.queued_tool_calls.remove(0);
+        self.run_tool_call(call)
+    }
+
+    /// Runs a tool, which stopping generation aborts
+    fn run_tool_call(&mut self, call: ToolCall) -> Task<Message> {
+        let (task, handle) = Task::perform(tools::run(call), Message::ToolCallFinished).abortable();
+        self.conversation.generation = Some(handle);
+        task
+    }
+
+    /// Changes the open conversation's versions at the loaded message `index`, where `change` is
+    /// given the whole conversation and the message's index in it, and can only change what comes
+    /// from there on
+    fn change_branches(&mut self, index: usize, change: impl FnOnce(&mut Branch, usize)) {
+        let conversation_index = self.conversation.unloaded_chats.len() + index;
+        let saved = self.saved_conversation();
+        let mut conversation = Branch {
+            messages: saved.messages,
+            stats: saved.stats,
+            citations: saved.citations,
+            web_sources: saved.web_sources,
+            sent_at: saved.sent_at,
+            tool_calls: saved.tool_calls,
+            formats: saved.formats,
+            branches: saved.branches,
+        };
+        change(&mut conversation, conversation_index);
+        // The messages from the index on may have come from another version, with what was kept
+        // about them
+        self.conversation.response_stats = conversation.stats;
+        self.conversation.citations = conversation.citations;
+        self.conversation.web_sources = conversation.web_sources;
+        self.conversation.sent_times = conversation.sent_at;
+        self.conversation.tool_calls = conversation.tool_calls;
+        self.conversation.formats = conversation.formats;
+        self.conversation.branches = conversation.branches;
+        self.chat_editor = None;
+        self.conversation.chats_list.truncate(index);
+        for chat_message in conversation.messages.into_iter().skip(conversation_index) {
+            let markdown_items = self.markdown_cache.parse(&chat_message.content);
+            self.conversation
+                .chats_list
+                .push((chat_message, Some(markdown_items)));
+        }
+        self.conversation.has_unsaved_changes = true;
+    }
+
+    /// Statistics of each loaded response, with the running total of tokens in the conversation
+    /// up to and including it
+    fn response_stats_with_totals(&self) -> Vec<Option<(ResponseStats, u32)>> {
+        let unloaded = self.conversation.unloaded_chats.len();
+        let mut total_tokens: u32 = self
+            .conversation
+            .response_stats
+            .range(..unloaded)
+            .map(|(_index, stats)| stats.total_tokens())
+            .sum();
+        (unloaded..self.message_count())
+            .map(|index| {
+                let stats = *self.conversation.response_stats.get(&index)?;
+                total_tokens += stats.total_tokens();
+                Some((stats, total_tokens))
+            })
+            .collect()
+    }
+
+    /// Switches to another conversation's generation params, showing them if the panel is open
+    fn set_generation_params(&mut self, generation_params: GenerationParams) {
+        self.conversation.generation_params = generation_params;
+        if let Some(params_panel) = self.params_panel.as_mut() {
+            *params_panel = ParamsPanel::new(generation_params);
+        }
+    }
+
+    fn view_sidebar(&self) -> Element<'_, Message> {
+        if !self.show_sidebar {
+            return container(column![]).into();
+        }
+        container(
+            column![
+                text(tr!("conversations"))
+                    .width(Length::Fill)
+                    .align_x(Center)
+                    .size(24),
+                text_input(&tr!("search-conversations"), &self.search_query)
+                    .id(search_input_id())
+                    .on_input(Message::UpdateSearch),
+            ]
+            .push_maybe(self.view_tag_filter())
+            .push(scrollable(if self.search_query.trim().is_empty() {
+                self.view_conversation_list()
+            } else {
+                self.view_search_results()
+            }))
+            .spacing(5),
+        )
+        .style(container::bordered_box)
+        .height(Length::Fill)
+        .width(Length::FillPortion(1))
+        .into()
+    }
+
+    /// Conversations grouped by folder under the pinned ones, with the archived ones in a section
+    /// of their own at the end, leaving out those without the tag being filtered by
+    fn view_conversation_list(&self) -> Column<'_, Message> {
+        let mut pinned = vec![];
+        let mut archived = vec![];
+        let mut folders: BTreeMap<Option<&str>, Vec<&PathBuf>> = BTreeMap::new();
+        for conversation_path in &self.conversations_list {
+            let summary = self.conversation_index.get(conversation_path);
+            if let Some(tag_filter) = &self.tag_filter {
+                if !summary.is_some_and(|summary| summary.tags.contains(tag_filter)) {
+                    continue;
+                }
+            }
+            match summary.map(|summary| summary.listing).unwrap_or_default() {
+                Listing::Pinned => pinned.push(conversation_path),
+                Listing::Archived => archived.push(conversation_path),
+                Listing::Listed => {
+                    let folder = summary.and_then(|summary| summary.folder.as_deref());
+                    folders.entry(folder).or_default().push(conversation_path);
+                }
+            }
+        }
+        // Unfiled conversations sort first, so they're listed above the folders
+        let folders = column(folders.into_iter().map(|(folder, conversation_paths)| {
+            let entries = self.view_sidebar_entries(conversation_paths);
+            match folder {
+                Some(folder) => column![text(folder).size(18), entries].spacing(5).into(),
+                None => entries.into(),
+            }
+        }))
+        .spacing(15);
+        column![]
+            .push_maybe((!pinned.is_empty()).then(|| {
+                column![
+                    text(tr!("pinned")).size(18),
+                    self.view_sidebar_entries(pinned)
+                ]
+                .spacing(5)
+            }))
+            .push(folders)
+            .push_maybe((!archived.is_empty()).then(|| {
+                let heading = tr!("archived", count = archived.len());
+                column![button(text(heading).size(18))
+                    .on_press(Message::ToggleArchived)
+                    .style(button::text)
+                    .padding(0)]
+                .push_maybe(
+                    self.show_archived
+                        .then(|| self.view_sidebar_entries(archived)),
+                )
+                .spacing(5)
+            }))
+            .spacing(15)
+    }
+
+    fn view_sidebar_entries<'a>(
+        &'a self,
+        conversation_paths: Vec<&'a PathBuf>,
+    ) -> Column<'a, Message> {
+        column(
+            conversation_paths
+                .into_iter()
+                .map(|conversation_path| self.view_sidebar_entry(conversation_path)),
+        )
+        .spacing(5)
+    }
+
+    /// A conversation in the sidebar, with its tags and the actions opened for it
+    fn view_sidebar_entry<'a>(&'a self, conversation_path: &'a PathBuf) -> Element<'a, Message> {
+        if let Some(SidebarAction::Rename(path, title)) = self.sidebar_action.as_ref() {
+            if path == conversation_path {
+                return row![
+                    text_input(&tr!("conversation-title"), title)
+                        .on_input(Message::UpdateRenameTitle)
+                        .on_submit(Message::RenameConversation),
+                    button(text(tr!("cancel")))
+                        .on_press(Message::SetSidebarAction(None))
+                        .style(button::secondary),
+                ]
+                .spacing(5)
+                .into();
+            }
+        }
+        let tags = self
+            .conversation_index
+            .get(conversation_path)
+            .map(|summary| summary.tags.as_slice())
+            .unwrap_or_default();
+        // Saving counts as using it for conversations without message times, e.g. imported ones
+        let last_used = self
+            .conversation_index
+            .get(conversation_path)
+            .and_then(|summary| summary.last_message_at)
+            .or_else(|| self.conversation_times.get(conversation_path).copied());
+        let conversation_button = button(
+            column![text(
+                conversation_path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_str()
+                    .unwrap_or_default(),
+            )]
+            .push_maybe(last_used.map(|last_used| {
+                text(i18n::time_ago(last_used))
+                    .size(12)
+                    .style(text::secondary)
+            }))
+            .push_maybe(
+                self.background_conversations
+                    .get(conversation_path)
+                    .is_some_and(|background| background.is_generating)
+                    .then(|| {
+                        text(tr!("generating-in-background"))
+                            .size(12)
+                            .style(text::secondary)
+                    }),
+            )
+            .push_maybe((!tags.is_empty()).then(|| {
+                row(tags
+                    .iter()
+                    .map(|tag| text(tag).size(12).color(tag_color(tag)).into()))
+                .spacing(5)
+            }))
+            .width(Length::Fill)
+            .align_x(Center),
+        )
+        .width(Length::Fill)
+        .on_press(Message::SetConversationFile(Some(
+            conversation_path.clone(),
+        )));
+        let conversation_button: Element<'_, Message> =
+            match self.conversation_index.get(conversation_path) {
+                Some(summary) => Tooltip::new(
+                    conversation_button,
+                    container(text(tr!(
+                        "conversation-summary",
+                        count = summary.message_count,
+                        preview = summary.preview.as_str()
+                    )))
+                    .padding(10)
+                    .max_width(300)
+                    .style(container::rounded_box),
+                    iced::widget::tooltip::Position::Right,
+                )
+                .into(),
+                None => conversation_button.into(),
+            };
+        column![row![
+            conversation_button,
+            button(text("⋯"))
+                .on_press(Message::ToggleConversationMenu(conversation_path.clone()))
+                .style(button::secondary),
+        ]
+        .spacing(5)]
+        .push_maybe(self.view_sidebar_action(conversation_path))
+        .spacing(5)
+        .into()
+    }
+
+    /// Every tag given to a conversation, to filter the sidebar by
+    fn view_tag_filter(&self) -> Option<Element<'_, Message>> {
+        let tags: BTreeSet<&str> = self
+            .conversation_index
+            .values()
+            .flat_map(|summary| summary.tags.iter().map(String::as_str))
+            .collect();
+        if tags.is_empty() {
+            return None;
+        }
+        let chips = row(tags.into_iter().map(|tag| {
+            let is_selected = self.tag_filter.as_deref() == Some(tag);
+            button(text(tag).size(12).color(tag_color(tag)))
+                .on_press(Message::FilterByTag(
+                    (!is_selected).then(|| tag.to_string()),
+                ))
+                .style(if is_selected {
+                    button::secondary
+                } else {
+                    button::text
+                })
+                .into()
+        }))
+        .spacing(5);
+        Some(
+            scrollable(chips)
+                .direction(scrollable::Direction::Horizontal(
+                    scrollable::Scrollbar::new().width(5).scroller_width(5),
+                ))
+                .into(),
+        )
+    }
+
+    /// Conversations whose titles or messages match the search, with the text around the match
+    fn view_search_results(&self) -> Column<'_, Message> {
+        let results: Vec<Element<'_, Message>> = self
+            .conversations_list
+            .iter()
+            .filter_map(|conversation_path| {
+                let title = conversation_path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                // Conversations that haven't been indexed yet can still match on their title
+                let search_match = match self.conversation_index.get(conversation_path) {
+                    Some(summary) => summary.search_index.search(&title, &self.search_query),
+                    None => SearchIndex::default().search(&title, &self.search_query),
+                }?;
+                Some(
+                    button(
+                        column![text(title)]
+                            .push_maybe(
+                                (!search_match.snippet.is_empty())
+                                    .then(|| text(search_match.snippet).size(12)),
+                            )
+                            .spacing(2),
+                    )
+                    .on_press(Message::OpenSearchResult(
+                        conversation_path.clone(),
+                        search_match.message_index,
+                    ))
+                    .width(Length::Fill)
+                    .into(),
+                )
+            })
+            .collect();
+        if results.is_empty() {
+            return column![text(tr!("no-search-results"))
+                .width(Length::Fill)
+                .align_x(Center)];
+        }
+        column(results).spacing(5)
+    }
+
+    /// The installed models to pick from, with their details and a way to pull or delete them
+    fn view_model_manager(&self) -> Column<'_, Message> {
+        let pull_row = row![
+            text_input(&tr!("pull-model-placeholder"), &self.pull_model_name)
+                .on_input(Message::UpdatePullModelName)
+                .on_submit(Message::PullModel),
+            button(text(tr!("pull-model"))).on_press_maybe(
+                (self.model_pull.is_none() && !self.pull_model_name.trim().is_empty())
+                    .then_some(Message::PullModel)
+            ),
+            button(text(tr!("create-model")))
+                .on_press_maybe((!self.models_list.is_empty()).then_some(Message::ShowModelBuilder))
+                .style(button::secondary),
+        ]
+        .spacing(10)
+        .align_y(Center);
+        let pull_progress = self.view_pull_progress();
+        let models = column(self.models_list.iter().map(|model| {
+            // OpenAI-compatible servers only give the names of their models
+            let mut details = if self.conversation.backend.manages_models() {
+                vec![models::format_size(model.size)]
+            } else {
+                vec![]
+            };
+            if let Some(family) = self.model_details.get(&model.name) {
+                if !family.parameter_size.is_empty() {
+                    details.push(tr!(
+                        "model-parameters",
+                        count = family.parameter_size.clone()
+                    ));
+                }
+                if !family.quantization_level.is_empty() {
+                    details.push(family.quantization_level.clone());
+                }
+            }
+            if !model.modified_at.is_empty() {
+                details.push(tr!(
+                    "model-modified",
+                    date = models::modified_date(&model.modified_at)
+                ));
+            }
+            let delete: Element<Message> = if !self.conversation.backend.manages_models() {
+                Space::with_width(0).into()
+            } else if self.confirm_delete_model.as_ref() == Some(&model.name) {
+                row![
+                    text(tr!("confirm-delete-model")),
+                    button(text(tr!("delete")))
+                        .on_press(Message::DeleteModel(model.name.clone()))
+                        .style(button::danger),
+                    button(text(tr!("cancel")))
+                        .on_press(Message::ConfirmDeleteModel(None))
+                        .style(button::secondary),
+                ]
+                .spacing(5)
+                .align_y(Center)
+                .into()
+            } else {
+                button(text(tr!("delete")))
+                    .on_press(Message::ConfirmDeleteModel(Some(model.name.clone())))
+                    .style(button::secondary)
+                    .into()
+            };
+            row![
+                button(
+                    text(&model.name)
+                        .width(Length::Fixed(250.0))
+                        .align_x(Center)
+                        .size(20),
+                )
+                .on_press(Message::SetModel(Some(model.clone()))),
+                text(details.join(" · ")).width(Length::Fill),
+                delete,
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into()
+        }))
+        .spacing(10);
+        let unreachable_banner = (self.connection == ConnectionStatus::Unreachable).then(|| {
+            container(
+                row![
+                    text(tr!(
+                        "server-unreachable-banner",
+                        server = self.conversation.server.to_string()
+                    ))
+                    .style(text::danger)
+                    .width(Length::Fill),
+                    button(text(tr!("retry"))).on_press(Message::LoadModelsList),
+                    button(text(tr!("settings")))
+                        .on_press(Message::ShowSettings)
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            )
+            .padding(10)
+            .style(container::rounded_box)
+        });
+        column![]
+            .push_maybe(unreachable_banner)
+            .push_maybe(
+                self.conversation
+                    .backend
+                    .manages_models()
+                    .then_some(pull_row),
+            )
+            .push_maybe(pull_progress)
+            .push(self.view_toasts())
+            .push(scrollable(models))
+            .spacing(20)
+            .padding(30)
+            .max_width(900)
+            .width(Length::Fill)
+    }
+
+    /// Actions for a conversation in the sidebar, if they've been opened for it
+    fn view_sidebar_action(&self, conversation_path: &PathBuf) -> Option<Element<'_, Message>> {
+        let sidebar_action = self
+            .sidebar_action
+            .as_ref()
+            .filter(|sidebar_action| sidebar_action.path() == conversation_path)?;
+        // The conversation can't be moved out from under a response being written to it
+        let is_busy = self.is_generating_in(conversation_path);
+        let actions = match sidebar_action {
+            SidebarAction::Menu(path) => {
+                let listing = self
+                    .conversation_index
+                    .get(path)
+                    .map(|summary| summary.listing)
+                    .unwrap_or_default();
+                let title = path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                row![
+                    button(text(tr!("rename")))
+                        .on_press_maybe((!is_busy).then(|| {
+                            Message::SetSidebarAction(Some(SidebarAction::Rename(
+                                path.clone(),
+                                title,
+                            )))
+                        }))
+                        .style(button::secondary),
+                    button(text(tr!("duplicate")))
+                        .on_press(Message::DuplicateConversation(path.clone()))
+                        .style(button::secondary),
+                    button(text(tr!("open-in-new-window")))
+                        .on_press(Message::OpenInNewWindow(path.clone()))
+                        .style(button::secondary),
+                    button(text(tr!("organize")))
+                        .on_press_maybe((!is_busy).then(|| {
+                            let summary = self.conversation_index.get(path);
+                            Message::SetSidebarAction(Some(SidebarAction::Organize(
+                                path.clone(),
+                                summary
+                                    .and_then(|summary| summary.folder.clone())
+                                    .unwrap_or_default(),
+                                summary
+                                    .map(|summary| summary.tags.join(", "))
+                                    .unwrap_or_default(),
+                            )))
+                        }))
+                        .style(button::secondary),
+                    button(text(if listing == Listing::Pinned {
+                        tr!("unpin")
+                    } else {
+                        tr!("pin")
+                    }))
+                    .on_press_maybe((!is_busy).then(|| {
+                        Message::SetListing(
+                            path.clone(),
+                            if listing == Listing::Pinned {
+                                Listing::Listed
+                            } else {
+                                Listing::Pinned
+                            },
+                        )
+                    }))
+                    .style(button::secondary),
+                    button(text(if listing == Listing::Archived {
+                        tr!("unarchive")
+                    } else {
+                        tr!("archive")
+                    }))
+                    .on_press_maybe((!is_busy).then(|| {
+                        Message::SetListing(
+                            path.clone(),
+                            if listing == Listing::Archived {
+                                Listing::Listed
+                            } else {
+                                Listing::Archived
+                            },
+                        )
+                    }))
+                    .style(button::secondary),
+                    export_pick_list(Some(path.clone())),
+                    button(text(tr!("delete")))
+                        .on_press_maybe((!is_busy).then(|| {
+                            Message::SetSidebarAction(Some(SidebarAction::ConfirmDelete(
+                                path.clone(),
+                            )))
+                        }))
+                        .style(button::danger),
+                ]
+            }
+            SidebarAction::ConfirmDelete(path) => row![
+                text(tr!("confirm-delete")).width(Length::Fill),
+                button(text(tr!("delete")))
+                    .on_press_maybe((!is_busy).then(|| Message::DeleteConversation(path.clone())))
+                    .style(button::danger),
+                button(text(tr!("cancel")))
+                    .on_press(Message::SetSidebarAction(None))
+                    .style(button::secondary),
+            ],
+            SidebarAction::Organize(_, folder, tags) => {
+                return Some(
+                    column![
+                        text_input(&tr!("folder-placeholder"), folder)
+                            .on_input(Message::UpdateOrganizeFolder)
+                            .on_submit(Message::OrganizeConversation),
+                        text_input(&tr!("tags-placeholder"), tags)
+                            .on_input(Message::UpdateOrganizeTags)
+                            .on_submit(Message::OrganizeConversation),
+                        row![
+                            button(text(tr!("save"))).on_press_maybe(
+                                (!is_busy).then_some(Message::OrganizeConversation)
+                            ),
+                            button(text(tr!("cancel")))
+                                .on_press(Message::SetSidebarAction(None))
+                                .style(button::secondary),
+                        ]
+                        .spacing(5),
+                    ]
+                    .spacing(5)
+                    .into(),
+                );
+            }
+            SidebarAction::Rename(..) => return None,
+        };
+        Some(actions.spacing(5).align_y(Center).into())
+    }
+
+    fn view_background_jobs(&self) -> Element<'_, Message> {
+        if !self.show_background_jobs {
+            return column![].into();
+        }
+        container(if self.background_jobs.is_empty() {
+            column![text(tr!("no-background-tasks"))]
+        } else {
+            column(self.background_jobs.iter().map(|job_status| {
+                row![
+                    text(&job_status.name).width(Length::Fill),
+                    match job_status.progress {
+                        Some(progress) => Element::from(
+                            progress_bar(0.0..=1.0, progress)
+                                .height(Length::Fixed(10.0))
+                                .width(Length::Fixed(150.0))
+                        ),
+                        None => text(tr!("job-queued")).into(),
+                    }
+                ]
+                .spacing(10)
+                .align_y(Center)
+                .into()
+            }))
+            .spacing(5)
+        })
+        .padding(10)
+        .width(Length::Fill)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    fn view_profile_picker<'a>(
+        &'a self,
+        profile_picker: &'a ProfilePicker,
+    ) -> Element<'a, Message> {
+        let new_profile = Some(profile_picker.new_profile.trim().to_string())
+            .filter(|name| profile::is_valid_name(name));
+        container(
+            column![
+                text(tr!("choose-profile")).size(24),
+                button(text(tr!("default-profile")))
+                    .on_press(Message::ChooseProfile(None))
+                    .width(Length::Fill),
+                column(profile_picker.profiles.iter().map(|name| {
+                    button(text(name))
+                        .on_press(Message::ChooseProfile(Some(name.clone())))
+                        .width(Length::Fill)
+                        .into()
+                }))
+                .spacing(10),
+                row![
+                    text_input(&tr!("new-profile"), &profile_picker.new_profile)
+                        .on_input(Message::UpdateNewProfile)
+                        .on_submit_maybe(
+                            new_profile
+                                .clone()
+                                .map(|name| Message::ChooseProfile(Some(name)))
+                        ),
+                    button(text(tr!("create-profile")))
+                        .on_press_maybe(new_profile.map(|name| Message::ChooseProfile(Some(name))))
+                        .style(button::secondary),
+                ]
+                .spacing(10),
+                self.view_toasts(),
+            ]
+            .spacing(15)
+            .max_width(500),
+        )
+        .center(Length::Fill)
+        .padding(20)
+        .into()
+    }
+
+    fn view_passphrase_screen(&self, passphrase_screen: PassphraseScreen) -> Element<'_, Message> {
+        let (title, explanation, submit_label) = match passphrase_screen {
+            PassphraseScreen::Unlock => (
+                tr!("unlock-title"),
+                tr!("unlock-explanation"),
+                tr!("unlock"),
+            ),
+            PassphraseScreen::Choose => (
+                tr!("encrypt-title"),
+                tr!("encrypt-explanation"),
+                tr!("encrypt"),
+            ),
+        };
+        let buttons = row![button(text(submit_label))
+            .on_press_maybe((!self.passphrase.is_empty()).then_some(Message::SubmitPassphrase))]
+        .spacing(10);
+        let buttons = if passphrase_screen == PassphraseScreen::Choose {
+            buttons.push(
+                button(text(tr!("cancel")))
+                    .on_press(Message::CancelPassphrase)
+                    .style(button::secondary),
+            )
+        } else {
+            buttons
+        };
+        container(
+            column![
+                text(title).size(24),
+                text(explanation),
+                text_input(&tr!("passphrase"), &self.passphrase)
+                    .secure(true)
+                    .on_input(Message::UpdatePassphrase)
+                    .on_submit(Message::SubmitPassphrase),
+                buttons,
+                self.view_toasts(),
+            ]
+            .spacing(15)
+            .max_width(500),
+        )
+        .center(Length::Fill)
+        .padding(20)
+        .into()
+    }
+
+    fn view_history<'a>(&'a self, history_view: &'a HistoryView) -> Element<'a, Message> {
+        let versions = column(
+            history_view
+                .versions
+                .iter()
+                .enumerate()
+                .map(|(index, version)| {
+                    let is_previewed = history_view
+                        .preview
+                        .as_ref()
+                        .is_some_and(|(previewed, _)| *previewed == index);
+                    button(column![
+                        text(&version.message),
+                        text(&version.date).size(12)
+                    ])
+                    .on_press(Message::PreviewVersion(index))
+                    .style(if is_previewed {
+                        button::primary
+                    } else {
+                        button::secondary
+                    })
+                    .width(Length::Fill)
+                    .into()
+                }),
+        )
+        .spacing(5);
+        let preview: Element<'a, Message> = match history_view.preview.as_ref() {
+            Some((index, conversation)) => column![
+                button(text(tr!("restore-version"))).on_press_maybe(
+                    history_view
+                        .versions
+                        .get(*index)
+                        .map(|version| Message::RestoreVersion(version.clone()))
+                ),
+                scrollable(
+                    column(conversation.iter().map(|chat_message| {
+                        container(text(&chat_message.content))
+                            .padding(10)
+                            .style(if chat_message.role == MessageRole::User {
+                                container::rounded_box
+                            } else {
+                                container::transparent
+                            })
+                            .into()
+                    }))
+                    .spacing(10)
+                )
+                .height(Length::Fill),
+            ]
+            .spacing(10)
+            .into(),
+            None if history_view.versions.is_empty() => text(tr!("no-versions")).into(),
+            None => text(tr!("select-version")).into(),
+        };
+        column![
+            row![
+                text(tr!("history")).width(Length::Fill).size(24),
+                button(text(tr!("close")))
+                    .on_press(Message::CloseHistory)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            row![
+                scrollable(versions).width(Length::FillPortion(1)),
+                container(preview).width(Length::FillPortion(2)),
+            ]
+            .spacing(20)
+            .height(Length::Fill),
+            self.view_toasts(),
+        ]
+        .spacing(10)
+        .padding(20)
+        .into()
+    }
+
+    /// The open conversation's system message, which is always its first message
+    fn system_prompt(&self) -> Option<&str> {
+        self.conversation
+            .unloaded_chats
+            .first()
+            .or(self
+                .conversation
+                .chats_list
+                .first()
+                .map(|(chat_message, _markdown_items)| chat_message))
+            .filter(|chat_message| chat_message.role == MessageRole::System)
+            .map(|chat_message| chat_message.content.as_str())
+    }
+
+    /// Replaces the open conversation's system message, removing it if the prompt is empty
+    fn set_system_prompt(&mut self, system_prompt: String) -> Task<Message> {
+        let system_prompt = system_prompt.trim().to_string();
+        let had_system_prompt = self.system_prompt().is_some();
+        // The first message is either the oldest unloaded one or, if they're all loaded, the
+        // first one on screen
+        if self.conversation.unloaded_chats.is_empty() {
+            let markdown_items = self.markdown_cache.parse(&system_prompt);
+            let entry = (
+                ChatMessage::system(system_prompt.clone()),
+                Some(markdown_items),
+            );
+            match (had_system_prompt, system_prompt.is_empty()) {
+                (true, true) => {
+                    self.conversation.chats_list.remove(0);
+                }
+                (true, false) => self.conversation.chats_list[0] = entry,
+                (false, false) => self.conversation.chats_list.insert(0, entry),
+                (false, true) => {}
+            }
+        } else {
+            let system_message = ChatMessage::system(system_prompt.clone());
+            match (had_system_prompt, system_prompt.is_empty()) {
+                (true, true) => {
+                    self.conversation.unloaded_chats.remove(0);
+                }
+                (true, false) => self.conversation.unloaded_chats[0] = system_message,
+                (false, false) => self.conversation.unloaded_chats.insert(0, system_message),
+                (false, true) => {}
+            }
+        }
+        // Versions and what's kept about each message are by its index, which moves along with it
+        match (had_system_prompt, system_prompt.is_empty()) {
+            (true, true) => self.move_indexes(|index| index.checked_sub(1)),
+            (false, false) => self.move_indexes(|index| Some(index + 1)),
+            (true, false) | (false, true) => {}
+        }
+        if self.conversation.current_conversation.is_some() {
+            return Task::done(Message::SaveConversation);
+        }
+        Task::none()
+    }
+
+    /// Moves the versions and what's kept about each message of the open conversation to the
+    /// index `move_index` gives, dropping those it gives none
+    fn move_indexes(&mut self, move_index: impl Fn(usize) -> Option<usize> + Copy) {
+        let conversation = &mut self.conversation;
+        move_by_index(&mut conversation.branches, move_index);
+        move_by_index(&mut conversation.response_stats, move_index);
+        move_by_index(&mut conversation.citations, move_index);
+        move_by_index(&mut conversation.web_sources, move_index);
+        move_by_index(&mut conversation.sent_times, move_index);
+        move_by_index(&mut conversation.tool_calls, move_index);
+        move_by_index(&mut conversation.formats, move_index);
+    }
+
+    /// Whether the server is reachable, and a picker to switch to another saved server
+    fn view_server_picker(&self) -> Element<'_, Message> {
+        let (status_style, status_label): (fn(&Theme) -> text::Style, String) =
+            match self.connection {
+                ConnectionStatus::Connecting => (text::secondary, tr!("server-connecting")),
+                ConnectionStatus::Connected => (text::success, tr!("server-connected")),
+                ConnectionStatus::Unreachable => (text::danger, tr!("server-unreachable")),
+            };
+        let server = &self.conversation.server;
+        let status = Tooltip::new(
+            text("●").style(status_style),
+            text(format!("{server}: {status_label}")),
+            iced::widget::tooltip::Position::Bottom,
+        );
+        if self.settings.servers.is_empty() {
+            return container(status).center_y(Length::Fill).into();
+        }
+        row![
+            status,
+            pick_list(
+                self.settings.servers.as_slice(),
+                self.settings
+                    .servers
+                    .iter()
+                    .find(|saved_server| *saved_server == server),
+                Message::SwitchServer
+            )
+            .width(Length::Fixed(150.0)),
+        ]
+        .spacing(5)
+        .align_y(Center)
+        .into()
+    }
+
+    fn view_settings<'a>(&'a self, settings: &'a Settings) -> Element<'a, Message> {
+        let setting = |label: String, widget: Element<'a, Message>| {
+            row![
+                text(label).width(Length::FillPortion(1)),
+                container(widget).width(Length::FillPortion(2))
+            ]
+            .spacing(20)
+            .align_y(Center)
+        };
+        let toggle = |label: String, is_checked: bool, update: fn(&mut Settings, bool)| {
+            checkbox(label, is_checked).on_toggle(move |is_checked| {
+                let mut settings = settings.clone();
+                update(&mut settings, is_checked);
+                Message::UpdateSettingsDraft(settings)
+            })
+        };
+        let model_choices: Vec<Choice> = std::iter::once(Choice {
+            value: None,
+            label: tr!("no-default-model"),
+        })
+        .chain(self.models_list.iter().map(|model| Choice {
+            value: Some(model.name.clone()),
+            label: model.name.clone(),
+        }))
+        .collect();
+        let language_choices: Vec<Choice> = std::iter::once(Choice {
+            value: None,
+            label: tr!("system-language"),
+        })
+        .chain(i18n::languages().map(|(code, name)| Choice {
+            value: Some(code.to_string()),
+            label: name.to_string(),
+        }))
+        .collect();
+        let selected_model = model_choices
+            .iter()
+            .find(|choice| choice.value == settings.default_model)
+            .cloned();
+        let title_model_choices: Vec<Choice> = std::iter::once(Choice {
+            value: None,
+            label: tr!("conversation-model"),
+        })
+        .chain(model_choices.iter().skip(1).cloned())
+        .collect();
+        let selected_title_model = title_model_choices
+            .iter()
+            .find(|choice| choice.value == settings.title_model)
+            .cloned();
+        let selected_language = language_choices
+            .iter()
+            .find(|choice| choice.value == settings.language)
+            .cloned();
+        let theme_choices: Vec<Choice> = [
+            (settings::SYSTEM_THEME, tr!("theme-system")),
+            (settings::CUSTOM_THEME, tr!("theme-custom")),
+        ]
+        .into_iter()
+        .map(|(value, label)| Choice {
+            value: Some(value.to_string()),
+            label,
+        })
+        .chain(Theme::ALL.iter().map(|theme| Choice {
+            value: Some(theme.to_string()),
+            label: theme.to_string(),
+        }))
+        .collect();
+        let selected_theme = theme_choices
+            .iter()
+            .find(|choice| choice.value.as_ref() == Some(&settings.theme))
+            .cloned();
+        let conversations_dir = settings
+            .conversations_dir
+            .as_ref()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+        let default_conversations_dir = storage::default_conversations_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+        column![
+            row![
+                text(tr!("settings")).width(Length::Fill).size(24),
+                button(text(tr!("open-settings-file")))
+                    .on_press_maybe(settings::settings_file().ok().map(Message::OpenFile))
+                    .style(button::secondary),
+                button(text(tr!("save"))).on_press_maybe(
+                    (*settings != self.settings).then(|| Message::SaveSettings(settings.clone()))
+                ),
+                button(text(tr!("close")))
+                    .on_press(Message::CloseSettings)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            scrollable(
+                column![
+                    setting(
+                        tr!("setting-server-url"),
+                        text_input("http://127.0.0.1:11434", &settings.server_url)
+                            .on_input(|server_url| Message::UpdateSettingsDraft(Settings {
+                                server_url,
+                                ..settings.clone()
+                            }))
+                            .into()
+                    ),
+                    text(tr!("saved-servers")),
+                    column(settings.servers.iter().enumerate().map(|(index, server)| {
+                        let update_server = move |update: fn(&mut Server, String)| {
+                            move |value| {
+                                let mut settings = settings.clone();
+                                update(&mut settings.servers[index], value);
+                                Message::UpdateSettingsDraft(settings)
+                            }
+                        };
+                        row![
+                            text_input(&tr!("server-name"), &server.name)
+                                .on_input(update_server(|server, name| server.name = name)),
+                            text_input("http://192.168.1.20:11434", &server.url)
+                                .on_input(update_server(|server, url| server.url = url)),
+                            pick_list(
+                                [ServerKind::Ollama, ServerKind::OpenAi].map(ServerKindChoice),
+                                Some(ServerKindChoice(server.kind)),
+                                move |ServerKindChoice(kind)| {
+                                    let mut settings = settings.clone();
+                                    settings.servers[index].kind = kind;
+                                    Message::UpdateSettingsDraft(settings)
+                                }
+                            ),
+                            text_input(
+                                &tr!("server-username"),
+                                server.username.as_deref().unwrap_or_default()
+                            )
+                            .on_input(update_server(
+                                |server, username| {
+                                    server.username = (!username.is_empty()).then_some(username)
+                                }
+                            )),
+                            text_input(
+                                &tr!("server-password"),
+                                server.password.as_deref().unwrap_or_default()
+                            )
+                            .secure(true)
+                            .on_input(update_server(
+                                |server, password| {
+                                    server.password = (!password.is_empty()).then_some(password)
+                                }
+                            )),
+                            text_input(
+                                &tr!("server-api-key"),
+                                server.api_key.as_deref().unwrap_or_default()
+                            )
+                            .secure(true)
+                            .on_input(update_server(
+                                |server, api_key| {
+                                    server.api_key = (!api_key.is_empty()).then_some(api_key)
+                                }
+                            )),
+                            button(text(tr!("remove")))
+                                .on_press({
+                                    let mut settings = settings.clone();
+                                    settings.servers.remove(index);
+                                    Message::UpdateSettingsDraft(settings)
+                                })
+                                .style(button::secondary),
+                        ]
+                        .spacing(10)
+                        .into()
+                    }))
+                    .spacing(10),
+                    text(tr!("server-logins-unencrypted")).size(14),
+                    button(text(tr!("add-server")))
+                        .on_press({
+                            let mut settings = settings.clone();
+                            settings.servers.push(Server {
+                                url: settings.server_url.clone(),
+                                ..Default::default()
+                            });
+                            Message::UpdateSettingsDraft(settings)
+                        })
+                        .style(button::secondary),
+                    setting(
+                        tr!("setting-default-model"),
+                        pick_list(model_choices, selected_model, |choice| {
+                            Message::UpdateSettingsDraft(Settings {
+                                default_model: choice.value,
+                                ..settings.clone()
+                            })
+                        })
+                        .into()
+                    ),
+                    setting(
+                        tr!("setting-theme"),
+                        pick_list(theme_choices, selected_theme, |choice| {
+                            Message::UpdateSettingsDraft(Settings {
+                                theme: choice.value.unwrap_or_default(),
+                                ..settings.clone()
+                            })
+                        })
+                        .into()
+                    ),
+                    view_palette_editor(settings),
+                    setting(
+                        tr!("setting-zoom"),
+                        row![
+                            slider(
+                                settings::MIN_ZOOM..=settings::MAX_ZOOM,
+                                settings.zoom,
+                                |zoom| {
+                                    Message::UpdateSettingsDraft(Settings {
+                                        zoom,
+                                        ..settings.clone()
+                                    })
+                                }
+                            )
+                            .step(settings::ZOOM_STEP),
+                            text(format!("{:.0}%", settings.zoom * 100.0))
+                                .width(Length::Fixed(50.0)),
+                        ]
+                        .spacing(10)
+                        .align_y(Center)
+                        .into()
+                    ),
+                    setting(
+                        tr!("setting-language"),
+                        pick_list(language_choices, selected_language, |choice| {
+                            Message::UpdateSettingsDraft(Settings {
+                                language: choice.value,
+                                ..settings.clone()
+                            })
                         })
-                        .width(Length::Fixed(50.0)),
-                        "Toggle Sidebar",
-                        iced::widget::tooltip::Position::Bottom
+                        .into()
+                    ),
+                    setting(
+                        tr!("setting-conversations-dir"),
+                        text_input(&default_conversations_dir, &conversations_dir)
+                            .on_input(|conversations_dir| {
+                                Message::UpdateSettingsDraft(Settings {
+                                    // Left empty to go back to the default
+                                    conversations_dir: (!conversations_dir.is_empty())
+                                        .then(|| PathBuf::from(conversations_dir)),
+                                    ..settings.clone()
+                                })
+                            })
+                            .into()
+                    ),
+                    toggle(
+                        tr!("setting-show-sidebar"),
+                        settings.show_sidebar,
+                        |settings, show_sidebar| settings.show_sidebar = show_sidebar
+                    ),
+                    toggle(
+                        tr!("setting-encrypt-conversations"),
+                        settings.encrypt_conversations,
+                        |settings, encrypt| settings.encrypt_conversations = encrypt
+                    ),
+                    toggle(
+                        tr!("setting-git-history"),
+                        settings.git_history,
+                        |settings, git_history| settings.git_history = git_history
+                    ),
+                    toggle(
+                        tr!("setting-watch-clipboard"),
+                        settings.watch_clipboard,
+                        |settings, watch_clipboard| settings.watch_clipboard = watch_clipboard
+                    ),
+                    toggle(
+                        tr!("setting-minimize-to-tray"),
+                        settings.minimize_to_tray,
+                        |settings, minimize_to_tray| settings.minimize_to_tray = minimize_to_tray
+                    ),
+                    setting(
+                        tr!("setting-quick-chat-shortcut"),
+                        text_input("Ctrl+Alt+Space", &settings.shortcuts.quick_chat)
+                            .on_input(|quick_chat| {
+                                let mut settings = settings.clone();
+                                settings.shortcuts.quick_chat = quick_chat;
+                                Message::UpdateSettingsDraft(settings)
+                            })
+                            .into()
+                    ),
+                    toggle(
+                        tr!("setting-summarize-context"),
+                        settings.summarize_context,
+                        |settings, summarize_context| settings.summarize_context =
+                            summarize_context
+                    ),
+                    toggle(
+                        tr!("setting-include-reasoning"),
+                        settings.include_reasoning,
+                        |settings, include_reasoning| settings.include_reasoning =
+                            include_reasoning
+                    ),
+                    setting(
+                        tr!("setting-share-url"),
+                        text_input("https://transfer.sh", &settings.share_url)
+                            .on_input(|share_url| {
+                                Message::UpdateSettingsDraft(Settings {
+                                    share_url,
+                                    ..settings.clone()
+                                })
+                            })
+                            .into()
+                    ),
+                    setting(
+                        tr!("setting-tools"),
+                        Row::with_children(Tool::ALL.map(|tool| {
+                            checkbox(tool_name(tool), settings.tools.contains(&tool))
+                                .on_toggle(move |is_offered| {
+                                    let mut settings = settings.clone();
+                                    settings.tools.retain(|offered| *offered != tool);
+                                    if is_offered {
+                                        settings.tools.push(tool);
+                                    }
+                                    Message::UpdateSettingsDraft(settings)
+                                })
+                                .into()
+                        }))
+                        .spacing(15)
+                        .wrap()
+                        .into()
+                    ),
+                    toggle(
+                        tr!("setting-auto-title"),
+                        settings.auto_title,
+                        |settings, auto_title| settings.auto_title = auto_title
+                    ),
+                    setting(
+                        tr!("setting-title-model"),
+                        pick_list(title_model_choices, selected_title_model, |choice| {
+                            Message::UpdateSettingsDraft(Settings {
+                                title_model: choice.value,
+                                ..settings.clone()
+                            })
+                        })
+                        .into()
+                    ),
+                    setting(
+                        tr!("setting-embedding-model"),
+                        text_input("nomic-embed-text", &settings.embedding_model)
+                            .on_input(|embedding_model| {
+                                Message::UpdateSettingsDraft(Settings {
+                                    embedding_model,
+                                    ..settings.clone()
+                                })
+                            })
+                            .into()
+                    ),
+                    setting(
+                        tr!("setting-math-renderer"),
+                        text_input("tex2svg", &settings.math_renderer)
+                            .on_input(|math_renderer| {
+                                Message::UpdateSettingsDraft(Settings {
+                                    math_renderer,
+                                    ..settings.clone()
+                                })
+                            })
+                            .into()
+                    ),
+                    setting(
+                        tr!("setting-markdown-memory-budget"),
+                        row![
+                            slider(
+                                settings::MIN_MARKDOWN_MEMORY_BUDGET
+                                    ..=settings::MAX_MARKDOWN_MEMORY_BUDGET,
+                                settings.markdown_memory_budget,
+                                |markdown_memory_budget| {
+                                    Message::UpdateSettingsDraft(Settings {
+                                        markdown_memory_budget,
+                                        ..settings.clone()
+                                    })
+                                }
+                            ),
+                            text(tr!("megabytes", count = settings.markdown_memory_budget))
+                                .width(Length::Fixed(60.0)),
+                        ]
+                        .spacing(10)
+                        .align_y(Center)
+                        .into()
+                    ),
+                    view_web_search_settings(settings),
+                    self.view_speech_settings(settings),
+                    view_dictation_settings(settings),
+                ]
+                .spacing(15)
+                .max_width(800)
+            )
+            .height(Length::Fill),
+            self.view_toasts(),
+        ]
+        .spacing(10)
+        .padding(20)
+        .into()
+    }
+
+    /// The text-to-speech engine and its voice, picked from espeak-ng's voices once they're listed
+    fn view_speech_settings<'a>(&'a self, settings: &'a Settings) -> Element<'a, Message> {
+        let speech = &settings.speech;
+        let update = move |speech: Speech| {
+            Message::UpdateSettingsDraft(Settings {
+                speech,
+                ..settings.clone()
+            })
+        };
+        let setting = |label: String, widget: Element<'a, Message>| -> Element<'a, Message> {
+            row![
+                text(label).width(Length::FillPortion(1)),
+                container(widget).width(Length::FillPortion(2))
+            ]
+            .spacing(20)
+            .align_y(Center)
+            .into()
+        };
+        let engine = setting(
+            tr!("setting-speech-engine"),
+            pick_list(
+                [TtsEngine::EspeakNg, TtsEngine::Piper].map(TtsEngineChoice),
+                Some(TtsEngineChoice(speech.engine)),
+                move |TtsEngineChoice(engine)| {
+                    update(Speech {
+                        engine,
+                        ..speech.clone()
+                    })
+                },
+            )
+            .into(),
+        );
+        let engine_settings = match speech.engine {
+            TtsEngine::EspeakNg if !self.voices.is_empty() => {
+                let voice_choices: Vec<Choice> = std::iter::once(Choice {
+                    value: None,
+                    label: tr!("default-voice"),
+                })
+                .chain(self.voices.iter().map(|voice| Choice {
+                    value: Some(voice.language.clone()),
+                    label: voice.to_string(),
+                }))
+                .collect();
+                let selected_voice = voice_choices
+                    .iter()
+                    .find(|choice| choice.value == speech.voice)
+                    .cloned();
+                vec![setting(
+                    tr!("setting-voice"),
+                    pick_list(voice_choices, selected_voice, move |choice| {
+                        update(Speech {
+                            voice: choice.value,
+                            ..speech.clone()
+                        })
+                    })
+                    .into(),
+                )]
+            }
+            TtsEngine::EspeakNg => vec![setting(
+                tr!("setting-voice"),
+                text_input("en-gb", speech.voice.as_deref().unwrap_or_default())
+                    .on_input(move |voice| {
+                        update(Speech {
+                            voice: (!voice.is_empty()).then_some(voice),
+                            ..speech.clone()
+                        })
+                    })
+                    .into(),
+            )],
+            TtsEngine::Piper => vec![
+                setting(
+                    tr!("setting-piper-model"),
+                    text_input(
+                        "en_GB-alan-medium.onnx",
+                        &speech
+                            .piper_model
+                            .as_ref()
+                            .map(|piper_model| piper_model.display().to_string())
+                            .unwrap_or_default(),
+                    )
+                    .on_input(move |piper_model| {
+                        update(Speech {
+                            piper_model: (!piper_model.is_empty())
+                                .then(|| PathBuf::from(piper_model)),
+                            ..speech.clone()
+                        })
+                    })
+                    .into(),
+                ),
+                setting(
+                    tr!("setting-audio-player"),
+                    text_input(&Speech::default().player, &speech.player)
+                        .on_input(move |player| {
+                            update(Speech {
+                                player,
+                                ..speech.clone()
+                            })
+                        })
+                        .into(),
+                ),
+            ],
+        };
+        column![engine].extend(engine_settings).spacing(15).into()
+    }
+
+    /// Button to start dictating, which shows it's listening until it's pressed again to stop
+    fn view_dictation_button(&self) -> Element<'_, Message> {
+        match self.dictation {
+            None => Tooltip::new(
+                button(text(tr!("dictate"))).on_press(Message::StartDictation),
+                text(tr!("dictate-tooltip")),
+                iced::widget::tooltip::Position::Top,
+            )
+            .into(),
+            Some(DictationState::Listening(_)) => Tooltip::new(
+                button(
+                    row![
+                        Spinner::new()
+                            .width(Length::Fixed(16.0))
+                            .height(Length::Fixed(16.0)),
+                        text(tr!("listening")),
+                    ]
+                    .spacing(5)
+                    .align_y(Center),
+                )
+                .on_press(Message::StopDictation)
+                .style(button::danger),
+                text(tr!("listening-tooltip")),
+                iced::widget::tooltip::Position::Top,
+            )
+            .into(),
+            Some(DictationState::Transcribing) => button(text(tr!("transcribing"))).into(),
+        }
+    }
+
+    fn view_benchmark<'a>(&'a self, benchmark_view: &'a BenchmarkView) -> Element<'a, Message> {
+        let models = column(self.models_list.iter().map(|model| {
+            checkbox(
+                &model.name,
+                benchmark_view.selected_models.contains(&model.name),
+            )
+            .on_toggle(|is_selected| Message::ToggleBenchmarkModel(model.name.clone(), is_selected))
+            .into()
+        }))
+        .spacing(5);
+        let status: Element<'a, Message> = match benchmark_view.running.as_ref() {
+            Some((model_name, _prompt)) => row![
+                Spinner::new(),
+                text(tr!(
+                    "benchmark-running",
+                    model = model_name.clone(),
+                    remaining = benchmark_view.queue.len()
+                )),
+                button(text(tr!("stop")))
+                    .on_press_maybe(
+                        (!benchmark_view.queue.is_empty()).then_some(Message::StopBenchmark)
+                    )
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+            None => button(text(tr!("run-benchmark")))
+                .on_press_maybe(
+                    (!benchmark_view.selected_models.is_empty()
+                        && !benchmark_view.prompts.text().trim().is_empty())
+                    .then_some(Message::RunBenchmark),
+                )
+                .into(),
+        };
+        let header = |label: String, column: BenchmarkColumn, width: Length| {
+            let label = match benchmark_view.sort {
+                (sort_column, true) if sort_column == column => format!("{label} ▲"),
+                (sort_column, false) if sort_column == column => format!("{label} ▼"),
+                _ => label,
+            };
+            button(text(label).size(14))
+                .on_press(Message::SortBenchmark(column))
+                .style(button::text)
+                .width(width)
+        };
+        let results = column![row![
+            header(
+                tr!("benchmark-model"),
+                BenchmarkColumn::Model,
+                Length::FillPortion(2)
+            ),
+            header(
+                tr!("benchmark-prompt"),
+                BenchmarkColumn::Prompt,
+                Length::FillPortion(2)
+            ),
+            header(
+                tr!("benchmark-time-to-first-token"),
+                BenchmarkColumn::TimeToFirstToken,
+                Length::FillPortion(1)
+            ),
+            header(
+                tr!("benchmark-total-time"),
+                BenchmarkColumn::TotalTime,
+                Length::FillPortion(1)
+            ),
+            header(
+                tr!("benchmark-tokens-per-second"),
+                BenchmarkColumn::TokensPerSecond,
+                Length::FillPortion(1)
+            ),
+            text(tr!("benchmark-output"))
+                .size(14)
+                .width(Length::FillPortion(4)),
+        ]
+        .spacing(10)
+        .align_y(Center)]
+        .extend(benchmark_view.results.iter().map(|result| {
+            container(
+                row![
+                    text(&result.model).width(Length::FillPortion(2)),
+                    text(notifications::snippet(&result.prompt)).width(Length::FillPortion(2)),
+                    text(format!("{:.2} s", result.time_to_first_token.as_secs_f64()))
+                        .width(Length::FillPortion(1)),
+                    text(format!("{:.2} s", result.total_time.as_secs_f64()))
+                        .width(Length::FillPortion(1)),
+                    text(
+                        result
+                            .tokens_per_second
+                            .map(|tokens_per_second| format!("{tokens_per_second:.1}"))
+                            .unwrap_or_else(|| "–".to_string())
+                    )
+                    .width(Length::FillPortion(1)),
+                    row![
+                        text(notifications::snippet(&result.output)).width(Length::Fill),
+                        button(text(tr!("copy")).size(12))
+                            .on_press(Message::CopyChat(result.output.clone()))
+                            .style(button::secondary),
+                    ]
+                    .spacing(5)
+                    .width(Length::FillPortion(4)),
+                ]
+                .spacing(10),
+            )
+            .padding(5)
+            .style(container::rounded_box)
+            .into()
+        }))
+        .spacing(5);
+        column![
+            row![
+                text(tr!("benchmark")).width(Length::Fill).size(24),
+                button(text(tr!("export-csv"))).on_press_maybe(
+                    (!benchmark_view.results.is_empty()).then_some(Message::ExportBenchmark)
+                ),
+                button(text(tr!("close")))
+                    .on_press(Message::CloseBenchmark)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            row![
+                column![
+                    text(tr!("benchmark-prompts")),
+                    text_editor(&benchmark_view.prompts)
+                        .placeholder(tr!("benchmark-prompts-placeholder"))
+                        .on_action(Message::EditBenchmarkPrompts)
+                        .height(Length::Fixed(150.0)),
+                    text(tr!("benchmark-models")),
+                    scrollable(models).height(Length::Fill),
+                    status,
+                ]
+                .spacing(10)
+                .width(Length::FillPortion(1)),
+                scrollable(results).width(Length::FillPortion(3)),
+            ]
+            .spacing(20)
+            .height(Length::Fill),
+            self.view_save_as(),
+            self.view_toasts(),
+        ]
+        .spacing(10)
+        .padding(20)
+        .into()
+    }
+
+    fn view_model_builder<'a>(&'a self, model_builder: &'a ModelBuilder) -> Element<'a, Message> {
+        let base_models: Vec<String> = self
+            .models_list
+            .iter()
+            .map(|model| model.name.clone())
+            .collect();
+        let inputs = GenerationParam::ALL.into_iter().map(|param| {
+            let input = &model_builder.inputs[param as usize];
+            let is_valid = param.set(&mut GenerationParams::default(), input);
+            column![
+                text(param.label()).size(12),
+                text_input(&tr!("param-default"), input)
+                    .on_input(move |input| Message::UpdateNewModelParam(param, input))
+                    .style(move |theme: &Theme, status| {
+                        let mut style = text_input::default(theme, status);
+                        if !is_valid {
+                            style.border.color = theme.extended_palette().danger.base.color;
+                        }
+                        style
+                    }),
+            ]
+            .spacing(2)
+            .width(Length::Fill)
+            .into()
+        });
+        let status: Element<'a, Message> = match model_builder.status.as_ref() {
+            Some(status) => row![
+                Spinner::new(),
+                text(tr!(
+                    "creating-model",
+                    model = model_builder.name.trim().to_string(),
+                    status = status.clone()
+                )),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+            None => button(text(tr!("create-model")))
+                .on_press_maybe(
+                    (!model_builder.name.trim().is_empty() && model_builder.base.is_some())
+                        .then_some(Message::CreateModel),
+                )
+                .into(),
+        };
+        column![
+            row![
+                text(tr!("create-model")).width(Length::Fill).size(24),
+                button(text(tr!("close")))
+                    .on_press(Message::CloseModelBuilder)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            row![
+                column![
+                    text(tr!("new-model-name")),
+                    text_input(&tr!("new-model-name-placeholder"), &model_builder.name)
+                        .on_input(Message::UpdateNewModelName)
+                        .on_submit(Message::CreateModel),
+                    text(tr!("new-model-base")),
+                    pick_list(
+                        base_models,
+                        model_builder.base.clone(),
+                        Message::SetNewModelBase
+                    ),
+                    text(tr!("system-prompt")),
+                    text_editor(&model_builder.system_prompt)
+                        .placeholder(tr!("new-model-system-prompt-placeholder"))
+                        .on_action(Message::EditNewModelSystemPrompt)
+                        .height(Length::Fixed(200.0)),
+                    Row::with_children(inputs).spacing(10),
+                    status,
+                ]
+                .spacing(10)
+                .width(Length::FillPortion(3)),
+                column![
+                    text(tr!("modelfile-preview")),
+                    scrollable(
+                        container(text(model_builder.modelfile()).font(iced::Font::MONOSPACE))
+                            .padding(10)
+                            .width(Length::Fill)
+                            .style(container::rounded_box)
+                    )
+                    .height(Length::Fill),
+                ]
+                .spacing(10)
+                .width(Length::FillPortion(2)),
+            ]
+            .spacing(20)
+            .height(Length::Fill),
+            self.view_toasts(),
+        ]
+        .spacing(10)
+        .padding(20)
+        .into()
+    }
+
+    fn view_embeddings_playground<'a>(
+        &'a self,
+        playground: &'a EmbeddingsPlayground,
+    ) -> Element<'a, Message> {
+        const CELL_WIDTH: f32 = 60.0;
+        let texts = column(playground.texts.iter().enumerate().map(|(index, content)| {
+            row![
+                text(format!("{}", index + 1)).width(Length::Fixed(20.0)),
+                text_input(&tr!("playground-text"), content)
+                    .on_input(move |content| Message::UpdatePlaygroundText(index, content)),
+                button(text(tr!("remove")))
+                    .on_press_maybe(
+                        (playground.texts.len() > 2)
+                            .then_some(Message::RemovePlaygroundText(index))
+                    )
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into()
+        }))
+        .spacing(10);
+        let can_compare = !playground.is_comparing
+            && playground
+                .texts
+                .iter()
+                .all(|content| !content.trim().is_empty());
+        // Each text against each of the others, shaded by how alike they are
+        let similarities = playground.similarities.as_ref().map(|similarities| {
+            let header = row![Space::with_width(Length::Fixed(20.0))]
+                .extend((1..=similarities.len()).map(|number| {
+                    text(number.to_string())
+                        .width(Length::Fixed(CELL_WIDTH))
+                        .align_x(Center)
+                        .into()
+                }))
+                .spacing(5);
+            column![header]
+                .extend(
+                    similarities
+                        .iter()
+                        .enumerate()
+                        .map(|(index, row_similarities)| {
+                            row![text(format!("{}", index + 1)).width(Length::Fixed(20.0))]
+                                .extend(row_similarities.iter().map(|&similarity| {
+                                    container(text(format!("{similarity:.2}")).size(14))
+                                        .width(Length::Fixed(CELL_WIDTH))
+                                        .align_x(Center)
+                                        .padding(5)
+                                        .style(move |theme: &Theme| container::Style {
+                                            background: Some(
+                                                Color {
+                                                    a: similarity.clamp(0.0, 1.0),
+                                                    ..theme.palette().primary
+                                                }
+                                                .into(),
+                                            ),
+                                            ..Default::default()
+                                        })
+                                        .into()
+                                }))
+                                .spacing(5)
+                                .align_y(Center)
+                                .into()
+                        }),
+                )
+                .spacing(5)
+        });
+        column![
+            row![
+                text(tr!("embeddings-playground"))
+                    .width(Length::Fill)
+                    .size(24),
+                button(text(tr!("close")))
+                    .on_press(Message::CloseEmbeddingsPlayground)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            text(tr!(
+                "playground-explanation",
+                model = self.settings.embedding_model.clone()
+            ))
+            .style(text::secondary),
+            scrollable(
+                column![
+                    texts,
+                    row![
+                        button(text(tr!("add-text")))
+                            .on_press(Message::AddPlaygroundText)
+                            .style(button::secondary),
+                        button(text(tr!("compare")))
+                            .on_press_maybe(can_compare.then_some(Message::ComparePlaygroundTexts)),
+                    ]
+                    .push_maybe(playground.is_comparing.then(Spinner::new))
+                    .spacing(10)
+                    .align_y(Center),
+                ]
+                .push_maybe(similarities)
+                .spacing(15)
+            )
+            .height(Length::Fill),
+        ]
+        .spacing(15)
+        .padding(20)
+        .into()
+    }
+
+    fn view_usage<'a>(&'a self, usage: &'a Usage) -> Element<'a, Message> {
+        const CHART_HEIGHT: f32 = 150.0;
+        let most_messages = usage
+            .messages_per_day
+            .iter()
+            .map(|(_date, count)| *count)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        let bars = Row::with_children(usage.messages_per_day.iter().map(|(date, count)| {
+            let height = CHART_HEIGHT * *count as f32 / most_messages as f32;
+            let bar = container(Space::new(Length::Fill, Length::Fixed(height))).style(
+                |theme: &Theme| container::Style {
+                    background: Some(theme.palette().primary.into()),
+                    ..Default::default()
+                },
+            );
+            Tooltip::new(
+                column![Space::with_height(Length::Fill), bar]
+                    .height(Length::Fixed(CHART_HEIGHT))
+                    .width(Length::Fill),
+                container(text(tr!("usage-day", date = date.clone(), count = *count)))
+                    .padding(5)
+                    .style(container::rounded_box),
+                iced::widget::tooltip::Position::Top,
+            )
+            .into()
+        }))
+        .spacing(2);
+        let (first_date, last_date) = match (
+            usage.messages_per_day.first(),
+            usage.messages_per_day.last(),
+        ) {
+            (Some((first_date, _)), Some((last_date, _))) => {
+                (first_date.as_str(), last_date.as_str())
+            }
+            _ => ("", ""),
+        };
+        let most_tokens = usage
+            .models
+            .iter()
+            .map(|model_usage| model_usage.generated_tokens)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        let most_responses = usage
+            .models
+            .first()
+            .map_or(1, |model_usage| model_usage.responses);
+        let models: Element<'a, Message> = if usage.models.is_empty() {
+            text(tr!("usage-no-responses")).into()
+        } else {
+            column![row![
+                text(tr!("benchmark-model"))
+                    .size(14)
+                    .width(Length::FillPortion(2)),
+                text(tr!("usage-responses"))
+                    .size(14)
+                    .width(Length::FillPortion(2)),
+                text(tr!("usage-generated-tokens"))
+                    .size(14)
+                    .width(Length::FillPortion(2)),
+                text(tr!("usage-average-latency"))
+                    .size(14)
+                    .width(Length::FillPortion(1)),
+            ]
+            .spacing(10)]
+            .extend(usage.models.iter().map(|model_usage| {
+                let bar = |value: f32, max: f32, label: String| {
+                    row![
+                        progress_bar(0.0..=max, value)
+                            .height(10)
+                            .width(Length::Fill),
+                        text(label).size(14).width(Length::Fixed(70.0)),
+                    ]
+                    .spacing(5)
+                    .align_y(Center)
+                    .width(Length::FillPortion(2))
+                };
+                row![
+                    text(&model_usage.model).width(Length::FillPortion(2)),
+                    bar(
+                        model_usage.responses as f32,
+                        most_responses as f32,
+                        model_usage.responses.to_string()
+                    ),
+                    bar(
+                        model_usage.generated_tokens as f32,
+                        most_tokens as f32,
+                        model_usage.generated_tokens.to_string()
+                    ),
+                    text(
+                        model_usage
+                            .average_latency()
+                            .map(|latency| format!("{:.1} s", latency.as_secs_f64()))
+                            .unwrap_or_else(|| "–".to_string())
+                    )
+                    .width(Length::FillPortion(1)),
+                ]
+                .spacing(10)
+                .align_y(Center)
+                .into()
+            }))
+            .spacing(10)
+            .into()
+        };
+        column![
+            row![
+                text(tr!("usage")).width(Length::Fill).size(24),
+                button(text(tr!("close")))
+                    .on_press(Message::CloseUsage)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            scrollable(
+                column![
+                    text(tr!("usage-messages-per-day", days = usage::DAYS)).size(18),
+                    container(bars).padding(10).style(container::rounded_box),
+                    row![
+                        text(first_date).size(12).width(Length::Fill),
+                        text(last_date).size(12),
+                    ],
+                    text(tr!("usage-models")).size(18),
+                    models,
+                ]
+                .spacing(10)
+            )
+            .height(Length::Fill),
+            self.view_toasts(),
+        ]
+        .spacing(10)
+        .padding(20)
+        .into()
+    }
+
+    fn view_batch<'a>(&'a self, batch_view: &'a BatchView) -> Element<'a, Message> {
+        let prompts: Element<'a, Message> = match &batch_view.loaded {
+            Some((file_name, prompts)) => row![
+                text(tr!(
+                    "batch-loaded",
+                    count = prompts.len(),
+                    file = file_name.as_str()
+                ))
+                .width(Length::Fill),
+                button(text(tr!("remove")))
+                    .on_press(Message::ClearBatchFile)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+            None => text_editor(&batch_view.prompts)
+                .placeholder(tr!("benchmark-prompts-placeholder"))
+                .on_action(Message::EditBatchPrompts)
+                .height(Length::Fixed(150.0))
+                .into(),
+        };
+        let has_prompts = match &batch_view.loaded {
+            Some((_file_name, prompts)) => !prompts.is_empty(),
+            None => !batch_view.prompts.text().trim().is_empty(),
+        };
+        let total = batch_view.results.len()
+            + batch_view.queue.len()
+            + usize::from(batch_view.running.is_some());
+        let status: Element<'a, Message> = if batch_view.running.is_some() {
+            column![
+                row![
+                    Spinner::new(),
+                    text(tr!(
+                        "batch-running",
+                        model = batch_view.model.as_str(),
+                        done = batch_view.results.len(),
+                        total = total
+                    ))
+                    .width(Length::Fill),
+                    button(text(tr!("stop")))
+                        .on_press_maybe(
+                            (!batch_view.queue.is_empty()).then_some(Message::StopBatch)
+                        )
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                progress_bar(0.0..=total as f32, batch_view.results.len() as f32).height(10),
+            ]
+            .spacing(5)
+            .into()
+        } else {
+            button(text(tr!(
+                "run-batch",
+                model = self
+                    .conversation
+                    .current_model
+                    .as_ref()
+                    .map(|model| model.name.as_str())
+                    .unwrap_or_default()
+            )))
+            .on_press_maybe(
+                (has_prompts && self.conversation.current_model.is_some())
+                    .then_some(Message::RunBatch),
+            )
+            .into()
+        };
+        let results = column(batch_view.results.iter().map(|result| {
+            container(
+                column![
+                    text(&result.prompt).size(14).style(text::secondary),
+                    match &result.error {
+                        Some(err) => text(err).style(text::danger),
+                        None => text(&result.output),
+                    },
+                    row![
+                        text(match result.tokens_per_second {
+                            Some(tokens_per_second) => format!(
+                                "{:.2} s · {tokens_per_second:.1} {}",
+                                result.total_time.as_secs_f64(),
+                                tr!("benchmark-tokens-per-second")
+                            ),
+                            None => format!("{:.2} s", result.total_time.as_secs_f64()),
+                        })
+                        .size(12)
+                        .width(Length::Fill),
+                        button(text(tr!("copy")).size(12))
+                            .on_press(Message::CopyChat(result.output.clone()))
+                            .style(button::secondary),
+                    ]
+                    .align_y(Center),
+                ]
+                .spacing(5),
+            )
+            .padding(10)
+            .width(Length::Fill)
+            .style(container::rounded_box)
+            .into()
+        }))
+        .spacing(10);
+        let has_results = !batch_view.results.is_empty();
+        column![
+            row![
+                text(tr!("batch")).width(Length::Fill).size(24),
+                button(text(tr!("export-csv")))
+                    .on_press_maybe(has_results.then_some(Message::ExportBatch(BatchExport::Csv))),
+                button(text(tr!("export-json")))
+                    .on_press_maybe(has_results.then_some(Message::ExportBatch(BatchExport::Json))),
+                button(text(tr!("close")))
+                    .on_press(Message::CloseBatch)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            row![
+                column![
+                    text(tr!("benchmark-prompts")),
+                    prompts,
+                    text(tr!("batch-file")),
+                    row![
+                        text_input(&tr!("batch-file-placeholder"), &batch_view.file)
+                            .on_input(Message::UpdateBatchFile)
+                            .on_submit(Message::LoadBatchFile),
+                        text_input(&tr!("batch-csv-column"), &batch_view.csv_column)
+                            .on_input(Message::UpdateBatchColumn)
+                            .width(Length::Fixed(100.0)),
+                        button(text(tr!("batch-load"))).on_press_maybe(
+                            (!batch_view.file.trim().is_empty()).then_some(Message::LoadBatchFile)
+                        ),
+                    ]
+                    .spacing(10),
+                    status,
+                ]
+                .spacing(10)
+                .width(Length::FillPortion(1)),
+                scrollable(results).width(Length::FillPortion(2)),
+            ]
+            .spacing(20)
+            .height(Length::Fill),
+            self.view_save_as(),
+            self.view_toasts(),
+        ]
+        .spacing(10)
+        .padding(20)
+        .into()
+    }
+
+    fn view_logs<'a>(&'a self, log: &'a str) -> Element<'a, Message> {
+        column![
+            row![
+                text(tr!("logs")).width(Length::Fill).size(24),
+                button(text(tr!("open-log-folder")))
+                    .on_press_maybe(logging::logs_dir().map(Message::OpenFile)),
+                button(text(tr!("copy"))).on_press(Message::CopyChat(log.to_string())),
+                button(text(tr!("close")))
+                    .on_press(Message::CloseLogs)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+            scrollable(text(log).font(iced::Font::MONOSPACE).size(14))
+                .anchor_bottom()
+                .height(Length::Fill)
+                .width(Length::Fill),
+        ]
+        .spacing(10)
+        .padding(20)
+        .into()
+    }
+
+    /// The system prompt editor, or a persona picker when starting a new conversation
+    fn view_system_prompt(&self) -> Element<'_, Message> {
+        let Some(editor) = self.system_prompt_editor.as_ref() else {
+            if !self.conversation.chats_list.is_empty() || self.personas.is_empty() {
+                return column![].into();
+            }
+            return row![
+                text(tr!("start-with-persona")),
+                pick_list(
+                    self.personas.as_slice(),
+                    None::<Persona>,
+                    Message::UsePersona
+                )
+                .placeholder(tr!("persona")),
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into();
+        };
+        let persona_name = editor.persona_name.trim();
+        let selected_persona = self
+            .personas
+            .iter()
+            .find(|persona| persona.name == persona_name);
+        container(
+            column![
+                row![
+                    text(tr!("system-prompt")).width(Length::Fill),
+                    pick_list(
+                        self.personas.as_slice(),
+                        selected_persona,
+                        Message::UsePersona
+                    )
+                    .placeholder(tr!("persona")),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                text_editor(&editor.system_prompt)
+                    .placeholder(tr!("system-prompt-placeholder"))
+                    .on_action(Message::SystemPromptAction)
+                    .height(Length::Fixed(150.0)),
+                row![
+                    text_input(&tr!("persona-name"), &editor.persona_name)
+                        .on_input(Message::UpdatePersonaName)
+                        .width(Length::Fixed(200.0)),
+                    button(text(tr!("save-persona")))
+                        .on_press_maybe((!persona_name.is_empty()).then_some(Message::SavePersona))
+                        .style(button::secondary),
+                    button(text(tr!("delete-persona")))
+                        .on_press_maybe(
+                            selected_persona
+                                .map(|persona| Message::DeletePersona(persona.name.clone()))
+                        )
+                        .style(button::secondary),
+                    Space::with_width(Length::Fill),
+                    button(text(tr!("apply"))).on_press(Message::ApplySystemPrompt),
+                    button(text(tr!("cancel")))
+                        .on_press(Message::CancelSystemPrompt)
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            ]
+            .spacing(10),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    /// Inputs for the open conversation's generation params, left empty to use the model's default
+    fn view_params_panel(&self) -> Element<'_, Message> {
+        let Some(params_panel) = self.params_panel.as_ref() else {
+            return column![].into();
+        };
+        let inputs = GenerationParam::ALL.into_iter().map(|param| {
+            let input = &params_panel.inputs[param as usize];
+            let is_valid = param.set(&mut GenerationParams::default(), input);
+            column![
+                text(param.label()).size(12),
+                text_input(&tr!("param-default"), input)
+                    .on_input(move |input| Message::UpdateGenerationParam(param, input))
+                    .style(move |theme: &Theme, status| {
+                        let mut style = text_input::default(theme, status);
+                        if !is_valid {
+                            style.border.color = theme.extended_palette().danger.base.color;
+                        }
+                        style
+                    }),
+            ]
+            .spacing(2)
+            .width(Length::Fill)
+            .into()
+        });
+        let language_choices: Vec<Choice> = std::iter::once(Choice {
+            value: None,
+            label: tr!("any-language"),
+        })
+        .chain(translation::LANGUAGES.iter().map(|language| Choice {
+            value: Some(language.name.to_string()),
+            label: language.native_name.to_string(),
+        }))
+        .collect();
+        let selected_language = language_choices
+            .iter()
+            .find(|choice| choice.value == self.conversation.response_language)
+            .cloned();
+        let response_language = column![
+            text(tr!("response-language")).size(12),
+            pick_list(language_choices, selected_language, |choice| {
+                Message::SetResponseLanguage(choice.value)
+            }),
+        ]
+        .spacing(2);
+        container(
+            Row::with_children(inputs)
+                .push(response_language)
+                .push(
+                    button(text(tr!("reset")))
+                        .on_press(Message::ResetGenerationParams)
+                        .style(button::secondary),
+                )
+                .spacing(10)
+                .align_y(iced::Bottom),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    /// The folder of documents the open conversation's prompts are answered from, and how much of
+    /// it has been indexed
+    fn view_knowledge_panel(&self) -> Element<'_, Message> {
+        let Some(path) = self.knowledge_panel.as_ref() else {
+            return column![].into();
+        };
+        let status = match self.conversation.knowledge_dir.as_ref() {
+            Some(dir) => match self.indexed_knowledge.get(dir) {
+                Some(chunk_count) => tr!("knowledge-indexed", count = *chunk_count),
+                None => tr!("knowledge-attached"),
+            },
+            None => tr!("knowledge-explanation"),
+        };
+        container(
+            column![
+                text(status),
+                row![
+                    text_input(&tr!("knowledge-folder"), path)
+                        .on_input(Message::UpdateKnowledgePath)
+                        .on_submit(Message::AttachKnowledge),
+                    button(text(tr!("attach"))).on_press_maybe(
+                        (!path.trim().is_empty()
+                            && self.conversation.knowledge_dir.as_ref()
+                                != Some(&PathBuf::from(path.trim())))
+                        .then_some(Message::AttachKnowledge)
+                    ),
+                    button(text(tr!("reindex")))
+                        .on_press_maybe(
+                            self.conversation
+                                .knowledge_dir
+                                .is_some()
+                                .then_some(Message::IndexKnowledge)
+                        )
+                        .style(button::secondary),
+                    button(text(tr!("detach")))
+                        .on_press_maybe(
+                            self.conversation
+                                .knowledge_dir
+                                .is_some()
+                                .then_some(Message::DetachKnowledge)
+                        )
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            ]
+            .spacing(10),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    /// The conversations most like the open one, to go to from it
+    fn view_related_panel(&self) -> Element<'_, Message> {
+        if !self.show_related {
+            return column![].into();
+        }
+        let related: Vec<(PathBuf, f32)> = match (
+            self.conversation_embeddings.as_ref(),
+            self.conversation.current_conversation.as_ref(),
+        ) {
+            (Some(embeddings), Some(conversation)) => embeddings
+                .related(conversation, usize::MAX)
+                .into_iter()
+                // Ones deleted since they were embedded are still in the embeddings
+                .filter(|(path, _similarity)| self.conversation_times.contains_key(path))
+                .take(related::RELATED_COUNT)
+                .collect(),
+            _ => vec![],
+        };
+        let related: Element<'_, Message> = if !related.is_empty() {
+            column(related.into_iter().map(|(path, similarity)| {
+                let title = path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                button(
+                    row![
+                        text(title).width(Length::Fill),
+                        text(tr!(
+                            "related-similarity",
+                            percent = (similarity * 100.0).round() as u32
+                        ))
+                        .size(12)
+                        .style(text::secondary),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                )
+                .on_press(Message::SetConversationFile(Some(path)))
+                .style(button::secondary)
+                .width(Length::Fill)
+                .into()
+            }))
+            .spacing(5)
+            .into()
+        } else if self.embedding_job.is_some() {
+            text(tr!("finding-related")).into()
+        } else if self.conversation_embeddings.is_none() {
+            button(text(tr!("find-related")))
+                .on_press(Message::EmbedConversations)
+                .into()
+        } else if self.conversation.current_conversation.is_none() {
+            text(tr!("related-unsaved")).into()
+        } else {
+            text(tr!("no-related")).into()
+        };
+        container(
+            column![
+                row![
+                    text(tr!("related-conversations")).width(Length::Fill),
+                    button(text(tr!("embeddings-playground")))
+                        .on_press(Message::ShowEmbeddingsPlayground)
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+                related,
+            ]
+            .spacing(10),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    /// How far along the model being pulled is
+    fn view_pull_progress(&self) -> Option<Column<'_, Message>> {
+        self.model_pull.as_ref().map(|model_pull| {
+            column![
+                text(tr!(
+                    "pulling-model",
+                    model = model_pull.model_name.clone(),
+                    status = model_pull.status.clone()
+                )),
+                progress_bar(0.0..=1.0, model_pull.progress.unwrap_or(0.0)).height(10),
+            ]
+            .spacing(5)
+        })
+    }
+
+    fn view_composer(&self) -> Element<'_, Message> {
+        let attachments = (!self.attachments.is_empty()).then(|| {
+            Row::with_children(self.attachments.iter().enumerate().map(|(index, image)| {
+                column![
+                    image_thumbnail(image, ATTACHMENT_THUMBNAIL_SIZE),
+                    button(text(tr!("remove")).size(12))
+                        .on_press(Message::RemoveAttachment(index))
+                        .style(button::secondary),
+                ]
+                .spacing(2)
+                .align_x(Center)
+                .into()
+            }))
+            .spacing(10)
+        });
+        let file_attachments = (!self.file_attachments.is_empty()).then(|| {
+            Row::with_children(
+                self.file_attachments
+                    .iter()
+                    .enumerate()
+                    .map(|(index, file)| {
+                        container(
+                            row![
+                                text(if file.is_truncated {
+                                    tr!("file-chip-truncated", name = file.name.as_str())
+                                } else {
+                                    file.name.clone()
+                                })
+                                .size(14),
+                                button(text("×").size(14))
+                                    .on_press(Message::RemoveFileAttachment(index))
+                                    .style(button::text),
+                            ]
+                            .spacing(5)
+                            .align_y(Center),
+                        )
+                        .padding([2, 10])
+                        .style(container::rounded_box)
+                        .into()
+                    }),
+            )
+            .spacing(10)
+            .wrap()
+        });
+        let attach_path = self.attach_path.as_ref().map(|path| {
+            row![
+                text_input(&tr!("image-path"), path)
+                    .on_input(Message::UpdateAttachPath)
+                    .on_submit(Message::ConfirmAttachImage),
+                button(text(tr!("attach"))).on_press(Message::ConfirmAttachImage),
+                button(text(tr!("cancel")))
+                    .on_press(Message::CancelAttachImage)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center)
+        });
+        let schema_input = self.conversation.structured_output.as_ref().map(|schema| {
+            column![row![
+                text(tr!("json-mode")),
+                text_input(&tr!("json-schema-placeholder"), schema)
+                    .on_input(Message::UpdateOutputSchema)
+                    .font(iced::Font::MONOSPACE),
+            ]
+            .spacing(10)
+            .align_y(Center)]
+            .push_maybe(self.output_format().err().map(|err| {
+                text(tr!("invalid-schema", error = err))
+                    .size(12)
+                    .style(text::danger)
+            }))
+            .spacing(5)
+        });
+        // The arrow keys go through the sent prompts from the ends of the prompt, like in a shell
+        let (cursor_line, _cursor_column) = self.prompt.cursor_position();
+        let can_go_back = cursor_line == 0;
+        let can_go_forward =
+            self.prompt_history.is_browsing() && cursor_line + 1 >= self.prompt.line_count();
+        let composer = row![text_editor(&self.prompt)
+            .placeholder(tr!("prompt-placeholder"))
+            .on_action(Message::EditPrompt)
+            .key_binding(move |key_press| {
+                composer_key_binding(key_press, can_go_back, can_go_forward)
+            })
+            .height(if self.prompt.line_count() > COMPOSER_MAX_LINES {
+                Length::Fixed(COMPOSER_MAX_HEIGHT)
+            } else {
+                Length::Shrink
+            })]
+        .spacing(5)
+        .align_y(iced::Bottom);
+        #[cfg(target_os = "linux")]
+        let composer = composer.push(Tooltip::new(
+            button(text(tr!("paste-selection"))).on_press(Message::PastePrimarySelection),
+            text(tr!("paste-selection-tooltip")),
+            iced::widget::tooltip::Position::Top,
+        ));
+        let composer = composer
+            .push(Tooltip::new(
+                button(text(tr!("attach-image"))).on_press(Message::ShowAttachImage),
+                text(tr!("attach-image-tooltip")),
+                iced::widget::tooltip::Position::Top,
+            ))
+            .push(button(text(tr!("paste-image"))).on_press(Message::PasteImage))
+            .push(Tooltip::new(
+                button(text(tr!("screenshot"))).on_press(Message::CaptureScreenshot),
+                text(tr!("screenshot-tooltip")),
+                iced::widget::tooltip::Position::Top,
+            ))
+            .push(self.view_dictation_button())
+            .push(
+                button(text(tr!("templates")))
+                    .on_press(Message::ToggleTemplates)
+                    .style(if self.template_panel.is_some() {
+                        button::primary
+                    } else {
+                        button::secondary
+                    }),
+            )
+            .push_maybe(
+                self.conversation
+                    .backend
+                    .supports_structured_output()
+                    .then(|| {
+                        Tooltip::new(
+                            button(text("JSON"))
+                                .on_press(Message::ToggleStructuredOutput)
+                                .style(if self.conversation.structured_output.is_some() {
+                                    button::primary
+                                } else {
+                                    button::secondary
+                                }),
+                            text(tr!("json-mode-tooltip")),
+                            iced::widget::tooltip::Position::Top,
+                        )
+                    }),
+            )
+            .push(Tooltip::new(
+                button(text(tr!("web-search")))
+                    .on_press_maybe(
+                        self.settings
+                            .web_search
+                            .is_set_up()
+                            .then_some(Message::ToggleWebSearch),
+                    )
+                    .style(if self.conversation.search_web {
+                        button::primary
+                    } else {
+                        button::secondary
+                    }),
+                text(if self.settings.web_search.is_set_up() {
+                    tr!("web-search-tooltip")
+                } else {
+                    tr!("web-search-tooltip-not-set-up")
+                }),
+                iced::widget::tooltip::Position::Top,
+            ))
+            .push_maybe(
+                self.conversation
+                    .is_searching_web
+                    .then(|| text(tr!("searching-web")).style(text::secondary)),
+            )
+            .push_maybe(
+                self.conversation
+                    .is_generating
+                    .then(|| button(text(tr!("stop"))).on_press(Message::StopGeneration)),
+            )
+            .push(if self.conversation.is_generating {
+                column![Spinner::new()].width(30.0)
+            } else {
+                column![].width(30.0)
+            });
+        column![]
+            .push_maybe(self.view_template_panel())
+            .push_maybe(self.view_context_meter())
+            .extend(self.view_queued_prompts())
+            .push_maybe(attachments)
+            .push_maybe(file_attachments)
+            .push_maybe(attach_path)
+            .push_maybe(schema_input)
+            // A conversation's model being pulled, as the model picker isn't showing
+            .push_maybe(self.view_pull_progress())
+            .push(composer)
+            .spacing(10)
+            .padding(10)
+            .into()
+    }
+
+    /// How full the model's context window is, with a button to summarise the earliest messages
+    /// once it's getting full
+    fn view_context_meter(&self) -> Option<Element<'_, Message>> {
+        if self.conversation.chats_list.is_empty() {
+            return None;
+        }
+        let conversation: Vec<&ChatMessage> = self
+            .conversation
+            .unloaded_chats
+            .iter()
+            .chain(
+                self.conversation
+                    .chats_list
+                    .iter()
+                    .map(|(chat_message, _markdown_items)| chat_message),
+            )
+            .collect();
+        let context_length = self.context_length();
+        let used =
+            context::estimate_sent(&conversation, self.conversation.context_summary.as_ref())
+                + context::estimate_tokens(&self.prompt_text());
+        let usage = used as f32 / context_length as f32;
+        let is_filling_up = usage > context::SUMMARY_THRESHOLD;
+        let summarize_button = (is_filling_up && !self.settings.summarize_context).then(|| {
+            button(text(tr!("summarize-context")).size(12))
+                .on_press_maybe(
+                    (!self.conversation.is_generating && !self.conversation.is_summarizing_context)
+                        .then_some(Message::SummarizeContext),
+                )
+                .style(button::secondary)
+        });
+        Some(
+            row![
+                Space::with_width(Length::Fill),
+                Tooltip::new(
+                    row![
+                        text(tr!("context-usage", used = used, length = context_length)).size(12),
+                        progress_bar(0.0..=1.0, usage.min(1.0))
+                            .width(Length::Fixed(120.0))
+                            .height(6)
+                            .style(if is_filling_up {
+                                progress_bar::danger
+                            } else {
+                                progress_bar::primary
+                            }),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                    text(tr!("context-usage-tooltip")),
+                    iced::widget::tooltip::Position::Top,
+                ),
+            ]
+            .push_maybe(summarize_button)
+            .push_maybe(
+                self.conversation
+                    .is_summarizing_context
+                    .then(|| text(tr!("summarizing")).size(12)),
+            )
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+        )
+    }
+
+    /// The saved templates to pick from, or a form for the variables of the one picked
+    fn view_template_panel(&self) -> Option<Element<'_, Message>> {
+        let template_panel = self.template_panel.as_ref()?;
+        let contents = match &template_panel.filling {
+            Some((template, values)) => column![text(&template.name).size(18)]
+                .extend(values.iter().enumerate().map(|(index, (variable, value))| {
+                    row![
+                        text(variable).width(Length::FillPortion(1)),
+                        text_input(variable, value)
+                            .on_input(move |value| Message::UpdateTemplateVariable(index, value))
+                            .on_submit(Message::InsertTemplate)
+                            .width(Length::FillPortion(3)),
+                    ]
+                    .spacing(10)
+                    .align_y(Center)
+                    .into()
+                }))
+                .push(
+                    row![
+                        button(text(tr!("insert-template"))).on_press(Message::InsertTemplate),
+                        button(text(tr!("cancel")))
+                            .on_press(Message::ToggleTemplates)
+                            .style(button::secondary),
+                    ]
+                    .spacing(10),
+                ),
+            None => {
+                let template_name = template_panel.template_name.trim();
+                let can_save = !template_name.is_empty() && !self.prompt_text().trim().is_empty();
+                column![if self.templates.is_empty() {
+                    Element::from(text(tr!("no-templates")))
+                } else {
+                    Row::with_children(self.templates.iter().map(|template| {
+                        row![
+                            Tooltip::new(
+                                button(text(&template.name))
+                                    .on_press(Message::UseTemplate(template.clone())),
+                                container(text(&template.prompt).size(12))
+                                    .max_width(500)
+                                    .padding(10)
+                                    .style(container::rounded_box),
+                                iced::widget::tooltip::Position::Top,
+                            ),
+                            button(text("×"))
+                                .on_press(Message::DeleteTemplate(template.name.clone()))
+                                .style(button::text),
+                        ]
+                        .align_y(Center)
+                        .into()
+                    }))
+                    .spacing(10)
+                    .wrap()
+                    .into()
+                }]
+                .push(
+                    row![
+                        text_input(&tr!("template-name"), &template_panel.template_name)
+                            .on_input(Message::UpdateTemplateName)
+                            .width(Length::Fixed(200.0)),
+                        Tooltip::new(
+                            button(text(tr!("save-template")))
+                                .on_press_maybe(can_save.then_some(Message::SaveTemplate))
+                                .style(button::secondary),
+                            text(tr!("save-template-tooltip")),
+                            iced::widget::tooltip::Position::Top,
+                        ),
+                    ]
+                    .spacing(10)
+                    .align_y(Center),
+                )
+            }
+        };
+        Some(
+            container(contents.spacing(10))
+                .padding(10)
+                .style(container::rounded_box)
+                .into(),
+        )
+    }
+
+    fn view_conflicts(&self) -> Element<'_, Message> {
+        let database_conflicts = self.database_conflicts.iter().map(|copy| {
+            let file = copy
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let folder = copy.parent().unwrap_or(copy).to_path_buf();
+            container(
+                row![
+                    text(tr!("database-conflict", file = file)).width(Length::Fill),
+                    button(text(tr!("open-folder")))
+                        .on_press(Message::OpenFile(folder))
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            )
+            .padding(10)
+            .style(container::rounded_box)
+            .into()
+        });
+        let conflicts = self.conflicts.iter().map(|conflict| {
+            let title = conflict
+                .original
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            container(
+                row![
+                    text(tr!("conflict", title = title)).width(Length::Fill),
+                    button(text(tr!("merge-both"))).on_press(Message::ResolveConflict(
+                        conflict.clone(),
+                        Resolution::Merge
+                    )),
+                    button(text(tr!("keep-original"))).on_press(Message::ResolveConflict(
+                        conflict.clone(),
+                        Resolution::KeepOriginal
+                    )),
+                    button(text(tr!("keep-copy"))).on_press(Message::ResolveConflict(
+                        conflict.clone(),
+                        Resolution::KeepCopy
+                    )),
+                    button(text(tr!("open-copy")))
+                        .on_press(Message::OpenFile(conflict.copy.clone()))
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            )
+            .padding(10)
+            .style(container::rounded_box)
+            .into()
+        });
+        column(database_conflicts.chain(conflicts))
+            .spacing(5)
+            .into()
+    }
+
+    fn view_save_as(&self) -> Element<'_, Message> {
+        let Some((_contents, path)) = self.save_as.as_ref().filter(|_| !self.is_save_dialog_open)
+        else {
+            return column![].into();
+        };
+        container(
+            row![
+                text_input(&tr!("save-path"), path)
+                    .on_input(Message::UpdateSavePath)
+                    .on_submit(Message::ConfirmSave),
+                button(text(tr!("save"))).on_press(Message::ConfirmSave),
+                button(text(tr!("cancel")))
+                    .on_press(Message::CancelSave)
+                    .style(button::secondary),
+            ]
+            .spacing(10)
+            .align_y(Center),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    /// Where to import another app's conversations from, and which apps' exports can be read
+    fn view_import_panel(&self) -> Element<'_, Message> {
+        let Some(path) = self.import_panel.as_ref() else {
+            return column![].into();
+        };
+        container(
+            column![
+                text(tr!("import-explanation")),
+                row![
+                    text_input(&tr!("import-path"), path)
+                        .on_input(Message::UpdateImportPath)
+                        .on_submit(Message::ImportConversations),
+                    button(text(tr!("import"))).on_press_maybe(
+                        (!path.trim().is_empty()).then_some(Message::ImportConversations)
+                    ),
+                    button(text(tr!("cancel")))
+                        .on_press(Message::ToggleImportPanel)
+                        .style(button::secondary),
+                ]
+                .spacing(10)
+                .align_y(Center),
+            ]
+            .spacing(10),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into()
+    }
+
+    fn view_toasts(&self) -> Element<'_, Message> {
+        column(self.toasts.iter().enumerate().map(|(index, toast)| {
+            container(
+                row![text(&toast.message).width(Length::Fill)]
+                    .extend(toast.actions.iter().map(|(label, message)| {
+                        button(text(label)).on_press(message.clone()).into()
+                    }))
+                    .push(
+                        button(text(tr!("dismiss")))
+                            .on_press(Message::DismissToast(index))
+                            .style(button::secondary),
+                    )
+                    .spacing(10)
+                    .align_y(Center),
+            )
+            .padding(10)
+            .style(container::rounded_box)
+            .into()
+        }))
+        .spacing(5)
+        .into()
+    }
+
+    fn view_chat_list(&self) -> Element<'_, Message> {
+        let (visible_start, visible_end) = self.visible_chat_range();
+        let response_stats = self.response_stats_with_totals();
+        let height_above: f32 = self.conversation.chats_list[..visible_start]
+            .iter()
+            .map(|(chat_message, _markdown_items)| estimated_chat_height(chat_message))
+            .sum();
+        let height_below: f32 = self.conversation.chats_list[visible_end..]
+            .iter()
+            .map(|(chat_message, _markdown_items)| estimated_chat_height(chat_message))
+            .sum();
+        let load_earlier_button: Element<Message> = if self.conversation.unloaded_chats.is_empty() {
+            column![].into()
+        } else {
+            button(
+                text(tr!(
+                    "load-earlier-messages",
+                    count = self.conversation.unloaded_chats.len()
+                ))
+                .width(Length::Fill)
+                .align_x(Center),
+            )
+            .on_press(Message::LoadEarlierMessages)
+            .style(button::secondary)
+            .width(Length::Fill)
+            .into()
+        };
+        let chats = scrollable(
+            column![
+                load_earlier_button,
+                Space::with_height(Length::Fixed(height_above))
+            ]
+            .extend(
+                self.conversation.chats_list[visible_start..visible_end]
+                    .iter()
+                    .zip(&response_stats[visible_start..visible_end])
+                    .enumerate()
+                    .map(|(index, ((chat_message, markdown_items), stats))| {
+                        self.view_chat_message(
+                            visible_start + index,
+                            chat_message,
+                            markdown_items,
+                            *stats,
+                        )
+                    }),
+            )
+            .push(Space::with_height(Length::Fixed(height_below))),
+        )
+        .id(chat_scrollable_id())
+        .on_scroll(Message::ChatScrolled)
+        .height(Length::Fill);
+        if self.is_chat_at_bottom || self.conversation.chats_list.is_empty() {
+            return chats.into();
+        }
+        let jump_button = container(
+            button(text(tr!("jump-to-latest")).size(14))
+                .on_press(Message::JumpToLatest)
+                .style(button::secondary),
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .align_x(iced::alignment::Horizontal::Right)
+        .align_y(iced::alignment::Vertical::Bottom);
+        stack![chats, jump_button].into()
+    }
+
+    /// Arrows to flip between the versions of the conversation from a loaded message on, if it has
+    /// more than one
+    fn view_version_picker(&self, index: usize) -> Option<Element<'_, Message>> {
+        let branches = self
+            .conversation
+            .branches
+            .get(&(self.conversation.unloaded_chats.len() + index))?;
+        let switch = |version: usize| {
+            (!self.conversation.is_generating && version < branches.count())
+                .then_some(Message::SwitchVersion(index, version))
+        };
+        Some(
+            row![
+                button(text("‹").size(14))
+                    .on_press_maybe(branches.position.checked_sub(1).and_then(switch))
+                    .style(button::secondary),
+                text(tr!(
+                    "version-count",
+                    position = branches.position + 1,
+                    count = branches.count()
+                ))
+                .size(14),
+                button(text("›").size(14))
+                    .on_press_maybe(switch(branches.position + 1))
+                    .style(button::secondary),
+            ]
+            .spacing(5)
+            .align_y(Center)
+            .into(),
+        )
+    }
+
+    /// Returns the range of messages overlapping the scroll viewport, padded by a buffer either side
+    fn visible_chat_range(&self) -> (usize, usize) {
+        let Some((offset, viewport_height)) = self.chat_viewport else {
+            return (0, self.conversation.chats_list.len());
+        };
+        let mut visible_start = self.conversation.chats_list.len();
+        let mut visible_end = self.conversation.chats_list.len();
+        let mut height_so_far = 0.0;
+        for (index, (chat_message, _markdown_items)) in
+            self.conversation.chats_list.iter().enumerate()
+        {
+            let chat_height = estimated_chat_height(chat_message);
+            if visible_start == self.conversation.chats_list.len()
+                && height_so_far + chat_height >= offset
+            {
+                visible_start = index;
+            }
+            if height_so_far > offset + viewport_height {
+                visible_end = index;
+                break;
+            }
+            height_so_far += chat_height;
+        }
+        (
+            visible_start.saturating_sub(VIRTUALIZATION_BUFFER),
+            (visible_end + VIRTUALIZATION_BUFFER).min(self.conversation.chats_list.len()),
+        )
+    }
+
+    fn view_chat_message<'a>(
+        &'a self,
+        index: usize,
+        chat_message: &'a ChatMessage,
+        markdown_items: &'a Option<Arc<ParsedMarkdown>>,
+        stats: Option<(ResponseStats, u32)>,
+    ) -> Element<'a, Message> {
+        if let Some((_, content)) = self
+            .chat_editor
+            .as_ref()
+            .filter(|(editing_index, _)| *editing_index == index)
+        {
+            return column![
+                text_editor(content).on_action(Message::ChatEditorAction),
+                row![
+                    button(text(tr!("send-edited-message"))).on_press_maybe(
+                        (!self.conversation.is_generating).then_some(Message::ResendEditedChat)
                     ),
+                    button(text(tr!("cancel")))
+                        .on_press(Message::CancelChatEdit)
+                        .style(button::secondary),
+                ]
+                .spacing(10),
+            ]
+            .spacing(10)
+            .padding(20)
+            .into();
+        }
+        let is_last_response = chat_message.role == MessageRole::Assistant
+            && index + 1 == self.conversation.chats_list.len();
+        // What's kept about the message is by its index in the whole conversation
+        let message_index = self.conversation.unloaded_chats.len() + index;
+        let (reasoning, answer) = reasoning::split(&chat_message.content);
+        // Responses asked for as JSON are shown as a tree once they've arrived
+        let structured = self
+            .conversation
+            .formats
+            .get(&message_index)
+            .filter(|_| !(is_last_response && self.conversation.is_generating))
+            .map(|format| (format, structured::parse_reply(answer)));
+        let message_action = |label: String, message: Message| {
+            button(text(label).size(14))
+                .on_press_maybe((!self.conversation.is_generating).then_some(message))
+                .style(button::secondary)
+        };
+        let message_actions = row![]
+            .push_maybe(
+                (chat_message.role == MessageRole::User)
+                    .then(|| message_action(tr!("edit-message"), Message::EditChat(index))),
+            )
+            .push_maybe(
+                is_last_response
+                    .then(|| message_action(tr!("regenerate"), Message::RetryGeneration)),
+            )
+            .push_maybe((chat_message.role == MessageRole::Assistant).then(|| {
+                let is_speaking = self.speaking.as_ref().is_some_and(|(speaking, _handle)| {
+                    *speaking == content_hash(&chat_message.content)
+                });
+                if is_speaking {
+                    button(text(tr!("stop-reading")).size(14))
+                        .on_press(Message::StopSpeaking)
+                        .style(button::secondary)
+                } else {
+                    // The response still streaming in can be read once it's finished
+                    button(text(tr!("read-aloud")).size(14))
+                        .on_press_maybe(
+                            (!(is_last_response && self.conversation.is_generating))
+                                .then(|| Message::ReadAloud(chat_message.content.clone())),
+                        )
+                        .style(button::secondary)
+                }
+            }))
+            .push_maybe(
+                (chat_message.role == MessageRole::Assistant
+                    && !(is_last_response && self.conversation.is_generating)
+                    && self.conversation.current_model.is_some())
+                .then(|| {
+                    let content = chat_message.content.clone();
+                    pick_list(translation::LANGUAGES, None::<Language>, move |language| {
+                        Message::TranslateResponse(content.clone(), language)
+                    })
+                    .placeholder(tr!("translate"))
+                    .text_size(14)
+                }),
+            )
+            .push(message_action(
+                tr!("delete-message"),
+                Message::DeleteChat(index),
+            ))
+            .push_maybe(self.view_version_picker(index))
+            .push_maybe(stats.map(|(stats, total_tokens)| {
+                let mut stats_line = vec![tr!("stats-tokens", count = stats.generated_tokens)];
+                if let Some(tokens_per_second) = stats.tokens_per_second() {
+                    stats_line.push(tr!(
+                        "stats-speed",
+                        speed = format!("{tokens_per_second:.1}")
+                    ));
+                }
+                stats_line.push(format!("{:.1} s", stats.total_time().as_secs_f64()));
+                stats_line.push(tr!("stats-conversation-total", count = total_tokens));
+                text(stats_line.join(" · "))
+                    .size(14)
+                    .width(Length::Fill)
+                    .align_x(iced::alignment::Horizontal::Right)
+            }))
+            .spacing(10)
+            .align_y(Center);
+        column![
+            {
+                let chat_message_title_row = Row::new().spacing(10);
+                let role_label = text(role_name(&chat_message.role)).size(20);
+                let title_text: Element<Message> =
+                    match self.conversation.sent_times.get(&message_index) {
+                        Some(sent_at) => column![
+                            role_label,
+                            Tooltip::new(
+                                text(i18n::time_ago(*sent_at))
+                                    .size(12)
+                                    .style(text::secondary),
+                                text(time::date_time(*sent_at)),
+                                iced::widget::tooltip::Position::Bottom,
+                            ),
+                        ]
+                        .align_x(if chat_message.role == MessageRole::User {
+                            iced::alignment::Horizontal::Left
+                        } else {
+                            iced::alignment::Horizontal::Right
+                        })
+                        .into(),
+                        None => role_label.into(),
+                    };
+                let spacer = Space::with_width(Length::Fill);
+                let copied = if self.settings.include_reasoning {
+                    chat_message.content.as_str()
+                } else {
+                    answer
+                };
+                let copy_button: Element<Message> = row![
                     Tooltip::new(
-                        button(Svg::new(Handle::from_memory(include_bytes!(
-                            "../icons/add.svg"
-                        ))))
-                        .height(Length::Fill)
-                        .on_press(Message::NewChatButtonPressed)
+                        button(
+                            Svg::new(Handle::from_memory(include_bytes!("../icons/copy.svg")))
+                                .height(Length::Fixed(20.0)),
+                        )
+                        .on_press(Message::CopyChat(copied.to_string()))
                         .width(Length::Fixed(50.0)),
-                        "New Chat",
-                        iced::widget::tooltip::Position::Bottom
+                        text(tr!("copy")),
+                        iced::widget::tooltip::Position::Bottom,
                     ),
-                    button(text("Select Model").width(Length::Fill).align_x(Center))
-                        .on_press(Message::SetModel(None))
-                        .height(Length::Fill)
-                        .width(Length::Fixed(170.0)),
-                    text(
-                        self.current_model
-                            .clone()
-                            .map(|model| model.name)
-                            .unwrap_or_default()
+                    pick_list(
+                        CopyFormat::ALL.map(CopyFormatChoice),
+                        None::<CopyFormatChoice>,
+                        move |choice| Message::CopyChatAs(copied.to_string(), choice.0),
                     )
-                    .width(Length::Fill)
-                    .align_x(Center)
-                    .size(24),
+                    .placeholder(tr!("copy-as"))
+                    .text_size(14),
                 ]
-                .height(Length::Fixed(30.0)),
-                row![
-                    if self.show_sidebar {
-                        container(column![
-                            text("Conversations")
-                                .width(Length::Fill)
-                                .align_x(Center)
-                                .size(24),
-                            scrollable(
-                                column(self.conversations_list.iter().map(|conversation_path| {
-                                    button(
-                                        text(
-                                            conversation_path
-                                                .file_stem()
-                                                .unwrap_or_default()
-                                                .to_str()
-                                                .unwrap_or_default(),
-                                        )
-                                        .width(Length::Fill)
-                                        .align_x(Center),
-                                    )
-                                    .width(Length::Fill)
-                                    .on_press(Message::SetConversationFile(Some(
-                                        conversation_path.clone(),
-                                    )))
-                                    .into()
-                                }))
-                                .spacing(5)
+                .spacing(5)
+                .align_y(Center)
+                .into();
+                if let MessageRole::User = chat_message.role {
+                    chat_message_title_row
+                        .push(title_text)
+                        .push(copy_button)
+                        .push(spacer)
+                } else {
+                    chat_message_title_row
+                        .push(spacer)
+                        .push(copy_button)
+                        .push(title_text)
+                }
+            },
+            column![]
+                .push_maybe(reasoning.map(|reasoning| {
+                    self.view_reasoning(
+                        index,
+                        reasoning,
+                        is_last_response && self.conversation.is_generating && answer.is_empty(),
+                    )
+                }))
+                .push_maybe(self.view_tool_calls(chat_message, message_index, is_last_response))
+                .push_maybe(structured.as_ref().and_then(|(format, reply)| {
+                    view_structured_problems(format, reply.as_ref())
+                }))
+                .push(match (&structured, markdown_items) {
+                    (Some((_format, Some(reply))), _) => view_json(
+                        reply,
+                        None,
+                        "$".to_string(),
+                        content_hash(&chat_message.content),
+                        &self.conversation.collapsed_json,
+                    ),
+                    (_, Some(markdown_items)) => self.view_markdown(markdown_items),
+                    (_, None) => text(answer).into(),
+                })
+                .spacing(5),
+        ]
+        .push_maybe(chat_message.images.as_ref().map(|images| {
+            Row::with_children(
+                images
+                    .iter()
+                    .map(|image| image_thumbnail(image, CHAT_THUMBNAIL_SIZE)),
+            )
+            .spacing(10)
+        }))
+        .push_maybe(self.view_translation(chat_message))
+        .push_maybe(self.view_citations(chat_message, message_index))
+        .push_maybe(self.view_web_sources(chat_message, message_index))
+        .push(message_actions)
+        .spacing(5)
+        .padding(20)
+        .into()
+    }
+
+    /// The reasoning written before a response, folded away to its heading until it's clicked and
+    /// set apart from the answer in a box of smaller, fainter italic text
+    fn view_reasoning<'a>(
+        &self,
+        index: usize,
+        reasoning: &'a str,
+        is_being_written: bool,
+    ) -> Element<'a, Message> {
+        let is_expanded = self.conversation.expanded_reasoning.contains(&index);
+        let heading = if is_being_written {
+            tr!("reasoning-in-progress")
+        } else {
+            tr!("reasoning")
+        };
+        let heading = button(
+            text(format!("{} {heading}", if is_expanded { "▾" } else { "▸" }))
+                .size(14)
+                .style(text::secondary),
+        )
+        .on_press(Message::ToggleReasoning(index))
+        .style(button::text)
+        .padding(0);
+        if !is_expanded || reasoning.is_empty() {
+            return heading.into();
+        }
+        column![
+            heading,
+            container(
+                text(reasoning)
+                    .size(14)
+                    .style(text::secondary)
+                    .font(iced::Font {
+                        style: iced::font::Style::Italic,
+                        ..iced::Font::DEFAULT
+                    })
+            )
+            .style(container::rounded_box)
+            .padding(10)
+            .width(Length::Fill),
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    /// A displayed equation, as an image once it's been rendered and as text until then
+    fn view_equation<'a>(&self, tex: &str) -> Element<'a, Message> {
+        let equation: Element<Message> = match self.math_images.get(&content_hash(tex)) {
+            Some(Some(image)) => Svg::new(image.clone())
+                .width(Length::Shrink)
+                .style(|theme: &Theme, _status| iced::widget::svg::Style {
+                    color: Some(theme.palette().text),
+                })
+                .into(),
+            _ => text(math::to_unicode(tex)).size(18).into(),
+        };
+        container(equation).center_x(Length::Fill).padding(5).into()
+    }
+
+    /// The tools the model called while writing a response, with a bubble for each call and one for
+    /// its result, and while it's being written the call waiting for the user to confirm it
+    fn view_tool_calls<'a>(
+        &'a self,
+        chat_message: &ChatMessage,
+        message_index: usize,
+        is_last_response: bool,
+    ) -> Option<Element<'a, Message>> {
+        if chat_message.role != MessageRole::Assistant {
+            return None;
+        }
+        let is_being_written = is_last_response && self.conversation.is_generating;
+        let tool_exchanges = if is_being_written {
+            &self.conversation.pending_tool_exchanges
+        } else {
+            self.conversation.tool_calls.get(&message_index)?
+        };
+        let waiting_call = self
+            .conversation
+            .queued_tool_calls
+            .first()
+            .filter(|_call| is_being_written);
+        if tool_exchanges.is_empty() && waiting_call.is_none() {
+            return None;
+        }
+        let bubble = |label: String, content: String| {
+            container(
+                column![
+                    text(label).size(12).style(text::secondary),
+                    container(scrollable(
+                        text(content).font(iced::Font::MONOSPACE).size(14)
+                    ))
+                    .max_height(200),
+                ]
+                .spacing(5),
+            )
+            .padding(10)
+            .width(Length::Fill)
+            .style(container::rounded_box)
+        };
+        let call_bubble =
+            |call: &ToolCall| bubble(tr!("tool-call", tool = call.name.clone()), call.input());
+        let mut bubbles = Column::new().spacing(5);
+        for tool_exchange in tool_exchanges {
+            bubbles = bubbles
+                .push(call_bubble(&tool_exchange.call))
+                .push(bubble(tr!("tool-result"), tool_exchange.result.clone()));
+        }
+        if let Some(call) = waiting_call {
+            bubbles = bubbles.push(
+                column![
+                    bubble(tr!("tool-confirm", tool = call.name.clone()), call.input()),
+                    row![
+                        button(text(tr!("run-tool")).size(14))
+                            .on_press(Message::ConfirmToolCall)
+                            .style(button::danger),
+                        button(text(tr!("decline-tool")).size(14))
+                            .on_press(Message::DeclineToolCall)
+                            .style(button::secondary),
+                    ]
+                    .spacing(10),
+                ]
+                .spacing(5),
+            );
+        }
+        Some(bubbles.into())
+    }
+
+    /// The files a response was given passages from, each opening its file, with the passages
+    /// shown on hover
+    fn view_citations(
+        &self,
+        chat_message: &ChatMessage,
+        message_index: usize,
+    ) -> Option<Element<'_, Message>> {
+        if chat_message.role != MessageRole::Assistant || self.conversation.citations.is_empty() {
+            return None;
+        }
+        let citations = self.conversation.citations.get(&message_index)?;
+        let mut sources: Vec<(&PathBuf, Vec<&str>)> = vec![];
+        for citation in citations {
+            match sources
+                .iter_mut()
+                .find(|(path, _excerpts)| **path == citation.path)
+            {
+                Some((_path, excerpts)) => excerpts.push(&citation.excerpt),
+                None => sources.push((&citation.path, vec![&citation.excerpt])),
+            }
+        }
+        let label: Element<Message> = text(tr!("sources")).size(14).into();
+        Some(
+            Row::with_children(std::iter::once(label).chain(sources.into_iter().map(
+                |(path, excerpts)| {
+                    Tooltip::new(
+                        button(
+                            text(
+                                path.file_name()
+                                    .unwrap_or_default()
+                                    .to_string_lossy()
+                                    .into_owned(),
                             )
-                        ])
-                        .style(container::bordered_box)
-                        .height(Length::Fill)
-                        .width(Length::FillPortion(1))
-                    } else {
-                        container(column![])
-                    },
-                    column![
-                        scrollable(column(self.chats_list.iter().map(
-                            |(chat_message, markdown_items)| {
+                            .size(14),
+                        )
+                        .on_press(Message::OpenFile(path.clone()))
+                        .style(button::secondary),
+                        container(text(excerpts.join("\n\n…\n\n")).size(12))
+                            .max_width(500)
+                            .padding(10)
+                            .style(container::rounded_box),
+                        iced::widget::tooltip::Position::Top,
+                    )
+                    .into()
+                },
+            )))
+            .spacing(5)
+            .align_y(Center)
+            .wrap()
+            .into(),
+        )
+    }
+
+    /// A response's translation, in a box under it with a spinner until it's in
+    fn view_translation(&self, chat_message: &ChatMessage) -> Option<Element<'_, Message>> {
+        let hash = content_hash(&chat_message.content);
+        let translation = self.conversation.translations.get(&hash)?;
+        let language = translation.language.as_str();
+        let body: Element<Message> = match &translation.text {
+            Some((_translated, markdown_items)) => self.view_markdown(markdown_items),
+            None => row![
+                Spinner::new(),
+                text(tr!("translating", language = language))
+            ]
+            .spacing(10)
+            .align_y(Center)
+            .into(),
+        };
+        let header = row![text(tr!("translation", language = language))
+            .size(14)
+            .style(text::secondary)
+            .width(Length::Fill)]
+        .push_maybe(
+            translation
+                .text
+                .as_ref()
+                .map(|(translated, _markdown_items)| {
+                    button(text(tr!("copy")).size(12))
+                        .on_press(Message::CopyChat(translated.clone()))
+                        .style(button::secondary)
+                }),
+        )
+        .push(
+            button(text(tr!("close")).size(12))
+                .on_press(Message::CloseTranslation(hash))
+                .style(button::secondary),
+        )
+        .spacing(10)
+        .align_y(Center);
+        Some(
+            container(column![header, body].spacing(10))
+                .padding(10)
+                .width(Length::Fill)
+                .style(container::rounded_box)
+                .into(),
+        )
+    }
+
+    /// The pages a response was given from the web, numbered as the model was told to cite them,
+    /// each opening its page, with its title and address shown on hover
+    fn view_web_sources(
+        &self,
+        chat_message: &ChatMessage,
+        message_index: usize,
+    ) -> Option<Element<'_, Message>> {
+        if chat_message.role != MessageRole::Assistant || self.conversation.web_sources.is_empty() {
+            return None;
+        }
+        let web_sources = self.conversation.web_sources.get(&message_index)?;
+        let label: Element<Message> = text(tr!("web-sources")).size(14).into();
+        Some(
+            Row::with_children(
+                std::iter::once(label).chain(web_sources.iter().enumerate().map(
+                    |(index, web_source)| {
+                        let host = markdown::Url::parse(&web_source.url)
+                            .ok()
+                            .and_then(|url| url.host_str().map(str::to_string))
+                            .unwrap_or_else(|| web_source.url.clone());
+                        Tooltip::new(
+                            button(text(format!("[{}] {host}", index + 1)).size(14))
+                                .on_press(Message::OpenLink(web_source.url.clone()))
+                                .style(button::secondary),
+                            container(
                                 column![
-                                    {
-                                        let chat_message_title_row = Row::new().spacing(10);
-                                        let title_text: Element<Message> =
-                                            text(match chat_message.role {
-                                                MessageRole::User => "User",
-                                                MessageRole::Assistant => "Assistant",
-                                                MessageRole::System => "System",
-                                            })
-                                            .size(20)
-                                            .into();
-                                        let spacer = Space::with_width(Length::Fill);
-                                        let copy_button: Element<Message> = Tooltip::new(
-                                            button(
-                                                Svg::new(Handle::from_memory(include_bytes!(
-                                                    "../icons/copy.svg"
-                                                )))
-                                                .height(Length::Fixed(20.0)),
-                                            )
-                                            .on_press(Message::CopyChat(
-                                                chat_message.content.clone(),
-                                            ))
-                                            .width(Length::Fixed(50.0)),
-                                            "Copy",
-                                            iced::widget::tooltip::Position::Bottom,
-                                        )
-                                        .into();
-                                        if let MessageRole::User = chat_message.role {
-                                            chat_message_title_row
-                                                .push(title_text)
-                                                .push(copy_button)
-                                                .push(spacer)
-                                        } else {
-                                            chat_message_title_row
-                                                .push(spacer)
-                                                .push(copy_button)
-                                                .push(title_text)
-                                        }
-                                    },
-                                    markdown::view(
-                                        markdown_items,
-                                        markdown::Settings::default(),
-                                        markdown::Style::from_palette(
-                                            Theme::TokyoNightStorm.palette()
-                                        ),
-                                    )
-                                    .map(Message::LinkClicked),
+                                    text(&web_source.title).size(14),
+                                    text(&web_source.url).size(12).style(text::secondary),
                                 ]
-                                .padding(20)
-                                .into()
-                            }
-                        )))
-                        .height(Length::Fill),
-                        row![
-                            text_input("Enter your chat", &self.prompt)
-                                .on_input(Message::UpdatePrompt)
-                                .on_submit(Message::SubmitPrompt),
-                            if self.is_generating {
-                                column![Spinner::new()].width(30.0)
-                            } else {
-                                column![].width(30.0)
-                            }
-                        ]
-                        .padding(10)
+                                .spacing(5),
+                            )
+                            .max_width(500)
+                            .padding(10)
+                            .style(container::rounded_box),
+                            iced::widget::tooltip::Position::Top,
+                        )
+                        .into()
+                    },
+                )),
+            )
+            .spacing(5)
+            .align_y(Center)
+            .wrap()
+            .into(),
+        )
+    }
+
+    /// Renders a message's markdown, with each code block's language and buttons to copy, save or
+    /// open it above the block
+    fn view_markdown<'a>(&self, parsed: &'a ParsedMarkdown) -> Element<'a, Message> {
+        match parsed.parts.as_slice() {
+            [part] => self.view_markdown_part(part),
+            parts => column(parts.iter().map(|part| self.view_markdown_part(part)))
+                .spacing(10)
+                .into(),
+        }
+    }
+
+    fn view_markdown_part<'a>(&self, part: &'a ParsedPart) -> Element<'a, Message> {
+        let style = markdown::Style::from_palette(self.theme().palette());
+        let view_items = |items: &'a [markdown::Item]| {
+            markdown::view(items, markdown::Settings::default(), style).map(Message::LinkClicked)
+        };
+        let markdown_items = part.items.as_slice();
+        if part.code_blocks.is_empty() {
+            return view_items(markdown_items);
+        }
+        let mut code_blocks = part.code_blocks.iter().cloned();
+        let mut sections = column![].spacing(10);
+        let mut section_start = 0;
+        for (index, item) in markdown_items.iter().enumerate() {
+            let markdown::Item::CodeBlock(_) = item else {
+                continue;
+            };
+            let Some(code_block) = code_blocks.next() else {
+                break;
+            };
+            if section_start < index {
+                sections = sections.push(view_items(&markdown_items[section_start..index]));
+            }
+            section_start = index + 1;
+            if code_block.language_name() == math::MATH_LANGUAGE {
+                sections = sections.push(self.view_equation(code_block.code.trim_end()));
+                continue;
+            }
+            sections = sections
+                .push(
+                    row![
+                        text(code_block.language_name().to_string())
+                            .font(iced::Font::MONOSPACE)
+                            .size(14)
+                            .width(Length::Fill),
+                        button(text(tr!("copy")).size(14))
+                            .on_press(Message::CopyChat(code_block.code.clone()))
+                            .style(button::secondary),
+                        button(text(tr!("save-code-block")).size(14))
+                            .on_press(Message::SaveCodeBlock(code_block.clone()))
+                            .style(button::secondary),
+                        button(text(tr!("open-in-editor")).size(14))
+                            .on_press(Message::OpenCodeBlock(code_block))
+                            .style(button::secondary),
                     ]
-                    .width(Length::FillPortion(2))
-                    .padding(10)
-                ]
+                    .spacing(10)
+                    .align_y(Center),
+                )
+                .push(view_items(&markdown_items[index..=index]));
+        }
+        sections
+            .push(view_items(&markdown_items[section_start..]))
+            .into()
+    }
+
+    /// Zooms the interface, saving it in the settings
+    fn zoom(&mut self, zoom: f32) -> Task<Message> {
+        if zoom == self.settings.zoom {
+            return Task::none();
+        }
+        self.update(Message::SaveSettings(Settings {
+            zoom,
+            ..self.settings.clone()
+        }))
+    }
+
+    /// How much the interface is zoomed, kept in range in case the settings file was edited by hand
+    fn scale_factor(&self) -> f64 {
+        f64::from(
+            self.settings
+                .zoom
+                .clamp(settings::MIN_ZOOM, settings::MAX_ZOOM),
+        )
+    }
+
+    fn theme(&self) -> Theme {
+        match self.settings.theme.as_str() {
+            settings::SYSTEM_THEME if self.is_system_dark => Theme::Dark,
+            settings::SYSTEM_THEME => Theme::Light,
+            settings::CUSTOM_THEME => custom_theme(&self.settings.custom_palette),
+            name => Theme::ALL
+                .iter()
+                .find(|theme| theme.to_string() == name)
+                .cloned()
+                .unwrap_or(Theme::TokyoNightStorm),
+        }
+    }
+}
+
+/// The theme made from the custom palette, with the default theme's colour for any that can't be
+/// read
+fn custom_theme(palette: &CustomPalette) -> Theme {
+    let fallback = Theme::TokyoNightStorm.palette();
+    let color = |hex: &str, fallback: Color| {
+        settings::parse_hex_color(hex).map_or(fallback, |[red, green, blue]| {
+            Color::from_rgb8(red, green, blue)
+        })
+    };
+    Theme::custom(
+        tr!("theme-custom"),
+        iced::theme::Palette {
+            background: color(&palette.background, fallback.background),
+            text: color(&palette.text, fallback.text),
+            primary: color(&palette.primary, fallback.primary),
+            success: color(&palette.success, fallback.success),
+            danger: color(&palette.danger, fallback.danger),
+        },
+    )
+}
+
+fn system_prefers_dark() -> bool {
+    dark_light::detect() == dark_light::Mode::Dark
+}
+
+/// An image drawn at a fixed size, which goes through the SVG renderer as that's what's built in
+fn image_thumbnail<'a>(image: &Image, size: u32) -> Element<'a, Message> {
+    Svg::new(Handle::from_memory(images::thumbnail_svg(image, size)))
+        .width(Length::Fixed(size as f32))
+        .height(Length::Fixed(size as f32))
+        .into()
+}
+
+/// A response's JSON as a tree, where clicking an object or array folds it away to one line
+fn view_json<'a>(
+    value: &serde_json::Value,
+    key: Option<&str>,
+    path: String,
+    response: u64,
+    collapsed: &HashSet<(u64, String)>,
+) -> Element<'a, Message> {
+    let key_label = key.map(|key| format!("{key}: ")).unwrap_or_default();
+    let (children, open, close): (Vec<(String, String, &serde_json::Value)>, _, _) = match value {
+        serde_json::Value::Object(object) => (
+            object
+                .iter()
+                .map(|(name, child)| (name.clone(), format!("{path}.{name}"), child))
+                .collect(),
+            "{",
+            "}",
+        ),
+        serde_json::Value::Array(items) => (
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, child)| (index.to_string(), format!("{path}[{index}]"), child))
+                .collect(),
+            "[",
+            "]",
+        ),
+        scalar => {
+            return text(format!("{key_label}{scalar}"))
+                .font(iced::Font::MONOSPACE)
+                .size(14)
+                .into()
+        }
+    };
+    let is_collapsed = collapsed.contains(&(response, path.clone()));
+    let header = if is_collapsed {
+        format!("▸ {key_label}{open}…{close} ({})", children.len())
+    } else {
+        format!("▾ {key_label}{open}")
+    };
+    let header = button(text(header).font(iced::Font::MONOSPACE).size(14))
+        .on_press(Message::ToggleJsonNode(response, path))
+        .style(button::text)
+        .padding(0);
+    if is_collapsed {
+        return header.into();
+    }
+    column![
+        header,
+        row![
+            Space::with_width(Length::Fixed(20.0)),
+            column(children.into_iter().map(|(name, child_path, child)| {
+                view_json(child, Some(&name), child_path, response, collapsed)
+            }))
+            .spacing(2),
+        ],
+        text(close).font(iced::Font::MONOSPACE).size(14),
+    ]
+    .spacing(2)
+    .into()
+}
+
+/// Flags a response asked for as JSON that isn't JSON, or doesn't match the schema it was asked
+/// to, with everything that's wrong with it
+fn view_structured_problems<'a>(
+    format: &OutputFormat,
+    reply: Option<&serde_json::Value>,
+) -> Option<Element<'a, Message>> {
+    let Some(reply) = reply else {
+        return Some(
+            text(tr!("reply-not-json"))
+                .size(14)
+                .style(text::danger)
+                .into(),
+        );
+    };
+    let problems = structured::validate(reply, format.schema()?);
+    if problems.is_empty() {
+        return None;
+    }
+    Some(
+        container(
+            column![text(tr!("reply-doesnt-match-schema"))
+                .size(14)
+                .style(text::danger)]
+            .extend(
+                problems
+                    .into_iter()
+                    .map(|problem| text(problem).size(12).into()),
+            )
+            .spacing(2),
+        )
+        .padding(10)
+        .style(container::rounded_box)
+        .into(),
+    )
+}
+
+/// Attaches a file as an image if it is one, or otherwise as text to inline in the prompt
+fn attach_message(path: PathBuf) -> Message {
+    if files::is_image(&path) {
+        Message::AttachImage(path)
+    } else {
+        Message::AttachFile(path)
+    }
+}
+
+fn chat_scrollable_id() -> scrollable::Id {
+    scrollable::Id::new("chat")
+}
+
+fn scroll_chat_to_bottom<T>() -> Task<T> {
+    scrollable::snap_to(chat_scrollable_id(), scrollable::RelativeOffset::END)
+}
+
+fn search_input_id() -> text_input::Id {
+    text_input::Id::new("search")
+}
+
+/// Rough rendered height of a message, used to size the placeholders for messages that aren't laid out
+fn estimated_chat_height(chat_message: &ChatMessage) -> f32 {
+    // Reasoning starts out folded away
+    let wrapped_lines: usize = reasoning::answer(&chat_message.content)
+        .lines()
+        .map(|line| line.len() / 100 + 1)
+        .sum();
+    let images_height = if chat_message.images.is_some() {
+        CHAT_THUMBNAIL_SIZE as f32 + 10.0
+    } else {
+        0.0
+    };
+    110.0 + wrapped_lines as f32 * 22.0 + images_height
+}
+
+/// Inputs for the custom theme's colours, each with a swatch of it, when it's the theme chosen
+fn view_palette_editor(settings: &Settings) -> Element<'_, Message> {
+    if settings.theme != settings::CUSTOM_THEME {
+        return column![].into();
+    }
+    let palette = &settings.custom_palette;
+    let colors: [(String, &String, fn(&mut CustomPalette, String)); 5] = [
+        (
+            tr!("palette-background"),
+            &palette.background,
+            |palette, hex| palette.background = hex,
+        ),
+        (tr!("palette-text"), &palette.text, |palette, hex| {
+            palette.text = hex
+        }),
+        (tr!("palette-primary"), &palette.primary, |palette, hex| {
+            palette.primary = hex
+        }),
+        (tr!("palette-success"), &palette.success, |palette, hex| {
+            palette.success = hex
+        }),
+        (tr!("palette-danger"), &palette.danger, |palette, hex| {
+            palette.danger = hex
+        }),
+    ];
+    column(colors.into_iter().map(|(label, hex, update)| {
+        let swatch = settings::parse_hex_color(hex)
+            .map(|[red, green, blue]| Color::from_rgb8(red, green, blue));
+        row![
+            text(label).width(Length::FillPortion(1)),
+            row![
+                text_input("#7aa2f7", hex).on_input(move |hex| {
+                    let mut settings = settings.clone();
+                    update(&mut settings.custom_palette, hex);
+                    Message::UpdateSettingsDraft(settings)
+                }),
+                container(Space::new(Length::Fixed(24.0), Length::Fixed(24.0))).style(
+                    move |_theme| container::Style {
+                        background: swatch.map(iced::Background::from),
+                        border: iced::Border {
+                            color: Color::from_rgb8(128, 128, 128),
+                            width: 1.0,
+                            radius: 4.0.into(),
+                        },
+                        ..Default::default()
+                    }
+                ),
             ]
-        }]
+            .spacing(10)
+            .align_y(Center)
+            .width(Length::FillPortion(2)),
+        ]
+        .spacing(20)
+        .align_y(Center)
         .into()
+    }))
+    .spacing(10)
+    .into()
+}
+
+/// The recorder and whisper.cpp model dictated prompts are transcribed with
+fn view_dictation_settings<'a>(settings: &'a Settings) -> Element<'a, Message> {
+    let dictation = &settings.dictation;
+    let update = move |dictation: Dictation| {
+        Message::UpdateSettingsDraft(Settings {
+            dictation,
+            ..settings.clone()
+        })
+    };
+    let setting = |label: String, widget: Element<'a, Message>| -> Element<'a, Message> {
+        row![
+            text(label).width(Length::FillPortion(1)),
+            container(widget).width(Length::FillPortion(2))
+        ]
+        .spacing(20)
+        .align_y(Center)
+        .into()
+    };
+    column![
+        setting(
+            tr!("setting-whisper-model"),
+            text_input(
+                "ggml-base.bin",
+                &dictation
+                    .whisper_model
+                    .as_ref()
+                    .map(|whisper_model| whisper_model.display().to_string())
+                    .unwrap_or_default(),
+            )
+            .on_input(move |whisper_model| {
+                update(Dictation {
+                    whisper_model: (!whisper_model.is_empty())
+                        .then(|| PathBuf::from(whisper_model)),
+                    ..dictation.clone()
+                })
+            })
+            .into(),
+        ),
+        setting(
+            tr!("setting-whisper-command"),
+            text_input(&Dictation::default().whisper, &dictation.whisper)
+                .on_input(move |whisper| {
+                    update(Dictation {
+                        whisper,
+                        ..dictation.clone()
+                    })
+                })
+                .into(),
+        ),
+        setting(
+            tr!("setting-recorder"),
+            text_input(&Dictation::default().recorder, &dictation.recorder)
+                .on_input(move |recorder| {
+                    update(Dictation {
+                        recorder,
+                        ..dictation.clone()
+                    })
+                })
+                .into(),
+        ),
+    ]
+    .spacing(15)
+    .into()
+}
+
+/// The search engine prompts are searched for on the web with, and where to reach it
+fn view_web_search_settings<'a>(settings: &'a Settings) -> Element<'a, Message> {
+    let web_search = &settings.web_search;
+    let update = move |web_search: WebSearch| {
+        Message::UpdateSettingsDraft(Settings {
+            web_search,
+            ..settings.clone()
+        })
+    };
+    let setting = |label: String, widget: Element<'a, Message>| -> Element<'a, Message> {
+        row![
+            text(label).width(Length::FillPortion(1)),
+            container(widget).width(Length::FillPortion(2))
+        ]
+        .spacing(20)
+        .align_y(Center)
+        .into()
+    };
+    let engine_setting = match web_search.engine {
+        SearchEngine::Searxng => setting(
+            tr!("setting-searxng-url"),
+            text_input("http://localhost:8888", &web_search.url)
+                .on_input(move |url| {
+                    update(WebSearch {
+                        url,
+                        ..web_search.clone()
+                    })
+                })
+                .into(),
+        ),
+        SearchEngine::Brave => setting(
+            tr!("setting-brave-api-key"),
+            text_input("", &web_search.api_key)
+                .on_input(move |api_key| {
+                    update(WebSearch {
+                        api_key,
+                        ..web_search.clone()
+                    })
+                })
+                .secure(true)
+                .into(),
+        ),
+    };
+    column![
+        setting(
+            tr!("setting-search-engine"),
+            pick_list(
+                [SearchEngine::Searxng, SearchEngine::Brave].map(SearchEngineChoice),
+                Some(SearchEngineChoice(web_search.engine)),
+                move |SearchEngineChoice(engine)| {
+                    update(WebSearch {
+                        engine,
+                        ..web_search.clone()
+                    })
+                },
+            )
+            .into(),
+        ),
+        engine_setting,
+    ]
+    .spacing(15)
+    .into()
+}
+
+/// Dropdown for exporting a saved conversation, or the open one if there's no path
+fn export_pick_list<'a>(path: Option<PathBuf>) -> Element<'a, Message> {
+    pick_list(
+        export::Format::ALL.map(ExportChoice),
+        None::<ExportChoice>,
+        move |choice| Message::ExportConversation(path.clone(), choice.0),
+    )
+    .placeholder(tr!("export"))
+    .into()
+}
+
+fn role_name(role: &MessageRole) -> String {
+    match role {
+        MessageRole::User => tr!("role-user"),
+        MessageRole::Assistant => tr!("role-assistant"),
+        MessageRole::System => tr!("role-system"),
     }
+}
 
-    fn theme(&self) -> Theme {
-        Theme::TokyoNightStorm
+fn tool_name(tool: Tool) -> String {
+    match tool {
+        Tool::Calculator => tr!("tool-calculator"),
+        Tool::CurrentTime => tr!("tool-current-time"),
+        Tool::ReadFile => tr!("tool-read-file"),
+        Tool::RunCommand => tr!("tool-run-command"),
+    }
+}
+
+/// Writes a file, returning the path it was written to for the toast
+async fn save_to_file(contents: Vec<u8>, path: PathBuf) -> Result<PathBuf, Error> {
+    tokio::fs::write(&path, contents)
+        .await
+        .map_err(|err| Error::Write {
+            path: path.clone(),
+            message: err.to_string(),
+        })?;
+    Ok(path)
+}
+
+/// Sends the prompt on Enter, leaving Shift+Enter to start a new line
+fn composer_key_binding(
+    key_press: KeyPress,
+    can_go_back: bool,
+    can_go_forward: bool,
+) -> Option<Binding<Message>> {
+    if key_press.status != text_editor::Status::Focused {
+        return Binding::from_key_press(key_press);
+    }
+    let modifiers = key_press.modifiers;
+    match key_press.key.as_ref() {
+        iced::keyboard::Key::Named(key::Named::Enter) if !modifiers.shift() => {
+            Some(Binding::Custom(Message::SubmitPrompt))
+        }
+        iced::keyboard::Key::Named(key::Named::ArrowUp) if can_go_back && modifiers.is_empty() => {
+            Some(Binding::Custom(Message::PreviousPrompt))
+        }
+        iced::keyboard::Key::Named(key::Named::ArrowDown)
+            if can_go_forward && modifiers.is_empty() =>
+        {
+            Some(Binding::Custom(Message::NextPrompt))
+        }
+        // Shift changes the character typed, so Ctrl+Shift+Z arrives as "Z"
+        iced::keyboard::Key::Character(c) if modifiers.command() && c.eq_ignore_ascii_case("z") => {
+            Some(Binding::Custom(if modifiers.shift() {
+                Message::RedoPrompt
+            } else {
+                Message::UndoPrompt
+            }))
+        }
+        iced::keyboard::Key::Character("y") if modifiers.command() => {
+            Some(Binding::Custom(Message::RedoPrompt))
+        }
+        iced::keyboard::Key::Character(c) if modifiers.command() && c.eq_ignore_ascii_case("v") => {
+            Some(Binding::Custom(Message::PasteIntoPrompt))
+        }
+        _ => Binding::from_key_press(key_press),
+    }
+}
+
+/// Puts back a response that was cut off by a crash, unless the saved conversation already has more of it
+fn restore_partial_response(conversation: &mut Vec<ChatMessage>, partial_response: String) {
+    match conversation.last_mut() {
+        Some(chat_message) if chat_message.role == MessageRole::Assistant => {
+            if chat_message.content.len() < partial_response.len() {
+                chat_message.content = partial_response;
+            }
+        }
+        _ => conversation.push(ChatMessage {
+            role: MessageRole::Assistant,
+            content: partial_response,
+            images: None,
+        }),
+    }
+}
+
+/// A colour for a tag that stays the same between runs, so it's recognisable at a glance
+fn tag_color(tag: &str) -> Color {
+    const PALETTE: [Color; 8] = [
+        color!(0xe06c75),
+        color!(0xd19a66),
+        color!(0xe5c07b),
+        color!(0x98c379),
+        color!(0x56b6c2),
+        color!(0x61afef),
+        color!(0xc678dd),
+        color!(0xbe5046),
+    ];
+    PALETTE[(content_hash(tag) % PALETTE.len() as u64) as usize]
+}
+
+/// Moves each value kept by message index to the index `move_index` gives, dropping those it
+/// gives none
+fn move_by_index<V>(values: &mut BTreeMap<usize, V>, move_index: impl Fn(usize) -> Option<usize>) {
+    *values = std::mem::take(values)
+        .into_iter()
+        .filter_map(|(index, value)| Some((move_index(index)?, value)))
+        .collect();
+}
+
+fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Parses a message's markdown, with its maths prepared to be shown
+fn parse_markdown(content: &str) -> ParsedMarkdown {
+    ParsedMarkdown::new(&math::prepare(content))
+}
+
+/// A message's markdown parsed to be shown, in parts that are shared rather than copied so a
+/// response being streamed only parses what's new each time
+#[derive(Debug, Default, Clone)]
+struct ParsedMarkdown {
+    parts: Vec<Arc<ParsedPart>>,
+}
+
+/// Some of a message's markdown blocks, parsed
+#[derive(Debug)]
+struct ParsedPart {
+    items: Vec<markdown::Item>,
+    /// Its code blocks, found along with parsing it rather than every time it's shown
+    code_blocks: Vec<CodeBlock>,
+}
+
+impl ParsedMarkdown {
+    /// Parses markdown that's had its maths prepared
+    fn new(prepared: &str) -> Self {
+        Self {
+            parts: vec![Arc::new(ParsedPart::new(prepared))],
+        }
+    }
+}
+
+impl ParsedPart {
+    fn new(prepared: &str) -> Self {
+        Self {
+            items: markdown::parse(prepared).collect(),
+            code_blocks: code_blocks::extract(prepared),
+        }
+    }
+}
+
+/// Messages' parsed markdown by a hash of their content, so reopening conversations is instant,
+/// along with their markdown as it's prepared to be parsed, which is kept between sessions
+///
+/// Both are held to the markdown memory budget.
+struct MarkdownCache {
+    parsed: Lru<Arc<ParsedMarkdown>>,
+    prepared: Lru<String>,
+}
+
+impl MarkdownCache {
+    fn new(budget: usize) -> Self {
+        Self {
+            parsed: Lru::new(budget),
+            prepared: Lru::new(budget),
+        }
+    }
+
+    fn set_budget(&mut self, budget: usize) {
+        self.parsed.set_budget(budget);
+        self.prepared.set_budget(budget);
+    }
+
+    /// The message's markdown parsed, leaving out its reasoning
+    fn parse(&mut self, content: &str) -> Arc<ParsedMarkdown> {
+        let answer = reasoning::answer(content);
+        let key = markdown_cache::content_hash(answer);
+        if let Some(parsed) = self.parsed.get(key) {
+            return parsed.clone();
+        }
+        let prepared = markdown_cache::prepare(&mut self.prepared, answer);
+        let parsed = Arc::new(ParsedMarkdown::new(&prepared));
+        self.parsed.insert(key, parsed.clone(), answer.len());
+        parsed
+    }
+}
+
+/// A response being streamed, parsed up to the last block that's been finished so only the block
+/// being written is parsed again as more arrives
+#[derive(Default)]
+struct StreamedMarkdown {
+    /// Bytes of the response that have been parsed
+    settled_len: usize,
+    /// Hash of those bytes, to tell when the response being streamed isn't the one they're from
+    settled_hash: u64,
+    settled: ParsedMarkdown,
+}
+
+impl StreamedMarkdown {
+    fn is_empty(&self) -> bool {
+        self.settled_len == 0
+    }
+
+    fn parse(&mut self, content: &str) -> ParsedMarkdown {
+        let is_continued = content
+            .get(..self.settled_len)
+            .is_some_and(|settled| content_hash(settled) == self.settled_hash);
+        if !is_continued {
+            *self = StreamedMarkdown::default();
+        }
+        let settled_len = streamed::finished_blocks_len(content);
+        if settled_len > self.settled_len {
+            let settled = math::prepare(&content[self.settled_len..settled_len]);
+            self.settled.parts.push(Arc::new(ParsedPart::new(&settled)));
+            self.settled_len = settled_len;
+            self.settled_hash = content_hash(&content[..settled_len]);
+        }
+        let mut parsed = self.settled.clone();
+        let tail = math::prepare(&content[self.settled_len..]);
+        parsed.parts.push(Arc::new(ParsedPart::new(&tail)));
+        parsed
     }
 }
+
+/// Reports when the settings file is edited, by the app or anything else
+fn watch_settings_file() -> impl Stream<Item = Message> {
+    let settings_file = settings::settings_file().ok();
+    watch_dir(settings::config_dir())
+        .filter(move |paths| {
+            std::future::ready(
+                settings_file
+                    .as_ref()
+                    .is_some_and(|settings_file| paths.contains(settings_file)),
+            )
+        })
+        .map(|_| Message::SettingsFileChanged)
+}
+
+/// Reports the paths of files in the directory whenever they change
+fn watch_dir(dir: Result<PathBuf, Error>) -> impl Stream<Item = Vec<PathBuf>> {
+    iced::stream::channel(100, |output| async move {
+        let Ok(dir) = dir else {
+            return;
+        };
+        if std::fs::create_dir_all(&dir).is_err() {
+            return;
+        }
+        let mut watcher_output = output.clone();
+        let Ok(mut watcher) = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let _ = watcher_output.try_send(event.paths);
+            }
+        }) else {
+            return;
+        };
+        if watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok() {
+            // The watcher stops when dropped, so keep it alive for as long as the subscription
+            std::future::pending::<()>().await;
+        }
+    })
+}
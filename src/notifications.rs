@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Number of characters of the response shown in a notification
+const SNIPPET_LENGTH: usize = 120;
+
+/// How long a response has to take to be worth a notification once it's ready, as anything
+/// quicker was likely still being watched for
+pub const MIN_GENERATION_TIME: Duration = Duration::from_secs(10);
+
+#[cfg(target_os = "linux")]
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: std::collections::HashMap<&str, zbus::zvariant::Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+pub fn snippet(response: &str) -> String {
+    let mut snippet: String = response.chars().take(SNIPPET_LENGTH).collect();
+    if snippet.len() < response.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// The first line of a response that has anything on it, cut down to fit in a notification
+pub fn first_line(response: &str) -> String {
+    snippet(
+        response
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or_default(),
+    )
+}
+
+/// Shows a desktop notification, resolving to `conversation` once it's clicked or `None` if it's
+/// dismissed instead
+#[cfg(target_os = "linux")]
+pub async fn notify(
+    summary: String,
+    body: String,
+    open_label: String,
+    conversation: Option<PathBuf>,
+) -> Option<PathBuf> {
+    use iced::futures::{FutureExt, StreamExt};
+
+    let result: zbus::Result<bool> = async {
+        let connection = zbus::Connection::session().await?;
+        let proxy = NotificationsProxy::new(&connection).await?;
+        // Subscribed before sending so a quick click can't be missed
+        let mut actions = proxy.receive_action_invoked().await?;
+        let mut closed = proxy.receive_notification_closed().await?;
+        let id = proxy
+            .notify(
+                "Comhrá",
+                0,
+                "",
+                &summary,
+                &body,
+                &["default", &open_label],
+                std::collections::HashMap::new(),
+                -1,
+            )
+            .await?;
+        loop {
+            iced::futures::select! {
+                action = actions.next().fuse() => match action {
+                    Some(action) if action.args()?.id == id => return Ok(true),
+                    Some(_) => continue,
+                    None => return Ok(false),
+                },
+                closed_signal = closed.next().fuse() => match closed_signal {
+                    Some(closed_signal) if closed_signal.args()?.id == id => return Ok(false),
+                    Some(_) => continue,
+                    None => return Ok(false),
+                },
+            }
+        }
+    }
+    .await;
+    match result {
+        Ok(clicked) => conversation.filter(|_| clicked),
+        Err(err) => {
+            tracing::warn!("Couldn't show a notification: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn notify(
+    _summary: String,
+    _body: String,
+    _open_label: String,
+    _conversation: Option<PathBuf>,
+) -> Option<PathBuf> {
+    None
+}
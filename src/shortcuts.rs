@@ -0,0 +1,147 @@
+use comhra_core::settings::Shortcuts;
+use iced::keyboard::{key, Key, Modifiers};
+
+/// Something that can be done with a keyboard shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    NewChat,
+    ToggleSidebar,
+    Search,
+    Submit,
+    StopGeneration,
+    NextConversation,
+    PreviousConversation,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+}
+
+/// Finds the action the settings give to a key press, if any
+pub fn action(shortcuts: &Shortcuts, key: &Key, modifiers: Modifiers) -> Option<Action> {
+    [
+        (&shortcuts.new_chat, Action::NewChat),
+        (&shortcuts.toggle_sidebar, Action::ToggleSidebar),
+        (&shortcuts.search, Action::Search),
+        (&shortcuts.submit, Action::Submit),
+        (&shortcuts.stop_generation, Action::StopGeneration),
+        (&shortcuts.next_conversation, Action::NextConversation),
+        (
+            &shortcuts.previous_conversation,
+            Action::PreviousConversation,
+        ),
+        (&shortcuts.zoom_in, Action::ZoomIn),
+        (&shortcuts.zoom_out, Action::ZoomOut),
+        (&shortcuts.reset_zoom, Action::ResetZoom),
+    ]
+    .into_iter()
+    .find(|(shortcut, _action)| matches(shortcut, key, modifiers))
+    .map(|(_shortcut, action)| action)
+}
+
+/// Whether a key press is the shortcut, written like `Ctrl+Shift+Tab`
+///
+/// Shortcuts that can't be understood never match, so a typo disables the shortcut rather than
+/// taking over some other key.
+fn matches(shortcut: &str, pressed_key: &Key, pressed_modifiers: Modifiers) -> bool {
+    let mut parts: Vec<String> = shortcut
+        .split('+')
+        .map(|part| part.trim().to_lowercase())
+        .collect();
+    let Some(mut key_name) = parts.pop().filter(|key_name| !key_name.is_empty()) else {
+        return false;
+    };
+    // Written out, as a `+` would be taken for joining keys
+    if key_name == "plus" {
+        key_name = "+".to_string();
+    }
+    let mut modifiers = Modifiers::empty();
+    for part in parts {
+        modifiers |= match part.as_str() {
+            "ctrl" | "control" => Modifiers::CTRL,
+            "shift" => Modifiers::SHIFT,
+            "alt" | "option" => Modifiers::ALT,
+            "super" | "cmd" | "command" | "meta" | "logo" => Modifiers::LOGO,
+            _ => return false,
+        };
+    }
+    if modifiers != pressed_modifiers {
+        return false;
+    }
+    match (named_key(&key_name), pressed_key.as_ref()) {
+        (Some(named), Key::Named(pressed_named)) => named == pressed_named,
+        // Shift changes the character typed, e.g. Ctrl+Shift+N arrives as "N"
+        (None, Key::Character(pressed_character)) => pressed_character.to_lowercase() == key_name,
+        _ => false,
+    }
+}
+
+fn named_key(key_name: &str) -> Option<key::Named> {
+    Some(match key_name {
+        "enter" | "return" => key::Named::Enter,
+        "escape" | "esc" => key::Named::Escape,
+        "tab" => key::Named::Tab,
+        "space" => key::Named::Space,
+        "backspace" => key::Named::Backspace,
+        "delete" | "del" => key::Named::Delete,
+        "insert" => key::Named::Insert,
+        "up" => key::Named::ArrowUp,
+        "down" => key::Named::ArrowDown,
+        "left" => key::Named::ArrowLeft,
+        "right" => key::Named::ArrowRight,
+        "home" => key::Named::Home,
+        "end" => key::Named::End,
+        "pageup" => key::Named::PageUp,
+        "pagedown" => key::Named::PageDown,
+        "f1" => key::Named::F1,
+        "f2" => key::Named::F2,
+        "f3" => key::Named::F3,
+        "f4" => key::Named::F4,
+        "f5" => key::Named::F5,
+        "f6" => key::Named::F6,
+        "f7" => key::Named::F7,
+        "f8" => key::Named::F8,
+        "f9" => key::Named::F9,
+        "f10" => key::Named::F10,
+        "f11" => key::Named::F11,
+        "f12" => key::Named::F12,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn character(character: &str) -> Key {
+        Key::Character(character.into())
+    }
+
+    #[test]
+    fn modifiers_have_to_match_exactly() {
+        let ctrl_shift = Modifiers::CTRL | Modifiers::SHIFT;
+        assert!(matches("Ctrl+Shift+N", &character("N"), ctrl_shift));
+        assert!(matches("control + shift + n", &character("n"), ctrl_shift));
+        assert!(!matches("Ctrl+N", &character("n"), ctrl_shift));
+        assert!(!matches("Ctrl+Shift+N", &character("n"), Modifiers::CTRL));
+        assert!(matches("Cmd+Plus", &character("+"), Modifiers::LOGO));
+    }
+
+    #[test]
+    fn named_keys_are_found_whatever_their_case() {
+        let tab = Key::Named(key::Named::Tab);
+        assert!(matches("Ctrl+Tab", &tab, Modifiers::CTRL));
+        assert!(matches("ctrl+TAB", &tab, Modifiers::CTRL));
+        assert!(!matches("Ctrl+Tab", &character("t"), Modifiers::CTRL));
+        assert_eq!(named_key("pagedown"), Some(key::Named::PageDown));
+        assert_eq!(named_key("f13"), None);
+    }
+
+    #[test]
+    fn shortcuts_that_cant_be_understood_never_match() {
+        let enter = Key::Named(key::Named::Enter);
+        assert!(!matches("", &enter, Modifiers::empty()));
+        assert!(!matches("Ctrl+", &enter, Modifiers::CTRL));
+        assert!(!matches("Hyper+Enter", &enter, Modifiers::empty()));
+        assert!(!matches("Ctrl+Enter", &character("enter"), Modifiers::CTRL));
+    }
+}
@@ -0,0 +1,364 @@
+//! The icon in the system tray that keeps the app at hand while its window is closed, and the
+//! global shortcut that opens the quick chat window from anywhere on the desktop
+//!
+//! Both go over D-Bus, as a StatusNotifierItem with a menu for the icon and the XDG desktop
+//! portal's global shortcuts for the shortcut, so there's neither on other platforms.
+
+use iced::futures::Stream;
+
+/// Something picked from the tray icon or its menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    ShowWindow,
+    QuickChat,
+    Quit,
+}
+
+/// Shows the tray icon for as long as it's listened to
+#[cfg(target_os = "linux")]
+pub fn icon() -> impl Stream<Item = Event> {
+    iced::stream::channel(10, |output| async move {
+        if let Err(err) = linux::serve_icon(output).await {
+            tracing::warn!("Couldn't show the tray icon: {err}");
+        }
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn icon() -> impl Stream<Item = Event> {
+    iced::futures::stream::empty()
+}
+
+/// Registers a shortcut like `Ctrl+Alt+Space` with the desktop for as long as it's listened to,
+/// giving an item each time it's pressed, whichever app has focus
+#[cfg(target_os = "linux")]
+pub fn global_shortcut(shortcut: String) -> impl Stream<Item = ()> {
+    iced::stream::channel(10, |output| async move {
+        let Some(trigger) = linux::portal_trigger(&shortcut) else {
+            tracing::warn!("Couldn't understand the quick chat shortcut {shortcut}");
+            return;
+        };
+        if let Err(err) = linux::bind_shortcut(trigger, output).await {
+            tracing::warn!("Couldn't register the quick chat shortcut: {err}");
+        }
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn global_shortcut(_shortcut: String) -> impl Stream<Item = ()> {
+    iced::futures::stream::empty()
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::collections::HashMap;
+
+    use iced::futures::channel::mpsc;
+    use iced::futures::{SinkExt, StreamExt};
+    use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, StructureBuilder, Value};
+
+    use super::Event;
+    use crate::i18n::tr;
+
+    const ITEM_PATH: &str = "/StatusNotifierItem";
+    const MENU_PATH: &str = "/MenuBar";
+
+    /// The quick chat shortcut's id with the portal
+    const SHORTCUT_ID: &str = "quick-chat";
+
+    #[zbus::proxy(
+        interface = "org.kde.StatusNotifierWatcher",
+        default_service = "org.kde.StatusNotifierWatcher",
+        default_path = "/StatusNotifierWatcher"
+    )]
+    trait StatusNotifierWatcher {
+        fn register_status_notifier_item(&self, service: &str) -> zbus::Result<()>;
+    }
+
+    #[zbus::proxy(
+        interface = "org.freedesktop.portal.GlobalShortcuts",
+        default_service = "org.freedesktop.portal.Desktop",
+        default_path = "/org/freedesktop/portal/desktop"
+    )]
+    trait GlobalShortcuts {
+        fn create_session(
+            &self,
+            options: HashMap<&str, Value<'_>>,
+        ) -> zbus::Result<OwnedObjectPath>;
+
+        fn bind_shortcuts(
+            &self,
+            session_handle: &ObjectPath<'_>,
+            shortcuts: &[(&str, HashMap<&str, Value<'_>>)],
+            parent_window: &str,
+            options: HashMap<&str, Value<'_>>,
+        ) -> zbus::Result<OwnedObjectPath>;
+
+        #[zbus(signal)]
+        fn activated(
+            &self,
+            session_handle: OwnedObjectPath,
+            shortcut_id: String,
+            timestamp: u64,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::Result<()>;
+    }
+
+    struct StatusNotifierItem {
+        output: mpsc::Sender<Event>,
+    }
+
+    #[zbus::interface(name = "org.kde.StatusNotifierItem")]
+    impl StatusNotifierItem {
+        async fn activate(&mut self, _x: i32, _y: i32) {
+            let _ = self.output.send(Event::ShowWindow).await;
+        }
+
+        /// A middle click
+        async fn secondary_activate(&mut self, _x: i32, _y: i32) {
+            let _ = self.output.send(Event::QuickChat).await;
+        }
+
+        #[zbus(property)]
+        fn category(&self) -> &str {
+            "ApplicationStatus"
+        }
+
+        #[zbus(property)]
+        fn id(&self) -> &str {
+            "comhra"
+        }
+
+        #[zbus(property)]
+        fn title(&self) -> &str {
+            "Comhrá"
+        }
+
+        #[zbus(property)]
+        fn status(&self) -> &str {
+            "Active"
+        }
+
+        #[zbus(property)]
+        fn icon_name(&self) -> &str {
+            "internet-group-chat"
+        }
+
+        #[zbus(property)]
+        fn item_is_menu(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn menu(&self) -> ObjectPath<'_> {
+            ObjectPath::from_static_str_unchecked(MENU_PATH)
+        }
+    }
+
+    /// The tray icon's menu, as the `com.canonical.dbusmenu` interface the tray asks for it with
+    struct Menu {
+        output: mpsc::Sender<Event>,
+    }
+
+    impl Menu {
+        /// Each item's id in the menu, which starts at 1 as 0 is the menu itself
+        const ITEMS: [Event; 3] = [Event::ShowWindow, Event::QuickChat, Event::Quit];
+
+        fn item(id: i32) -> Option<Event> {
+            Self::ITEMS
+                .get(usize::try_from(id).ok()?.checked_sub(1)?)
+                .copied()
+        }
+
+        fn properties(id: i32) -> HashMap<String, Value<'static>> {
+            let label = match Self::item(id) {
+                Some(Event::ShowWindow) => tr!("tray-show-window"),
+                Some(Event::QuickChat) => tr!("quick-chat"),
+                Some(Event::Quit) => tr!("tray-quit"),
+                None => return HashMap::from([("children-display".to_string(), "submenu".into())]),
+            };
+            HashMap::from([("label".to_string(), Value::from(label))])
+        }
+    }
+
+    #[zbus::interface(name = "com.canonical.dbusmenu")]
+    impl Menu {
+        /// The menu is flat, so whatever part of it is asked for there's only ever the one level
+        fn get_layout(
+            &self,
+            parent_id: i32,
+            _recursion_depth: i32,
+            _property_names: Vec<String>,
+        ) -> (
+            u32,
+            (i32, HashMap<String, Value<'static>>, Vec<Value<'static>>),
+        ) {
+            let children = if parent_id == 0 {
+                (1..=Self::ITEMS.len() as i32)
+                    .map(|id| {
+                        Value::from(
+                            StructureBuilder::new()
+                                .add_field(id)
+                                .add_field(Self::properties(id))
+                                .add_field(Vec::<Value<'static>>::new())
+                                .build(),
+                        )
+                    })
+                    .collect()
+            } else {
+                vec![]
+            };
+            (1, (parent_id, Self::properties(parent_id), children))
+        }
+
+        fn get_group_properties(
+            &self,
+            ids: Vec<i32>,
+            _property_names: Vec<String>,
+        ) -> Vec<(i32, HashMap<String, Value<'static>>)> {
+            ids.into_iter()
+                .map(|id| (id, Self::properties(id)))
+                .collect()
+        }
+
+        async fn event(&mut self, id: i32, event_id: String, _data: OwnedValue, _timestamp: u32) {
+            if event_id != "clicked" {
+                return;
+            }
+            if let Some(event) = Self::item(id) {
+                let _ = self.output.send(event).await;
+            }
+        }
+
+        fn about_to_show(&self, _id: i32) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn version(&self) -> u32 {
+            3
+        }
+
+        #[zbus(property)]
+        fn status(&self) -> &str {
+            "normal"
+        }
+
+        #[zbus(property)]
+        fn text_direction(&self) -> &str {
+            "ltr"
+        }
+    }
+
+    /// Serves the tray icon and its menu, staying registered until it's dropped
+    pub async fn serve_icon(output: mpsc::Sender<Event>) -> zbus::Result<()> {
+        let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+        let connection = zbus::connection::Builder::session()?
+            .name(name.as_str())?
+            .serve_at(
+                ITEM_PATH,
+                StatusNotifierItem {
+                    output: output.clone(),
+                },
+            )?
+            .serve_at(MENU_PATH, Menu { output })?
+            .build()
+            .await?;
+        StatusNotifierWatcherProxy::new(&connection)
+            .await?
+            .register_status_notifier_item(&name)
+            .await?;
+        // The connection has to stay open for the icon to stay in the tray
+        let _connection = connection;
+        std::future::pending().await
+    }
+
+    /// A shortcut as the settings write it, like `Ctrl+Alt+Space`, as the portal's shortcuts are
+    /// written, like `CTRL+ALT+space`
+    pub fn portal_trigger(shortcut: &str) -> Option<String> {
+        let mut parts: Vec<&str> = shortcut.split('+').map(str::trim).collect();
+        let key_name = parts.pop().filter(|key_name| !key_name.is_empty())?;
+        let mut trigger: Vec<String> = parts
+            .into_iter()
+            .map(|part| match part.to_lowercase().as_str() {
+                "ctrl" | "control" => Some("CTRL".to_string()),
+                "shift" => Some("SHIFT".to_string()),
+                "alt" | "option" => Some("ALT".to_string()),
+                "super" | "cmd" | "command" | "meta" | "logo" => Some("LOGO".to_string()),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        // Keys go by their XKB names, which are lowercase for letters
+        trigger.push(match key_name.to_lowercase().as_str() {
+            "enter" | "return" => "Return".to_string(),
+            "escape" | "esc" => "Escape".to_string(),
+            "tab" => "Tab".to_string(),
+            "space" => "space".to_string(),
+            key_name => key_name.to_string(),
+        });
+        Some(trigger.join("+"))
+    }
+
+    /// Binds the shortcut in a global shortcuts session, sending each press of it on
+    pub async fn bind_shortcut(trigger: String, mut output: mpsc::Sender<()>) -> zbus::Result<()> {
+        let connection = zbus::Connection::session().await?;
+        let portal = GlobalShortcutsProxy::new(&connection).await?;
+        // The portal names the session after the connection and a token of the app's choosing
+        let sender = connection
+            .unique_name()
+            .map(|name| name.trim_start_matches(':').replace('.', "_"))
+            .unwrap_or_default();
+        let token = format!("comhra{}", std::process::id());
+        let session = ObjectPath::try_from(format!(
+            "/org/freedesktop/portal/desktop/session/{sender}/{token}"
+        ))?;
+        let request = zbus::Proxy::new(
+            &connection,
+            "org.freedesktop.portal.Desktop",
+            format!("/org/freedesktop/portal/desktop/request/{sender}/{token}"),
+            "org.freedesktop.portal.Request",
+        )
+        .await?;
+        // Subscribed before asking so the answer can't be missed
+        let mut responses = request.receive_signal("Response").await?;
+        let mut activations = portal.receive_activated().await?;
+        portal
+            .create_session(HashMap::from([
+                ("handle_token", Value::from(token.as_str())),
+                ("session_handle_token", Value::from(token.as_str())),
+            ]))
+            .await?;
+        let (response, _results): (u32, HashMap<String, OwnedValue>) = match responses.next().await
+        {
+            Some(message) => message.body().deserialize()?,
+            None => return Ok(()),
+        };
+        if response != 0 {
+            return Err(zbus::Error::Failure(
+                "the global shortcuts session wasn't allowed".to_string(),
+            ));
+        }
+        let description = tr!("quick-chat");
+        portal
+            .bind_shortcuts(
+                &session,
+                &[(
+                    SHORTCUT_ID,
+                    HashMap::from([
+                        ("description", Value::from(description.as_str())),
+                        ("preferred_trigger", Value::from(trigger.as_str())),
+                    ]),
+                )],
+                "",
+                HashMap::new(),
+            )
+            .await?;
+        while let Some(activated) = activations.next().await {
+            let args = activated.args()?;
+            if args.session_handle.as_str() == session.as_str() && args.shortcut_id == SHORTCUT_ID {
+                let _ = output.send(()).await;
+            }
+        }
+        Ok(())
+    }
+}
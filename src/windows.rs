@@ -0,0 +1,267 @@
+//! The app's windows: the main one it opens with, conversations opened in windows of their own
+//! beside it, each with its own model and response being generated, and the quick chat window
+
+use std::collections::BTreeMap;
+
+use iced::keyboard::{key, Key};
+use iced::widget::{container, text, text_input};
+use iced::{window, Element, Length, Size, Subscription, Task, Theme};
+
+use crate::args::Activation;
+use crate::i18n::tr;
+use crate::{App, Message};
+
+/// Size of the quick chat window, which only has room for the prompt
+const QUICK_CHAT_SIZE: Size = Size::new(560.0, 60.0);
+
+pub struct Windows {
+    /// The window the app opened with, which quits the app when it's closed
+    main: window::Id,
+    apps: BTreeMap<window::Id, App>,
+    quick_chat: Option<QuickChat>,
+}
+
+/// A small window for asking something from anywhere, which starts a new conversation in the
+/// main window once it's sent
+struct QuickChat {
+    window: window::Id,
+    prompt: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum WindowMessage {
+    App(window::Id, Message),
+    Closed(window::Id),
+    UpdateQuickChat(String),
+    SubmitQuickChat,
+}
+
+fn settings() -> window::Settings {
+    window::Settings {
+        // The session is saved before the window closes
+        exit_on_close_request: false,
+        ..window::Settings::default()
+    }
+}
+
+fn quick_chat_settings() -> window::Settings {
+    window::Settings {
+        size: QUICK_CHAT_SIZE,
+        position: window::Position::Centered,
+        resizable: false,
+        decorations: false,
+        level: window::Level::AlwaysOnTop,
+        ..settings()
+    }
+}
+
+fn quick_chat_input_id() -> text_input::Id {
+    text_input::Id::new("quick-chat")
+}
+
+impl Windows {
+    pub fn new(activation: Activation, choose_profile: bool) -> (Self, Task<WindowMessage>) {
+        let (main, open) = window::open(settings());
+        let (mut app, task) = App::new(activation, choose_profile);
+        app.window = Some(main);
+        let windows = Self {
+            main,
+            apps: BTreeMap::from([(main, app)]),
+            quick_chat: None,
+        };
+        let task = task.map(move |message| WindowMessage::App(main, message));
+        (windows, open.discard().chain(task))
+    }
+
+    pub fn title(&self, id: window::Id) -> String {
+        if self.is_quick_chat(id) {
+            return tr!("quick-chat");
+        }
+        self.apps.get(&id).map(App::title).unwrap_or_default()
+    }
+
+    fn is_quick_chat(&self, id: window::Id) -> bool {
+        self.quick_chat
+            .as_ref()
+            .is_some_and(|quick_chat| quick_chat.window == id)
+    }
+
+    pub fn update(&mut self, message: WindowMessage) -> Task<WindowMessage> {
+        match message {
+            // It's gone as soon as it's done with, like a menu
+            WindowMessage::App(id, message) if self.is_quick_chat(id) => match message {
+                Message::CloseRequested(_)
+                | Message::WindowFocusChanged(false)
+                | Message::KeyPressed(Key::Named(key::Named::Escape), _) => self.close_quick_chat(),
+                _ => Task::none(),
+            },
+            WindowMessage::App(_id, Message::OpenQuickChat) => match &self.quick_chat {
+                Some(quick_chat) => window::gain_focus(quick_chat.window),
+                None => {
+                    let (id, open) = window::open(quick_chat_settings());
+                    self.quick_chat = Some(QuickChat {
+                        window: id,
+                        prompt: String::new(),
+                    });
+                    open.discard()
+                        .chain(window::gain_focus(id))
+                        .chain(text_input::focus(quick_chat_input_id()))
+                }
+            },
+            WindowMessage::UpdateQuickChat(prompt) => {
+                if let Some(quick_chat) = self.quick_chat.as_mut() {
+                    quick_chat.prompt = prompt;
+                }
+                Task::none()
+            }
+            WindowMessage::SubmitQuickChat => {
+                let Some(prompt) = self
+                    .quick_chat
+                    .as_ref()
+                    .map(|quick_chat| &quick_chat.prompt)
+                else {
+                    return Task::none();
+                };
+                if prompt.trim().is_empty() {
+                    return Task::none();
+                }
+                // A prompt on its own starts a new conversation, sent once a model's selected
+                let activation = Activation {
+                    prompt: Some(prompt.clone()),
+                    send: true,
+                    ..Activation::default()
+                };
+                let close = self.close_quick_chat();
+                Task::batch([
+                    close,
+                    self.update(WindowMessage::App(
+                        self.main,
+                        Message::Activated(activation),
+                    )),
+                ])
+            }
+            WindowMessage::App(id, message) => {
+                let Some(app) = self.apps.get_mut(&id) else {
+                    return Task::none();
+                };
+                let detached = match &message {
+                    Message::OpenInNewWindow(conversation) => {
+                        Some(app.detach(conversation.clone()))
+                    }
+                    _ => None,
+                };
+                // The others follow the main window's settings rather than loading them again, and
+                // close with it, each saving its conversation
+                let forwarded = match &message {
+                    Message::SettingsLoaded(Ok(_)) if id == self.main => Some(message.clone()),
+                    Message::CloseRequested(_) if id == self.main && !app.hides_on_close() => {
+                        Some(message.clone())
+                    }
+                    _ => None,
+                };
+                let mut tasks = vec![app
+                    .update(message)
+                    .map(move |message| WindowMessage::App(id, message))];
+                if let Some(forwarded) = forwarded {
+                    let main = self.main;
+                    let others = self.apps.iter_mut().filter(|(other, _app)| **other != main);
+                    for (&other, app) in others {
+                        let forwarded = match &forwarded {
+                            Message::CloseRequested(_) => Message::CloseRequested(other),
+                            forwarded => forwarded.clone(),
+                        };
+                        tasks.push(
+                            app.update(forwarded)
+                                .map(move |message| WindowMessage::App(other, message)),
+                        );
+                    }
+                }
+                if let Some((app, task)) = detached {
+                    tasks.push(self.open(app, task));
+                }
+                Task::batch(tasks)
+            }
+            WindowMessage::Closed(id) => {
+                if self.is_quick_chat(id) {
+                    self.quick_chat = None;
+                }
+                self.apps.remove(&id);
+                if self.apps.is_empty() {
+                    return iced::exit();
+                }
+                Task::none()
+            }
+        }
+    }
+
+    fn close_quick_chat(&mut self) -> Task<WindowMessage> {
+        match self.quick_chat.take() {
+            Some(quick_chat) => window::close(quick_chat.window),
+            None => Task::none(),
+        }
+    }
+
+    fn open(&mut self, mut app: App, task: Task<Message>) -> Task<WindowMessage> {
+        let (id, open) = window::open(settings());
+        app.window = Some(id);
+        self.apps.insert(id, app);
+        open.discard()
+            .chain(task.map(move |message| WindowMessage::App(id, message)))
+    }
+
+    pub fn view(&self, id: window::Id) -> Element<'_, WindowMessage> {
+        if let Some(quick_chat) = self.quick_chat.as_ref().filter(|_| self.is_quick_chat(id)) {
+            return container(
+                text_input(&tr!("quick-chat-placeholder"), &quick_chat.prompt)
+                    .id(quick_chat_input_id())
+                    .on_input(WindowMessage::UpdateQuickChat)
+                    .on_submit(WindowMessage::SubmitQuickChat)
+                    .padding(10),
+            )
+            .padding(8)
+            .center_y(Length::Fill)
+            .into();
+        }
+        match self.apps.get(&id) {
+            Some(app) => app
+                .view()
+                .map(move |message| WindowMessage::App(id, message)),
+            None => text("").into(),
+        }
+    }
+
+    pub fn theme(&self, id: window::Id) -> Theme {
+        self.apps
+            .get(&id)
+            .or_else(|| self.apps.get(&self.main))
+            .map_or(Theme::TokyoNightStorm, App::theme)
+    }
+
+    pub fn scale_factor(&self, id: window::Id) -> f64 {
+        self.apps
+            .get(&id)
+            .or_else(|| self.apps.get(&self.main))
+            .map_or(1.0, App::scale_factor)
+    }
+
+    pub fn subscription(&self) -> Subscription<WindowMessage> {
+        Subscription::batch(
+            self.apps
+                .iter()
+                .map(|(&id, app)| {
+                    app.subscription()
+                        .with(id)
+                        .map(|(id, message)| WindowMessage::App(id, message))
+                })
+                .chain([
+                    window::close_requests()
+                        .map(|id| WindowMessage::App(id, Message::CloseRequested(id))),
+                    window::close_events().map(WindowMessage::Closed),
+                    iced::event::listen_with(|event, status, id| {
+                        App::window_event(event, status)
+                            .map(|message| WindowMessage::App(id, message))
+                    }),
+                ]),
+        )
+    }
+}